@@ -1,6 +1,8 @@
 //! Contains structs for serializing and deserializing TKET circuits to and from
 //! JSON.
 
+use std::collections::HashMap;
+
 use crate::clexpr::ClExpr;
 use crate::opbox::OpBox;
 use crate::optype::OpType;
@@ -133,12 +135,12 @@ pub enum Classical {
 #[cfg_attr(feature = "schemars", derive(JsonSchema))]
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 pub struct Wasm {
-    n: u64,
-    ww_n: u64,
-    width_i_parameter: Vec<u64>,
-    width_o_parameter: Vec<u64>,
-    func_name: String,
-    wasm_file_uid: String,
+    pub(crate) n: u64,
+    pub(crate) ww_n: u64,
+    pub(crate) width_i_parameter: Vec<u64>,
+    pub(crate) width_o_parameter: Vec<u64>,
+    pub(crate) func_name: String,
+    pub(crate) wasm_file_uid: String,
 }
 
 /// Serializable operation descriptor.
@@ -364,4 +366,48 @@ impl<P> SerialCircuit<P> {
             discarded_qubits: self.discarded_qubits,
         }
     }
+
+    /// Returns the swaps of qubits that implement the implicit permutation of
+    /// the circuit when applied in order after its commands, or `None` if it
+    /// is not a permutation of the declared qubits.
+    ///
+    /// Qubits missing from the implicit permutation map to themselves.
+    pub(crate) fn permutation_swaps(&self) -> Option<Vec<(&ElementId, &ElementId)>> {
+        let index: HashMap<&ElementId, usize> = self
+            .qubits
+            .iter()
+            .enumerate()
+            .map(|(i, qb)| (&qb.id, i))
+            .collect();
+        let n = self.qubits.len();
+        let mut sources: Vec<usize> = (0..n).collect();
+        for entry in &self.implicit_permutation {
+            let from = *index.get(&entry.0.id)?;
+            let to = *index.get(&entry.1.id)?;
+            sources[to] = from;
+        }
+        let mut is_source = vec![false; n];
+        for &from in &sources {
+            if std::mem::replace(&mut is_source[from], true) {
+                return None;
+            }
+        }
+
+        // `wires[q]` is the wire holding the state of qubit `q`, and
+        // `states[w]` the qubit whose state is on wire `w`.
+        let mut wires: Vec<usize> = (0..n).collect();
+        let mut states: Vec<usize> = (0..n).collect();
+        let mut swaps = Vec::new();
+        for (to, &from) in sources.iter().enumerate() {
+            let wire = wires[from];
+            if wire != to {
+                swaps.push((&self.qubits[to].id, &self.qubits[wire].id));
+                let other = states[to];
+                states.swap(to, wire);
+                wires[from] = to;
+                wires[other] = wire;
+            }
+        }
+        Some(swaps)
+    }
 }
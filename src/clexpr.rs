@@ -1,7 +1,19 @@
 //! Classical expressions
+//!
+//! A [`ClExpr`] can be written as infix text, e.g. `b2 = b0 ^ b1` or
+//! `out[8..12] = (r0 + r1) << 2 where r0 = [0..4], r1 = [4..8]`, and parsed
+//! back from it. See [`parse`] for the syntax.
 
+pub mod builder;
+pub mod check;
+mod display;
+pub mod eval;
+pub mod legacy;
+pub mod lower;
 pub mod op;
 pub mod operator;
+pub mod parse;
+mod rewrite;
 
 use operator::ClOperator;
 #[cfg(feature = "schemars")]
@@ -39,7 +51,7 @@ pub struct ClExpr {
     pub reg_posn: Vec<InputClRegister>,
     /// The output bits of the expression.
     ///
-    /// This is a list of bit indices in [`ClExpr::bit_posn`].
+    /// This is a list of positions in the operation's `args` list.
     pub output_posn: ClRegisterBits,
 }
 
@@ -55,13 +67,14 @@ pub struct InputClRegister {
     pub index: u32,
     /// The sequence of positions of bits comprising the register variable.
     ///
-    /// The indexes in this sequence refer to the bits in the [`ClExpr::bit_posn`] list.
+    /// The indexes in this sequence are positions in the operation's `args`
+    /// list.
     pub bits: ClRegisterBits,
 }
 
 /// The sequence of positions of bits in the output.
 ///
-/// The indices here refer to the bit identifiers in the operation's [`ClExpr::bit_posn`].
+/// The indices here are positions in the operation's `args` list.
 ///
 /// Registers are little-endian, so the first bit is the least significant.
 #[cfg_attr(feature = "schemars", derive(JsonSchema))]
@@ -0,0 +1,353 @@
+//! A builder for classical expressions over circuit bits.
+//!
+//! [`ClExprBuilder`] assigns the local variable indices and argument
+//! positions of a [`ClExpr`], so expressions can be written in terms of the
+//! circuit's bits and registers using operator overloading:
+//!
+//! ```
+//! # use tket_json_rs::clexpr::builder::ClExprBuilder;
+//! # use tket_json_rs::register::{BitRegister, ElementId};
+//! let mut builder = ClExprBuilder::new();
+//! let a = builder.register(&BitRegister { name: "a".into(), size: 3 });
+//! let b = builder.register(&BitRegister { name: "b".into(), size: 3 });
+//! let c = builder.bit(ElementId("c".into(), vec![0]));
+//!
+//! // f[0] = ((a + b) << 1 == 4) ^ c[0]
+//! let value = ((a + b) << 1).eq(4) ^ c;
+//! let flag = ElementId("f".into(), vec![0]);
+//! let (expr, args) = builder.build(value, [flag]).unwrap();
+//! assert_eq!(args.len(), 8);
+//! ```
+
+use std::ops;
+
+use super::check::{ClType, TypeError};
+use super::op::ClOp;
+use super::operator::{ClArgument, ClOperator, ClTerminal, ClVariable};
+use super::{ClExpr, ClRegisterBits, InputClRegister};
+use crate::circuit_json::{Command, Operation};
+use crate::optype::OpType;
+use crate::register::{BitRegister, ElementId};
+
+/// Builds a [`ClExpr`] together with the argument list of its command.
+#[derive(Clone, Debug, Default)]
+pub struct ClExprBuilder {
+    /// The bits in the command's argument list.
+    args: Vec<ElementId>,
+    /// The bit variables, as `(index, position)` pairs.
+    bit_posn: Vec<(u32, u32)>,
+    /// The register variables.
+    reg_posn: Vec<InputClRegister>,
+    /// Whether register results may be resized to the output width.
+    resizing: bool,
+}
+
+/// A value in a classical expression being built.
+///
+/// Values are combined with the usual arithmetic and bitwise operators.
+/// Bitwise operators (`&`, `|`, `^`, `!`) act on single bits when their
+/// left-hand side is a bit, and on whole registers otherwise.
+#[derive(Clone, Debug)]
+pub struct ClValue {
+    arg: ClArgument,
+    kind: Kind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Bit,
+    Register,
+    Int,
+}
+
+impl ClExprBuilder {
+    /// Returns a new empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows a register-valued result to be written to any number of
+    /// output bits, checking the expression with [`ClExpr::check_resizing`]
+    /// instead of [`ClExpr::check`].
+    pub fn resizing(mut self) -> Self {
+        self.resizing = true;
+        self
+    }
+
+    /// Returns the position of a bit in the argument list, adding it if
+    /// needed.
+    fn position(&mut self, bit: ElementId) -> u32 {
+        let position = match self.args.iter().position(|b| *b == bit) {
+            Some(position) => position,
+            None => {
+                self.args.push(bit);
+                self.args.len() - 1
+            }
+        };
+        position as u32
+    }
+
+    /// Returns a bit variable for a circuit bit.
+    pub fn bit(&mut self, bit: impl Into<ElementId>) -> ClValue {
+        let position = self.position(bit.into());
+        let index = match self.bit_posn.iter().find(|(_, p)| *p == position) {
+            Some(&(index, _)) => index,
+            None => {
+                let index = self.bit_posn.len() as u32;
+                self.bit_posn.push((index, position));
+                index
+            }
+        };
+        ClValue::variable(ClVariable::Bit { index }, Kind::Bit)
+    }
+
+    /// Returns a register variable for a list of circuit bits, the first one
+    /// being the least significant.
+    pub fn bits(&mut self, bits: impl IntoIterator<Item = impl Into<ElementId>>) -> ClValue {
+        let positions = bits
+            .into_iter()
+            .map(|bit| self.position(bit.into()))
+            .collect();
+        let bits = ClRegisterBits(positions);
+        let index = match self.reg_posn.iter().find(|reg| reg.bits == bits) {
+            Some(reg) => reg.index,
+            None => {
+                let index = self.reg_posn.len() as u32;
+                self.reg_posn.push(InputClRegister { index, bits });
+                index
+            }
+        };
+        ClValue::variable(ClVariable::Register { index }, Kind::Register)
+    }
+
+    /// Returns a register variable for all the bits of a circuit register.
+    pub fn register(&mut self, register: &BitRegister) -> ClValue {
+        self.bits((0..register.size).map(|i| ElementId(register.name.clone(), vec![i as i64])))
+    }
+
+    /// Finishes the expression, writing its result to `outputs`.
+    ///
+    /// Returns the expression and the argument list of the command applying
+    /// it. Output bits that are also inputs are not repeated in the argument
+    /// list. Returns the errors reported by [`ClExpr::check`], or
+    /// [`ClExpr::check_resizing`], if the expression is not well typed.
+    pub fn build(
+        mut self,
+        value: impl Into<ClValue>,
+        outputs: impl IntoIterator<Item = impl Into<ElementId>>,
+    ) -> Result<(ClExpr, Vec<ElementId>), Vec<TypeError>> {
+        let output_posn = outputs
+            .into_iter()
+            .map(|bit| self.position(bit.into()))
+            .collect();
+        let value = value.into();
+        let expr = match value.arg {
+            ClArgument::Expression(operator) => *operator,
+            // A bare terminal is copied by or-ing it with zero.
+            terminal => ClOperator {
+                op: match value.kind {
+                    Kind::Bit => ClOp::BitOr,
+                    Kind::Register | Kind::Int => ClOp::RegOr,
+                },
+                args: vec![terminal, ClArgument::Terminal(ClTerminal::Int(0))],
+            },
+        };
+        let expr = ClExpr {
+            bit_posn: self.bit_posn,
+            expr,
+            reg_posn: self.reg_posn,
+            output_posn: ClRegisterBits(output_posn),
+        };
+        if self.resizing {
+            expr.check_resizing()?;
+        } else {
+            expr.check()?;
+        }
+        Ok((expr, self.args))
+    }
+
+    /// Finishes the expression as a [`OpType::ClExpr`] command writing its
+    /// result to `outputs`. See [`ClExprBuilder::build`].
+    pub fn build_command(
+        self,
+        value: impl Into<ClValue>,
+        outputs: impl IntoIterator<Item = impl Into<ElementId>>,
+    ) -> Result<Command, Vec<TypeError>> {
+        let (expr, args) = self.build(value, outputs)?;
+        let mut op = Operation::from_optype(OpType::ClExpr);
+        op.classical_expr = Some(expr);
+        Ok(Command {
+            op,
+            args,
+            opgroup: None,
+        })
+    }
+}
+
+impl ClValue {
+    fn variable(var: ClVariable, kind: Kind) -> Self {
+        Self {
+            arg: ClArgument::Terminal(ClTerminal::Variable(var)),
+            kind,
+        }
+    }
+
+    /// Returns an integer constant.
+    pub fn int(value: u64) -> Self {
+        Self {
+            arg: ClArgument::Terminal(ClTerminal::Int(value)),
+            kind: Kind::Int,
+        }
+    }
+
+    /// Returns the constant zero bit.
+    pub fn zero_bit() -> Self {
+        Self::apply(ClOp::BitZero, vec![])
+    }
+
+    /// Returns the constant one bit.
+    pub fn one_bit() -> Self {
+        Self::apply(ClOp::BitOne, vec![])
+    }
+
+    /// Returns the type of the value. Integer constants are registers.
+    pub fn ty(&self) -> ClType {
+        match self.kind {
+            Kind::Bit => ClType::Bit,
+            Kind::Register | Kind::Int => ClType::Register,
+        }
+    }
+
+    /// Applies an operation to a list of values.
+    pub fn apply(op: ClOp, args: Vec<ClValue>) -> Self {
+        let kind = match op.signature() {
+            Some((_, ClType::Bit)) => Kind::Bit,
+            _ => Kind::Register,
+        };
+        let args = args.into_iter().map(|v| v.arg).collect();
+        Self {
+            arg: ClArgument::Expression(Box::new(ClOperator { op, args })),
+            kind,
+        }
+    }
+
+    /// Whether a binary operation between `self` and `other` acts on bits.
+    fn is_bitwise_on_bits(&self, other: &ClValue) -> bool {
+        self.kind == Kind::Bit || (self.kind == Kind::Int && other.kind == Kind::Bit)
+    }
+
+    /// Applies a bit or register operation, depending on the operands.
+    fn binary(self, other: impl Into<ClValue>, bit_op: ClOp, reg_op: ClOp) -> Self {
+        let other = other.into();
+        let op = if self.is_bitwise_on_bits(&other) {
+            bit_op
+        } else {
+            reg_op
+        };
+        Self::apply(op, vec![self, other])
+    }
+
+    /// Equality comparison, producing a bit.
+    pub fn eq(self, other: impl Into<ClValue>) -> Self {
+        self.binary(other, ClOp::BitEq, ClOp::RegEq)
+    }
+
+    /// Inequality comparison, producing a bit.
+    pub fn ne(self, other: impl Into<ClValue>) -> Self {
+        self.binary(other, ClOp::BitNeq, ClOp::RegNeq)
+    }
+
+    /// Integer less-than comparison, producing a bit.
+    pub fn lt(self, other: impl Into<ClValue>) -> Self {
+        Self::apply(ClOp::RegLt, vec![self, other.into()])
+    }
+
+    /// Integer greater-than comparison, producing a bit.
+    pub fn gt(self, other: impl Into<ClValue>) -> Self {
+        Self::apply(ClOp::RegGt, vec![self, other.into()])
+    }
+
+    /// Integer less-than-or-equal comparison, producing a bit.
+    pub fn le(self, other: impl Into<ClValue>) -> Self {
+        Self::apply(ClOp::RegLeq, vec![self, other.into()])
+    }
+
+    /// Integer greater-than-or-equal comparison, producing a bit.
+    pub fn ge(self, other: impl Into<ClValue>) -> Self {
+        Self::apply(ClOp::RegGeq, vec![self, other.into()])
+    }
+
+    /// Integer exponentiation.
+    pub fn pow(self, other: impl Into<ClValue>) -> Self {
+        Self::apply(ClOp::RegPow, vec![self, other.into()])
+    }
+}
+
+impl From<u64> for ClValue {
+    fn from(value: u64) -> Self {
+        ClValue::int(value)
+    }
+}
+
+impl From<ClValue> for ClArgument {
+    fn from(value: ClValue) -> Self {
+        value.arg
+    }
+}
+
+/// Implements a binary operator on registers.
+macro_rules! register_op {
+    ($trait:ident, $method:ident, $op:ident) => {
+        impl<T: Into<ClValue>> ops::$trait<T> for ClValue {
+            type Output = ClValue;
+
+            fn $method(self, rhs: T) -> ClValue {
+                ClValue::apply(ClOp::$op, vec![self, rhs.into()])
+            }
+        }
+    };
+}
+
+register_op!(Add, add, RegAdd);
+register_op!(Sub, sub, RegSub);
+register_op!(Mul, mul, RegMul);
+register_op!(Div, div, RegDiv);
+register_op!(Shl, shl, RegLsh);
+register_op!(Shr, shr, RegRsh);
+
+/// Implements a binary operator acting on bits or registers.
+macro_rules! bitwise_op {
+    ($trait:ident, $method:ident, $bit_op:ident, $reg_op:ident) => {
+        impl<T: Into<ClValue>> ops::$trait<T> for ClValue {
+            type Output = ClValue;
+
+            fn $method(self, rhs: T) -> ClValue {
+                self.binary(rhs, ClOp::$bit_op, ClOp::$reg_op)
+            }
+        }
+    };
+}
+
+bitwise_op!(BitAnd, bitand, BitAnd, RegAnd);
+bitwise_op!(BitOr, bitor, BitOr, RegOr);
+bitwise_op!(BitXor, bitxor, BitXor, RegXor);
+
+impl ops::Not for ClValue {
+    type Output = ClValue;
+
+    fn not(self) -> ClValue {
+        let op = match self.kind {
+            Kind::Bit => ClOp::BitNot,
+            Kind::Register | Kind::Int => ClOp::RegNot,
+        };
+        ClValue::apply(op, vec![self])
+    }
+}
+
+impl ops::Neg for ClValue {
+    type Output = ClValue;
+
+    fn neg(self) -> ClValue {
+        ClValue::apply(ClOp::RegNeg, vec![self])
+    }
+}
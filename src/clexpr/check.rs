@@ -0,0 +1,335 @@
+//! Type checking of classical expressions.
+//!
+//! [`ClExpr::check`] infers whether each node of an expression evaluates to a
+//! single bit or to a register, and reports the problems that deserialization
+//! does not catch. Problems are located by a
+//! [JSON pointer](https://www.rfc-editor.org/rfc/rfc6901) into the serialized
+//! [`ClExpr`].
+//!
+//! The output bits must match the width of the result. Where a result is
+//! meant to be truncated or zero-extended to its output, as in pytket's
+//! legacy `ClassicalExpBox`, [`ClExpr::check_resizing`] relaxes this rule
+//! for register-valued results.
+
+use std::collections::HashSet;
+
+use derive_more::Display;
+
+use super::op::ClOp;
+use super::operator::{ClArgument, ClOperator, ClTerminal, ClVariable};
+use super::ClExpr;
+
+/// The type of a value in a classical expression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Display)]
+#[non_exhaustive]
+pub enum ClType {
+    /// A single bit.
+    #[display("bit")]
+    Bit,
+    /// A register of bits, interpreted as an unsigned integer.
+    #[display("register")]
+    Register,
+}
+
+/// A problem found while type checking a [`ClExpr`].
+#[derive(Clone, Debug, PartialEq, Display)]
+#[display("{path}: {kind}")]
+#[non_exhaustive]
+pub struct TypeError {
+    /// JSON pointer to the offending element, e.g. `/expr/args/1`.
+    pub path: String,
+    /// The problem found at that location.
+    pub kind: TypeErrorKind,
+}
+
+/// The kinds of problems reported by [`ClExpr::check`].
+#[derive(Clone, Debug, PartialEq, Display)]
+#[non_exhaustive]
+pub enum TypeErrorKind {
+    /// The expression contains a [`ClOp::INVALID`] operation.
+    #[display("invalid operation")]
+    InvalidOp,
+    /// An operation has the wrong number of arguments.
+    #[display("{op:?} cannot take {found} arguments")]
+    ArgCount {
+        /// The operation.
+        op: ClOp,
+        /// The number of arguments given.
+        found: usize,
+    },
+    /// An operation argument has the wrong type.
+    #[display("{op:?} expects {expected} arguments, but got a {found}")]
+    TypeMismatch {
+        /// The operation.
+        op: ClOp,
+        /// The type of arguments the operation expects.
+        expected: ClType,
+        /// The type of the argument.
+        found: ClType,
+    },
+    /// A constant used as a bit is neither 0 nor 1.
+    #[display("{_0} is not a valid bit value")]
+    InvalidBitConstant(u64),
+    /// A variable is not declared in `bit_posn` or `reg_posn`.
+    #[display("variable {_0:?} is not declared")]
+    UndeclaredVariable(ClVariable),
+    /// A variable is declared more than once in `bit_posn` or `reg_posn`.
+    #[display("variable {_0:?} is declared more than once")]
+    DuplicateVariable(ClVariable),
+    /// An argument position is used by more than one input variable, or more
+    /// than once in the output.
+    #[display("argument position {_0} is used more than once")]
+    ReusedPosition(u32),
+    /// The number of output bits does not match the width of the result.
+    #[display("the result has width {expected}, but {found} output bits were given")]
+    OutputWidth {
+        /// The width of the result.
+        expected: usize,
+        /// The number of bits in `output_posn`.
+        found: usize,
+    },
+}
+
+/// The inferred type of a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Inferred {
+    Bit,
+    /// A register, with its width if it is determined by its inputs.
+    Register(Option<usize>),
+    /// An integer constant, which may be used as either a bit or a register.
+    Int(u64),
+}
+
+impl ClExpr {
+    /// Checks that the expression is well typed, returning the type of its
+    /// result.
+    ///
+    /// Reports every problem found in the expression tree, the variable
+    /// declarations, and the output bits.
+    pub fn check(&self) -> Result<ClType, Vec<TypeError>> {
+        self.check_with(false)
+    }
+
+    /// Checks that the expression is well typed like [`ClExpr::check`], but
+    /// allows a register-valued result to be written to any number of
+    /// output bits.
+    ///
+    /// The result is then truncated, or zero-extended, to the width of
+    /// [`ClExpr::output_posn`], which is what [`ClExpr::evaluate`] does. A
+    /// bit-valued result must still have exactly one output bit.
+    pub fn check_resizing(&self) -> Result<ClType, Vec<TypeError>> {
+        self.check_with(true)
+    }
+
+    /// Returns the width of a register-valued result, as determined by the
+    /// widths of the registers it is computed from.
+    ///
+    /// Returns `None` if the expression is bit-valued, ill-typed, or only
+    /// computed from constants.
+    pub(crate) fn result_width(&self) -> Option<usize> {
+        match self.infer(true) {
+            Ok(Some(Inferred::Register(width))) => width,
+            _ => None,
+        }
+    }
+
+    fn check_with(&self, resizing: bool) -> Result<ClType, Vec<TypeError>> {
+        match self.infer(resizing)? {
+            Some(Inferred::Bit) => Ok(ClType::Bit),
+            _ => Ok(ClType::Register),
+        }
+    }
+
+    /// Checks the expression, returning the inferred type of its result.
+    fn infer(&self, resizing: bool) -> Result<Option<Inferred>, Vec<TypeError>> {
+        let mut checker = Checker {
+            expr: self,
+            resizing,
+            errors: Vec::new(),
+        };
+        checker.check_declarations();
+        let result = checker.check_operator(&self.expr, "/expr");
+        checker.check_output(result);
+        if checker.errors.is_empty() {
+            Ok(result)
+        } else {
+            Err(checker.errors)
+        }
+    }
+}
+
+/// Accumulates the type errors of an expression.
+struct Checker<'a> {
+    expr: &'a ClExpr,
+    /// Whether register results may differ in width from the output.
+    resizing: bool,
+    errors: Vec<TypeError>,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, path: impl Into<String>, kind: TypeErrorKind) {
+        self.errors.push(TypeError {
+            path: path.into(),
+            kind,
+        });
+    }
+
+    fn check_declarations(&mut self) {
+        let mut positions = HashSet::new();
+
+        let mut bits = HashSet::new();
+        for (i, &(index, position)) in self.expr.bit_posn.iter().enumerate() {
+            if !bits.insert(index) {
+                let var = ClVariable::Bit { index };
+                self.report(
+                    format!("/bit_posn/{i}"),
+                    TypeErrorKind::DuplicateVariable(var),
+                );
+            }
+            if !positions.insert(position) {
+                self.report(
+                    format!("/bit_posn/{i}/1"),
+                    TypeErrorKind::ReusedPosition(position),
+                );
+            }
+        }
+
+        let mut registers = HashSet::new();
+        for (i, reg) in self.expr.reg_posn.iter().enumerate() {
+            if !registers.insert(reg.index) {
+                let var = ClVariable::Register { index: reg.index };
+                self.report(
+                    format!("/reg_posn/{i}"),
+                    TypeErrorKind::DuplicateVariable(var),
+                );
+            }
+            for (j, &position) in reg.bits.0.iter().enumerate() {
+                if !positions.insert(position) {
+                    self.report(
+                        format!("/reg_posn/{i}/1/{j}"),
+                        TypeErrorKind::ReusedPosition(position),
+                    );
+                }
+            }
+        }
+
+        let mut outputs = HashSet::new();
+        for (i, &position) in self.expr.output_posn.0.iter().enumerate() {
+            if !outputs.insert(position) {
+                self.report(
+                    format!("/output_posn/{i}"),
+                    TypeErrorKind::ReusedPosition(position),
+                );
+            }
+        }
+    }
+
+    /// Checks that the output width matches the result of the expression.
+    fn check_output(&mut self, result: Option<Inferred>) {
+        let expected = match result {
+            Some(Inferred::Bit) => 1,
+            Some(Inferred::Register(Some(width))) if !self.resizing => width,
+            _ => return,
+        };
+        let found = self.expr.output_posn.0.len();
+        if expected != found {
+            self.report(
+                "/output_posn",
+                TypeErrorKind::OutputWidth { expected, found },
+            );
+        }
+    }
+
+    /// Infers the type of an operator node, or returns `None` if it cannot
+    /// be determined due to an error.
+    fn check_operator(&mut self, operator: &ClOperator, path: &str) -> Option<Inferred> {
+        let op = &operator.op;
+        let args: Vec<Option<Inferred>> = operator
+            .args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| self.check_argument(arg, &format!("{path}/args/{i}")))
+            .collect();
+
+        let Some((arg_type, result_type)) = op.signature() else {
+            self.report(path, TypeErrorKind::InvalidOp);
+            return None;
+        };
+        if !op.arg_count().contains(&args.len()) {
+            let found = args.len();
+            self.report(
+                path,
+                TypeErrorKind::ArgCount {
+                    op: op.clone(),
+                    found,
+                },
+            );
+        }
+
+        let mut width = None;
+        for (i, arg) in args.iter().enumerate() {
+            let arg_path = format!("{path}/args/{i}");
+            let found = match (*arg, arg_type) {
+                (None, _) | (Some(Inferred::Int(0 | 1)), ClType::Bit) => continue,
+                (Some(Inferred::Int(n)), ClType::Bit) => {
+                    self.report(arg_path, TypeErrorKind::InvalidBitConstant(n));
+                    continue;
+                }
+                (Some(Inferred::Int(_)), ClType::Register) => continue,
+                (Some(Inferred::Register(w)), ClType::Register) => {
+                    // Shifts and powers keep the width of their first argument.
+                    let sets_width =
+                        i == 0 || !matches!(op, ClOp::RegLsh | ClOp::RegRsh | ClOp::RegPow);
+                    if sets_width {
+                        width = width.max(w);
+                    }
+                    continue;
+                }
+                (Some(Inferred::Bit), ClType::Bit) => continue,
+                (Some(Inferred::Bit), ClType::Register) => ClType::Bit,
+                (Some(Inferred::Register(_)), ClType::Bit) => ClType::Register,
+            };
+            self.report(
+                arg_path,
+                TypeErrorKind::TypeMismatch {
+                    op: op.clone(),
+                    expected: arg_type,
+                    found,
+                },
+            );
+        }
+
+        Some(match result_type {
+            ClType::Bit => Inferred::Bit,
+            ClType::Register => Inferred::Register(width),
+        })
+    }
+
+    fn check_argument(&mut self, arg: &ClArgument, path: &str) -> Option<Inferred> {
+        let var = match arg {
+            ClArgument::Expression(operator) => {
+                return self.check_operator(operator, &format!("{path}/input"))
+            }
+            ClArgument::Terminal(ClTerminal::Int(n)) => return Some(Inferred::Int(*n)),
+            ClArgument::Terminal(ClTerminal::Variable(var)) => var,
+        };
+        let inferred = match *var {
+            ClVariable::Bit { index } => self
+                .expr
+                .bit_posn
+                .iter()
+                .any(|&(i, _)| i == index)
+                .then_some(Inferred::Bit),
+            ClVariable::Register { index } => self
+                .expr
+                .reg_posn
+                .iter()
+                .find(|reg| reg.index == index)
+                .map(|reg| Inferred::Register(Some(reg.bits.0.len()))),
+        };
+        if inferred.is_none() {
+            self.report(path, TypeErrorKind::UndeclaredVariable(var.clone()));
+        }
+        inferred
+    }
+}
@@ -0,0 +1,374 @@
+//! Infix text form of classical expressions.
+//!
+//! Operations are written with Rust-like operator symbols where possible,
+//! and as function calls such as `RegAdd(r0, r1, r2)` otherwise. A whole
+//! [`ClExpr`] is written as an assignment, all positions being indices in
+//! the command's arguments. Bit variables are named `b{position}` after
+//! their argument position, and a bit result written to a single position
+//! is assigned to that bit:
+//!
+//! ```text
+//! b2 = b0 ^ b1
+//! ```
+//!
+//! Register variables are named `r{index}`, and declared in a `where`
+//! clause following the assignment:
+//!
+//! ```text
+//! out[0..3] = (r0 + r1) << 2 where r0 = [3..6], r1 = [6..9]
+//! ```
+//!
+//! The parser numbers bit variables in the order they first appear. If
+//! [`ClExpr::bit_posn`] does not follow that order, bit variables are
+//! instead named `b{index}` and declared in the `where` clause as well, as in
+//! `out[2] = b1 ^ b0 where b0 = 0, b1 = 1`.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+
+use super::check::ClType;
+use super::op::ClOp;
+use super::operator::{ClArgument, ClOperator, ClTerminal, ClVariable};
+use super::{ClExpr, ClRegisterBits};
+
+/// The precedence of the unary operators `!` and `-`.
+pub(super) const UNARY_PRECEDENCE: u8 = 8;
+/// The precedence of terminals, function calls and parenthesized
+/// expressions.
+const ATOM_PRECEDENCE: u8 = 10;
+
+/// The kind of value an argument evaluates to, as seen from its syntax.
+///
+/// Symbols shared by bit and register operations, such as `&`, are resolved
+/// from the kinds of their arguments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Kind {
+    Bit,
+    Register,
+    Int,
+}
+
+impl Kind {
+    pub(super) fn of(arg: &ClArgument) -> Self {
+        match arg {
+            ClArgument::Terminal(ClTerminal::Int(_)) => Kind::Int,
+            ClArgument::Terminal(ClTerminal::Variable(ClVariable::Bit { .. })) => Kind::Bit,
+            ClArgument::Terminal(ClTerminal::Variable(ClVariable::Register { .. })) => {
+                Kind::Register
+            }
+            ClArgument::Expression(operator) => match operator.op.signature() {
+                Some((_, ClType::Bit)) => Kind::Bit,
+                _ => Kind::Register,
+            },
+        }
+    }
+}
+
+/// Returns the precedence of a binary operator symbol. Operators with higher
+/// precedence bind more tightly.
+pub(super) fn binary_precedence(symbol: &str) -> Option<u8> {
+    Some(match symbol {
+        "==" | "!=" | "<" | ">" | "<=" | ">=" => 1,
+        "|" => 2,
+        "^" => 3,
+        "&" => 4,
+        "<<" | ">>" => 5,
+        "+" | "-" => 6,
+        "*" | "/" => 7,
+        "**" => 9,
+        _ => return None,
+    })
+}
+
+/// Returns the operation denoted by a binary operator symbol.
+///
+/// Symbols shared by bit and register operations denote the bit operation if
+/// either argument is a bit.
+pub(super) fn binary_op(symbol: &str, lhs: Kind, rhs: Kind) -> Option<ClOp> {
+    let on_bits = lhs == Kind::Bit || rhs == Kind::Bit;
+    let shared = |bit_op, reg_op| if on_bits { bit_op } else { reg_op };
+    Some(match symbol {
+        "==" => shared(ClOp::BitEq, ClOp::RegEq),
+        "!=" => shared(ClOp::BitNeq, ClOp::RegNeq),
+        "|" => shared(ClOp::BitOr, ClOp::RegOr),
+        "^" => shared(ClOp::BitXor, ClOp::RegXor),
+        "&" => shared(ClOp::BitAnd, ClOp::RegAnd),
+        "<" => ClOp::RegLt,
+        ">" => ClOp::RegGt,
+        "<=" => ClOp::RegLeq,
+        ">=" => ClOp::RegGeq,
+        "<<" => ClOp::RegLsh,
+        ">>" => ClOp::RegRsh,
+        "+" => ClOp::RegAdd,
+        "-" => ClOp::RegSub,
+        "*" => ClOp::RegMul,
+        "/" => ClOp::RegDiv,
+        "**" => ClOp::RegPow,
+        _ => return None,
+    })
+}
+
+/// Returns the operation denoted by a unary operator symbol.
+pub(super) fn unary_op(symbol: &str, arg: Kind) -> Option<ClOp> {
+    match symbol {
+        "!" if arg == Kind::Bit => Some(ClOp::BitNot),
+        "!" => Some(ClOp::RegNot),
+        "-" => Some(ClOp::RegNeg),
+        _ => None,
+    }
+}
+
+/// Returns the operator symbol of an operation, if it has one.
+fn symbol(op: &ClOp) -> Option<&'static str> {
+    Some(match op {
+        ClOp::BitEq | ClOp::RegEq => "==",
+        ClOp::BitNeq | ClOp::RegNeq => "!=",
+        ClOp::BitOr | ClOp::RegOr => "|",
+        ClOp::BitXor | ClOp::RegXor => "^",
+        ClOp::BitAnd | ClOp::RegAnd => "&",
+        ClOp::BitNot | ClOp::RegNot => "!",
+        ClOp::RegLt => "<",
+        ClOp::RegGt => ">",
+        ClOp::RegLeq => "<=",
+        ClOp::RegGeq => ">=",
+        ClOp::RegLsh => "<<",
+        ClOp::RegRsh => ">>",
+        ClOp::RegAdd => "+",
+        ClOp::RegSub | ClOp::RegNeg => "-",
+        ClOp::RegMul => "*",
+        ClOp::RegDiv => "/",
+        ClOp::RegPow => "**",
+        _ => return None,
+    })
+}
+
+/// How an operation is written.
+enum Form {
+    Unary(&'static str),
+    Binary(&'static str, u8),
+    Call,
+}
+
+impl Form {
+    /// Returns the form of an operation. Operator symbols are only used when
+    /// parsing them back gives the same operation, so that e.g. a `BitAnd`
+    /// of two integer constants is written as a function call.
+    fn of(operator: &ClOperator) -> Self {
+        let Some(symbol) = symbol(&operator.op) else {
+            return Form::Call;
+        };
+        let op = Some(&operator.op);
+        match operator.args.as_slice() {
+            [arg] if unary_op(symbol, Kind::of(arg)).as_ref() == op => Form::Unary(symbol),
+            [lhs, rhs] if binary_op(symbol, Kind::of(lhs), Kind::of(rhs)).as_ref() == op => {
+                Form::Binary(symbol, binary_precedence(symbol).unwrap_or(ATOM_PRECEDENCE))
+            }
+            _ => Form::Call,
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Form::Unary(_) => UNARY_PRECEDENCE,
+            Form::Binary(_, precedence) => *precedence,
+            Form::Call => ATOM_PRECEDENCE,
+        }
+    }
+}
+
+/// Writes an argument, wrapped in parentheses if it binds less tightly than
+/// `min_precedence`.
+fn fmt_arg(f: &mut fmt::Formatter<'_>, arg: &ClArgument, min_precedence: u8) -> fmt::Result {
+    let precedence = match arg {
+        ClArgument::Terminal(_) => ATOM_PRECEDENCE,
+        ClArgument::Expression(operator) => Form::of(operator).precedence(),
+    };
+    if precedence < min_precedence {
+        write!(f, "({arg})")
+    } else {
+        write!(f, "{arg}")
+    }
+}
+
+impl fmt::Display for ClOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (Form::of(self), self.args.as_slice()) {
+            (Form::Unary(symbol), [arg]) => {
+                f.write_str(symbol)?;
+                fmt_arg(f, arg, UNARY_PRECEDENCE)
+            }
+            (Form::Binary("**", _), [lhs, rhs]) => {
+                // Exponentiation is right-associative, and binds tighter than
+                // a unary operator on its base but not on its exponent.
+                fmt_arg(f, lhs, ATOM_PRECEDENCE)?;
+                f.write_str(" ** ")?;
+                fmt_arg(f, rhs, UNARY_PRECEDENCE)
+            }
+            (Form::Binary(symbol, precedence), [lhs, rhs]) => {
+                // Parenthesize arithmetic in shifts and chained comparisons,
+                // which are easily misread, even where it is not needed.
+                let (lhs_min, rhs_min) = match symbol {
+                    "<<" | ">>" => (UNARY_PRECEDENCE, UNARY_PRECEDENCE),
+                    _ if precedence == 1 => (2, 2),
+                    _ => (precedence, precedence + 1),
+                };
+                fmt_arg(f, lhs, lhs_min)?;
+                write!(f, " {symbol} ")?;
+                fmt_arg(f, rhs, rhs_min)
+            }
+            _ => {
+                write!(f, "{:?}(", self.op)?;
+                for (i, arg) in self.args.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ClArgument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClArgument::Terminal(terminal) => write!(f, "{terminal}"),
+            ClArgument::Expression(operator) => write!(f, "{operator}"),
+        }
+    }
+}
+
+impl fmt::Display for ClTerminal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClTerminal::Variable(var) => write!(f, "{var}"),
+            ClTerminal::Int(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+impl fmt::Display for ClVariable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClVariable::Register { index } => write!(f, "r{index}"),
+            ClVariable::Bit { index } => write!(f, "b{index}"),
+        }
+    }
+}
+
+/// Writes the positions as a list, with runs of three or more consecutive
+/// positions written as ranges, e.g. `[0..3, 7]`.
+impl fmt::Display for ClRegisterBits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        let mut rest = self.0.as_slice();
+        let mut first = true;
+        while let Some(&start) = rest.first() {
+            let run = rest
+                .iter()
+                .enumerate()
+                .take_while(|&(i, &p)| start.checked_add(i as u32) == Some(p))
+                .count();
+            if !first {
+                f.write_str(", ")?;
+            }
+            first = false;
+            let end = start.checked_add(run as u32).filter(|_| run >= 3);
+            if let Some(end) = end {
+                write!(f, "{start}..{end}")?;
+                rest = &rest[run..];
+            } else {
+                write!(f, "{start}")?;
+                rest = &rest[1..];
+            }
+        }
+        f.write_str("]")
+    }
+}
+
+/// Appends the bit variables of an operation that are not in `order` yet,
+/// in the order they appear.
+pub(super) fn bit_order(operator: &ClOperator, order: &mut Vec<u32>) {
+    for arg in &operator.args {
+        match arg {
+            ClArgument::Terminal(ClTerminal::Variable(ClVariable::Bit { index })) => {
+                if !order.contains(index) {
+                    order.push(*index);
+                }
+            }
+            ClArgument::Terminal(_) => {}
+            ClArgument::Expression(operator) => bit_order(operator, order),
+        }
+    }
+}
+
+/// Renames the bit variables of an operation.
+pub(super) fn rename_bits(operator: &mut ClOperator, rename: &mut impl FnMut(u32) -> u32) {
+    for arg in &mut operator.args {
+        match arg {
+            ClArgument::Terminal(ClTerminal::Variable(ClVariable::Bit { index })) => {
+                *index = rename(*index);
+            }
+            ClArgument::Terminal(_) => {}
+            ClArgument::Expression(operator) => rename_bits(operator, rename),
+        }
+    }
+}
+
+impl ClExpr {
+    /// Returns the expression with its bit variables named after their
+    /// positions, or `None` if parsing it back would not give the same
+    /// variables.
+    fn positional_bits(&self) -> Option<Cow<'_, ClOperator>> {
+        let mut order = Vec::new();
+        bit_order(&self.expr, &mut order);
+        if order.len() != self.bit_posn.len() {
+            return None;
+        } else if order.is_empty() {
+            return Some(Cow::Borrowed(&self.expr));
+        }
+        // The variables must be numbered in order of appearance, with
+        // distinct positions.
+        let mut positions = HashSet::new();
+        for (i, (&(index, position), first)) in self.bit_posn.iter().zip(order).enumerate() {
+            if index != i as u32 || first != index || !positions.insert(position) {
+                return None;
+            }
+        }
+        let mut expr = self.expr.clone();
+        rename_bits(&mut expr, &mut |index| self.bit_posn[index as usize].1);
+        Some(Cow::Owned(expr))
+    }
+}
+
+impl fmt::Display for ClExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let positional = self.positional_bits();
+        match (&positional, self.output_posn.0.as_slice()) {
+            (Some(expr), &[position]) if matches!(expr.op.signature(), Some((_, ClType::Bit))) => {
+                write!(f, "b{position} = {expr}")?;
+            }
+            (Some(expr), _) => write!(f, "out{} = {expr}", self.output_posn)?,
+            (None, _) => write!(f, "out{} = {}", self.output_posn, self.expr)?,
+        }
+        let bits =
+            self.bit_posn
+                .iter()
+                .filter(|_| positional.is_none())
+                .map(|&(index, position)| {
+                    let var = ClVariable::Bit { index };
+                    format!("{var} = {position}")
+                });
+        let registers = self.reg_posn.iter().map(|reg| {
+            let var = ClVariable::Register { index: reg.index };
+            format!("{var} = {}", reg.bits)
+        });
+        for (i, declaration) in bits.chain(registers).enumerate() {
+            f.write_str(if i == 0 { " where " } else { ", " })?;
+            f.write_str(&declaration)?;
+        }
+        Ok(())
+    }
+}
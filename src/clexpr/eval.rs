@@ -0,0 +1,266 @@
+//! Evaluation of classical expressions on concrete bit values.
+//!
+//! Registers are little-endian, so the first bit of a register is its least
+//! significant bit. Register values are unsigned integers, and each
+//! register-valued node is computed modulo 2^w, where `w` is the width that
+//! [`ClExpr::check`] infers for it: the width of its widest register operand,
+//! or of its first operand for shifts and powers. Nodes computed only from
+//! constants wrap around at 2^64. The result is truncated, or zero-extended,
+//! to the width of [`ClExpr::output_posn`] when it is written out; outputs
+//! of a different width are only accepted by [`ClExpr::check_resizing`].
+
+use derive_more::{Display, Error};
+
+use super::check::ClType;
+use super::op::ClOp;
+use super::operator::{ClArgument, ClOperator, ClTerminal, ClVariable};
+use super::ClExpr;
+
+/// Error produced when evaluating a [`ClExpr`].
+#[derive(Clone, Debug, PartialEq, Eq, Display, Error)]
+#[non_exhaustive]
+pub enum EvalError {
+    /// A variable in the expression is not declared in `bit_posn` or
+    /// `reg_posn`.
+    #[display("variable {_0:?} is not declared")]
+    UndeclaredVariable(#[error(not(source))] ClVariable),
+    /// An argument position is out of range of the given bit values.
+    #[display("argument position {position} is out of range for {len} arguments")]
+    PositionOutOfRange {
+        /// The argument position.
+        position: u32,
+        /// The number of bit values given.
+        len: usize,
+    },
+    /// An operation has the wrong number of arguments.
+    #[display("{op:?} cannot take {found} arguments")]
+    ArgCount {
+        /// The operation.
+        op: ClOp,
+        /// The number of arguments given.
+        found: usize,
+    },
+    /// A bit operation has a register argument.
+    #[display("{op:?} expects bit arguments, but got a register")]
+    ExpectedBit {
+        /// The operation.
+        op: ClOp,
+    },
+    /// A register operation has a bit argument.
+    #[display("{op:?} expects register arguments, but got a bit")]
+    ExpectedRegister {
+        /// The operation.
+        op: ClOp,
+    },
+    /// A constant used as a bit is neither 0 nor 1.
+    #[display("{_0} is not a valid bit value")]
+    InvalidBitConstant(#[error(not(source))] u64),
+    /// A register division by zero.
+    #[display("division by zero")]
+    DivisionByZero,
+    /// The expression contains a [`ClOp::INVALID`] operation.
+    #[display("invalid operation")]
+    InvalidOp,
+}
+
+/// The value of a node in an expression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Value {
+    /// A single bit.
+    Bit(bool),
+    /// A register, as an unsigned integer, with its width if it is
+    /// determined by its inputs.
+    Register(u64, Option<usize>),
+    /// An integer constant, which may be used as either a bit or a register.
+    Int(u64),
+}
+
+impl ClExpr {
+    /// Evaluates the expression on the values of the operation's arguments.
+    ///
+    /// `args` holds the value of each bit in the command's argument list, as
+    /// referenced by [`ClExpr::bit_posn`], [`ClExpr::reg_posn`] and
+    /// [`ClExpr::output_posn`]. Returns the values of the output bits, in the
+    /// order of `output_posn`.
+    ///
+    /// A bit-valued result is written as a 0 or 1 register. Register results
+    /// are truncated to the width of the output.
+    pub fn evaluate(&self, args: &[bool]) -> Result<Vec<bool>, EvalError> {
+        let value = match self.eval_operator(&self.expr, args)? {
+            Value::Bit(b) => b as u64,
+            Value::Register(v, _) | Value::Int(v) => v,
+        };
+        let width = self.output_posn.0.len();
+        Ok((0..width)
+            .map(|i| i < 64 && (value >> i) & 1 == 1)
+            .collect())
+    }
+
+    /// Evaluates the expression, and writes the output bits back into
+    /// `args`.
+    pub fn apply(&self, args: &mut [bool]) -> Result<(), EvalError> {
+        let outputs = self.evaluate(args)?;
+        for (&position, value) in self.output_posn.0.iter().zip(outputs) {
+            *arg_mut(args, position)? = value;
+        }
+        Ok(())
+    }
+
+    fn eval_operator(&self, operator: &ClOperator, args: &[bool]) -> Result<Value, EvalError> {
+        let values = operator
+            .args
+            .iter()
+            .map(|arg| match arg {
+                ClArgument::Terminal(ClTerminal::Int(n)) => Ok(Value::Int(*n)),
+                ClArgument::Terminal(ClTerminal::Variable(var)) => self.eval_variable(var, args),
+                ClArgument::Expression(expr) => self.eval_operator(expr, args),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        apply_op(&operator.op, &values)
+    }
+
+    fn eval_variable(&self, var: &ClVariable, args: &[bool]) -> Result<Value, EvalError> {
+        let undeclared = || EvalError::UndeclaredVariable(var.clone());
+        match *var {
+            ClVariable::Bit { index } => {
+                let &(_, position) = self
+                    .bit_posn
+                    .iter()
+                    .find(|(i, _)| *i == index)
+                    .ok_or_else(undeclared)?;
+                Ok(Value::Bit(arg(args, position)?))
+            }
+            ClVariable::Register { index } => {
+                let reg = self
+                    .reg_posn
+                    .iter()
+                    .find(|reg| reg.index == index)
+                    .ok_or_else(undeclared)?;
+                let mut value = 0u64;
+                for (i, &position) in reg.bits.0.iter().enumerate().take(64) {
+                    value |= (arg(args, position)? as u64) << i;
+                }
+                Ok(Value::Register(value, Some(reg.bits.0.len())))
+            }
+        }
+    }
+}
+
+fn arg(args: &[bool], position: u32) -> Result<bool, EvalError> {
+    args.get(position as usize)
+        .copied()
+        .ok_or(EvalError::PositionOutOfRange {
+            position,
+            len: args.len(),
+        })
+}
+
+fn arg_mut(args: &mut [bool], position: u32) -> Result<&mut bool, EvalError> {
+    let len = args.len();
+    args.get_mut(position as usize)
+        .ok_or(EvalError::PositionOutOfRange { position, len })
+}
+
+/// Applies an operation to the values of its arguments.
+fn apply_op(op: &ClOp, values: &[Value]) -> Result<Value, EvalError> {
+    let Some((arg_type, _)) = op.signature() else {
+        return Err(EvalError::InvalidOp);
+    };
+    if !op.arg_count().contains(&values.len()) {
+        return Err(EvalError::ArgCount {
+            op: op.clone(),
+            found: values.len(),
+        });
+    }
+
+    if arg_type == ClType::Bit {
+        let bits = values
+            .iter()
+            .map(|v| match *v {
+                Value::Bit(b) => Ok(b),
+                Value::Int(n @ (0 | 1)) => Ok(n == 1),
+                Value::Int(n) => Err(EvalError::InvalidBitConstant(n)),
+                Value::Register(..) => Err(EvalError::ExpectedBit { op: op.clone() }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(Value::Bit(match op {
+            ClOp::BitZero => false,
+            ClOp::BitOne => true,
+            ClOp::BitNot => !bits[0],
+            ClOp::BitAnd => bits.into_iter().fold(true, |a, b| a & b),
+            ClOp::BitOr => bits.into_iter().fold(false, |a, b| a | b),
+            ClOp::BitXor => bits.into_iter().fold(false, |a, b| a ^ b),
+            ClOp::BitEq => bits[0] == bits[1],
+            _ => bits[0] != bits[1],
+        }));
+    }
+
+    let mut width = None;
+    let regs = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| match *v {
+            Value::Register(n, w) => {
+                // Shifts and powers keep the width of their first argument.
+                if i == 0 || !matches!(op, ClOp::RegLsh | ClOp::RegRsh | ClOp::RegPow) {
+                    width = width.max(w);
+                }
+                Ok(n)
+            }
+            Value::Int(n) => Ok(n),
+            Value::Bit(_) => Err(EvalError::ExpectedRegister { op: op.clone() }),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let shift = |f: fn(u64, u32) -> Option<u64>| {
+        u32::try_from(regs[1])
+            .ok()
+            .and_then(|b| f(regs[0], b))
+            .unwrap_or(0)
+    };
+    let value = match op {
+        ClOp::RegEq => return Ok(Value::Bit(regs[0] == regs[1])),
+        ClOp::RegNeq => return Ok(Value::Bit(regs[0] != regs[1])),
+        ClOp::RegLt => return Ok(Value::Bit(regs[0] < regs[1])),
+        ClOp::RegGt => return Ok(Value::Bit(regs[0] > regs[1])),
+        ClOp::RegLeq => return Ok(Value::Bit(regs[0] <= regs[1])),
+        ClOp::RegGeq => return Ok(Value::Bit(regs[0] >= regs[1])),
+        ClOp::RegZero => 0,
+        ClOp::RegOne => u64::MAX,
+        ClOp::RegNot => !regs[0],
+        ClOp::RegNeg => regs[0].wrapping_neg(),
+        ClOp::RegAnd => regs.into_iter().fold(u64::MAX, |a, b| a & b),
+        ClOp::RegOr => regs.into_iter().fold(0, |a, b| a | b),
+        ClOp::RegXor => regs.into_iter().fold(0, |a, b| a ^ b),
+        ClOp::RegAdd => regs.into_iter().fold(0, u64::wrapping_add),
+        ClOp::RegMul => regs.into_iter().fold(1, u64::wrapping_mul),
+        ClOp::RegSub => regs[0].wrapping_sub(regs[1]),
+        ClOp::RegDiv => regs[0]
+            .checked_div(regs[1])
+            .ok_or(EvalError::DivisionByZero)?,
+        ClOp::RegPow => wrapping_pow(regs[0], regs[1]),
+        ClOp::RegLsh => shift(u64::checked_shl),
+        _ => shift(u64::checked_shr),
+    };
+    Ok(Value::Register(truncate(value, width), width))
+}
+
+/// Truncates a register value to `width` bits, if its width is known.
+fn truncate(value: u64, width: Option<usize>) -> u64 {
+    match width {
+        Some(width) if width < 64 => value & ((1 << width) - 1),
+        _ => value,
+    }
+}
+
+/// Computes `base^exp` modulo 2^64.
+fn wrapping_pow(mut base: u64, mut exp: u64) -> u64 {
+    let mut result = 1u64;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exp >>= 1;
+    }
+    result
+}
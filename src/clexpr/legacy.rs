@@ -0,0 +1,208 @@
+//! Conversion of deprecated `ClassicalExpBox` operations to [`ClExpr`].
+//!
+//! Older versions of pytket encode classical expressions as an
+//! [`OpBox::ClassicalExpBox`] holding a [`ClassicalExp`] tree, whose
+//! operations are strings such as `"RegWiseOp.ADD"` and whose leaves refer to
+//! circuit bits and registers by name. [`SerialCircuit::convert_classical_exp_boxes`]
+//! rewrites these commands as equivalent [`OpType::ClExpr`] operations.
+//!
+//! The command arguments of a `ClassicalExpBox` are its `n_i` input-only bits,
+//! followed by its `n_io` input-output bits and its `n_o` output-only bits.
+//! The result of the expression is written to the last `n_io + n_o`
+//! arguments, the first one being the least significant bit. As in pytket,
+//! a register result is truncated or zero-extended to the width of the
+//! output, so the converted expressions are checked with
+//! [`ClExpr::check_resizing`].
+//!
+//!   [`ClExpr`]: super::ClExpr
+//!   [`ClExpr::check_resizing`]: super::ClExpr::check_resizing
+//!   [`OpType::ClExpr`]: crate::optype::OpType::ClExpr
+
+use derive_more::{Display, Error};
+
+use super::builder::{ClExprBuilder, ClValue};
+use super::check::TypeError;
+use super::op::ClOp;
+use super::rewrite::{rewrite_circuit, ClExprCommand, Rewriter};
+use crate::circuit_json::{ClassicalExp, ClassicalExpUnit, Operation, SerialCircuit};
+use crate::opbox::OpBox;
+use crate::register::ElementId;
+
+/// A problem found while converting a `ClassicalExpBox`.
+#[derive(Clone, Debug, PartialEq, Display, Error)]
+#[display("{path}: {kind}")]
+#[non_exhaustive]
+pub struct ConvertError {
+    /// JSON pointer to the offending element, e.g. `/commands/3/op/box/exp`.
+    pub path: String,
+    /// The problem found at that location.
+    #[error(not(source))]
+    pub kind: ConvertErrorKind,
+}
+
+/// The kinds of problems reported by
+/// [`SerialCircuit::convert_classical_exp_boxes`].
+#[derive(Clone, Debug, PartialEq, Display)]
+#[non_exhaustive]
+pub enum ConvertErrorKind {
+    /// The operation string of a [`ClassicalExp`] has no [`ClOp`] equivalent.
+    #[display("unknown classical operation `{_0}`")]
+    UnknownOp(String),
+    /// The command does not have `n_i + n_io + n_o` bit arguments.
+    #[display("the box acts on {expected} bits, but {found} were given")]
+    ArgCount {
+        /// The number of bits the box acts on.
+        expected: usize,
+        /// The number of bit arguments in the command.
+        found: usize,
+    },
+    /// The converted expression does not type check.
+    #[display("the converted expression is not well typed: {_0}")]
+    InvalidExpression(TypeError),
+}
+
+impl<P: Clone> SerialCircuit<P> {
+    /// Replaces the deprecated `ClassicalExpBox` operations in the circuit by
+    /// equivalent [`OpType::ClExpr`] operations.
+    ///
+    /// Boxes under a classical condition, and in the circuits of `CircBox`
+    /// and `CustomGate` operations, are converted too.
+    ///
+    /// Returns every problem found if some box cannot be converted, in which
+    /// case the circuit is left unchanged.
+    ///
+    ///   [`OpType::ClExpr`]: crate::optype::OpType::ClExpr
+    pub fn convert_classical_exp_boxes(&mut self) -> Result<(), Vec<ConvertError>> {
+        let mut circ = self.clone();
+        let mut converter = Converter { errors: Vec::new() };
+        rewrite_circuit(&mut circ, "", &mut converter);
+        if !converter.errors.is_empty() {
+            return Err(converter.errors);
+        }
+        *self = circ;
+        Ok(())
+    }
+}
+
+/// Converts `ClassicalExpBox` operations, accumulating errors.
+struct Converter {
+    errors: Vec<ConvertError>,
+}
+
+impl Rewriter for Converter {
+    fn rewrite<P>(
+        &mut self,
+        op: &Operation<P>,
+        args: &[ElementId],
+        path: &str,
+    ) -> Option<Vec<ClExprCommand>> {
+        let Some(OpBox::ClassicalExpBox {
+            n_i,
+            n_io,
+            n_o,
+            exp,
+            ..
+        }) = &op.op_box
+        else {
+            return None;
+        };
+        let errors = &mut self.errors;
+        let n_i = *n_i as usize;
+        let expected = n_i + *n_io as usize + *n_o as usize;
+        if args.len() != expected {
+            errors.push(ConvertError {
+                path: path.to_string(),
+                kind: ConvertErrorKind::ArgCount {
+                    expected,
+                    found: args.len(),
+                },
+            });
+            return None;
+        }
+
+        let n_errors = errors.len();
+        let mut builder = ClExprBuilder::new().resizing();
+        let value = convert_exp(exp, &mut builder, &format!("{path}/box/exp"), errors);
+        if errors.len() > n_errors {
+            return None;
+        }
+        match builder.build(value, args[n_i..].iter().cloned()) {
+            Ok(built) => Some(vec![built]),
+            Err(type_errors) => {
+                errors.extend(type_errors.into_iter().map(|e| ConvertError {
+                    path: format!("{path}/box/exp"),
+                    kind: ConvertErrorKind::InvalidExpression(e),
+                }));
+                None
+            }
+        }
+    }
+}
+
+/// Converts a legacy expression tree, reporting unknown operations.
+fn convert_exp(
+    exp: &ClassicalExp,
+    builder: &mut ClExprBuilder,
+    path: &str,
+    errors: &mut Vec<ConvertError>,
+) -> ClValue {
+    let args = exp
+        .args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| match arg {
+            ClassicalExpUnit::U32(n) => ClValue::int(*n as u64),
+            ClassicalExpUnit::Bit(bit) => builder.bit(bit.clone()),
+            ClassicalExpUnit::BitRegister(register) => builder.register(register),
+            ClassicalExpUnit::ClassicalExpUnit(exp) => {
+                convert_exp(exp, builder, &format!("{path}/args/{i}"), errors)
+            }
+        })
+        .collect();
+    let op = legacy_op(&exp.op).unwrap_or_else(|| {
+        errors.push(ConvertError {
+            path: path.to_string(),
+            kind: ConvertErrorKind::UnknownOp(exp.op.clone()),
+        });
+        ClOp::INVALID
+    });
+    ClValue::apply(op, args)
+}
+
+/// Maps a pytket `BitWiseOp` or `RegWiseOp` name to the equivalent operation.
+fn legacy_op(name: &str) -> Option<ClOp> {
+    if let Some(name) = name.strip_prefix("BitWiseOp.") {
+        return Some(match name {
+            "AND" => ClOp::BitAnd,
+            "OR" => ClOp::BitOr,
+            "XOR" => ClOp::BitXor,
+            "EQ" => ClOp::BitEq,
+            "NEQ" => ClOp::BitNeq,
+            "NOT" => ClOp::BitNot,
+            "ZERO" => ClOp::BitZero,
+            "ONE" => ClOp::BitOne,
+            _ => return None,
+        });
+    }
+    Some(match name.strip_prefix("RegWiseOp.")? {
+        "AND" => ClOp::RegAnd,
+        "OR" => ClOp::RegOr,
+        "XOR" => ClOp::RegXor,
+        "EQ" => ClOp::RegEq,
+        "NEQ" => ClOp::RegNeq,
+        "LT" => ClOp::RegLt,
+        "GT" => ClOp::RegGt,
+        "LEQ" => ClOp::RegLeq,
+        "GEQ" => ClOp::RegGeq,
+        "ADD" => ClOp::RegAdd,
+        "SUB" => ClOp::RegSub,
+        "MUL" => ClOp::RegMul,
+        "DIV" => ClOp::RegDiv,
+        "POW" => ClOp::RegPow,
+        "LSH" => ClOp::RegLsh,
+        "RSH" => ClOp::RegRsh,
+        "NOT" => ClOp::RegNot,
+        "NEG" => ClOp::RegNeg,
+        _ => return None,
+    })
+}
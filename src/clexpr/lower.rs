@@ -0,0 +1,401 @@
+//! Lowering of classical operations to [`ClExpr`].
+//!
+//! The operations carrying a [`Classical`] field each encode classical logic
+//! differently. [`SerialCircuit::lower_classical_ops`] rewrites them as
+//! equivalent [`OpType::ClExpr`] operations:
+//!
+//! | Operation           | Expression                                    |
+//! |---------------------|-----------------------------------------------|
+//! | `SetBits`           | the constant bits                             |
+//! | `CopyBits`          | the input bits, as a register                 |
+//! | `RangePredicate`    | `(r0 >= lower) & (r0 <= upper)`               |
+//! | `ExplicitPredicate` | `(r0 == k0) \| (r0 == k1) \| ...` over the true rows of the table |
+//! | `ExplicitModifier`  | the same, with the modified bit as the most significant input |
+//! | `ClassicalTransform`| `(r0 == k0) * v0 \| (r0 == k1) * v1 \| ...` over the non-zero rows of the table |
+//! | `MultiBit`          | one expression per application of the inner operation |
+//!
+//! As in tket, a `CopyBits` operation copies its first `n_i` arguments to
+//! the next `n_i`.
+//!
+//! Truth tables are indexed by the little-endian value of the input bits, so
+//! the first argument is the least significant bit of the index. The
+//! comparisons in a `ClassicalTransform` expression are written in register
+//! arithmetic, so that each row contributes a register value.
+//!
+//!   [`ClExpr`]: super::ClExpr
+//!   [`OpType::ClExpr`]: crate::optype::OpType::ClExpr
+
+use derive_more::{Display, Error};
+
+use super::builder::{ClExprBuilder, ClValue};
+use super::check::TypeError;
+use super::op::ClOp;
+use super::rewrite::{rewrite_circuit, ClExprCommand, Rewriter};
+use crate::circuit_json::{Classical, Operation, SerialCircuit};
+use crate::optype::OpType;
+use crate::register::ElementId;
+
+/// The largest number of bits an operation can act on as a register.
+const MAX_WIDTH: usize = 64;
+
+/// A problem found while lowering a classical operation.
+#[derive(Clone, Debug, PartialEq, Display, Error)]
+#[display("{path}: {kind}")]
+#[non_exhaustive]
+pub struct LowerError {
+    /// JSON pointer to the offending operation, e.g. `/commands/3/op`.
+    pub path: String,
+    /// The problem found at that location.
+    #[error(not(source))]
+    pub kind: LowerErrorKind,
+}
+
+/// The kinds of problems reported by [`SerialCircuit::lower_classical_ops`].
+#[derive(Clone, Debug, PartialEq, Display)]
+#[non_exhaustive]
+pub enum LowerErrorKind {
+    /// The operation does not have the `classical` field its type requires.
+    #[display("{_0} operation is missing its `classical` field")]
+    MissingClassical(OpType),
+    /// The operation has the wrong number of arguments.
+    #[display("{op_type} expects {expected} arguments, but {found} were given")]
+    ArgCount {
+        /// The operation type.
+        op_type: OpType,
+        /// The number of arguments the operation expects.
+        expected: usize,
+        /// The number of arguments given.
+        found: usize,
+    },
+    /// A truth table does not have one entry per combination of inputs.
+    #[display("{op_type} truth table has {found} entries, expected {expected}")]
+    TableSize {
+        /// The operation type.
+        op_type: OpType,
+        /// The number of entries expected.
+        expected: usize,
+        /// The number of entries given.
+        found: usize,
+    },
+    /// The operation acts on too many bits to be expressed as a
+    /// [`ClExpr`](super::ClExpr).
+    #[display("{op_type} on {width} bits cannot be expressed as a classical expression")]
+    TooWide {
+        /// The operation type.
+        op_type: OpType,
+        /// The number of bits the operation acts on.
+        width: usize,
+    },
+    /// The arguments of a `MultiBit` operation cannot be split into `n`
+    /// equal groups.
+    #[display("MultiBit operation applied {n} times cannot take {found} arguments")]
+    MultiBitArgCount {
+        /// The number of applications of the inner operation.
+        n: usize,
+        /// The number of arguments given.
+        found: usize,
+    },
+    /// The inner operation of a `MultiBit` operation cannot be lowered.
+    #[display("cannot lower MultiBit operations over {_0}")]
+    UnsupportedMultiBit(OpType),
+    /// The lowered expression does not type check.
+    #[display("the lowered expression is not well typed: {_0}")]
+    InvalidExpression(TypeError),
+}
+
+impl<P: Clone> SerialCircuit<P> {
+    /// Replaces the `SetBits`, `CopyBits`, `RangePredicate`,
+    /// `ExplicitPredicate`, `ExplicitModifier`, `ClassicalTransform` and
+    /// `MultiBit` operations in the circuit by equivalent
+    /// [`OpType::ClExpr`] operations. See the [module
+    /// documentation](crate::clexpr::lower) for the expressions used.
+    ///
+    /// Operations under a classical condition, and in the circuits of
+    /// `CircBox` and `CustomGate` operations, are lowered too. A `MultiBit`
+    /// operation is replaced by one command per application of its inner
+    /// operation.
+    ///
+    /// Returns every problem found if some operation cannot be lowered, in
+    /// which case the circuit is left unchanged.
+    pub fn lower_classical_ops(&mut self) -> Result<(), Vec<LowerError>> {
+        let mut circ = self.clone();
+        let mut lowerer = Lowerer { errors: Vec::new() };
+        rewrite_circuit(&mut circ, "", &mut lowerer);
+        if !lowerer.errors.is_empty() {
+            return Err(lowerer.errors);
+        }
+        *self = circ;
+        Ok(())
+    }
+}
+
+/// Lowers classical operations, accumulating errors.
+struct Lowerer {
+    errors: Vec<LowerError>,
+}
+
+impl Rewriter for Lowerer {
+    fn rewrite<P>(
+        &mut self,
+        op: &Operation<P>,
+        args: &[ElementId],
+        path: &str,
+    ) -> Option<Vec<ClExprCommand>> {
+        match lower(op.op_type, op.classical.as_deref(), args, path)? {
+            Ok(commands) => Some(commands),
+            Err(error) => {
+                self.errors.push(error);
+                None
+            }
+        }
+    }
+}
+
+/// Lowers an operation, or returns `None` if it is not a classical operation
+/// handled by this pass.
+pub(crate) fn lower(
+    op_type: OpType,
+    classical: Option<&Classical>,
+    args: &[ElementId],
+    path: &str,
+) -> Option<Result<Vec<ClExprCommand>, LowerError>> {
+    let error = |kind| LowerError {
+        path: path.to_string(),
+        kind,
+    };
+    let lowered = match (op_type, classical) {
+        (
+            OpType::SetBits
+            | OpType::CopyBits
+            | OpType::RangePredicate
+            | OpType::ExplicitPredicate
+            | OpType::ExplicitModifier
+            | OpType::ClassicalTransform
+            | OpType::MultiBit,
+            None,
+        ) => Err(LowerErrorKind::MissingClassical(op_type)),
+        (OpType::MultiBit, Some(Classical::MultiBit { op, n })) => {
+            return Some(lower_multi_bit(op, *n as usize, args, path));
+        }
+        (_, Some(classical)) => lower_classical(op_type, classical, args)?.map(|c| vec![c]),
+        (_, None) => return None,
+    };
+    Some(lowered.map_err(error))
+}
+
+/// Lowers each application of the inner operation of a `MultiBit` operation.
+///
+/// As for any classical operation, the arguments list all the input-only bits
+/// first, then the input-output bits, and then the output-only bits. Each of
+/// these groups holds the bits of the first application, then those of the
+/// second, and so on.
+fn lower_multi_bit(
+    op: &Operation,
+    n: usize,
+    args: &[ElementId],
+    path: &str,
+) -> Result<Vec<ClExprCommand>, LowerError> {
+    let error = |path: &str, kind| LowerError {
+        path: path.to_string(),
+        kind,
+    };
+    let lowering = op
+        .classical
+        .as_deref()
+        .and_then(|classical| lowering(op.op_type, classical))
+        .ok_or_else(|| error(path, LowerErrorKind::UnsupportedMultiBit(op.op_type)))?;
+    if n == 0 || args.len() != n * lowering.n_args {
+        return Err(error(
+            path,
+            LowerErrorKind::MultiBitArgCount {
+                n,
+                found: args.len(),
+            },
+        ));
+    }
+    let n_i = lowering.n_args - lowering.outputs;
+    let n_io = lowering.inputs + lowering.outputs - lowering.n_args;
+    let n_o = lowering.n_args - lowering.inputs;
+    let (inputs, rest) = args.split_at(n * n_i);
+    let (io, outputs) = rest.split_at(n * n_io);
+    let inner_path = format!("{path}/classical/op");
+    (0..n)
+        .map(|k| {
+            let group: Vec<ElementId> = [
+                &inputs[k * n_i..][..n_i],
+                &io[k * n_io..][..n_io],
+                &outputs[k * n_o..][..n_o],
+            ]
+            .concat();
+            lowering
+                .apply(op.op_type, &group)
+                .map_err(|kind| error(&inner_path, kind))
+        })
+        .collect()
+}
+
+/// Lowers an operation with a [`Classical`] field other than `MultiBit`.
+fn lower_classical(
+    op_type: OpType,
+    classical: &Classical,
+    args: &[ElementId],
+) -> Option<Result<ClExprCommand, LowerErrorKind>> {
+    Some(lowering(op_type, classical)?.apply(op_type, args))
+}
+
+/// Returns the expression computed by an operation with a [`Classical`]
+/// field other than `MultiBit`.
+fn lowering(op_type: OpType, classical: &Classical) -> Option<Lowering<'_>> {
+    Some(match (op_type, classical) {
+        (OpType::SetBits, Classical::SetBits { values }) => Lowering {
+            n_args: values.len(),
+            inputs: 0,
+            outputs: values.len(),
+            table: None,
+            value: Box::new(|_, _| {
+                let value = values
+                    .iter()
+                    .enumerate()
+                    .fold(0, |acc, (i, &b)| acc | (b as u64) << i);
+                ClValue::int(value)
+            }),
+        },
+        (OpType::CopyBits, Classical::CopyBits { n_i }) => Lowering {
+            n_args: 2 * *n_i as usize,
+            inputs: *n_i as usize,
+            outputs: *n_i as usize,
+            table: None,
+            value: Box::new(|builder, inputs| builder.bits(inputs.iter().cloned())),
+        },
+        (OpType::RangePredicate, Classical::RangePredicate { n_i, lower, upper }) => Lowering {
+            n_args: *n_i as usize + 1,
+            inputs: *n_i as usize,
+            outputs: 1,
+            table: None,
+            value: Box::new(|builder, inputs| {
+                let value = builder.bits(inputs.iter().cloned());
+                value.clone().ge(*lower) & value.le(*upper)
+            }),
+        },
+        (OpType::ExplicitPredicate, Classical::Explicit { n_i, values, .. }) => Lowering {
+            n_args: *n_i as usize + 1,
+            inputs: *n_i as usize,
+            outputs: 1,
+            table: Some(values.len()),
+            value: Box::new(|builder, inputs| truth_table(builder, inputs, values)),
+        },
+        (OpType::ExplicitModifier, Classical::Explicit { n_i, values, .. }) => Lowering {
+            // The modified bit is also the last input.
+            n_args: *n_i as usize + 1,
+            inputs: *n_i as usize + 1,
+            outputs: 1,
+            table: Some(values.len()),
+            value: Box::new(|builder, inputs| truth_table(builder, inputs, values)),
+        },
+        (OpType::ClassicalTransform, Classical::ClassicalTransform { n_io, values, .. }) => {
+            let n_io = *n_io as usize;
+            Lowering {
+                n_args: n_io,
+                inputs: n_io,
+                outputs: n_io,
+                table: Some(values.len()),
+                value: Box::new(move |builder, inputs| lookup(builder, inputs, values)),
+            }
+        }
+        _ => return None,
+    })
+}
+
+/// The expression computed by a classical operation.
+struct Lowering<'a> {
+    /// The number of arguments of the operation.
+    n_args: usize,
+    /// The number of leading arguments that are inputs.
+    inputs: usize,
+    /// The number of trailing arguments that are outputs.
+    outputs: usize,
+    /// The number of entries in the truth table, if the operation has one.
+    table: Option<usize>,
+    /// Builds the result from the input bits.
+    #[allow(clippy::type_complexity)]
+    value: Box<dyn Fn(&mut ClExprBuilder, &[ElementId]) -> ClValue + 'a>,
+}
+
+impl Lowering<'_> {
+    fn apply(&self, op_type: OpType, args: &[ElementId]) -> Result<ClExprCommand, LowerErrorKind> {
+        if args.len() != self.n_args {
+            return Err(LowerErrorKind::ArgCount {
+                op_type,
+                expected: self.n_args,
+                found: args.len(),
+            });
+        }
+        let width = self.inputs.max(self.outputs);
+        if width > MAX_WIDTH || (self.table.is_some() && self.inputs >= MAX_WIDTH) {
+            return Err(LowerErrorKind::TooWide { op_type, width });
+        }
+        if let Some(found) = self.table {
+            let expected = 1 << self.inputs;
+            if found != expected {
+                return Err(LowerErrorKind::TableSize {
+                    op_type,
+                    expected,
+                    found,
+                });
+            }
+        }
+
+        let mut builder = ClExprBuilder::new();
+        let value = (self.value)(&mut builder, &args[..self.inputs]);
+        let outputs = args[args.len() - self.outputs..].iter().cloned();
+        builder
+            .build(value, outputs)
+            .map_err(|mut errors| LowerErrorKind::InvalidExpression(errors.remove(0)))
+    }
+}
+
+/// Returns the bit `values[inputs]`, as the disjunction of the rows where it
+/// is true.
+fn truth_table(builder: &mut ClExprBuilder, inputs: &[ElementId], values: &[bool]) -> ClValue {
+    let index = builder.bits(inputs.iter().cloned());
+    let mut rows: Vec<ClValue> = values
+        .iter()
+        .enumerate()
+        .filter(|(_, &value)| value)
+        .map(|(row, _)| index.clone().eq(row as u64))
+        .collect();
+    match rows.len() {
+        0 => ClValue::zero_bit(),
+        1 => rows.remove(0),
+        _ => ClValue::apply(ClOp::BitOr, rows),
+    }
+}
+
+/// Returns the register `values[inputs]`, as the sum of the products
+/// `values[row] * (inputs == row)` over the non-zero rows of the table.
+///
+/// The comparisons must be registers, so they are written in register
+/// arithmetic: with `d = inputs ^ row`, the most significant bit of
+/// `(d - 1) & !d` is set if and only if `d` is zero.
+fn lookup(builder: &mut ClExprBuilder, inputs: &[ElementId], values: &[u32]) -> ClValue {
+    let Some(top) = inputs.len().checked_sub(1) else {
+        return ClValue::int(0);
+    };
+    let index = builder.bits(inputs.iter().cloned());
+    let mask = u64::MAX >> (63 - top);
+    let mut rows: Vec<ClValue> = values
+        .iter()
+        .enumerate()
+        .filter(|(_, &value)| value as u64 & mask != 0)
+        .map(|(row, &value)| {
+            let d = index.clone() ^ row as u64;
+            let matches = (((d.clone() - 1) & !d) >> top as u64) & 1;
+            matches * (value as u64 & mask)
+        })
+        .collect();
+    match rows.len() {
+        0 => ClValue::int(0),
+        1 => rows.remove(0),
+        _ => ClValue::apply(ClOp::RegOr, rows),
+    }
+}
@@ -1,10 +1,14 @@
 //! Classical expression operations.
 
+use std::ops::RangeInclusive;
+
 #[cfg(feature = "schemars")]
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use strum::EnumString;
 
+use super::check::ClType;
+
 /// List of supported classical expressions.
 ///
 /// Corresponds to `pytket.circuit.ClOp`.
@@ -74,3 +78,72 @@ pub enum ClOp {
     /// Integer negation
     RegNeg,
 }
+
+impl ClOp {
+    /// Returns the range of valid argument counts for the operation.
+    pub(crate) fn arg_count(&self) -> RangeInclusive<usize> {
+        match self {
+            ClOp::INVALID => 0..=usize::MAX,
+            ClOp::BitZero | ClOp::BitOne | ClOp::RegZero | ClOp::RegOne => 0..=0,
+            ClOp::BitNot | ClOp::RegNot | ClOp::RegNeg => 1..=1,
+            ClOp::BitAnd
+            | ClOp::BitOr
+            | ClOp::BitXor
+            | ClOp::RegAnd
+            | ClOp::RegOr
+            | ClOp::RegXor
+            | ClOp::RegAdd
+            | ClOp::RegMul => 2..=usize::MAX,
+            ClOp::BitEq
+            | ClOp::BitNeq
+            | ClOp::RegEq
+            | ClOp::RegNeq
+            | ClOp::RegLt
+            | ClOp::RegGt
+            | ClOp::RegLeq
+            | ClOp::RegGeq
+            | ClOp::RegSub
+            | ClOp::RegDiv
+            | ClOp::RegPow
+            | ClOp::RegLsh
+            | ClOp::RegRsh => 2..=2,
+        }
+    }
+
+    /// Returns the type of the arguments and the type of the result of the
+    /// operation, or `None` for [`ClOp::INVALID`].
+    pub(crate) fn signature(&self) -> Option<(ClType, ClType)> {
+        use ClType::{Bit, Register};
+        Some(match self {
+            ClOp::INVALID => return None,
+            ClOp::BitAnd
+            | ClOp::BitOr
+            | ClOp::BitXor
+            | ClOp::BitEq
+            | ClOp::BitNeq
+            | ClOp::BitNot
+            | ClOp::BitZero
+            | ClOp::BitOne => (Bit, Bit),
+            ClOp::RegEq
+            | ClOp::RegNeq
+            | ClOp::RegLt
+            | ClOp::RegGt
+            | ClOp::RegLeq
+            | ClOp::RegGeq => (Register, Bit),
+            ClOp::RegAnd
+            | ClOp::RegOr
+            | ClOp::RegXor
+            | ClOp::RegNot
+            | ClOp::RegZero
+            | ClOp::RegOne
+            | ClOp::RegAdd
+            | ClOp::RegSub
+            | ClOp::RegMul
+            | ClOp::RegDiv
+            | ClOp::RegPow
+            | ClOp::RegLsh
+            | ClOp::RegRsh
+            | ClOp::RegNeg => (Register, Register),
+        })
+    }
+}
@@ -49,7 +49,7 @@ pub enum ClTerminal {
 ///
 /// The indices refer to the local identifiers in the [`super::ClExpr`] structure.
 #[cfg_attr(feature = "schemars", derive(JsonSchema))]
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Hash)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, Hash)]
 #[non_exhaustive]
 #[serde(tag = "type", content = "var")]
 pub enum ClVariable {
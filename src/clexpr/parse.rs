@@ -0,0 +1,374 @@
+//! Parser for the text form of classical expressions.
+//!
+//! Accepts the syntax produced by the [`Display`](std::fmt::Display)
+//! implementation of [`ClExpr`]:
+//!
+//! ```
+//! # use tket_json_rs::clexpr::ClExpr;
+//! let expr: ClExpr = "b2 = b0 ^ b1".parse().unwrap();
+//! assert_eq!(expr.evaluate(&[true, false, false]).unwrap(), [true]);
+//! assert_eq!(expr.to_string(), "b2 = b0 ^ b1");
+//!
+//! let expr: ClExpr = "out[0..3] = (r0 + r1) << 2 where r0 = [3..6], r1 = [6..9]"
+//!     .parse()
+//!     .unwrap();
+//! assert_eq!(expr.reg_posn.len(), 2);
+//! ```
+//!
+//! Bit variables name argument positions, unless the `where` clause
+//! declares some bit variable, in which case they are local variables as in
+//! `out[2] = b0 ^ b1 where b0 = 0, b1 = 1`. Lists of positions may contain half-open ranges, so `[0..3, 7]` is
+//! `[0, 1, 2, 7]`. Operator precedence follows Rust, with `**` binding
+//! tighter than the unary operators on its left. Any operation can also be
+//! written as a function call of its [`ClOp`] name, e.g. `RegAdd(r0, r1, r2)`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use derive_more::{Display, Error};
+
+use super::display::{binary_op, binary_precedence, bit_order, rename_bits, unary_op, Kind};
+use super::op::ClOp;
+use super::operator::{ClArgument, ClOperator, ClTerminal, ClVariable};
+use super::{ClExpr, ClRegisterBits, InputClRegister};
+
+/// Error produced when parsing a [`ClExpr`] from a string.
+#[derive(Clone, Debug, PartialEq, Eq, Display, Error)]
+#[display("invalid classical expression at position {position}: {message}")]
+pub struct ParseClExprError {
+    /// Byte offset of the error in the input string.
+    pub position: usize,
+    /// Description of the error.
+    pub message: String,
+}
+
+/// Punctuation tokens, longest first.
+const SYMBOLS: &[&str] = &[
+    "**", "<<", ">>", "==", "!=", "<=", ">=", "..", "+", "-", "*", "/", "&", "|", "^", "!", "<",
+    ">", "=", "(", ")", "[", "]", ",",
+];
+
+/// A lexical token in an expression string.
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Int(u64),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Int(n) => write!(f, "{n}"),
+            Token::Ident(name) => f.write_str(name),
+            Token::Symbol(symbol) => f.write_str(symbol),
+        }
+    }
+}
+
+impl FromStr for ClExpr {
+    type Err = ParseClExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+            input_len: s.len(),
+        };
+        parser.clexpr()
+    }
+}
+
+fn error(position: usize, message: impl Into<String>) -> ParseClExprError {
+    ParseClExprError {
+        position,
+        message: message.into(),
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, ParseClExprError> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    while let Some(c) = input[start..].chars().next() {
+        let rest = &input[start..];
+        let (token, len) = if c.is_whitespace() {
+            start += c.len_utf8();
+            continue;
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let value = rest[..len]
+                .parse()
+                .map_err(|_| error(start, format!("invalid integer '{}'", &rest[..len])))?;
+            (Token::Int(value), len)
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (Token::Ident(rest[..len].to_string()), len)
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(*symbol))
+                .ok_or_else(|| error(start, format!("unexpected character '{c}'")))?;
+            (Token::Symbol(symbol), symbol.len())
+        };
+        tokens.push((start, token));
+        start += len;
+    }
+    Ok(tokens)
+}
+
+/// Parses a variable name, `b{index}` or `r{index}`.
+fn variable(name: &str) -> Option<ClVariable> {
+    let parse_index = |digits: &str| {
+        digits
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| digits.parse().ok())
+            .flatten()
+    };
+    if let Some(index) = name.strip_prefix('b').and_then(parse_index) {
+        Some(ClVariable::Bit { index })
+    } else {
+        let index = name.strip_prefix('r').and_then(parse_index)?;
+        Some(ClVariable::Register { index })
+    }
+}
+
+fn operation(op: ClOp, args: Vec<ClArgument>) -> ClArgument {
+    ClArgument::Expression(Box::new(ClOperator { op, args }))
+}
+
+/// A recursive descent parser over a token list.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    input_len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.input_len, |(offset, _)| *offset)
+    }
+
+    fn next(&mut self) -> Result<Token, ParseClExprError> {
+        let offset = self.offset();
+        let token = self.peek().cloned();
+        self.pos += 1;
+        token.ok_or_else(|| error(offset, "unexpected end of input"))
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Symbol(s)) => *s == symbol,
+            Some(Token::Ident(name)) => name == symbol,
+            _ => false,
+        };
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ParseClExprError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(error(self.offset(), format!("expected '{symbol}'")))
+        }
+    }
+
+    /// `clexpr := ('out' positions | bit_var) '=' expr ('where' decl (',' decl)*)?`
+    fn clexpr(&mut self) -> Result<ClExpr, ParseClExprError> {
+        let offset = self.offset();
+        let output_posn = match self.next()? {
+            Token::Ident(name) if name == "out" => self.positions()?,
+            Token::Ident(name) => match variable(&name) {
+                Some(ClVariable::Bit { index }) => ClRegisterBits(vec![index]),
+                _ => return Err(error(offset, "expected 'out' or an output bit")),
+            },
+            _ => return Err(error(offset, "expected 'out' or an output bit")),
+        };
+        self.expect("=")?;
+        let offset = self.offset();
+        let mut expr = match self.expr(0)? {
+            ClArgument::Expression(operator) => *operator,
+            ClArgument::Terminal(_) => return Err(error(offset, "expected an operation")),
+        };
+
+        let mut bit_posn = Vec::new();
+        let mut reg_posn = Vec::new();
+        if self.eat("where") {
+            loop {
+                self.declaration(&mut bit_posn, &mut reg_posn)?;
+                if !self.eat(",") {
+                    break;
+                }
+            }
+        }
+        if let Some((offset, token)) = self.tokens.get(self.pos) {
+            return Err(error(*offset, format!("unexpected '{token}'")));
+        }
+        if bit_posn.is_empty() {
+            // Bit variables are named after their positions, and numbered in
+            // the order they first appear.
+            let mut positions = Vec::new();
+            bit_order(&expr, &mut positions);
+            rename_bits(&mut expr, &mut |position| {
+                positions.iter().position(|&p| p == position).unwrap() as u32
+            });
+            bit_posn = (0..).zip(positions).collect();
+        }
+        Ok(ClExpr {
+            bit_posn,
+            expr,
+            reg_posn,
+            output_posn,
+        })
+    }
+
+    /// `decl := bit_var '=' position | reg_var '=' positions`
+    fn declaration(
+        &mut self,
+        bit_posn: &mut Vec<(u32, u32)>,
+        reg_posn: &mut Vec<InputClRegister>,
+    ) -> Result<(), ParseClExprError> {
+        let offset = self.offset();
+        let var = match self.next()? {
+            Token::Ident(name) => variable(&name),
+            _ => None,
+        };
+        match var.ok_or_else(|| error(offset, "expected a variable"))? {
+            ClVariable::Bit { index } => {
+                self.expect("=")?;
+                bit_posn.push((index, self.position()?));
+            }
+            ClVariable::Register { index } => {
+                self.expect("=")?;
+                let bits = self.positions()?;
+                reg_posn.push(InputClRegister { index, bits });
+            }
+        }
+        Ok(())
+    }
+
+    fn position(&mut self) -> Result<u32, ParseClExprError> {
+        let offset = self.offset();
+        match self.next()? {
+            Token::Int(n) => u32::try_from(n).map_err(|_| error(offset, "position out of range")),
+            token => Err(error(
+                offset,
+                format!("expected a position, found '{token}'"),
+            )),
+        }
+    }
+
+    /// `positions := '[' (item (',' item)*)? ']'` where
+    /// `item := position ('..' position)?`
+    fn positions(&mut self) -> Result<ClRegisterBits, ParseClExprError> {
+        self.expect("[")?;
+        let mut positions = Vec::new();
+        if self.eat("]") {
+            return Ok(ClRegisterBits(positions));
+        }
+        loop {
+            let start = self.position()?;
+            if self.eat("..") {
+                let offset = self.offset();
+                let end = self.position()?;
+                if end < start {
+                    return Err(error(offset, "range end is before its start"));
+                }
+                positions.extend(start..end);
+            } else {
+                positions.push(start);
+            }
+            if self.eat("]") {
+                return Ok(ClRegisterBits(positions));
+            }
+            self.expect(",")?;
+        }
+    }
+
+    /// Parses binary operations binding at least as tightly as
+    /// `min_precedence`, which are left-associative.
+    fn expr(&mut self, min_precedence: u8) -> Result<ClArgument, ParseClExprError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let symbol = match self.peek() {
+                Some(Token::Symbol(symbol)) if *symbol != "**" => *symbol,
+                _ => return Ok(lhs),
+            };
+            let precedence = match binary_precedence(symbol) {
+                Some(precedence) if precedence >= min_precedence => precedence,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            let rhs = self.expr(precedence + 1)?;
+            let op = binary_op(symbol, Kind::of(&lhs), Kind::of(&rhs)).unwrap_or_default();
+            lhs = operation(op, vec![lhs, rhs]);
+        }
+    }
+
+    /// `unary := ('!' | '-') unary | power`
+    fn unary(&mut self) -> Result<ClArgument, ParseClExprError> {
+        let symbol = match self.peek() {
+            Some(Token::Symbol(symbol @ ("!" | "-"))) => *symbol,
+            _ => return self.power(),
+        };
+        self.pos += 1;
+        let arg = self.unary()?;
+        let op = unary_op(symbol, Kind::of(&arg)).unwrap_or_default();
+        Ok(operation(op, vec![arg]))
+    }
+
+    /// `power := atom ('**' unary)?`
+    fn power(&mut self) -> Result<ClArgument, ParseClExprError> {
+        let base = self.atom()?;
+        if self.eat("**") {
+            let exponent = self.unary()?;
+            return Ok(operation(ClOp::RegPow, vec![base, exponent]));
+        }
+        Ok(base)
+    }
+
+    /// `atom := integer | variable | name '(' args ')' | '(' expr ')'`
+    fn atom(&mut self) -> Result<ClArgument, ParseClExprError> {
+        let offset = self.offset();
+        match self.next()? {
+            Token::Int(n) => Ok(ClArgument::Terminal(ClTerminal::Int(n))),
+            Token::Symbol("(") => {
+                let arg = self.expr(0)?;
+                self.expect(")")?;
+                Ok(arg)
+            }
+            Token::Ident(name) if self.eat("(") => {
+                let op = ClOp::from_str(&name)
+                    .map_err(|_| error(offset, format!("unknown operation '{name}'")))?;
+                let mut args = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        args.push(self.expr(0)?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(operation(op, args))
+            }
+            Token::Ident(name) => variable(&name)
+                .map(|var| ClArgument::Terminal(ClTerminal::Variable(var)))
+                .ok_or_else(|| error(offset, format!("unknown variable '{name}'"))),
+            token => Err(error(offset, format!("unexpected '{token}'"))),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Rewriting of circuit operations into [`OpType::ClExpr`] operations.
+
+use super::ClExpr;
+use crate::circuit_json::{Command, Operation, SerialCircuit};
+use crate::opbox::OpBox;
+use crate::optype::OpType;
+use crate::register::ElementId;
+
+/// A classical expression together with the arguments of its command.
+pub(crate) type ClExprCommand = (ClExpr, Vec<ElementId>);
+
+/// Rewrites some operations as a sequence of [`OpType::ClExpr`] operations.
+pub(super) trait Rewriter {
+    /// Returns the replacement of an operation applied to `args`, or `None`
+    /// to leave it unchanged. `path` is the JSON pointer to the operation.
+    fn rewrite<P>(
+        &mut self,
+        op: &Operation<P>,
+        args: &[ElementId],
+        path: &str,
+    ) -> Option<Vec<ClExprCommand>>;
+}
+
+/// Returns an [`OpType::ClExpr`] operation.
+pub(super) fn clexpr_operation<P>(expr: ClExpr) -> Operation<P> {
+    let mut op = Operation::from_optype(OpType::ClExpr);
+    op.classical_expr = Some(expr);
+    op
+}
+
+/// Rewrites the commands of a circuit, including those under a classical
+/// condition and in the circuits of `CircBox` and `CustomGate` operations.
+pub(super) fn rewrite_circuit<P: Clone>(
+    circ: &mut SerialCircuit<P>,
+    path: &str,
+    rewriter: &mut impl Rewriter,
+) {
+    let commands = std::mem::take(&mut circ.commands);
+    for (i, mut command) in commands.into_iter().enumerate() {
+        let path = format!("{path}/commands/{i}");
+        match rewrite_command(&mut command, &path, rewriter) {
+            Some(replacement) => circ.commands.extend(replacement),
+            None => circ.commands.push(command),
+        }
+    }
+}
+
+fn rewrite_command<P: Clone>(
+    command: &mut Command<P>,
+    path: &str,
+    rewriter: &mut impl Rewriter,
+) -> Option<Vec<Command<P>>> {
+    let op_path = format!("{path}/op");
+    if let Some(replacement) = rewriter.rewrite(&command.op, &command.args, &op_path) {
+        let replacement = replacement.into_iter().map(|(expr, args)| Command {
+            op: clexpr_operation(expr),
+            args,
+            opgroup: command.opgroup.clone(),
+        });
+        return Some(replacement.collect());
+    }
+
+    if let Some(conditional) = &command.op.conditional {
+        // The condition bits come before the arguments of the inner operation.
+        let width = (conditional.width as usize).min(command.args.len());
+        let (condition, args) = command.args.split_at(width);
+        let inner_path = format!("{op_path}/conditional/op");
+        if let Some(replacement) = rewriter.rewrite(&conditional.op, args, &inner_path) {
+            let replacement = replacement.into_iter().map(|(expr, args)| {
+                let mut op = command.op.clone();
+                if let Some(conditional) = &mut op.conditional {
+                    *conditional.op = clexpr_operation(expr);
+                }
+                Command {
+                    op,
+                    args: condition.iter().cloned().chain(args).collect(),
+                    opgroup: command.opgroup.clone(),
+                }
+            });
+            return Some(replacement.collect());
+        }
+    }
+
+    match &mut command.op.op_box {
+        Some(OpBox::CircBox { circuit, .. }) => {
+            rewrite_circuit(circuit, &format!("{op_path}/box/circuit"), rewriter)
+        }
+        Some(OpBox::CustomGate { gate, .. }) => rewrite_circuit(
+            &mut gate.definition,
+            &format!("{op_path}/box/gate/definition"),
+            rewriter,
+        ),
+        _ => {}
+    }
+    None
+}
@@ -0,0 +1,284 @@
+//! Dependency graph of the commands of a circuit.
+//!
+//! The commands of a [`SerialCircuit`] form a directed acyclic graph, where
+//! each wire (qubit, bit, WASM or RNG wire) connects the consecutive commands
+//! acting on it. [`SerialCircuit::dag`] builds this graph once, so that the
+//! neighbours of a command can be queried without rescanning the command list.
+//!
+//! Commands are identified by their index in [`SerialCircuit::commands`], and
+//! wires by their [`ElementId`]. The condition bits of a `Conditional`
+//! operation are among the command arguments, so the commands writing them
+//! are predecessors of the conditional command.
+//!
+//! The [depth](CommandDag::depth), [layers](CommandDag::layers) and
+//! [critical path](CommandDag::critical_path) of a circuit are computed from
+//! this graph, as well as [resource estimates](CommandDag::resources) for
+//! circuits containing `DummyBox` operations.
+
+mod depth;
+mod resources;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::circuit_json::{Command, SerialCircuit};
+use crate::register::ElementId;
+
+/// The dependency graph of the commands of a circuit.
+///
+/// Built by [`SerialCircuit::dag`].
+#[derive(Clone, Debug)]
+pub struct CommandDag<'c, P = String> {
+    commands: &'c [Command<P>],
+    /// The wires of the circuit, in the order returned by [`CommandDag::wires`].
+    wires: Vec<&'c ElementId>,
+    /// The declared qubits of the circuit.
+    qubits: HashSet<&'c ElementId>,
+    /// The previous and next command on each argument of each command.
+    links: Vec<Vec<Link>>,
+    /// The first and last command on each wire with some command.
+    ends: HashMap<&'c ElementId, (usize, usize)>,
+}
+
+/// The neighbours of a command on one of its wires.
+#[derive(Clone, Copy, Debug, Default)]
+struct Link {
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+impl<P> SerialCircuit<P> {
+    /// Returns the dependency graph of the circuit's commands.
+    ///
+    /// Commands in nested circuits, such as those of `CircBox` operations,
+    /// are not included.
+    pub fn dag(&self) -> CommandDag<'_, P> {
+        CommandDag::new(self)
+    }
+}
+
+impl<'c, P> CommandDag<'c, P> {
+    /// Builds the dependency graph of the circuit's commands.
+    pub fn new(circ: &'c SerialCircuit<P>) -> Self {
+        let mut wires: Vec<&ElementId> = Vec::new();
+        let mut seen = HashSet::new();
+        let declared = circ.qubits.iter().map(|qb| &qb.id);
+        let declared = declared.chain(circ.bits.iter().map(|bit| &bit.id));
+        for id in declared {
+            if seen.insert(id) {
+                wires.push(id);
+            }
+        }
+
+        let mut links: Vec<Vec<Link>> = Vec::with_capacity(circ.commands.len());
+        let mut ends: HashMap<&ElementId, (usize, usize)> = HashMap::new();
+        for (i, command) in circ.commands.iter().enumerate() {
+            let mut command_links = vec![Link::default(); command.args.len()];
+            for (arg, link) in command.args.iter().zip(&mut command_links) {
+                if seen.insert(arg) {
+                    wires.push(arg);
+                }
+                match ends.get_mut(arg) {
+                    // A wire repeated in the arguments of a command does not
+                    // create a self-loop.
+                    Some((_, last)) if *last == i => {}
+                    Some((_, last)) => {
+                        link.prev = Some(*last);
+                        let prev_args = &circ.commands[*last].args;
+                        if let Some(k) = prev_args.iter().position(|id| id == arg) {
+                            links[*last][k].next = Some(i);
+                        }
+                        *last = i;
+                    }
+                    None => {
+                        ends.insert(arg, (i, i));
+                    }
+                }
+            }
+            links.push(command_links);
+        }
+
+        Self {
+            commands: &circ.commands,
+            wires,
+            qubits: circ.qubits.iter().map(|qb| &qb.id).collect(),
+            links,
+            ends,
+        }
+    }
+
+    /// Returns the number of commands.
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Returns `true` if the circuit has no commands.
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Returns the command with the given index.
+    ///
+    /// # Panics
+    ///
+    /// If `command` is not the index of a command.
+    pub fn command(&self, command: usize) -> &'c Command<P> {
+        &self.commands[command]
+    }
+
+    /// Returns the wires of the circuit: the declared qubits and bits,
+    /// followed by the other wires used by the commands (such as WASM and RNG
+    /// wires) in order of first use.
+    pub fn wires(&self) -> impl Iterator<Item = &'c ElementId> + '_ {
+        self.wires.iter().copied()
+    }
+
+    /// Returns the first command acting on a wire.
+    pub fn first(&self, wire: &ElementId) -> Option<usize> {
+        self.ends.get(wire).map(|&(first, _)| first)
+    }
+
+    /// Returns the last command acting on a wire.
+    pub fn last(&self, wire: &ElementId) -> Option<usize> {
+        self.ends.get(wire).map(|&(_, last)| last)
+    }
+
+    /// Returns the commands acting on a wire, in order.
+    pub fn wire_commands(&self, wire: &ElementId) -> impl Iterator<Item = usize> + '_ {
+        let wire = wire.clone();
+        let mut next = self.first(&wire);
+        std::iter::from_fn(move || {
+            let current = next?;
+            next = self.successor(current, &wire);
+            Some(current)
+        })
+    }
+
+    /// Returns the command preceding `command` on `wire`, if `command` acts on
+    /// `wire` and is not the first to do so.
+    pub fn predecessor(&self, command: usize, wire: &ElementId) -> Option<usize> {
+        self.link(command, wire)?.prev
+    }
+
+    /// Returns the command following `command` on `wire`, if `command` acts on
+    /// `wire` and is not the last to do so.
+    pub fn successor(&self, command: usize, wire: &ElementId) -> Option<usize> {
+        self.link(command, wire)?.next
+    }
+
+    /// Returns the predecessors of a command on each of its wires, in the
+    /// order of its arguments. Wires on which the command is the first are
+    /// skipped.
+    ///
+    /// # Panics
+    ///
+    /// If `command` is not the index of a command.
+    pub fn predecessors(
+        &self,
+        command: usize,
+    ) -> impl Iterator<Item = (&'c ElementId, usize)> + '_ {
+        let args = &self.commands[command].args;
+        let links = &self.links[command];
+        args.iter()
+            .zip(links)
+            .filter_map(|(wire, link)| Some((wire, link.prev?)))
+    }
+
+    /// Returns the successors of a command on each of its wires, in the order
+    /// of its arguments. Wires on which the command is the last are skipped.
+    ///
+    /// # Panics
+    ///
+    /// If `command` is not the index of a command.
+    pub fn successors(&self, command: usize) -> impl Iterator<Item = (&'c ElementId, usize)> + '_ {
+        let args = &self.commands[command].args;
+        let links = &self.links[command];
+        args.iter()
+            .zip(links)
+            .filter_map(|(wire, link)| Some((wire, link.next?)))
+    }
+
+    /// Returns the commands with no predecessors, in index order.
+    pub fn front_layer(&self) -> Vec<usize> {
+        (0..self.len())
+            .filter(|&i| self.links[i].iter().all(|link| link.prev.is_none()))
+            .collect()
+    }
+
+    /// Returns the commands with no successors, in index order.
+    pub fn back_layer(&self) -> Vec<usize> {
+        (0..self.len())
+            .filter(|&i| self.links[i].iter().all(|link| link.next.is_none()))
+            .collect()
+    }
+
+    /// Returns an iterator over the commands in topological order.
+    ///
+    /// Commands are visited breadth-first from the [front
+    /// layer](CommandDag::front_layer): a command is yielded once all its
+    /// predecessors have been, and the commands made ready by the same
+    /// command are queued in index order.
+    pub fn topological(&self) -> Topological<'_, 'c, P> {
+        let pending = self
+            .links
+            .iter()
+            .map(|links| distinct(links.iter().filter_map(|link| link.prev)).count())
+            .collect();
+        Topological {
+            dag: self,
+            pending,
+            ready: self.front_layer().into(),
+        }
+    }
+
+    fn link(&self, command: usize, wire: &ElementId) -> Option<&Link> {
+        let k = self
+            .commands
+            .get(command)?
+            .args
+            .iter()
+            .position(|id| id == wire)?;
+        Some(&self.links[command][k])
+    }
+}
+
+/// An iterator over the commands of a [`CommandDag`] in topological order.
+///
+/// Returned by [`CommandDag::topological`].
+#[derive(Clone, Debug)]
+pub struct Topological<'a, 'c, P = String> {
+    dag: &'a CommandDag<'c, P>,
+    /// The number of distinct predecessors of each command not yet visited.
+    pending: Vec<usize>,
+    ready: VecDeque<usize>,
+}
+
+impl<P> Iterator for Topological<'_, '_, P> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let command = self.ready.pop_front()?;
+        let links = &self.dag.links[command];
+        let mut next: Vec<usize> = distinct(links.iter().filter_map(|link| link.next)).collect();
+        next.sort_unstable();
+        for successor in next {
+            self.pending[successor] -= 1;
+            if self.pending[successor] == 0 {
+                self.ready.push_back(successor);
+            }
+        }
+        Some(command)
+    }
+}
+
+/// Removes duplicates from a short list of commands, keeping the first
+/// occurrence.
+fn distinct(commands: impl Iterator<Item = usize>) -> impl Iterator<Item = usize> {
+    let mut seen = Vec::new();
+    commands.filter(move |&c| {
+        let new = !seen.contains(&c);
+        if new {
+            seen.push(c);
+        }
+        new
+    })
+}
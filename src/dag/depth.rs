@@ -0,0 +1,145 @@
+//! Depth and layering of the commands of a circuit.
+//!
+//! As in pytket, the depth of a circuit is the number of commands on the
+//! longest path through its dependency graph, not counting barriers. The
+//! variants restricted to some commands count only those commands, but
+//! still follow the dependencies through the others.
+
+use crate::circuit_json::{Command, SerialCircuit};
+use crate::optype::OpType;
+
+use super::CommandDag;
+
+impl<P> CommandDag<'_, P> {
+    /// Returns the depth of the circuit, ignoring barriers.
+    pub fn depth(&self) -> usize {
+        self.max_depth(|command| command.op.op_type != OpType::Barrier)
+    }
+
+    /// Returns the depth of the circuit, counting only the commands whose
+    /// operation has one of the given types.
+    pub fn depth_by_type(&self, op_types: &[OpType]) -> usize {
+        self.max_depth(|command| op_types.contains(&command.op.op_type))
+    }
+
+    /// Returns the depth of the circuit, counting only the commands acting on
+    /// exactly two qubits, barriers excepted.
+    pub fn two_qubit_depth(&self) -> usize {
+        self.max_depth(|command| {
+            command.op.op_type != OpType::Barrier && self.qubit_count(command) == 2
+        })
+    }
+
+    /// Returns the commands on a longest path through the circuit, in order,
+    /// ignoring barriers. The path has [`CommandDag::depth`] commands.
+    pub fn critical_path(&self) -> Vec<usize> {
+        let weight = |command: &Command<P>| (command.op.op_type != OpType::Barrier) as usize;
+        let depths = self.depths(weight);
+        // The last command of maximal depth, so that the path ends at the back
+        // of the circuit.
+        let Some((mut current, _)) = depths.iter().enumerate().max_by_key(|&(_, &depth)| depth)
+        else {
+            return Vec::new();
+        };
+        let mut path = Vec::new();
+        loop {
+            let command = self.command(current);
+            if weight(command) > 0 {
+                path.push(current);
+            }
+            let before = depths[current] - weight(command);
+            match self
+                .predecessors(current)
+                .find(|&(_, p)| depths[p] == before)
+            {
+                Some((_, p)) => current = p,
+                None => break,
+            }
+        }
+        path.reverse();
+        path
+    }
+
+    /// Returns an iterator over the layers of the circuit.
+    ///
+    /// Each command is in the layer following the last layer of its
+    /// predecessors, so the commands of a layer act on disjoint wires and
+    /// can be applied in parallel. Commands are listed in index order within
+    /// each layer. Unlike [`CommandDag::depth`], barriers are included.
+    pub fn layers(&self) -> impl Iterator<Item = Vec<usize>> {
+        let depths = self.depths(|_| 1);
+        let mut layers = vec![Vec::new(); depths.iter().copied().max().unwrap_or(0)];
+        for (command, depth) in depths.into_iter().enumerate() {
+            layers[depth - 1].push(command);
+        }
+        layers.into_iter()
+    }
+
+    /// Returns the number of arguments of a command that are qubits of the
+    /// circuit.
+    pub(super) fn qubit_count(&self, command: &Command<P>) -> usize {
+        command
+            .args
+            .iter()
+            .filter(|&arg| self.qubits.contains(arg))
+            .count()
+    }
+
+    /// Returns the largest number of counted commands on a path.
+    fn max_depth(&self, counted: impl Fn(&Command<P>) -> bool) -> usize {
+        self.max_weight(|command| counted(command) as usize)
+    }
+
+    /// Returns the largest total weight of the commands on a path.
+    pub(super) fn max_weight(&self, weight: impl Fn(&Command<P>) -> usize) -> usize {
+        self.depths(weight).into_iter().max().unwrap_or(0)
+    }
+
+    /// Returns, for each command, the largest total weight of the commands on
+    /// a path ending with it.
+    fn depths(&self, weight: impl Fn(&Command<P>) -> usize) -> Vec<usize> {
+        let mut depths = vec![0; self.len()];
+        // Predecessors always come earlier in the command list.
+        for command in 0..self.len() {
+            let before = self.predecessors(command).map(|(_, p)| depths[p]).max();
+            depths[command] = before.unwrap_or(0) + weight(self.command(command));
+        }
+        depths
+    }
+}
+
+impl<P> SerialCircuit<P> {
+    /// Returns the depth of the circuit, ignoring barriers.
+    ///
+    /// See [`CommandDag::depth`].
+    pub fn depth(&self) -> usize {
+        self.dag().depth()
+    }
+
+    /// Returns the depth of the circuit, counting only the commands whose
+    /// operation has one of the given types.
+    ///
+    /// See [`CommandDag::depth_by_type`].
+    pub fn depth_by_type(&self, op_types: &[OpType]) -> usize {
+        self.dag().depth_by_type(op_types)
+    }
+
+    /// Returns the depth of the circuit, counting only the commands acting on
+    /// exactly two qubits.
+    ///
+    /// See [`CommandDag::two_qubit_depth`].
+    pub fn two_qubit_depth(&self) -> usize {
+        self.dag().two_qubit_depth()
+    }
+
+    /// Returns an iterator over the layers of commands that can be applied in
+    /// parallel.
+    ///
+    /// See [`CommandDag::layers`].
+    pub fn layers(&self) -> impl Iterator<Item = Vec<&Command<P>>> {
+        let commands = &self.commands;
+        self.dag()
+            .layers()
+            .map(move |layer| layer.into_iter().map(|i| &commands[i]).collect())
+    }
+}
@@ -0,0 +1,117 @@
+//! Resource estimation for circuits containing `DummyBox` operations.
+//!
+//! A `DummyBox` stands for a region of a circuit that has not been
+//! synthesised yet, and declares bounds on its gate counts and depths in its
+//! [`ResourceData`]. [`SerialCircuit::resources`] combines these bounds with
+//! the exact contributions of the other commands, like pytket's
+//! `Circuit.get_resources()`.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::circuit_json::{Command, SerialCircuit};
+use crate::opbox::{OpBox, ResourceBounds, ResourceData};
+use crate::optype::OpType;
+
+use super::CommandDag;
+
+impl<P> CommandDag<'_, P> {
+    /// Returns bounds on the gate counts and depths of the circuit.
+    ///
+    /// Each command that is not a `DummyBox` counts once towards the number
+    /// of operations of its type, and towards the depths it is relevant to
+    /// as in [`CommandDag::depth`], [`CommandDag::depth_by_type`] and
+    /// [`CommandDag::two_qubit_depth`]. A `DummyBox` contributes the bounds
+    /// declared in its resource data instead, and nothing to the counts and
+    /// depths of types it does not mention. Other boxes count as single
+    /// operations.
+    ///
+    /// The minimum (maximum) depths are the depths obtained when every
+    /// `DummyBox` has its minimum (maximum) depth.
+    pub fn resources(&self) -> ResourceData {
+        let commands = (0..self.len()).map(|i| self.command(i));
+
+        let mut op_type_count: HashMap<OpType, ResourceBounds> = HashMap::new();
+        let mut op_types: HashSet<OpType> = HashSet::new();
+        for command in commands {
+            match dummy_data(command) {
+                Some(data) => {
+                    for (&op_type, bounds) in &data.op_type_count {
+                        add(op_type_count.entry(op_type).or_insert(ZERO), bounds);
+                    }
+                    op_types.extend(data.op_type_depth.keys());
+                }
+                None => {
+                    let count = op_type_count.entry(command.op.op_type).or_insert(ZERO);
+                    add(count, &ONE);
+                }
+            }
+        }
+        op_types.extend(op_type_count.keys());
+
+        let gate_depth = self.depth_bounds(|command| match dummy_data(command) {
+            Some(data) => data.gate_depth.clone(),
+            None if command.op.op_type == OpType::Barrier => ZERO,
+            None => ONE,
+        });
+        let op_type_depth = op_types
+            .into_iter()
+            .map(|op_type| {
+                let bounds = self.depth_bounds(|command| match dummy_data(command) {
+                    Some(data) => data.op_type_depth.get(&op_type).cloned().unwrap_or(ZERO),
+                    None if command.op.op_type == op_type => ONE,
+                    None => ZERO,
+                });
+                (op_type, bounds)
+            })
+            .collect();
+        let two_qubit_gate_depth = self.depth_bounds(|command| match dummy_data(command) {
+            Some(data) => data.two_qubit_gate_depth.clone(),
+            None if command.op.op_type == OpType::Barrier => ZERO,
+            None if self.qubit_count(command) == 2 => ONE,
+            None => ZERO,
+        });
+
+        ResourceData {
+            op_type_count,
+            gate_depth,
+            op_type_depth,
+            two_qubit_gate_depth,
+        }
+    }
+
+    /// Returns the bounds on the largest total weight of the commands on a
+    /// path.
+    fn depth_bounds(&self, weight: impl Fn(&Command<P>) -> ResourceBounds) -> ResourceBounds {
+        let min = self.max_weight(|command| weight(command).min as usize);
+        let max = self.max_weight(|command| weight(command).max as usize);
+        ResourceBounds {
+            min: u32::try_from(min).unwrap_or(u32::MAX),
+            max: u32::try_from(max).unwrap_or(u32::MAX),
+        }
+    }
+}
+
+impl<P> SerialCircuit<P> {
+    /// Returns bounds on the gate counts and depths of the circuit.
+    ///
+    /// See [`CommandDag::resources`].
+    pub fn resources(&self) -> ResourceData {
+        self.dag().resources()
+    }
+}
+
+const ZERO: ResourceBounds = ResourceBounds { min: 0, max: 0 };
+const ONE: ResourceBounds = ResourceBounds { min: 1, max: 1 };
+
+/// Returns the resource data of a `DummyBox` command.
+fn dummy_data<P>(command: &Command<P>) -> Option<&ResourceData> {
+    match &command.op.op_box {
+        Some(OpBox::DummyBox { resource_data, .. }) => Some(resource_data),
+        _ => None,
+    }
+}
+
+fn add(total: &mut ResourceBounds, bounds: &ResourceBounds) {
+    total.min = total.min.saturating_add(bounds.min);
+    total.max = total.max.saturating_add(bounds.max);
+}
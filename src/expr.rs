@@ -0,0 +1,269 @@
+//! Symbolic parameter expressions.
+//!
+//! Operation parameters and circuit phases are serialized by pytket as
+//! [symengine](https://github.com/symengine/symengine) expression strings,
+//! such as `"0.5*a + 1/3"`. [`Expr`] is a typed representation of these
+//! strings that can be parsed, inspected, and printed back in a form pytket
+//! accepts.
+//!
+//! An [`Expr`] serializes to and from its string form, so it can be used as
+//! the parameter type of a [`SerialCircuit`]:
+//!
+//! ```
+//! # use tket_json_rs::SerialCircuit;
+//! # use tket_json_rs::expr::Expr;
+//! let circ: SerialCircuit = SerialCircuit::new(None, "0.5*a".to_string());
+//! let circ: SerialCircuit<Expr> = circ.map_params(|p| p.parse().unwrap());
+//! assert_eq!(circ.phase.to_string(), "0.5*a");
+//! ```
+//!
+//!   [`SerialCircuit`]: crate::circuit_json::SerialCircuit
+
+mod eval;
+mod parse;
+mod substitute;
+mod symbols;
+pub(crate) mod visit;
+pub(crate) mod write;
+
+use std::fmt;
+use std::str::FromStr;
+
+use derive_more::{Display, Error};
+use serde::{Deserialize, Serialize};
+use strum::EnumString;
+
+pub(crate) use eval::evaluate_at;
+pub use eval::{EvaluateParamsError, UnboundSymbolError};
+pub use substitute::InvalidParamError;
+
+/// A symbolic expression, as used for operation parameters.
+///
+/// Angles are expressed in half-turns, so `1` corresponds to a rotation by π.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Expr {
+    /// An integer literal.
+    Integer(i64),
+    /// A floating point literal.
+    ///
+    /// Infinities are written as symengine's `oo` and `-oo`, and NaN as
+    /// `nan`. Symengine's complex infinity, `zoo`, is read as NaN.
+    Float(f64),
+    /// An exact rational number, `numerator/denominator`.
+    Rational(i64, i64),
+    /// A free symbol.
+    Symbol(String),
+    /// The constant π.
+    Pi,
+    /// Euler's number.
+    E,
+    /// Negation of an expression.
+    Neg(Box<Expr>),
+    /// Sum of two expressions.
+    Add(Box<Expr>, Box<Expr>),
+    /// Difference of two expressions.
+    Sub(Box<Expr>, Box<Expr>),
+    /// Product of two expressions.
+    Mul(Box<Expr>, Box<Expr>),
+    /// Quotient of two expressions.
+    Div(Box<Expr>, Box<Expr>),
+    /// An expression raised to a power.
+    Pow(Box<Expr>, Box<Expr>),
+    /// A function applied to a list of arguments.
+    Func(Function, Vec<Expr>),
+}
+
+/// Functions that may appear in an [`Expr`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, EnumString, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+#[non_exhaustive]
+pub enum Function {
+    /// Sine.
+    Sin,
+    /// Cosine.
+    Cos,
+    /// Tangent.
+    Tan,
+    /// Inverse sine.
+    Asin,
+    /// Inverse cosine.
+    Acos,
+    /// Inverse tangent.
+    Atan,
+    /// Two-argument inverse tangent, `atan2(y, x)`.
+    Atan2,
+    /// Hyperbolic sine.
+    Sinh,
+    /// Hyperbolic cosine.
+    Cosh,
+    /// Hyperbolic tangent.
+    Tanh,
+    /// Inverse hyperbolic sine.
+    Asinh,
+    /// Inverse hyperbolic cosine.
+    Acosh,
+    /// Inverse hyperbolic tangent.
+    Atanh,
+    /// Exponential function.
+    Exp,
+    /// Natural logarithm.
+    Log,
+    /// Square root.
+    Sqrt,
+    /// Absolute value.
+    Abs,
+}
+
+impl Function {
+    /// Returns the number of arguments the function takes.
+    pub fn arity(self) -> usize {
+        match self {
+            Function::Atan2 => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Error produced when parsing an [`Expr`] from a string.
+#[derive(Clone, Debug, PartialEq, Eq, Display, Error)]
+#[display("invalid expression at position {position}: {message}")]
+pub struct ParseExprError {
+    /// Byte offset of the error in the input string.
+    pub position: usize,
+    /// Description of the error.
+    pub message: String,
+}
+
+impl Expr {
+    /// Returns a new symbol expression.
+    pub fn symbol(name: impl Into<String>) -> Self {
+        Expr::Symbol(name.into())
+    }
+
+    /// Returns the binding strength of the expression's top-level operator,
+    /// used to decide where parentheses are needed when printing.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Add(..) | Expr::Sub(..) => 1,
+            Expr::Mul(..) | Expr::Div(..) | Expr::Rational(..) => 2,
+            Expr::Neg(_) => 3,
+            Expr::Integer(n) if *n < 0 => 3,
+            Expr::Float(x) if x.is_sign_negative() => 3,
+            Expr::Pow(..) => 4,
+            _ => 5,
+        }
+    }
+
+    /// Writes the expression, wrapped in parentheses if it binds less tightly
+    /// than `min_precedence`.
+    fn fmt_prec(&self, f: &mut fmt::Formatter<'_>, min_precedence: u8) -> fmt::Result {
+        if self.precedence() < min_precedence {
+            write!(f, "({self})")
+        } else {
+            write!(f, "{self}")
+        }
+    }
+
+    fn fmt_binary(
+        f: &mut fmt::Formatter<'_>,
+        lhs: &Expr,
+        op: &str,
+        rhs: &Expr,
+        precedence: u8,
+    ) -> fmt::Result {
+        lhs.fmt_prec(f, precedence)?;
+        f.write_str(op)?;
+        rhs.fmt_prec(f, precedence + 1)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Integer(n) => write!(f, "{n}"),
+            Expr::Float(x) if x.is_nan() => f.write_str("nan"),
+            Expr::Float(x) if x.is_infinite() => f.write_str(if *x > 0.0 { "oo" } else { "-oo" }),
+            Expr::Float(x) => write!(f, "{x:?}"),
+            Expr::Rational(n, d) => write!(f, "{n}/{d}"),
+            Expr::Symbol(s) => f.write_str(s),
+            Expr::Pi => f.write_str("pi"),
+            Expr::E => f.write_str("E"),
+            Expr::Neg(e) => {
+                f.write_str("-")?;
+                e.fmt_prec(f, 3)
+            }
+            Expr::Add(a, b) => Self::fmt_binary(f, a, " + ", b, 1),
+            Expr::Sub(a, b) => Self::fmt_binary(f, a, " - ", b, 1),
+            Expr::Mul(a, b) => Self::fmt_binary(f, a, "*", b, 2),
+            Expr::Div(a, b) => Self::fmt_binary(f, a, "/", b, 2),
+            Expr::Pow(a, b) => {
+                // Exponentiation is right-associative, and binds tighter than
+                // a unary minus on its base but not on its exponent.
+                a.fmt_prec(f, 5)?;
+                f.write_str("**")?;
+                b.fmt_prec(f, 3)
+            }
+            Expr::Func(func, args) => {
+                write!(f, "{func}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = ParseExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse::parse(s)
+    }
+}
+
+impl Default for Expr {
+    fn default() -> Self {
+        Expr::Integer(0)
+    }
+}
+
+impl From<f64> for Expr {
+    fn from(x: f64) -> Self {
+        Expr::Float(x)
+    }
+}
+
+impl From<i64> for Expr {
+    fn from(n: i64) -> Self {
+        Expr::Integer(n)
+    }
+}
+
+impl Serialize for Expr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Expr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "schemars")]
+impl schemars::JsonSchema for Expr {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Expr".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        String::json_schema(generator)
+    }
+}
@@ -0,0 +1,214 @@
+//! Numeric evaluation of expressions and circuit parameters.
+
+use std::collections::HashMap;
+
+use derive_more::{Display, Error};
+
+use super::visit::{self, ParamSite};
+use super::{Expr, Function, ParseExprError};
+use crate::circuit_json::SerialCircuit;
+
+/// Error produced when evaluating an [`Expr`] that refers to a symbol with no
+/// assigned value.
+#[derive(Clone, Debug, PartialEq, Eq, Display, Error)]
+#[display("unbound symbol `{symbol}`")]
+pub struct UnboundSymbolError {
+    /// The name of the symbol.
+    #[error(not(source))]
+    pub symbol: String,
+}
+
+/// Error produced by [`SerialCircuit::evaluate_params`].
+#[derive(Clone, Debug, PartialEq, Display, Error)]
+#[non_exhaustive]
+pub enum EvaluateParamsError {
+    /// A parameter is not a valid expression.
+    #[display("{path}: {source}")]
+    Parse {
+        /// JSON pointer to the parameter.
+        path: String,
+        /// The parsing error.
+        source: ParseExprError,
+    },
+    /// A parameter refers to a symbol with no assigned value.
+    #[display("{path}: unbound symbol `{symbol}`")]
+    UnboundSymbol {
+        /// JSON pointer to the parameter.
+        path: String,
+        /// The name of the symbol.
+        symbol: String,
+    },
+}
+
+impl Expr {
+    /// Evaluates the expression, looking up the value of free symbols in
+    /// `bindings`.
+    pub fn evaluate(&self, bindings: &HashMap<String, f64>) -> Result<f64, UnboundSymbolError> {
+        let eval = |e: &Expr| e.evaluate(bindings);
+        Ok(match self {
+            Expr::Integer(n) => *n as f64,
+            Expr::Float(x) => *x,
+            Expr::Rational(n, d) => *n as f64 / *d as f64,
+            Expr::Symbol(s) => *bindings
+                .get(s)
+                .ok_or_else(|| UnboundSymbolError { symbol: s.clone() })?,
+            Expr::Pi => std::f64::consts::PI,
+            Expr::E => std::f64::consts::E,
+            Expr::Neg(e) => -eval(e)?,
+            Expr::Add(a, b) => eval(a)? + eval(b)?,
+            Expr::Sub(a, b) => eval(a)? - eval(b)?,
+            Expr::Mul(a, b) => eval(a)? * eval(b)?,
+            Expr::Div(a, b) => eval(a)? / eval(b)?,
+            Expr::Pow(a, b) => eval(a)?.powf(eval(b)?),
+            Expr::Func(func, args) => {
+                let args = args.iter().map(eval).collect::<Result<Vec<_>, _>>()?;
+                func.apply(&args)
+            }
+        })
+    }
+}
+
+impl Function {
+    /// Applies the function to numeric arguments.
+    fn apply(self, args: &[f64]) -> f64 {
+        let x = args[0];
+        match self {
+            Function::Sin => x.sin(),
+            Function::Cos => x.cos(),
+            Function::Tan => x.tan(),
+            Function::Asin => x.asin(),
+            Function::Acos => x.acos(),
+            Function::Atan => x.atan(),
+            Function::Atan2 => x.atan2(args[1]),
+            Function::Sinh => x.sinh(),
+            Function::Cosh => x.cosh(),
+            Function::Tanh => x.tanh(),
+            Function::Asinh => x.asinh(),
+            Function::Acosh => x.acosh(),
+            Function::Atanh => x.atanh(),
+            Function::Exp => x.exp(),
+            Function::Log => x.ln(),
+            Function::Sqrt => x.sqrt(),
+            Function::Abs => x.abs(),
+        }
+    }
+}
+
+impl EvaluateParamsError {
+    /// Prepends `prefix` to the JSON pointer of the error, for parameters of
+    /// nested circuits.
+    pub(crate) fn with_path_prefix(self, prefix: &str) -> Self {
+        match self {
+            EvaluateParamsError::Parse { path, source } => EvaluateParamsError::Parse {
+                path: format!("{prefix}{path}"),
+                source,
+            },
+            EvaluateParamsError::UnboundSymbol { path, symbol } => {
+                EvaluateParamsError::UnboundSymbol {
+                    path: format!("{prefix}{path}"),
+                    symbol,
+                }
+            }
+        }
+    }
+}
+
+/// Parses and evaluates a parameter expression located at `path`.
+pub(crate) fn evaluate_at(
+    param: &str,
+    path: &str,
+    bindings: &HashMap<String, f64>,
+) -> Result<f64, EvaluateParamsError> {
+    let expr: Expr = param.parse().map_err(|source| EvaluateParamsError::Parse {
+        path: path.to_string(),
+        source,
+    })?;
+    expr.evaluate(bindings)
+        .map_err(|e| EvaluateParamsError::UnboundSymbol {
+            path: path.to_string(),
+            symbol: e.symbol,
+        })
+}
+
+impl SerialCircuit {
+    /// Evaluates all the parameters of the circuit, given values for its free
+    /// symbols.
+    ///
+    /// The command parameters and the global phase become numeric values.
+    /// Parameters stored inside operation boxes and conditional operations
+    /// are kept as strings, but replaced by their numeric value.
+    ///
+    /// Parameters inside `CustomGate` definitions that depend on the gate's
+    /// own arguments are not evaluated, but the values of the other symbols
+    /// are substituted into them.
+    pub fn evaluate_params(
+        &self,
+        bindings: &HashMap<String, f64>,
+    ) -> Result<SerialCircuit<f64>, EvaluateParamsError> {
+        let mut circ = self.clone();
+
+        let mut evaluate_nested = |param: &mut String, site: ParamSite| {
+            let expr: Expr = param.parse().map_err(|source| EvaluateParamsError::Parse {
+                path: site.path.to_string(),
+                source,
+            })?;
+            let symbols = expr.free_symbols();
+            if let Some(symbol) = symbols
+                .iter()
+                .find(|&s| !bindings.contains_key(s) && !site.bound.contains(s))
+            {
+                return Err(EvaluateParamsError::UnboundSymbol {
+                    path: site.path.to_string(),
+                    symbol: symbol.clone(),
+                });
+            }
+            if symbols.iter().any(|s| site.bound.contains(s)) {
+                // Gate arguments shadow the symbols of the enclosing circuit.
+                let values = symbols
+                    .into_iter()
+                    .filter(|s| !site.bound.contains(s))
+                    .map(|s| {
+                        let value = Expr::Float(bindings[&s]);
+                        (s, value)
+                    })
+                    .collect();
+                *param = expr.substitute(&values).to_string();
+            } else {
+                *param = Expr::Float(evaluate_at(param, site.path, bindings)?).to_string();
+            }
+            Ok(())
+        };
+        for (i, command) in circ.commands.iter_mut().enumerate() {
+            let path = format!("/commands/{i}/op");
+            visit::visit_nested(&mut command.op, &path, &[], &mut evaluate_nested)?;
+        }
+
+        let phase = evaluate_at(&circ.phase, "/phase", bindings)?;
+        let commands = circ
+            .commands
+            .into_iter()
+            .enumerate()
+            .map(|(i, command)| {
+                let params = command.op.params.iter().flatten().enumerate();
+                let mut values = params
+                    .map(|(j, p)| evaluate_at(p, &format!("/commands/{i}/op/params/{j}"), bindings))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter();
+                Ok(command.map_params(|_| values.next().unwrap_or_default()))
+            })
+            .collect::<Result<_, EvaluateParamsError>>()?;
+
+        Ok(SerialCircuit {
+            name: circ.name,
+            phase,
+            commands,
+            qubits: circ.qubits,
+            bits: circ.bits,
+            implicit_permutation: circ.implicit_permutation,
+            number_of_ws: circ.number_of_ws,
+            number_of_rs: circ.number_of_rs,
+            created_qubits: circ.created_qubits,
+            discarded_qubits: circ.discarded_qubits,
+        })
+    }
+}
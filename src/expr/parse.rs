@@ -0,0 +1,266 @@
+//! Parser for symengine expression strings.
+
+use std::iter::Peekable;
+use std::str::{CharIndices, FromStr};
+
+use super::{Expr, Function, ParseExprError};
+
+/// A lexical token in an expression string.
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Integer(i64),
+    Float(f64),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Pow,
+    LParen,
+    RParen,
+    Comma,
+}
+
+/// Parses an expression string.
+pub(super) fn parse(input: &str) -> Result<Expr, ParseExprError> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        input_len: input.len(),
+    };
+    let expr = parser.expr()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(expr),
+        Some((offset, token)) => Err(error(*offset, format!("unexpected token {token:?}"))),
+    }
+}
+
+fn error(position: usize, message: impl Into<String>) -> ParseExprError {
+    ParseExprError {
+        position,
+        message: message.into(),
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, ParseExprError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '0'..='9' | '.' => number(input, &mut chars)?,
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                Token::Ident(input[start..end].to_string())
+            }
+            _ => {
+                chars.next();
+                match c {
+                    '+' => Token::Plus,
+                    '-' => Token::Minus,
+                    '*' if chars.next_if(|&(_, c)| c == '*').is_some() => Token::Pow,
+                    '*' => Token::Star,
+                    '^' => Token::Pow,
+                    '/' => Token::Slash,
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    ',' => Token::Comma,
+                    _ => return Err(error(start, format!("unexpected character '{c}'"))),
+                }
+            }
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+/// Lexes a numeric literal, with an optional fractional part and exponent.
+fn number(input: &str, chars: &mut Peekable<CharIndices>) -> Result<Token, ParseExprError> {
+    let start = chars.peek().map_or(input.len(), |&(i, _)| i);
+    let mut is_float = false;
+    let mut end = start;
+    while let Some(&(i, c)) = chars.peek() {
+        match c {
+            '0'..='9' => {}
+            '.' => is_float = true,
+            'e' | 'E' => {
+                // Only treat this as an exponent if it is followed by digits,
+                // so that e.g. `2E` is not consumed as a number.
+                let rest = &input[i + 1..];
+                let rest = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+                if !rest.starts_with(|c: char| c.is_ascii_digit()) {
+                    break;
+                }
+                is_float = true;
+                chars.next();
+                chars.next_if(|&(_, c)| c == '+' || c == '-');
+            }
+            _ => break,
+        }
+        chars.next();
+        end = chars.peek().map_or(input.len(), |&(i, _)| i);
+    }
+    let literal = &input[start..end];
+    // Integers too large for an `i64` are read as floats.
+    let token = match i64::from_str(literal) {
+        Ok(n) if !is_float => Some(Token::Integer(n)),
+        _ => f64::from_str(literal).map(Token::Float).ok(),
+    };
+    token.ok_or_else(|| error(start, format!("invalid number '{literal}'")))
+}
+
+/// A recursive descent parser over a token list.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    input_len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.input_len, |(offset, _)| *offset)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), ParseExprError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(error(self.offset(), format!("expected {token:?}")))
+        }
+    }
+
+    /// `expr := term (('+' | '-') term)*`
+    fn expr(&mut self) -> Result<Expr, ParseExprError> {
+        let mut lhs = self.term()?;
+        loop {
+            if self.eat(&Token::Plus) {
+                lhs = Expr::Add(Box::new(lhs), Box::new(self.term()?));
+            } else if self.eat(&Token::Minus) {
+                lhs = Expr::Sub(Box::new(lhs), Box::new(self.term()?));
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+
+    /// `term := unary (('*' | '/') unary)*`
+    fn term(&mut self) -> Result<Expr, ParseExprError> {
+        let mut lhs = self.unary()?;
+        loop {
+            if self.eat(&Token::Star) {
+                lhs = Expr::Mul(Box::new(lhs), Box::new(self.unary()?));
+            } else if self.eat(&Token::Slash) {
+                let rhs = self.unary()?;
+                lhs = match (lhs, rhs) {
+                    (Expr::Integer(n), Expr::Integer(d)) => Expr::Rational(n, d),
+                    (lhs, rhs) => Expr::Div(Box::new(lhs), Box::new(rhs)),
+                };
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+
+    /// `unary := ('-' | '+') unary | power`
+    fn unary(&mut self) -> Result<Expr, ParseExprError> {
+        if self.eat(&Token::Plus) {
+            return self.unary();
+        }
+        if !self.eat(&Token::Minus) {
+            return self.power();
+        }
+        // Fold the sign into a numeric literal, unless the literal is the base
+        // of a power (`-2**2` is `-(2**2)`).
+        let next_is_pow = self.tokens.get(self.pos + 1).map(|(_, t)| t) == Some(&Token::Pow);
+        match self.peek() {
+            Some(&Token::Integer(n)) if !next_is_pow => {
+                self.pos += 1;
+                Ok(Expr::Integer(-n))
+            }
+            Some(&Token::Float(x)) if !next_is_pow => {
+                self.pos += 1;
+                Ok(Expr::Float(-x))
+            }
+            _ => Ok(Expr::Neg(Box::new(self.unary()?))),
+        }
+    }
+
+    /// `power := atom ('**' unary)?`
+    fn power(&mut self) -> Result<Expr, ParseExprError> {
+        let base = self.atom()?;
+        if self.eat(&Token::Pow) {
+            let exponent = self.unary()?;
+            return Ok(Expr::Pow(Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    /// `atom := number | symbol | function '(' args ')' | '(' expr ')'`
+    fn atom(&mut self) -> Result<Expr, ParseExprError> {
+        let offset = self.offset();
+        let Some((_, token)) = self.tokens.get(self.pos).cloned() else {
+            return Err(error(offset, "unexpected end of expression"));
+        };
+        self.pos += 1;
+        match token {
+            Token::Integer(n) => Ok(Expr::Integer(n)),
+            Token::Float(x) => Ok(Expr::Float(x)),
+            Token::LParen => {
+                let expr = self.expr()?;
+                self.expect(&Token::RParen)?;
+                Ok(expr)
+            }
+            Token::Ident(name) if self.peek() == Some(&Token::LParen) => {
+                let func = Function::from_str(&name)
+                    .map_err(|_| error(offset, format!("unknown function '{name}'")))?;
+                self.pos += 1;
+                let mut args = vec![self.expr()?];
+                while self.eat(&Token::Comma) {
+                    args.push(self.expr()?);
+                }
+                self.expect(&Token::RParen)?;
+                if args.len() != func.arity() {
+                    return Err(error(
+                        offset,
+                        format!("{func} takes {} arguments", func.arity()),
+                    ));
+                }
+                Ok(Expr::Func(func, args))
+            }
+            Token::Ident(name) => Ok(match name.as_str() {
+                "pi" => Expr::Pi,
+                "E" => Expr::E,
+                "oo" => Expr::Float(f64::INFINITY),
+                "nan" | "zoo" => Expr::Float(f64::NAN),
+                _ => Expr::Symbol(name),
+            }),
+            token => Err(error(offset, format!("unexpected token {token:?}"))),
+        }
+    }
+}
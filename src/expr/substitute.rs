@@ -0,0 +1,87 @@
+//! Substitution of symbols in expressions and circuit parameters.
+
+use std::collections::HashMap;
+
+use derive_more::{Display, Error};
+
+use super::visit::{self, ParamSite};
+use super::{Expr, ParseExprError};
+use crate::circuit_json::SerialCircuit;
+
+/// Error produced by [`SerialCircuit::symbol_substitution`] and
+/// [`SerialCircuit::free_symbols`] when a parameter is not a valid
+/// expression.
+#[derive(Clone, Debug, PartialEq, Eq, Display, Error)]
+#[display("{path}: {source}")]
+pub struct InvalidParamError {
+    /// JSON pointer to the parameter.
+    #[error(not(source))]
+    pub path: String,
+    /// The parsing error.
+    pub source: ParseExprError,
+}
+
+impl Expr {
+    /// Returns a copy of the expression with the symbols in `map` replaced by
+    /// their assigned expressions.
+    ///
+    /// Symbols not present in `map` are left unchanged.
+    pub fn substitute(&self, map: &HashMap<String, Expr>) -> Expr {
+        self.substitute_except(map, &[])
+    }
+
+    /// Like [`Expr::substitute`], but leaves the symbols in `excluded`
+    /// unchanged.
+    fn substitute_except(&self, map: &HashMap<String, Expr>, excluded: &[String]) -> Expr {
+        let sub = |e: &Expr| Box::new(e.substitute_except(map, excluded));
+        match self {
+            Expr::Symbol(s) if !excluded.contains(s) => map.get(s).unwrap_or(self).clone(),
+            Expr::Neg(e) => Expr::Neg(sub(e)),
+            Expr::Add(a, b) => Expr::Add(sub(a), sub(b)),
+            Expr::Sub(a, b) => Expr::Sub(sub(a), sub(b)),
+            Expr::Mul(a, b) => Expr::Mul(sub(a), sub(b)),
+            Expr::Div(a, b) => Expr::Div(sub(a), sub(b)),
+            Expr::Pow(a, b) => Expr::Pow(sub(a), sub(b)),
+            Expr::Func(func, args) => Expr::Func(
+                *func,
+                args.iter()
+                    .map(|e| e.substitute_except(map, excluded))
+                    .collect(),
+            ),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl SerialCircuit {
+    /// Replaces free symbols in the circuit by the expressions assigned to
+    /// them in `map`.
+    ///
+    /// This covers the command parameters and global phase, as well as the
+    /// expressions stored inside operation boxes, conditional operations, and
+    /// nested circuits. Inside a `CustomGate` definition, symbols that name
+    /// one of the gate's arguments are not replaced.
+    ///
+    /// Parameters that contain none of the substituted symbols are left
+    /// untouched. If any parameter fails to parse, the circuit is not
+    /// modified.
+    pub fn symbol_substitution(
+        &mut self,
+        map: &HashMap<String, Expr>,
+    ) -> Result<(), InvalidParamError> {
+        let mut circ = self.clone();
+        visit::visit_circuit(&mut circ, "", &[], &mut |param, site: ParamSite| {
+            let expr: Expr = param.parse().map_err(|source| InvalidParamError {
+                path: site.path.to_string(),
+                source,
+            })?;
+            let substituted = expr.substitute_except(map, site.bound);
+            if substituted != expr {
+                *param = substituted.to_string();
+            }
+            Ok(())
+        })?;
+        *self = circ;
+        Ok(())
+    }
+}
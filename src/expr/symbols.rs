@@ -0,0 +1,60 @@
+//! Collection of the free symbols in expressions and circuit parameters.
+
+use std::collections::BTreeSet;
+
+use super::visit::{self, ParamSite};
+use super::{Expr, InvalidParamError};
+use crate::circuit_json::SerialCircuit;
+
+impl Expr {
+    /// Returns the names of all the symbols appearing in the expression.
+    pub fn free_symbols(&self) -> BTreeSet<String> {
+        let mut symbols = BTreeSet::new();
+        self.collect_symbols(&mut symbols);
+        symbols
+    }
+
+    fn collect_symbols(&self, symbols: &mut BTreeSet<String>) {
+        match self {
+            Expr::Symbol(s) => {
+                symbols.insert(s.clone());
+            }
+            Expr::Neg(e) => e.collect_symbols(symbols),
+            Expr::Add(a, b)
+            | Expr::Sub(a, b)
+            | Expr::Mul(a, b)
+            | Expr::Div(a, b)
+            | Expr::Pow(a, b) => {
+                a.collect_symbols(symbols);
+                b.collect_symbols(symbols);
+            }
+            Expr::Func(_, args) => args.iter().for_each(|e| e.collect_symbols(symbols)),
+            _ => {}
+        }
+    }
+}
+
+impl SerialCircuit {
+    /// Returns the names of all the free symbols used in the circuit.
+    ///
+    /// This includes the symbols in command parameters and the global phase,
+    /// as well as the ones in expressions stored inside operation boxes,
+    /// conditional operations, and nested circuits. Symbols inside a
+    /// `CustomGate` definition that name one of the gate's arguments are not
+    /// free, and are not reported.
+    ///
+    /// Returns an error if a parameter is not a valid expression.
+    pub fn free_symbols(&self) -> Result<BTreeSet<String>, InvalidParamError> {
+        let mut symbols = BTreeSet::new();
+        visit::visit_circuit_ref(self, "", &[], &mut |param, site: ParamSite| {
+            let expr: Expr = param.parse().map_err(|source| InvalidParamError {
+                path: site.path.to_string(),
+                source,
+            })?;
+            let free = expr.free_symbols().into_iter();
+            symbols.extend(free.filter(|s| !site.bound.contains(s)));
+            Ok(())
+        })?;
+        Ok(symbols)
+    }
+}
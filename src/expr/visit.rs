@@ -0,0 +1,262 @@
+//! Traversal of the parameter expressions stored in a circuit.
+//!
+//! Besides the top-level command parameters, expression strings appear inside
+//! operation boxes, conditional operations, and nested circuits. These
+//! functions visit all of them, reporting the JSON pointer of each one.
+
+use crate::circuit_json::{Classical, Operation, SerialCircuit};
+use crate::opbox::OpBox;
+
+/// A parameter expression found in a circuit.
+pub(crate) struct ParamSite<'a> {
+    /// JSON pointer to the expression.
+    pub path: &'a str,
+    /// Symbols bound by the arguments of an enclosing `CustomGate` definition.
+    pub bound: &'a [String],
+}
+
+/// Visits the phase and every parameter of a circuit, including the ones in
+/// nested operations.
+pub(crate) fn visit_circuit<E>(
+    circ: &mut SerialCircuit,
+    path: &str,
+    bound: &[String],
+    f: &mut impl FnMut(&mut String, ParamSite) -> Result<(), E>,
+) -> Result<(), E> {
+    let phase_path = format!("{path}/phase");
+    f(
+        &mut circ.phase,
+        ParamSite {
+            path: &phase_path,
+            bound,
+        },
+    )?;
+    for (i, command) in circ.commands.iter_mut().enumerate() {
+        visit_op(
+            &mut command.op,
+            &format!("{path}/commands/{i}/op"),
+            bound,
+            f,
+        )?;
+    }
+    Ok(())
+}
+
+/// Visits the parameters of an operation, and the ones nested inside it.
+pub(crate) fn visit_op<E>(
+    op: &mut Operation,
+    path: &str,
+    bound: &[String],
+    f: &mut impl FnMut(&mut String, ParamSite) -> Result<(), E>,
+) -> Result<(), E> {
+    for (i, param) in op.params.iter_mut().flatten().enumerate() {
+        let path = format!("{path}/params/{i}");
+        f(param, ParamSite { path: &path, bound })?;
+    }
+    visit_nested(op, path, bound, f)
+}
+
+/// Visits the parameters nested inside an operation, excluding the
+/// operation's own `params`.
+///
+/// Nested parameters are always expression strings, regardless of the
+/// parameter type of the operation.
+pub(crate) fn visit_nested<P, E>(
+    op: &mut Operation<P>,
+    path: &str,
+    bound: &[String],
+    f: &mut impl FnMut(&mut String, ParamSite) -> Result<(), E>,
+) -> Result<(), E> {
+    if let Some(cond) = &mut op.conditional {
+        visit_op(&mut cond.op, &format!("{path}/conditional/op"), bound, f)?;
+    }
+    if let Some(Classical::MultiBit { op: inner, .. }) = op.classical.as_deref_mut() {
+        visit_op(inner, &format!("{path}/classical/op"), bound, f)?;
+    }
+    if let Some(op_box) = &mut op.op_box {
+        visit_box(op_box, &format!("{path}/box"), bound, f)?;
+    }
+    Ok(())
+}
+
+/// Visits the expressions stored in an operation box.
+fn visit_box<E>(
+    op_box: &mut OpBox,
+    path: &str,
+    bound: &[String],
+    f: &mut impl FnMut(&mut String, ParamSite) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut visit = |param: &mut String, path: String| f(param, ParamSite { path: &path, bound });
+    match op_box {
+        OpBox::CircBox { circuit, .. } => {
+            return visit_circuit(circuit, &format!("{path}/circuit"), bound, f);
+        }
+        OpBox::PauliExpBox { phase, .. } => visit(phase, format!("{path}/phase"))?,
+        OpBox::PauliExpPairBox { phase_pair, .. } => {
+            for (i, phase) in phase_pair.iter_mut().enumerate() {
+                visit(phase, format!("{path}/phase_pair/{i}"))?;
+            }
+        }
+        OpBox::PauliExpCommutingSetBox { pauli_gadgets, .. }
+        | OpBox::TermSequenceBox { pauli_gadgets, .. } => {
+            for (i, (_, phase)) in pauli_gadgets.iter_mut().enumerate() {
+                visit(phase, format!("{path}/pauli_gadgets/{i}/1"))?;
+            }
+        }
+        OpBox::PhasePolyBox {
+            phase_polynomial, ..
+        } => {
+            for (i, terms) in phase_polynomial.iter_mut().enumerate() {
+                for (j, (_, coeff)) in terms.iter_mut().enumerate() {
+                    visit(coeff, format!("{path}/phase_polynomial/{i}/{j}/1"))?;
+                }
+            }
+        }
+        OpBox::CustomGate { gate, params, .. } => {
+            for (i, param) in params.iter_mut().enumerate() {
+                visit(param, format!("{path}/params/{i}"))?;
+            }
+            let mut gate_bound = bound.to_vec();
+            gate_bound.extend(gate.args.iter().cloned());
+            let definition_path = format!("{path}/gate/definition");
+            return visit_circuit(&mut gate.definition, &definition_path, &gate_bound, f);
+        }
+        OpBox::QControlBox { op, .. } => return visit_op(op, &format!("{path}/op"), bound, f),
+        OpBox::ConjugationBox {
+            compute,
+            action,
+            uncompute,
+            ..
+        } => {
+            visit_op(compute, &format!("{path}/compute"), bound, f)?;
+            visit_op(action, &format!("{path}/action"), bound, f)?;
+            if let Some(uncompute) = uncompute {
+                visit_op(uncompute, &format!("{path}/uncompute"), bound, f)?;
+            }
+        }
+        OpBox::MultiplexorBox { op_map, .. }
+        | OpBox::MultiplexedRotationBox { op_map, .. }
+        | OpBox::MultiplexedU2Box { op_map, .. }
+        | OpBox::MultiplexedTensoredU2Box { op_map, .. } => {
+            for (i, (_, op)) in op_map.iter_mut().enumerate() {
+                visit_op(op, &format!("{path}/op_map/{i}/1"), bound, f)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Visits the phase and every parameter of a circuit without modifying them,
+/// like [`visit_circuit`].
+pub(crate) fn visit_circuit_ref<E>(
+    circ: &SerialCircuit,
+    path: &str,
+    bound: &[String],
+    f: &mut impl FnMut(&str, ParamSite) -> Result<(), E>,
+) -> Result<(), E> {
+    let phase_path = format!("{path}/phase");
+    f(
+        &circ.phase,
+        ParamSite {
+            path: &phase_path,
+            bound,
+        },
+    )?;
+    for (i, command) in circ.commands.iter().enumerate() {
+        visit_op_ref(&command.op, &format!("{path}/commands/{i}/op"), bound, f)?;
+    }
+    Ok(())
+}
+
+/// Visits the parameters of an operation, and the ones nested inside it,
+/// like [`visit_op`].
+fn visit_op_ref<E>(
+    op: &Operation,
+    path: &str,
+    bound: &[String],
+    f: &mut impl FnMut(&str, ParamSite) -> Result<(), E>,
+) -> Result<(), E> {
+    for (i, param) in op.params.iter().flatten().enumerate() {
+        let path = format!("{path}/params/{i}");
+        f(param, ParamSite { path: &path, bound })?;
+    }
+    if let Some(cond) = &op.conditional {
+        visit_op_ref(&cond.op, &format!("{path}/conditional/op"), bound, f)?;
+    }
+    if let Some(Classical::MultiBit { op: inner, .. }) = op.classical.as_deref() {
+        visit_op_ref(inner, &format!("{path}/classical/op"), bound, f)?;
+    }
+    if let Some(op_box) = &op.op_box {
+        visit_box_ref(op_box, &format!("{path}/box"), bound, f)?;
+    }
+    Ok(())
+}
+
+/// Visits the expressions stored in an operation box, like [`visit_box`].
+fn visit_box_ref<E>(
+    op_box: &OpBox,
+    path: &str,
+    bound: &[String],
+    f: &mut impl FnMut(&str, ParamSite) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut visit = |param: &str, path: String| f(param, ParamSite { path: &path, bound });
+    match op_box {
+        OpBox::CircBox { circuit, .. } => {
+            return visit_circuit_ref(circuit, &format!("{path}/circuit"), bound, f);
+        }
+        OpBox::PauliExpBox { phase, .. } => visit(phase, format!("{path}/phase"))?,
+        OpBox::PauliExpPairBox { phase_pair, .. } => {
+            for (i, phase) in phase_pair.iter().enumerate() {
+                visit(phase, format!("{path}/phase_pair/{i}"))?;
+            }
+        }
+        OpBox::PauliExpCommutingSetBox { pauli_gadgets, .. }
+        | OpBox::TermSequenceBox { pauli_gadgets, .. } => {
+            for (i, (_, phase)) in pauli_gadgets.iter().enumerate() {
+                visit(phase, format!("{path}/pauli_gadgets/{i}/1"))?;
+            }
+        }
+        OpBox::PhasePolyBox {
+            phase_polynomial, ..
+        } => {
+            for (i, terms) in phase_polynomial.iter().enumerate() {
+                for (j, (_, coeff)) in terms.iter().enumerate() {
+                    visit(coeff, format!("{path}/phase_polynomial/{i}/{j}/1"))?;
+                }
+            }
+        }
+        OpBox::CustomGate { gate, params, .. } => {
+            for (i, param) in params.iter().enumerate() {
+                visit(param, format!("{path}/params/{i}"))?;
+            }
+            let mut gate_bound = bound.to_vec();
+            gate_bound.extend(gate.args.iter().cloned());
+            let definition_path = format!("{path}/gate/definition");
+            return visit_circuit_ref(&gate.definition, &definition_path, &gate_bound, f);
+        }
+        OpBox::QControlBox { op, .. } => return visit_op_ref(op, &format!("{path}/op"), bound, f),
+        OpBox::ConjugationBox {
+            compute,
+            action,
+            uncompute,
+            ..
+        } => {
+            visit_op_ref(compute, &format!("{path}/compute"), bound, f)?;
+            visit_op_ref(action, &format!("{path}/action"), bound, f)?;
+            if let Some(uncompute) = uncompute {
+                visit_op_ref(uncompute, &format!("{path}/uncompute"), bound, f)?;
+            }
+        }
+        OpBox::MultiplexorBox { op_map, .. }
+        | OpBox::MultiplexedRotationBox { op_map, .. }
+        | OpBox::MultiplexedU2Box { op_map, .. }
+        | OpBox::MultiplexedTensoredU2Box { op_map, .. } => {
+            for (i, (_, op)) in op_map.iter().enumerate() {
+                visit_op_ref(op, &format!("{path}/op_map/{i}/1"), bound, f)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
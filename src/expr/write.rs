@@ -0,0 +1,161 @@
+//! Writing of parameter expressions in the syntax of other languages.
+//!
+//! The exporters to OpenQASM and Quil write parameters in half-turns as
+//! angles in radians, using the operators and functions of their language.
+
+use std::fmt::Write;
+
+use super::{Expr, Function};
+
+/// The syntax of parameter expressions in a version of OpenQASM, or in Quil.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Dialect {
+    Qasm2,
+    Qasm3,
+    Quil,
+}
+
+impl Dialect {
+    /// Returns the name of a function, if it has one in this dialect.
+    fn function(self, func: Function) -> Option<&'static str> {
+        Some(match (self, func) {
+            (_, Function::Sin) => "sin",
+            (_, Function::Cos) => "cos",
+            (Dialect::Qasm2 | Dialect::Qasm3, Function::Tan) => "tan",
+            (_, Function::Exp) => "exp",
+            (_, Function::Sqrt) => "sqrt",
+            (Dialect::Qasm2, Function::Log) => "ln",
+            (Dialect::Qasm3, Function::Log) => "log",
+            (Dialect::Qasm3, Function::Asin) => "arcsin",
+            (Dialect::Qasm3, Function::Acos) => "arccos",
+            (Dialect::Qasm3, Function::Atan) => "arctan",
+            _ => return None,
+        })
+    }
+}
+
+/// Writes a parameter in half-turns as an angle in radians, or returns `None`
+/// if it uses symbols other than `symbols`, or functions and numbers that
+/// cannot be written in the dialect.
+///
+/// The symbols are the parameters of a gate definition, which are angles in
+/// radians standing for the symbols of the definition multiplied by π.
+pub(crate) fn write_angle(
+    half_turns: &Expr,
+    symbols: &[String],
+    dialect: Dialect,
+) -> Option<String> {
+    let mut out = String::new();
+    if half_turns.free_symbols().is_empty() {
+        write_expr(&mut out, half_turns, 2, symbols, dialect)?;
+        out.push_str("*pi");
+    } else {
+        write_expr(&mut out, &multiply_by_pi(half_turns)?, 0, symbols, dialect)?;
+    }
+    Some(out)
+}
+
+/// Multiplies an expression that is linear in its symbols by π, leaving the
+/// symbols unchanged, or returns `None`.
+fn multiply_by_pi(expr: &Expr) -> Option<Expr> {
+    let constant = |e: &Expr| e.free_symbols().is_empty();
+    Some(match expr {
+        e if constant(e) => Expr::Mul(Box::new(e.clone()), Box::new(Expr::Pi)),
+        Expr::Symbol(_) => expr.clone(),
+        Expr::Neg(a) => Expr::Neg(Box::new(multiply_by_pi(a)?)),
+        Expr::Add(a, b) => Expr::Add(Box::new(multiply_by_pi(a)?), Box::new(multiply_by_pi(b)?)),
+        Expr::Sub(a, b) => Expr::Sub(Box::new(multiply_by_pi(a)?), Box::new(multiply_by_pi(b)?)),
+        Expr::Mul(a, b) if constant(a) => Expr::Mul(a.clone(), Box::new(multiply_by_pi(b)?)),
+        Expr::Mul(a, b) if constant(b) => Expr::Mul(Box::new(multiply_by_pi(a)?), b.clone()),
+        Expr::Div(a, b) if constant(b) => Expr::Div(Box::new(multiply_by_pi(a)?), b.clone()),
+        _ => return None,
+    })
+}
+
+/// Writes an expression, wrapped in parentheses if it binds less tightly than
+/// `min_precedence`.
+fn write_expr(
+    out: &mut String,
+    expr: &Expr,
+    min_precedence: u8,
+    symbols: &[String],
+    dialect: Dialect,
+) -> Option<()> {
+    let precedence = match expr {
+        Expr::Add(..) | Expr::Sub(..) => 1,
+        Expr::Mul(..) | Expr::Div(..) | Expr::Rational(..) => 2,
+        Expr::Neg(_) => 3,
+        Expr::Integer(n) if *n < 0 => 3,
+        Expr::Float(x) if x.is_sign_negative() => 3,
+        Expr::Pow(..) => 4,
+        _ => 5,
+    };
+    if precedence < min_precedence {
+        out.push('(');
+    }
+    match expr {
+        Expr::Integer(n) => write!(out, "{n}").ok()?,
+        Expr::Float(x) if x.is_finite() => {
+            // Real literals must have a decimal point.
+            let mut literal = format!("{x:?}");
+            if !literal.contains('.') {
+                let exponent = literal.find('e').unwrap_or(literal.len());
+                literal.insert_str(exponent, ".0");
+            }
+            out.push_str(&literal);
+        }
+        Expr::Float(_) => return None,
+        Expr::Rational(n, d) => write!(out, "{n}/{d}").ok()?,
+        Expr::Symbol(s) if symbols.contains(s) => out.push_str(s),
+        Expr::Symbol(_) => return None,
+        Expr::Pi => out.push_str("pi"),
+        Expr::E => out.push_str(match dialect {
+            Dialect::Qasm2 | Dialect::Quil => "exp(1)",
+            Dialect::Qasm3 => "euler",
+        }),
+        Expr::Neg(e) => {
+            out.push('-');
+            write_expr(out, e, 5, symbols, dialect)?;
+        }
+        Expr::Add(a, b) => write_binary(out, a, " + ", b, 1, symbols, dialect)?,
+        Expr::Sub(a, b) => write_binary(out, a, " - ", b, 1, symbols, dialect)?,
+        Expr::Mul(a, b) => write_binary(out, a, "*", b, 2, symbols, dialect)?,
+        Expr::Div(a, b) => write_binary(out, a, "/", b, 2, symbols, dialect)?,
+        Expr::Pow(a, b) => {
+            write_expr(out, a, 5, symbols, dialect)?;
+            out.push_str(match dialect {
+                Dialect::Qasm2 | Dialect::Quil => "^",
+                Dialect::Qasm3 => "**",
+            });
+            write_expr(out, b, 5, symbols, dialect)?;
+        }
+        Expr::Func(func, args) => {
+            let name = dialect.function(*func)?;
+            let [arg] = args.as_slice() else {
+                return None;
+            };
+            out.push_str(name);
+            out.push('(');
+            write_expr(out, arg, 0, symbols, dialect)?;
+            out.push(')');
+        }
+    }
+    if precedence < min_precedence {
+        out.push(')');
+    }
+    Some(())
+}
+
+fn write_binary(
+    out: &mut String,
+    lhs: &Expr,
+    op: &str,
+    rhs: &Expr,
+    precedence: u8,
+    symbols: &[String],
+    dialect: Dialect,
+) -> Option<()> {
+    write_expr(out, lhs, precedence, symbols, dialect)?;
+    out.push_str(op);
+    write_expr(out, rhs, precedence + 1, symbols, dialect)
+}
@@ -0,0 +1,245 @@
+//! An interpreter for the classical operations of a circuit.
+//!
+//! [`SerialCircuit::run_classical`] applies the commands of a circuit in
+//! order to the values of its bits. Classical operations, `ClExpr`
+//! operations and QASM-style conditions are evaluated directly, while the
+//! other operations are passed to a [`QuantumHandler`].
+//!
+//! The `SetBits`, `CopyBits`, `RangePredicate`, `ExplicitPredicate`,
+//! `ExplicitModifier`, `ClassicalTransform` and `MultiBit` operations are
+//! evaluated through their [`ClExpr`] lowering, so they follow the same
+//! conventions as [`SerialCircuit::lower_classical_ops`].
+//!
+//!   [`ClExpr`]: crate::clexpr::ClExpr
+
+use std::collections::HashMap;
+
+use derive_more::{Display, Error, From};
+
+use crate::circuit_json::{Operation, SerialCircuit};
+use crate::clexpr::eval::EvalError;
+use crate::clexpr::lower::{lower, LowerError};
+use crate::clexpr::ClExpr;
+use crate::optype::OpType;
+use crate::register::{Bit, ElementId};
+
+/// Applies the non-classical operations of a circuit.
+///
+/// Closures taking the same arguments as [`QuantumHandler::apply`]
+/// implement this trait.
+pub trait QuantumHandler {
+    /// The error returned when an operation cannot be applied.
+    type Error;
+
+    /// Applies an operation to its arguments. Measurements and other
+    /// operations writing to bits should update `bits`.
+    fn apply(
+        &mut self,
+        op: &Operation,
+        args: &[ElementId],
+        bits: &mut HashMap<Bit, bool>,
+    ) -> Result<(), Self::Error>;
+}
+
+impl<E, F> QuantumHandler for F
+where
+    F: FnMut(&Operation, &[ElementId], &mut HashMap<Bit, bool>) -> Result<(), E>,
+{
+    type Error = E;
+
+    fn apply(
+        &mut self,
+        op: &Operation,
+        args: &[ElementId],
+        bits: &mut HashMap<Bit, bool>,
+    ) -> Result<(), E> {
+        self(op, args, bits)
+    }
+}
+
+/// A [`QuantumHandler`] for purely classical circuits, which rejects every
+/// quantum operation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClassicalOnly;
+
+/// Error returned by [`ClassicalOnly`] for quantum operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display, Error)]
+#[display("{op_type} is not a classical operation")]
+#[non_exhaustive]
+pub struct QuantumOpError {
+    /// The operation type.
+    pub op_type: OpType,
+}
+
+impl QuantumHandler for ClassicalOnly {
+    type Error = QuantumOpError;
+
+    fn apply(
+        &mut self,
+        op: &Operation,
+        _args: &[ElementId],
+        _bits: &mut HashMap<Bit, bool>,
+    ) -> Result<(), QuantumOpError> {
+        Err(QuantumOpError {
+            op_type: op.op_type,
+        })
+    }
+}
+
+/// Error produced when running the classical operations of a circuit.
+#[derive(Clone, Debug, PartialEq, Display, Error, From)]
+#[non_exhaustive]
+pub enum InterpretError<E> {
+    /// The circuit contains a classical operation that cannot be
+    /// interpreted, such as a WASM call or a control flow operation.
+    #[display("{path}: cannot interpret {op_type} operations")]
+    #[from(ignore)]
+    UnsupportedOperation {
+        /// JSON pointer to the operation.
+        path: String,
+        /// The operation type.
+        op_type: OpType,
+    },
+    /// An operation is missing a field required by its type.
+    #[display("{path}: {op_type} operation is missing its `{field}` field")]
+    #[from(ignore)]
+    MissingField {
+        /// JSON pointer to the operation.
+        path: String,
+        /// The operation type.
+        op_type: OpType,
+        /// The name of the missing field.
+        field: &'static str,
+    },
+    /// A classical expression cannot be evaluated.
+    #[display("{path}: {source}")]
+    #[from(ignore)]
+    Eval {
+        /// JSON pointer to the operation.
+        path: String,
+        /// The evaluation error.
+        source: EvalError,
+    },
+    /// A classical operation cannot be expressed as a classical expression.
+    #[display("{_0}")]
+    Lower(LowerError),
+    /// The quantum handler failed to apply an operation.
+    #[display("{path}: {source}")]
+    #[from(ignore)]
+    Quantum {
+        /// JSON pointer to the operation.
+        path: String,
+        /// The error returned by the handler.
+        source: E,
+    },
+}
+
+impl SerialCircuit {
+    /// Runs the circuit on the given bit values, updating them in place.
+    ///
+    /// Bits missing from `bits` are read as `false`. Operations that are not
+    /// classical are passed to `quantum`; use [`ClassicalOnly`] to reject
+    /// them. Barriers and other meta operations are ignored.
+    pub fn run_classical<H: QuantumHandler>(
+        &self,
+        bits: &mut HashMap<Bit, bool>,
+        quantum: &mut H,
+    ) -> Result<(), InterpretError<H::Error>> {
+        for (i, command) in self.commands.iter().enumerate() {
+            let path = format!("/commands/{i}/op");
+            run_op(&command.op, &command.args, bits, quantum, &path)?;
+        }
+        Ok(())
+    }
+}
+
+fn run_op<H: QuantumHandler>(
+    op: &Operation,
+    args: &[ElementId],
+    bits: &mut HashMap<Bit, bool>,
+    quantum: &mut H,
+    path: &str,
+) -> Result<(), InterpretError<H::Error>> {
+    let missing_field = |field| InterpretError::MissingField {
+        path: path.to_string(),
+        op_type: op.op_type,
+        field,
+    };
+    match op.op_type {
+        OpType::Conditional => {
+            let conditional = op
+                .conditional
+                .as_ref()
+                .ok_or_else(|| missing_field("conditional"))?;
+            // The condition bits come before the arguments of the inner
+            // operation, the first one being the least significant.
+            let width = (conditional.width as usize).min(args.len());
+            let (condition, args) = args.split_at(width);
+            let value = condition
+                .iter()
+                .enumerate()
+                .take(64)
+                .fold(0u64, |acc, (i, id)| acc | (read(bits, id) as u64) << i);
+            if value == conditional.value as u64 {
+                let path = format!("{path}/conditional/op");
+                run_op(&conditional.op, args, bits, quantum, &path)?;
+            }
+            Ok(())
+        }
+        OpType::ClExpr => {
+            let expr = op
+                .classical_expr
+                .as_ref()
+                .ok_or_else(|| missing_field("expr"))?;
+            run_clexpr(expr, args, bits, path)
+        }
+        op_type if op_type.is_meta() => Ok(()),
+        op_type => match lower(op_type, op.classical.as_deref(), args, path) {
+            Some(lowered) => {
+                for (expr, args) in lowered? {
+                    run_clexpr(&expr, &args, bits, path)?;
+                }
+                Ok(())
+            }
+            None if op_type.is_classical() || op_type.is_flow() => {
+                Err(InterpretError::UnsupportedOperation {
+                    path: path.to_string(),
+                    op_type,
+                })
+            }
+            None => quantum
+                .apply(op, args, bits)
+                .map_err(|source| InterpretError::Quantum {
+                    path: path.to_string(),
+                    source,
+                }),
+        },
+    }
+}
+
+/// Evaluates a classical expression, and writes its output bits.
+fn run_clexpr<E>(
+    expr: &ClExpr,
+    args: &[ElementId],
+    bits: &mut HashMap<Bit, bool>,
+    path: &str,
+) -> Result<(), InterpretError<E>> {
+    let mut values: Vec<bool> = args.iter().map(|id| read(bits, id)).collect();
+    expr.apply(&mut values)
+        .map_err(|source| InterpretError::Eval {
+            path: path.to_string(),
+            source,
+        })?;
+    for &position in &expr.output_posn.0 {
+        let position = position as usize;
+        let bit = Bit {
+            id: args[position].clone(),
+        };
+        bits.insert(bit, values[position]);
+    }
+    Ok(())
+}
+
+fn read(bits: &HashMap<Bit, bool>, id: &ElementId) -> bool {
+    bits.get(&Bit { id: id.clone() }).copied().unwrap_or(false)
+}
@@ -4,11 +4,23 @@
 
 pub mod circuit_json;
 pub mod clexpr;
+pub mod dag;
+pub mod expr;
+pub mod interpreter;
+mod linalg;
 pub mod opbox;
 pub mod optype;
 #[cfg(feature = "pyo3")]
 pub mod pytket;
+pub mod qasm2;
+pub mod qasm3;
+pub mod qir;
+pub mod quil;
 pub mod register;
+pub mod simulator;
+pub mod stim;
+pub mod unitary;
+pub mod validate;
 
 pub use circuit_json::SerialCircuit;
 pub use optype::OpType;
@@ -0,0 +1,261 @@
+//! Dense complex linear algebra, used to compute the unitaries of operations
+//! and circuits.
+//!
+//! Qubits are ordered following the ILO-BE convention: the first qubit of an
+//! operation is the most significant bit of the basis state index.
+
+use std::ops::{Add, AddAssign, Index, IndexMut, Mul, Neg, Sub};
+
+use crate::circuit_json::Matrix;
+
+/// A complex number.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub const ZERO: Complex = Complex::new(0.0, 0.0);
+    pub const ONE: Complex = Complex::new(1.0, 0.0);
+    pub const I: Complex = Complex::new(0.0, 1.0);
+
+    pub const fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    /// Returns `e^{iθ}`.
+    pub fn cis(theta: f64) -> Self {
+        Self::new(theta.cos(), theta.sin())
+    }
+
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        Self::new(re, 0.0)
+    }
+}
+
+impl From<(f64, f64)> for Complex {
+    fn from((re, im): (f64, f64)) -> Self {
+        Self::new(re, im)
+    }
+}
+
+impl From<Complex> for (f64, f64) {
+    fn from(c: Complex) -> Self {
+        (c.re, c.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Complex) -> Complex {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl AddAssign for Complex {
+    fn add_assign(&mut self, rhs: Complex) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Mul<f64> for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: f64) -> Complex {
+        Complex::new(self.re * rhs, self.im * rhs)
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+
+    fn neg(self) -> Complex {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+/// A dense square matrix of complex numbers, stored in row-major order.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SquareMatrix {
+    dim: usize,
+    data: Vec<Complex>,
+}
+
+impl SquareMatrix {
+    pub fn zeros(dim: usize) -> Self {
+        Self {
+            dim,
+            data: vec![Complex::ZERO; dim * dim],
+        }
+    }
+
+    pub fn identity(dim: usize) -> Self {
+        Self::from_fn(
+            dim,
+            |r, c| if r == c { Complex::ONE } else { Complex::ZERO },
+        )
+    }
+
+    pub fn from_fn(dim: usize, mut f: impl FnMut(usize, usize) -> Complex) -> Self {
+        let data = (0..dim * dim).map(|i| f(i / dim, i % dim)).collect();
+        Self { dim, data }
+    }
+
+    /// Builds a diagonal matrix.
+    pub fn diagonal(diag: &[Complex]) -> Self {
+        let mut m = Self::zeros(diag.len());
+        for (i, &d) in diag.iter().enumerate() {
+            m[(i, i)] = d;
+        }
+        m
+    }
+
+    /// Builds a matrix from its rows. Panics if the rows are not all of the
+    /// same length as the number of rows.
+    pub fn from_rows<R: AsRef<[Complex]>>(rows: &[R]) -> Self {
+        let dim = rows.len();
+        Self::from_fn(dim, |r, c| rows[r].as_ref()[c])
+    }
+
+    pub fn to_matrix(&self) -> Matrix {
+        let data = (0..self.dim)
+            .map(|r| (0..self.dim).map(|c| self[(r, c)].into()).collect())
+            .collect();
+        Matrix { data }
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn scale(&self, factor: Complex) -> Self {
+        Self {
+            dim: self.dim,
+            data: self.data.iter().map(|&x| x * factor).collect(),
+        }
+    }
+
+    pub fn add(&self, other: &SquareMatrix) -> Self {
+        Self::from_fn(self.dim, |r, c| self[(r, c)] + other[(r, c)])
+    }
+
+    pub fn matmul(&self, other: &SquareMatrix) -> Self {
+        let n = self.dim;
+        let mut result = Self::zeros(n);
+        for r in 0..n {
+            for k in 0..n {
+                let a = self[(r, k)];
+                if a == Complex::ZERO {
+                    continue;
+                }
+                for c in 0..n {
+                    result[(r, c)] += a * other[(k, c)];
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns the Kronecker product `self ⊗ other`.
+    pub fn kron(&self, other: &SquareMatrix) -> Self {
+        let m = other.dim;
+        Self::from_fn(self.dim * m, |r, c| {
+            self[(r / m, c / m)] * other[(r % m, c % m)]
+        })
+    }
+
+    pub fn adjoint(&self) -> Self {
+        Self::from_fn(self.dim, |r, c| self[(c, r)].conj())
+    }
+
+    /// Returns the matrix controlled by `n_controls` qubits, placed before
+    /// the qubits of the original matrix.
+    pub fn controlled(&self, n_controls: u32) -> Self {
+        let dim = self.dim << n_controls;
+        let offset = dim - self.dim;
+        let mut result = Self::identity(dim);
+        for r in 0..self.dim {
+            for c in 0..self.dim {
+                result[(offset + r, offset + c)] = self[(r, c)];
+            }
+        }
+        result
+    }
+
+    /// Returns the largest absolute row sum of the matrix.
+    fn norm_inf(&self) -> f64 {
+        (0..self.dim)
+            .map(|r| (0..self.dim).map(|c| self[(r, c)].abs()).sum())
+            .fold(0.0, f64::max)
+    }
+
+    /// Returns the matrix exponential `e^A`.
+    ///
+    /// Uses a truncated Taylor series after scaling the matrix down to a
+    /// small norm, and squares the result back up.
+    pub fn exp(&self) -> Self {
+        let norm = self.norm_inf();
+        let squarings = if norm > 0.5 {
+            (norm / 0.5).log2().ceil() as u32
+        } else {
+            0
+        };
+        let scaled = self.scale(Complex::from(0.5f64.powi(squarings as i32)));
+
+        let mut result = Self::identity(self.dim);
+        let mut term = Self::identity(self.dim);
+        for k in 1..=20 {
+            term = term.matmul(&scaled).scale(Complex::from(1.0 / k as f64));
+            result = result.add(&term);
+        }
+        for _ in 0..squarings {
+            result = result.matmul(&result);
+        }
+        result
+    }
+}
+
+impl Index<(usize, usize)> for SquareMatrix {
+    type Output = Complex;
+
+    fn index(&self, (r, c): (usize, usize)) -> &Complex {
+        &self.data[r * self.dim + c]
+    }
+}
+
+impl IndexMut<(usize, usize)> for SquareMatrix {
+    fn index_mut(&mut self, (r, c): (usize, usize)) -> &mut Complex {
+        &mut self.data[r * self.dim + c]
+    }
+}
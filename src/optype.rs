@@ -8,6 +8,8 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use strum::EnumString;
 
+pub mod metadata;
+
 /// Operation types in a quantum circuit.
 #[cfg_attr(feature = "pyo3", pyclass(name = "RsOpType", eq, eq_int))]
 #[derive(
@@ -377,7 +379,11 @@ pub enum OpType {
     ISWAPMax,
 
     /// ISwap gate with extra `Rz`s on each qubit
-    // TODO: Matrix description
+    ///
+    /// \f$ (p, t) \mapsto \left[ \begin{array}{cccc} 1 & 0 & 0 & 0 \\ 0 &
+    /// \cos\frac{\pi t}{2} & i\sin\frac{\pi t}{2}e^{2i\pi p} & 0 \\ 0 &
+    /// i\sin\frac{\pi t}{2}e^{-2i\pi p} & \cos\frac{\pi t}{2} & 0 \\ 0 & 0 & 0 &
+    /// 1 \end{array} \right] \f$
     PhasedISWAP,
 
     /// N-controlled [`OpType::Rx`]
@@ -0,0 +1,242 @@
+//! Static information about each [`OpType`].
+
+use super::OpType;
+
+/// The number of wires of a given kind that an operation acts on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Arity {
+    /// The operation always acts on this many wires.
+    Fixed(u32),
+    /// The number of wires depends on the operation instance.
+    Variadic,
+}
+
+impl Arity {
+    /// Returns the number of wires, if it is fixed by the operation type.
+    pub fn fixed(self) -> Option<u32> {
+        match self {
+            Arity::Fixed(n) => Some(n),
+            Arity::Variadic => None,
+        }
+    }
+}
+
+/// Static metadata for an [`OpType`].
+///
+/// See [`OpType::metadata`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct OpTypeMetadata {
+    /// Number of qubits acted on.
+    pub qubits: Arity,
+    /// Number of classical bits acted on.
+    pub bits: Arity,
+    /// Number of WASM state wires acted on.
+    pub wasm_wires: Arity,
+    /// Number of RNG state wires acted on.
+    pub rng_wires: Arity,
+    /// Number of parameters in the operation's `params` list.
+    pub params: u32,
+    /// Whether this is a primitive quantum gate, including non-unitary ones such
+    /// as [`OpType::Measure`] and [`OpType::Reset`].
+    pub is_gate: bool,
+    /// Whether the operation carries an [`OpBox`](crate::opbox::OpBox).
+    pub is_box: bool,
+    /// Whether the operation acts only on classical data.
+    pub is_classical: bool,
+    /// Whether the operation alters the control flow of the program.
+    pub is_flow: bool,
+    /// Whether this is a boundary or barrier operation, which does not perform
+    /// any computation.
+    pub is_meta: bool,
+}
+
+impl OpTypeMetadata {
+    /// Metadata with no wires, no parameters and no flags set.
+    const EMPTY: Self = Self {
+        qubits: Arity::Fixed(0),
+        bits: Arity::Fixed(0),
+        wasm_wires: Arity::Fixed(0),
+        rng_wires: Arity::Fixed(0),
+        params: 0,
+        is_gate: false,
+        is_box: false,
+        is_classical: false,
+        is_flow: false,
+        is_meta: false,
+    };
+
+    /// A quantum gate on a fixed number of qubits.
+    const fn gate(qubits: u32, params: u32) -> Self {
+        Self {
+            qubits: Arity::Fixed(qubits),
+            params,
+            is_gate: true,
+            ..Self::EMPTY
+        }
+    }
+
+    /// A quantum gate on a variable number of qubits.
+    const fn variadic_gate(params: u32) -> Self {
+        Self {
+            qubits: Arity::Variadic,
+            params,
+            is_gate: true,
+            ..Self::EMPTY
+        }
+    }
+
+    /// A box acting on the given number of qubits and bits.
+    const fn op_box(qubits: Arity, bits: Arity) -> Self {
+        Self {
+            qubits,
+            bits,
+            is_box: true,
+            ..Self::EMPTY
+        }
+    }
+
+    /// A classical operation on the given number of bits and RNG wires.
+    const fn classical(bits: Arity, rng_wires: u32) -> Self {
+        Self {
+            bits,
+            rng_wires: Arity::Fixed(rng_wires),
+            is_classical: true,
+            ..Self::EMPTY
+        }
+    }
+
+    /// A control flow operation reading the given number of bits.
+    const fn flow(bits: u32) -> Self {
+        Self {
+            bits: Arity::Fixed(bits),
+            is_flow: true,
+            ..Self::EMPTY
+        }
+    }
+
+    /// A boundary or barrier operation.
+    const fn meta(qubits: Arity, bits: Arity, rng_wires: u32) -> Self {
+        Self {
+            qubits,
+            bits,
+            rng_wires: Arity::Fixed(rng_wires),
+            is_meta: true,
+            ..Self::EMPTY
+        }
+    }
+}
+
+impl OpType {
+    /// Returns the static metadata for this operation type.
+    pub const fn metadata(self) -> OpTypeMetadata {
+        use Arity::{Fixed, Variadic};
+        use OpType::*;
+        use OpTypeMetadata as M;
+
+        match self {
+            Input | Output | Create | Discard => M::meta(Fixed(1), Fixed(0), 0),
+            ClInput | ClOutput => M::meta(Fixed(0), Fixed(1), 0),
+            RNGInput | RNGOutput => M::meta(Fixed(0), Fixed(0), 1),
+            Barrier => M::meta(Variadic, Variadic, 0),
+
+            Label | Goto | Stop => M::flow(0),
+            Branch => M::flow(1),
+
+            ClassicalTransform | SetBits | CopyBits | RangePredicate | ExplicitPredicate
+            | ExplicitModifier | MultiBit | ClExpr => M::classical(Variadic, 0),
+            WASM => OpTypeMetadata {
+                wasm_wires: Variadic,
+                ..M::classical(Variadic, 0)
+            },
+            RNGSeed => M::classical(Fixed(64), 1),
+            RNGBound | RNGIndex | RNGNum => M::classical(Fixed(32), 1),
+            JobShotNum => M::classical(Fixed(32), 0),
+
+            Phase => M::gate(0, 1),
+            noop | Collapse | Reset | Z | X | Y | S | Sdg | T | Tdg | V | Vdg | SX | SXdg | H => {
+                M::gate(1, 0)
+            }
+            Measure => OpTypeMetadata {
+                bits: Fixed(1),
+                ..M::gate(1, 0)
+            },
+            Rx | Ry | Rz | U1 | GPI | GPI2 => M::gate(1, 1),
+            U2 | PhasedX => M::gate(1, 2),
+            U3 | TK1 => M::gate(1, 3),
+            CX | CY | CZ | CH | CV | CVdg | CSX | CSXdg | CS | CSdg | SWAP | ECR | ZZMax
+            | Sycamore | ISWAPMax => M::gate(2, 0),
+            CRz | CRx | CRy | CU1 | ISWAP | XXPhase | YYPhase | ZZPhase | ESWAP => M::gate(2, 1),
+            FSim | PhasedISWAP => M::gate(2, 2),
+            TK2 | CU3 | AAMS => M::gate(2, 3),
+            CCX | CSWAP | BRIDGE => M::gate(3, 0),
+            XXPhase3 => M::gate(3, 1),
+            CnX | CnY | CnZ => M::variadic_gate(0),
+            PhaseGadget | CnRx | CnRy | CnRz => M::variadic_gate(1),
+            NPhasedX => M::variadic_gate(2),
+
+            Unitary1qBox => M::op_box(Fixed(1), Fixed(0)),
+            Unitary2qBox | ExpBox => M::op_box(Fixed(2), Fixed(0)),
+            Unitary3qBox => M::op_box(Fixed(3), Fixed(0)),
+            PauliExpBox
+            | PauliExpPairBox
+            | PauliExpCommutingSetBox
+            | TermSequenceBox
+            | CliffBox
+            | PhasePolyBox
+            | QControlBox
+            | UnitaryTableauBox
+            | MultiplexorBox
+            | MultiplexedRotationBox
+            | MultiplexedU2Box
+            | MultiplexedTensoredU2Box
+            | ToffoliBox
+            | ConjugationBox
+            | StatePreparationBox
+            | DiagonalBox => M::op_box(Variadic, Fixed(0)),
+            CircBox | CustomGate | StabiliserAssertionBox | ProjectorAssertionBox | DummyBox => {
+                M::op_box(Variadic, Variadic)
+            }
+            ClassicalExpBox => OpTypeMetadata {
+                is_classical: true,
+                ..M::op_box(Fixed(0), Variadic)
+            },
+
+            Conditional => OpTypeMetadata {
+                qubits: Variadic,
+                bits: Variadic,
+                wasm_wires: Variadic,
+                rng_wires: Variadic,
+                ..M::EMPTY
+            },
+        }
+    }
+
+    /// Returns `true` if this is a primitive quantum gate.
+    ///
+    /// See [`OpTypeMetadata::is_gate`].
+    pub const fn is_gate(self) -> bool {
+        self.metadata().is_gate
+    }
+
+    /// Returns `true` if operations of this type carry an
+    /// [`OpBox`](crate::opbox::OpBox).
+    pub const fn is_box(self) -> bool {
+        self.metadata().is_box
+    }
+
+    /// Returns `true` if this operation acts only on classical data.
+    pub const fn is_classical(self) -> bool {
+        self.metadata().is_classical
+    }
+
+    /// Returns `true` if this operation alters the control flow of the program.
+    pub const fn is_flow(self) -> bool {
+        self.metadata().is_flow
+    }
+
+    /// Returns `true` if this is a boundary or barrier operation.
+    pub const fn is_meta(self) -> bool {
+        self.metadata().is_meta
+    }
+}
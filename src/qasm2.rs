@@ -0,0 +1,91 @@
+//! Conversion between circuits and OpenQASM 2.0 programs.
+//!
+//! [`SerialCircuit::to_qasm2`] writes a circuit as a program using the gates
+//! of the standard `qelib1.inc` header. Qubits and bits are grouped into
+//! `qreg` and `creg` registers by the name of their [`ElementId`], and must
+//! have one-dimensional indices. [`SerialCircuit::from_qasm2`] parses such a
+//! program back into a circuit.
+//!
+//! Parameters are converted between the half-turns used by pytket and the
+//! radians used by OpenQASM, so that `Rz(0.5)` is written `rz(0.5*pi)`.
+//!
+//!   [`SerialCircuit::to_qasm2`]: crate::circuit_json::SerialCircuit::to_qasm2
+//!   [`SerialCircuit::from_qasm2`]: crate::circuit_json::SerialCircuit::from_qasm2
+//!   [`ElementId`]: crate::register::ElementId
+
+pub mod export;
+pub mod import;
+
+use crate::optype::OpType;
+
+/// The gates of `qelib1.inc`, with the operation type they implement and
+/// their numbers of parameters and qubits.
+///
+/// Gate names are unique, except that `CnX` operations are written with the
+/// gate for their number of controls.
+const QELIB1_GATES: &[(OpType, &str, usize, usize)] = &[
+    (OpType::noop, "id", 0, 1),
+    (OpType::X, "x", 0, 1),
+    (OpType::Y, "y", 0, 1),
+    (OpType::Z, "z", 0, 1),
+    (OpType::H, "h", 0, 1),
+    (OpType::S, "s", 0, 1),
+    (OpType::Sdg, "sdg", 0, 1),
+    (OpType::T, "t", 0, 1),
+    (OpType::Tdg, "tdg", 0, 1),
+    (OpType::SX, "sx", 0, 1),
+    (OpType::SXdg, "sxdg", 0, 1),
+    (OpType::Rx, "rx", 1, 1),
+    (OpType::Ry, "ry", 1, 1),
+    (OpType::Rz, "rz", 1, 1),
+    (OpType::U1, "u1", 1, 1),
+    (OpType::U2, "u2", 2, 1),
+    (OpType::U3, "u3", 3, 1),
+    (OpType::CX, "cx", 0, 2),
+    (OpType::CY, "cy", 0, 2),
+    (OpType::CZ, "cz", 0, 2),
+    (OpType::CH, "ch", 0, 2),
+    (OpType::CSX, "csx", 0, 2),
+    (OpType::CRx, "crx", 1, 2),
+    (OpType::CRy, "cry", 1, 2),
+    (OpType::CRz, "crz", 1, 2),
+    (OpType::CU1, "cu1", 1, 2),
+    (OpType::CU3, "cu3", 3, 2),
+    (OpType::SWAP, "swap", 0, 2),
+    (OpType::XXPhase, "rxx", 1, 2),
+    (OpType::ZZPhase, "rzz", 1, 2),
+    (OpType::CCX, "ccx", 0, 3),
+    (OpType::CSWAP, "cswap", 0, 3),
+    (OpType::CnX, "cx", 0, 2),
+    (OpType::CnX, "ccx", 0, 3),
+    (OpType::CnX, "c3x", 0, 4),
+    (OpType::CnX, "c4x", 0, 5),
+];
+
+/// Returns the `qelib1.inc` gate implementing an operation type on the given
+/// number of qubits, with its number of parameters.
+fn qelib1_gate(op_type: OpType, n_qubits: usize) -> Option<(&'static str, usize)> {
+    QELIB1_GATES
+        .iter()
+        .find(|&&(t, _, _, n)| t == op_type && (t != OpType::CnX || n == n_qubits))
+        .map(|&(_, name, n_params, _)| (name, n_params))
+}
+
+/// Returns `true` if `name` is a valid OpenQASM 2.0 identifier that is not a
+/// keyword.
+fn is_identifier(name: &str) -> bool {
+    const RESERVED: &[&str] = &[
+        "OPENQASM", "include", "qreg", "creg", "gate", "opaque", "barrier", "measure", "reset",
+        "if", "pi", "sin", "cos", "tan", "exp", "ln", "sqrt", "U", "CX",
+    ];
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !RESERVED.contains(&name)
+}
+
+/// Returns `true` if `name` can name a register or a gate: it must be an
+/// identifier distinct from the `qelib1.inc` gates.
+fn is_global_name(name: &str) -> bool {
+    is_identifier(name) && !QELIB1_GATES.iter().any(|&(_, gate, _, _)| gate == name)
+}
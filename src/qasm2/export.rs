@@ -0,0 +1,494 @@
+//! Export of circuits as OpenQASM 2.0 programs.
+//!
+//! Operations are written as `qelib1.inc` gates where possible. `CustomGate`
+//! operations become `gate` definitions, and the commands of `CircBox`
+//! operations are written in place of the box. A `Conditional` operation is
+//! written as an `if` statement, which requires its condition bits to be a
+//! whole classical register, in order.
+//!
+//! The implicit permutation of a circuit, or of the circuit of a box, is
+//! written as `swap` gates after its commands. The global phase of the
+//! circuit is dropped, as OpenQASM 2.0 cannot express it.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use derive_more::{Display, Error};
+
+use super::{is_global_name, is_identifier, qelib1_gate};
+use crate::circuit_json::{CustomGate, Operation, SerialCircuit};
+use crate::expr::write::{write_angle, Dialect};
+use crate::expr::{Expr, ParseExprError};
+use crate::opbox::OpBox;
+use crate::optype::OpType;
+use crate::register::ElementId;
+
+/// A problem preventing a circuit from being written as OpenQASM 2.0.
+#[derive(Clone, Debug, PartialEq, Display, Error)]
+#[display("{path}: {kind}")]
+#[non_exhaustive]
+pub struct ExportError {
+    /// JSON pointer to the offending element, e.g. `/commands/3/op`.
+    pub path: String,
+    /// The problem found at that location.
+    #[error(not(source))]
+    pub kind: ExportErrorKind,
+}
+
+/// The kinds of problems reported by [`SerialCircuit::to_qasm2`].
+#[derive(Clone, Debug, PartialEq, Display)]
+#[non_exhaustive]
+pub enum ExportErrorKind {
+    /// A register or gate name is not a valid OpenQASM 2.0 identifier, or
+    /// clashes with a keyword or a `qelib1.inc` gate.
+    #[display("`{_0}` is not a valid OpenQASM 2.0 identifier")]
+    InvalidName(String),
+    /// A qubit or bit does not have a single non-negative index.
+    #[display("{_0} does not have a one-dimensional index")]
+    InvalidIndex(ElementId),
+    /// A register name is used by both qubits and bits.
+    #[display("`{_0}` names both a quantum and a classical register")]
+    RegisterClash(String),
+    /// A command refers to a wire that is not declared.
+    #[display("{_0} is not declared in the circuit")]
+    UndeclaredWire(ElementId),
+    /// The implicit permutation of a circuit is not a permutation of its
+    /// qubits.
+    #[display("the implicit permutation is not a permutation of the qubits")]
+    InvalidPermutation,
+    /// The operation has no OpenQASM 2.0 equivalent.
+    #[display("{_0} operations cannot be written in OpenQASM 2.0")]
+    UnsupportedOperation(OpType),
+    /// The condition bits of a `Conditional` operation are not a whole
+    /// classical register, or the conditioned operation cannot be written in
+    /// an `if` statement.
+    #[display("the condition cannot be written as an OpenQASM 2.0 `if` statement")]
+    UnsupportedCondition,
+    /// Two `CustomGate` operations have the same name but different
+    /// definitions.
+    #[display("the gate `{_0}` has conflicting definitions")]
+    ConflictingDefinition(String),
+    /// A command does not have the number of arguments its operation acts on.
+    #[display("{op_type} acts on {expected} arguments, but {found} were given")]
+    ArgCount {
+        /// The operation type.
+        op_type: OpType,
+        /// The number of arguments the operation acts on.
+        expected: usize,
+        /// The number of arguments in the command.
+        found: usize,
+    },
+    /// An operation does not have the number of parameters its type expects.
+    #[display("{op_type} takes {expected} parameters, but {found} were given")]
+    ParamCount {
+        /// The operation type.
+        op_type: OpType,
+        /// The number of parameters of the operation type.
+        expected: usize,
+        /// The number of parameters of the operation.
+        found: usize,
+    },
+    /// A parameter is not a valid expression.
+    #[display("{_0}")]
+    InvalidParam(ParseExprError),
+    /// A parameter uses free symbols, functions or numbers that cannot be
+    /// written in OpenQASM 2.0.
+    #[display("the parameter `{_0}` cannot be written in OpenQASM 2.0")]
+    UnsupportedParam(String),
+}
+
+impl SerialCircuit {
+    /// Writes the circuit as an OpenQASM 2.0 program using `qelib1.inc`.
+    ///
+    /// Returns the first problem found if some part of the circuit cannot be
+    /// written.
+    pub fn to_qasm2(&self) -> Result<String, ExportError> {
+        let qregs = registers(self.qubits.iter().map(|qb| &qb.id), "/qubits")?;
+        let cregs = registers(self.bits.iter().map(|bit| &bit.id), "/bits")?;
+        for (name, _) in &qregs {
+            if cregs.iter().any(|(creg, _)| creg == name) {
+                return Err(error("/bits", ExportErrorKind::RegisterClash(name.clone())));
+            }
+        }
+        let mut exporter = Exporter {
+            qubits: self.qubits.iter().map(|qb| qb.id.clone()).collect(),
+            cregs: cregs.iter().cloned().collect(),
+            ..Exporter::default()
+        };
+
+        let mut body = String::new();
+        for (i, command) in self.commands.iter().enumerate() {
+            let path = format!("/commands/{i}");
+            for statement in exporter.statements(&command.op, &command.args, &path)? {
+                body.push_str(&statement);
+                body.push('\n');
+            }
+        }
+        for (a, b) in permutation_swaps(self, "")? {
+            writeln!(body, "swap {a},{b};").unwrap();
+        }
+
+        let mut out = String::from("OPENQASM 2.0;\ninclude \"qelib1.inc\";\n\n");
+        out.push_str(&exporter.definitions);
+        for (name, size) in qregs {
+            writeln!(out, "qreg {name}[{size}];").unwrap();
+        }
+        for (name, size) in cregs {
+            writeln!(out, "creg {name}[{size}];").unwrap();
+        }
+        out.push_str(&body);
+        Ok(out)
+    }
+}
+
+fn error(path: impl Into<String>, kind: ExportErrorKind) -> ExportError {
+    ExportError {
+        path: path.into(),
+        kind,
+    }
+}
+
+/// Returns the swaps implementing the implicit permutation of a circuit.
+/// `path` is the JSON pointer to the circuit.
+fn permutation_swaps<'a>(
+    circ: &'a SerialCircuit,
+    path: &str,
+) -> Result<Vec<(&'a ElementId, &'a ElementId)>, ExportError> {
+    circ.permutation_swaps().ok_or_else(|| {
+        error(
+            format!("{path}/implicit_permutation"),
+            ExportErrorKind::InvalidPermutation,
+        )
+    })
+}
+
+/// Returns the registers of a list of wires, with their sizes, in order of
+/// first appearance.
+fn registers<'a>(
+    ids: impl Iterator<Item = &'a ElementId>,
+    path: &str,
+) -> Result<Vec<(String, i64)>, ExportError> {
+    let mut registers: Vec<(String, i64)> = Vec::new();
+    for (i, id) in ids.enumerate() {
+        let path = format!("{path}/{i}");
+        let index = match id.1.as_slice() {
+            [index] if *index >= 0 => *index,
+            _ => return Err(error(path, ExportErrorKind::InvalidIndex(id.clone()))),
+        };
+        if !is_global_name(&id.0) {
+            return Err(error(path, ExportErrorKind::InvalidName(id.0.clone())));
+        }
+        match registers.iter_mut().find(|(name, _)| *name == id.0) {
+            Some((_, size)) => *size = (*size).max(index + 1),
+            None => registers.push((id.0.clone(), index + 1)),
+        }
+    }
+    Ok(registers)
+}
+
+/// The state of an export in progress.
+#[derive(Default)]
+struct Exporter<'c> {
+    /// The declared qubits of the circuit.
+    qubits: HashSet<ElementId>,
+    /// The size of each classical register.
+    cregs: HashMap<String, i64>,
+    /// The `gate` definitions written so far.
+    definitions: String,
+    /// The `CustomGate` definitions written so far, by name.
+    defined: HashMap<&'c str, &'c CustomGate>,
+}
+
+impl<'c> Exporter<'c> {
+    /// Returns the statements implementing an operation on the given
+    /// arguments of the top-level circuit.
+    fn statements(
+        &mut self,
+        op: &'c Operation,
+        args: &[ElementId],
+        path: &str,
+    ) -> Result<Vec<String>, ExportError> {
+        let op_path = format!("{path}/op");
+        match op.op_type {
+            OpType::Phase => Ok(Vec::new()),
+            OpType::Measure => match args {
+                [qubit, bit] => Ok(vec![format!("measure {qubit} -> {bit};")]),
+                _ => Err(arg_count(op, 2, args.len(), path)),
+            },
+            OpType::Reset => match args {
+                [qubit] => Ok(vec![format!("reset {qubit};")]),
+                _ => Err(arg_count(op, 1, args.len(), path)),
+            },
+            OpType::Barrier => {
+                // Barriers on bits have no OpenQASM 2.0 equivalent.
+                let qubits: Vec<String> = args
+                    .iter()
+                    .filter(|id| self.qubits.contains(id))
+                    .map(ToString::to_string)
+                    .collect();
+                if qubits.is_empty() {
+                    return Ok(Vec::new());
+                }
+                Ok(vec![format!("barrier {};", qubits.join(","))])
+            }
+            OpType::Conditional => {
+                let conditional = op
+                    .conditional
+                    .as_ref()
+                    .ok_or_else(|| error(&op_path, ExportErrorKind::UnsupportedCondition))?;
+                let width = (conditional.width as usize).min(args.len());
+                let (condition, args) = args.split_at(width);
+                let register = self
+                    .condition_register(condition)
+                    .ok_or_else(|| error(&op_path, ExportErrorKind::UnsupportedCondition))?;
+                let inner_path = format!("{op_path}/conditional");
+                let statements = match conditional.op.op_type {
+                    OpType::Barrier | OpType::Conditional => Vec::new(),
+                    _ => self.statements(&conditional.op, args, &inner_path)?,
+                };
+                let [statement] = statements.as_slice() else {
+                    return Err(error(&op_path, ExportErrorKind::UnsupportedCondition));
+                };
+                let value = conditional.value;
+                Ok(vec![format!("if({register}=={value}) {statement}")])
+            }
+            OpType::CircBox => {
+                let Some(OpBox::CircBox { circuit, .. }) = &op.op_box else {
+                    return Err(unsupported(op, path));
+                };
+                self.inline(circuit, args, path)
+            }
+            op_type
+                if op_type != OpType::CustomGate && qelib1_gate(op_type, args.len()).is_none() =>
+            {
+                Err(unsupported(op, path))
+            }
+            _ => {
+                for (i, id) in args.iter().enumerate() {
+                    if !self.qubits.contains(id) {
+                        let path = format!("{path}/args/{i}");
+                        return Err(error(path, ExportErrorKind::UndeclaredWire(id.clone())));
+                    }
+                }
+                let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+                Ok(vec![self.gate(op, &args, &[], path)?])
+            }
+        }
+    }
+
+    /// Returns the statements implementing the commands of a `CircBox`
+    /// circuit, whose qubits and bits are mapped to `args`. `path` is the
+    /// JSON pointer to the `CircBox` command.
+    fn inline(
+        &mut self,
+        circ: &'c SerialCircuit,
+        args: &[ElementId],
+        path: &str,
+    ) -> Result<Vec<String>, ExportError> {
+        let inner = circ.qubits.iter().map(|qb| &qb.id);
+        let inner: Vec<&ElementId> = inner.chain(circ.bits.iter().map(|bit| &bit.id)).collect();
+        if inner.len() != args.len() {
+            let kind = ExportErrorKind::ArgCount {
+                op_type: OpType::CircBox,
+                expected: inner.len(),
+                found: args.len(),
+            };
+            return Err(error(path, kind));
+        }
+        let wires: HashMap<&ElementId, &ElementId> = inner.into_iter().zip(args).collect();
+        let path = format!("{path}/op/box/circuit");
+        let mut statements = Vec::new();
+        for (i, command) in circ.commands.iter().enumerate() {
+            let path = format!("{path}/commands/{i}");
+            let args = command
+                .args
+                .iter()
+                .enumerate()
+                .map(|(j, id)| match wires.get(id) {
+                    Some(&outer) => Ok(outer.clone()),
+                    None => Err(error(
+                        format!("{path}/args/{j}"),
+                        ExportErrorKind::UndeclaredWire(id.clone()),
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            statements.extend(self.statements(&command.op, &args, &path)?);
+        }
+        for (a, b) in permutation_swaps(circ, &path)? {
+            statements.push(format!("swap {},{};", wires[a], wires[b]));
+        }
+        Ok(statements)
+    }
+
+    /// Returns the name of the classical register whose bits are `condition`,
+    /// in order.
+    fn condition_register<'a>(&self, condition: &'a [ElementId]) -> Option<&'a str> {
+        let name = &condition.first()?.0;
+        let whole = condition
+            .iter()
+            .enumerate()
+            .all(|(i, id)| id.0 == *name && id.1 == [i as i64]);
+        let size = *self.cregs.get(name)?;
+        (whole && size == condition.len() as i64).then_some(name.as_str())
+    }
+
+    /// Returns a gate call statement. `symbols` are the parameters of the
+    /// enclosing gate definition, if any.
+    fn gate(
+        &mut self,
+        op: &'c Operation,
+        args: &[String],
+        symbols: &[String],
+        path: &str,
+    ) -> Result<String, ExportError> {
+        let op_path = format!("{path}/op");
+        let (name, params, params_path) = match (&op.op_box, op.op_type) {
+            (Some(OpBox::CustomGate { gate, params, .. }), OpType::CustomGate) => {
+                self.define(gate, &format!("{op_path}/box/gate"))?;
+                let n_qubits = gate.definition.qubits.len();
+                if args.len() != n_qubits {
+                    return Err(arg_count(op, n_qubits, args.len(), path));
+                }
+                if params.len() != gate.args.len() {
+                    let kind = ExportErrorKind::ParamCount {
+                        op_type: op.op_type,
+                        expected: gate.args.len(),
+                        found: params.len(),
+                    };
+                    return Err(error(op_path, kind));
+                }
+                (
+                    gate.name.as_str(),
+                    params.as_slice(),
+                    format!("{op_path}/box/params"),
+                )
+            }
+            _ => {
+                let Some((name, n_params)) = qelib1_gate(op.op_type, args.len()) else {
+                    return Err(unsupported(op, path));
+                };
+                let expected = op.op_type.metadata().qubits.fixed();
+                let expected = expected.map_or(args.len(), |n| n as usize);
+                if args.len() != expected {
+                    return Err(arg_count(op, expected, args.len(), path));
+                }
+                let params = op.params.as_deref().unwrap_or_default();
+                if params.len() != n_params {
+                    let kind = ExportErrorKind::ParamCount {
+                        op_type: op.op_type,
+                        expected: n_params,
+                        found: params.len(),
+                    };
+                    return Err(error(op_path, kind));
+                }
+                (name, params, format!("{op_path}/params"))
+            }
+        };
+
+        let mut statement = name.to_string();
+        if !params.is_empty() {
+            let angles = params
+                .iter()
+                .enumerate()
+                .map(|(i, param)| angle(param, symbols, &format!("{params_path}/{i}")))
+                .collect::<Result<Vec<_>, _>>()?;
+            write!(statement, "({})", angles.join(",")).unwrap();
+        }
+        write!(statement, " {};", args.join(",")).unwrap();
+        Ok(statement)
+    }
+
+    /// Writes the `gate` definition of a `CustomGate`, unless it has already
+    /// been written.
+    fn define(&mut self, gate: &'c CustomGate, path: &str) -> Result<(), ExportError> {
+        if let Some(&defined) = self.defined.get(gate.name.as_str()) {
+            if defined == gate {
+                return Ok(());
+            }
+            let kind = ExportErrorKind::ConflictingDefinition(gate.name.clone());
+            return Err(error(path, kind));
+        }
+        if !is_global_name(&gate.name) {
+            return Err(error(path, ExportErrorKind::InvalidName(gate.name.clone())));
+        }
+        for (i, arg) in gate.args.iter().enumerate() {
+            if !is_identifier(arg) {
+                let path = format!("{path}/args/{i}");
+                return Err(error(path, ExportErrorKind::InvalidName(arg.clone())));
+            }
+        }
+
+        // The qubits of the definition are named by their index.
+        let circ = &gate.definition;
+        let names: HashMap<&ElementId, String> = circ
+            .qubits
+            .iter()
+            .enumerate()
+            .map(|(i, qb)| (&qb.id, format!("q{i}")))
+            .collect();
+        let def_path = format!("{path}/definition");
+        let mut body = Vec::new();
+        for (i, command) in circ.commands.iter().enumerate() {
+            let path = format!("{def_path}/commands/{i}");
+            let args = command
+                .args
+                .iter()
+                .enumerate()
+                .map(|(j, id)| match names.get(id) {
+                    Some(name) => Ok(name.clone()),
+                    None => Err(error(
+                        format!("{path}/args/{j}"),
+                        ExportErrorKind::UndeclaredWire(id.clone()),
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            match command.op.op_type {
+                OpType::Phase => {}
+                OpType::Barrier => body.push(format!("barrier {};", args.join(","))),
+                _ => body.push(self.gate(&command.op, &args, &gate.args, &path)?),
+            }
+        }
+        for (a, b) in permutation_swaps(circ, &def_path)? {
+            body.push(format!("swap {},{};", names[a], names[b]));
+        }
+
+        let mut header = format!("gate {}", gate.name);
+        if !gate.args.is_empty() {
+            write!(header, "({})", gate.args.join(",")).unwrap();
+        }
+        let qubits: Vec<String> = (0..circ.qubits.len()).map(|i| format!("q{i}")).collect();
+        writeln!(header, " {} {{", qubits.join(",")).unwrap();
+        self.definitions.push_str(&header);
+        for statement in body {
+            writeln!(self.definitions, "  {statement}").unwrap();
+        }
+        self.definitions.push_str("}\n");
+        self.defined.insert(&gate.name, gate);
+        Ok(())
+    }
+}
+
+/// Writes a parameter in half-turns as an OpenQASM 2.0 angle.
+fn angle(param: &str, symbols: &[String], path: &str) -> Result<String, ExportError> {
+    let expr: Expr = param
+        .parse()
+        .map_err(|e| error(path, ExportErrorKind::InvalidParam(e)))?;
+    write_angle(&expr, symbols, Dialect::Qasm2)
+        .ok_or_else(|| error(path, ExportErrorKind::UnsupportedParam(param.to_string())))
+}
+
+fn arg_count(op: &Operation, expected: usize, found: usize, path: &str) -> ExportError {
+    let kind = ExportErrorKind::ArgCount {
+        op_type: op.op_type,
+        expected,
+        found,
+    };
+    error(path, kind)
+}
+
+fn unsupported(op: &Operation, path: &str) -> ExportError {
+    error(
+        format!("{path}/op"),
+        ExportErrorKind::UnsupportedOperation(op.op_type),
+    )
+}
@@ -0,0 +1,944 @@
+//! Import of OpenQASM 2.0 programs as circuits.
+//!
+//! [`SerialCircuit::from_qasm2`] accepts the statements of OpenQASM 2.0:
+//! register declarations, the gates of `qelib1.inc` and the built-in `U` and
+//! `CX`, `gate` definitions, `measure`, `reset`, `barrier` and `if`.
+//! Operations applied to whole registers are broadcast over their elements.
+//! User gates become `CustomGate` operations.
+//!
+//! Parameters are converted from radians to half-turns, so that
+//! `rz(pi/2)` becomes `Rz(1/2)`. Inside a `gate` body, the gate parameters
+//! are symbols standing for their value divided by π.
+//!
+//! As an extension used by pytket, classical registers and bits can be
+//! assigned expressions of registers, bits and integers, such as
+//! `d = (a + b) / 2 - c;`. These become `ClExpr` operations. `if` statements
+//! may also test a single bit, as in `if(c[0]==1) x q[0];`.
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt;
+
+use derive_more::{Display, Error};
+
+use super::{is_identifier, QELIB1_GATES};
+use crate::circuit_json::{
+    Command, Conditional, CustomGate, ImplicitPermutation, Operation, SerialCircuit,
+};
+use crate::clexpr::builder::{ClExprBuilder, ClValue};
+use crate::expr::{Expr, Function};
+use crate::opbox::{BoxID, OpBox};
+use crate::optype::OpType;
+use crate::register::{BitRegister, ElementId};
+
+/// Error produced when parsing an OpenQASM 2.0 program.
+#[derive(Clone, Debug, PartialEq, Eq, Display, Error)]
+#[display("invalid OpenQASM 2.0 program at line {line}, column {column}: {message}")]
+pub struct ParseQasmError {
+    /// Line of the error in the input, starting from 1.
+    pub line: usize,
+    /// Column of the error in its line, in characters, starting from 1.
+    pub column: usize,
+    /// Description of the error.
+    pub message: String,
+}
+
+/// The gates available without including `qelib1.inc`.
+const BUILTIN_GATES: &[(OpType, &str, usize, usize)] =
+    &[(OpType::U3, "U", 3, 1), (OpType::CX, "CX", 0, 2)];
+
+/// Gates of recent versions of `qelib1.inc` that have the same operation
+/// type as another gate.
+const QELIB1_ALIASES: &[(OpType, &str, usize, usize)] = &[
+    (OpType::U3, "u", 3, 1),
+    (OpType::U1, "p", 1, 1),
+    (OpType::CU1, "cp", 1, 2),
+];
+
+/// Punctuation tokens, longest first.
+const SYMBOLS: &[&str] = &[
+    "->", "==", "!=", "<=", ">=", "<<", ">>", ";", ",", "(", ")", "[", "]", "{", "}", "+", "-",
+    "*", "/", "^", "=", "<", ">", "&", "|", "~", "!",
+];
+
+/// A lexical token in a program.
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Int(u64),
+    Real(f64),
+    Ident(String),
+    Str(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Int(n) => write!(f, "{n}"),
+            Token::Real(x) => write!(f, "{x:?}"),
+            Token::Ident(name) => f.write_str(name),
+            Token::Str(s) => write!(f, "\"{s}\""),
+            Token::Symbol(symbol) => f.write_str(symbol),
+        }
+    }
+}
+
+impl SerialCircuit {
+    /// Parses an OpenQASM 2.0 program.
+    ///
+    /// Qubits and bits are named after their register, in declaration order,
+    /// and the circuit has no global phase. See the [module
+    /// documentation](crate::qasm2::import) for the supported statements.
+    pub fn from_qasm2(source: &str) -> Result<Self, ParseQasmError> {
+        let mut parser = Parser {
+            source,
+            tokens: tokenize(source)?,
+            pos: 0,
+            qelib1: false,
+            registers: HashMap::new(),
+            gates: HashMap::new(),
+            circ: SerialCircuit::new(None, "0.0".to_string()),
+        };
+        parser.program()?;
+        let mut circ = parser.circ;
+        circ.implicit_permutation = identity(&circ);
+        circ.created_qubits = Some(Vec::new());
+        circ.discarded_qubits = Some(Vec::new());
+        Ok(circ)
+    }
+}
+
+fn error(source: &str, offset: usize, message: impl Into<String>) -> ParseQasmError {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    ParseQasmError {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+        message: message.into(),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ParseQasmError> {
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    while let Some(c) = source[start..].chars().next() {
+        let rest = &source[start..];
+        let (token, len) = if c.is_whitespace() {
+            start += c.len_utf8();
+            continue;
+        } else if rest.starts_with("//") {
+            start += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        } else if c.is_ascii_digit()
+            || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            let mut len = digits(rest);
+            let mut real = false;
+            if rest[len..].starts_with('.') {
+                real = true;
+                len += 1 + digits(&rest[len + 1..]);
+            }
+            if rest[len..].starts_with(['e', 'E']) {
+                let mut exponent = len + 1;
+                if rest[exponent..].starts_with(['+', '-']) {
+                    exponent += 1;
+                }
+                let exponent_len = digits(&rest[exponent..]);
+                if exponent_len > 0 {
+                    real = true;
+                    len = exponent + exponent_len;
+                }
+            }
+            let literal = &rest[..len];
+            let token = if real {
+                literal.parse().map(Token::Real).ok()
+            } else {
+                literal.parse().map(Token::Int).ok()
+            };
+            let token =
+                token.ok_or_else(|| error(source, start, format!("invalid number '{literal}'")))?;
+            (token, len)
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (Token::Ident(rest[..len].to_string()), len)
+        } else if c == '"' {
+            let len = rest[1..]
+                .find('"')
+                .ok_or_else(|| error(source, start, "unterminated string"))?;
+            (Token::Str(rest[1..len + 1].to_string()), len + 2)
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(*symbol))
+                .ok_or_else(|| error(source, start, format!("unexpected character '{c}'")))?;
+            (Token::Symbol(symbol), symbol.len())
+        };
+        tokens.push((start, token));
+        start += len;
+    }
+    Ok(tokens)
+}
+
+/// Returns the identity permutation of the qubits of a circuit.
+fn identity(circ: &SerialCircuit) -> Vec<ImplicitPermutation> {
+    circ.qubits
+        .iter()
+        .map(|qb| ImplicitPermutation(qb.clone(), qb.clone()))
+        .collect()
+}
+
+/// An argument of a statement: a whole register, or one of its elements.
+struct Arg {
+    name: String,
+    size: usize,
+    index: Option<usize>,
+    quantum: bool,
+    offset: usize,
+}
+
+/// The condition of an `if` statement: the bits it tests and the value
+/// they must hold.
+type Condition = (Vec<ElementId>, u32);
+
+/// A recursive descent parser over a token list, building a circuit.
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// Whether `qelib1.inc` has been included.
+    qelib1: bool,
+    /// The size of each declared register, and whether it is quantum.
+    registers: HashMap<String, (usize, bool)>,
+    gates: HashMap<String, CustomGate>,
+    circ: SerialCircuit,
+}
+
+impl Parser<'_> {
+    fn error(&self, offset: usize, message: impl Into<String>) -> ParseQasmError {
+        error(self.source, offset, message)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.source.len(), |(offset, _)| *offset)
+    }
+
+    fn next(&mut self) -> Result<Token, ParseQasmError> {
+        let token = self.peek().cloned().ok_or_else(|| self.unexpected())?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    /// Returns an error for the next token.
+    fn unexpected(&self) -> ParseQasmError {
+        match self.peek() {
+            Some(token) => self.error(self.offset(), format!("unexpected '{token}'")),
+            None => self.error(self.offset(), "unexpected end of input"),
+        }
+    }
+
+    /// Returns `true` if the token after the next one is `symbol`.
+    fn is_second(&self, symbol: &str) -> bool {
+        matches!(self.tokens.get(self.pos + 1), Some((_, Token::Symbol(s))) if *s == symbol)
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Symbol(s)) => *s == symbol,
+            Some(Token::Ident(name)) => name == symbol,
+            _ => false,
+        };
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ParseQasmError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(self.offset(), format!("expected '{symbol}'")))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ParseQasmError> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.error(self.offset(), "expected an identifier")),
+        }
+    }
+
+    fn int(&mut self) -> Result<u64, ParseQasmError> {
+        match self.peek() {
+            Some(&Token::Int(n)) => {
+                self.pos += 1;
+                Ok(n)
+            }
+            _ => Err(self.error(self.offset(), "expected an integer")),
+        }
+    }
+
+    /// `ident (',' ident)*`
+    fn ident_list(&mut self) -> Result<Vec<(usize, String)>, ParseQasmError> {
+        let mut idents = Vec::new();
+        loop {
+            idents.push((self.offset(), self.ident()?));
+            if !self.eat(",") {
+                return Ok(idents);
+            }
+        }
+    }
+
+    /// `program := 'OPENQASM' real ';' statement*`
+    fn program(&mut self) -> Result<(), ParseQasmError> {
+        self.expect("OPENQASM")?;
+        let offset = self.offset();
+        let version = self.next()?;
+        if version != Token::Real(2.0) && version != Token::Int(2) {
+            let message = format!("unsupported OpenQASM version '{version}'");
+            return Err(self.error(offset, message));
+        }
+        self.expect(";")?;
+        while self.peek().is_some() {
+            self.statement()?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), ParseQasmError> {
+        let offset = self.offset();
+        let keyword = match self.peek() {
+            Some(Token::Ident(keyword)) => keyword.clone(),
+            _ => return Err(self.unexpected()),
+        };
+        match keyword.as_str() {
+            "include" => {
+                self.pos += 1;
+                let offset = self.offset();
+                match self.next()? {
+                    Token::Str(file) if file == "qelib1.inc" => self.qelib1 = true,
+                    Token::Str(file) => {
+                        let message = format!("unsupported include file \"{file}\"");
+                        return Err(self.error(offset, message));
+                    }
+                    _ => return Err(self.error(offset, "expected a file name")),
+                }
+                self.expect(";")
+            }
+            "qreg" | "creg" => self.register(keyword == "qreg"),
+            "gate" => self.gate_definition(),
+            "opaque" => Err(self.error(offset, "opaque gates are not supported")),
+            "if" => {
+                self.pos += 1;
+                self.expect("(")?;
+                let arg = self.arg()?;
+                if arg.quantum {
+                    let message = format!("'{}' is not a classical register", arg.name);
+                    return Err(self.error(arg.offset, message));
+                }
+                self.expect("==")?;
+                let offset = self.offset();
+                let value = u32::try_from(self.int()?)
+                    .map_err(|_| self.error(offset, "the condition value is too large"))?;
+                self.expect(")")?;
+                let bits = match arg.index {
+                    Some(index) => vec![element(&arg.name, index)],
+                    None => (0..arg.size).map(|i| element(&arg.name, i)).collect(),
+                };
+                self.operation(Some((bits, value)))
+            }
+            _ => self.operation(None),
+        }
+    }
+
+    /// `('qreg' | 'creg') ident '[' int ']' ';'`
+    fn register(&mut self, quantum: bool) -> Result<(), ParseQasmError> {
+        self.pos += 1;
+        let offset = self.offset();
+        let name = self.ident()?;
+        self.declare(&name, offset)?;
+        self.expect("[")?;
+        let size = self.int()? as usize;
+        self.expect("]")?;
+        self.expect(";")?;
+        for i in 0..size {
+            let id = element(&name, i);
+            if quantum {
+                self.circ.qubits.push(id.into());
+            } else {
+                self.circ.bits.push(id.into());
+            }
+        }
+        self.registers.insert(name, (size, quantum));
+        Ok(())
+    }
+
+    /// Checks that a new register or gate name is valid and unused.
+    fn declare(&self, name: &str, offset: usize) -> Result<(), ParseQasmError> {
+        if !is_identifier(name) {
+            return Err(self.error(offset, format!("'{name}' is not a valid identifier")));
+        }
+        if self.registers.contains_key(name) || self.gate_type(name).is_some() {
+            return Err(self.error(offset, format!("'{name}' is already defined")));
+        }
+        Ok(())
+    }
+
+    /// `'gate' ident ('(' idents? ')')? idents '{' gate_statement* '}'`
+    fn gate_definition(&mut self) -> Result<(), ParseQasmError> {
+        self.pos += 1;
+        let offset = self.offset();
+        let name = self.ident()?;
+        self.declare(&name, offset)?;
+        let mut params = Vec::new();
+        if self.eat("(") && !self.eat(")") {
+            params = self.ident_list()?;
+            self.expect(")")?;
+        }
+        let qubits = self.ident_list()?;
+        for (i, (offset, name)) in params.iter().chain(&qubits).enumerate() {
+            if !is_identifier(name) {
+                return Err(self.error(*offset, format!("'{name}' is not a valid identifier")));
+            }
+            if params.iter().chain(&qubits).take(i).any(|(_, n)| n == name) {
+                return Err(self.error(*offset, format!("'{name}' is already defined")));
+            }
+        }
+        let params: Vec<String> = params.into_iter().map(|(_, p)| p).collect();
+        let qubits: Vec<String> = qubits.into_iter().map(|(_, q)| q).collect();
+
+        let mut definition = SerialCircuit::new(None, "0".to_string());
+        definition.qubits = (0..qubits.len()).map(|i| element("q", i).into()).collect();
+        definition.implicit_permutation = identity(&definition);
+        self.expect("{")?;
+        while !self.eat("}") {
+            let offset = self.offset();
+            let gate = self.ident()?;
+            let exprs = self.params(&params)?;
+            let mut args = Vec::new();
+            for (offset, arg) in self.ident_list()? {
+                let Some(index) = qubits.iter().position(|q| *q == arg) else {
+                    return Err(self.error(offset, format!("'{arg}' is not a gate argument")));
+                };
+                args.push(element("q", index));
+            }
+            self.expect(";")?;
+            let op = match gate.as_str() {
+                "barrier" if exprs.is_empty() => Operation::from_optype(OpType::Barrier),
+                _ => self.gate(&gate, offset, &exprs, args.len())?,
+            };
+            self.check_distinct(&args, offset)?;
+            definition.commands.push(Command {
+                op,
+                args,
+                opgroup: None,
+            });
+        }
+
+        let gate = CustomGate {
+            name: name.clone(),
+            args: params,
+            definition: Box::new(definition),
+        };
+        self.gates.insert(name, gate);
+        Ok(())
+    }
+
+    /// Parses a quantum operation or a classical assignment, ending with `;`.
+    fn operation(&mut self, condition: Option<Condition>) -> Result<(), ParseQasmError> {
+        if self.is_second("=") || self.is_second("[") {
+            return self.assignment(condition);
+        }
+        let offset = self.offset();
+        let name = self.ident()?;
+        match name.as_str() {
+            "measure" => {
+                let qubits = self.arg()?;
+                self.expect("->")?;
+                let bits = self.arg()?;
+                self.expect(";")?;
+                self.check_kind(&qubits, true)?;
+                self.check_kind(&bits, false)?;
+                for args in self.broadcast(&[qubits, bits])? {
+                    let op = Operation::from_optype(OpType::Measure);
+                    self.push(op, args, condition.as_ref());
+                }
+            }
+            "reset" => {
+                let qubits = self.arg()?;
+                self.expect(";")?;
+                self.check_kind(&qubits, true)?;
+                for args in self.broadcast(&[qubits])? {
+                    let op = Operation::from_optype(OpType::Reset);
+                    self.push(op, args, condition.as_ref());
+                }
+            }
+            "barrier" => {
+                let args = self.args()?;
+                self.expect(";")?;
+                if condition.is_some() {
+                    return Err(self.error(offset, "barriers cannot be conditional"));
+                }
+                let mut qubits = Vec::new();
+                for arg in &args {
+                    self.check_kind(arg, true)?;
+                    let indices = match arg.index {
+                        Some(index) => index..index + 1,
+                        None => 0..arg.size,
+                    };
+                    qubits.extend(indices.map(|i| element(&arg.name, i)));
+                }
+                self.check_distinct(&qubits, offset)?;
+                self.push(Operation::from_optype(OpType::Barrier), qubits, None);
+            }
+            _ => {
+                let params = self.params(&[])?;
+                let args = self.args()?;
+                self.expect(";")?;
+                for arg in &args {
+                    self.check_kind(arg, true)?;
+                }
+                let op = self.gate(&name, offset, &params, args.len())?;
+                for args in self.broadcast(&args)? {
+                    self.check_distinct(&args, offset)?;
+                    let mut op = op.clone();
+                    // Each box has its own identifier.
+                    if let Some(OpBox::CustomGate { id, .. }) = &mut op.op_box {
+                        *id = BoxID::new();
+                    }
+                    self.push(op, args, condition.as_ref());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the operation type of a built-in or `qelib1.inc` gate, with
+    /// its numbers of parameters and qubits.
+    fn gate_type(&self, name: &str) -> Option<(OpType, usize, usize)> {
+        let qelib1 = QELIB1_GATES.iter().chain(QELIB1_ALIASES);
+        let mut gates = BUILTIN_GATES.iter().chain(qelib1.filter(|_| self.qelib1));
+        gates
+            .find(|&&(_, gate, _, _)| gate == name)
+            .map(|&(op_type, _, n_params, n_qubits)| (op_type, n_params, n_qubits))
+    }
+
+    /// Returns the operation applying a gate to `n_qubits` qubits, with
+    /// parameters in radians.
+    fn gate(
+        &self,
+        name: &str,
+        offset: usize,
+        params: &[Expr],
+        n_qubits: usize,
+    ) -> Result<Operation, ParseQasmError> {
+        let custom = self.gates.get(name);
+        let (op_type, n_params, expected_qubits) = match custom {
+            Some(gate) => (
+                OpType::CustomGate,
+                gate.args.len(),
+                gate.definition.qubits.len(),
+            ),
+            None => self
+                .gate_type(name)
+                .ok_or_else(|| self.error(offset, format!("unknown gate '{name}'")))?,
+        };
+        if params.len() != n_params {
+            let message = format!(
+                "'{name}' takes {n_params} parameters, but {} were given",
+                params.len()
+            );
+            return Err(self.error(offset, message));
+        }
+        if n_qubits != expected_qubits {
+            let message =
+                format!("'{name}' acts on {expected_qubits} qubits, but {n_qubits} were given");
+            return Err(self.error(offset, message));
+        }
+
+        let params: Vec<String> = params.iter().map(|p| half_turns(p).to_string()).collect();
+        let mut op = Operation::from_optype(op_type);
+        match custom {
+            Some(gate) => {
+                op.op_box = Some(OpBox::CustomGate {
+                    id: BoxID::new(),
+                    gate: gate.clone(),
+                    params,
+                });
+            }
+            None if params.is_empty() => {}
+            None => op.params = Some(params),
+        }
+        Ok(op)
+    }
+
+    /// `('(' (expr (',' expr)*)? ')')?`
+    ///
+    /// Parses the parameters of a gate, in radians.
+    fn params(&mut self, symbols: &[String]) -> Result<Vec<Expr>, ParseQasmError> {
+        let mut params = Vec::new();
+        if self.eat("(") && !self.eat(")") {
+            loop {
+                params.push(self.expr(0, symbols)?);
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            self.expect(")")?;
+        }
+        Ok(params)
+    }
+
+    /// `arg := ident ('[' int ']')?`
+    fn arg(&mut self) -> Result<Arg, ParseQasmError> {
+        let offset = self.offset();
+        let name = self.ident()?;
+        let &(size, quantum) = self
+            .registers
+            .get(&name)
+            .ok_or_else(|| self.error(offset, format!("unknown register '{name}'")))?;
+        let index = if self.eat("[") {
+            let offset = self.offset();
+            let index = self.int()? as usize;
+            if index >= size {
+                let message = format!("index {index} is out of range for '{name}'");
+                return Err(self.error(offset, message));
+            }
+            self.expect("]")?;
+            Some(index)
+        } else {
+            None
+        };
+        Ok(Arg {
+            name,
+            size,
+            index,
+            quantum,
+            offset,
+        })
+    }
+
+    /// `arg (',' arg)*`
+    fn args(&mut self) -> Result<Vec<Arg>, ParseQasmError> {
+        let mut args = Vec::new();
+        loop {
+            args.push(self.arg()?);
+            if !self.eat(",") {
+                return Ok(args);
+            }
+        }
+    }
+
+    /// Checks that an argument is a quantum or classical register, as
+    /// required.
+    fn check_kind(&self, arg: &Arg, quantum: bool) -> Result<(), ParseQasmError> {
+        if arg.quantum == quantum {
+            return Ok(());
+        }
+        let kind = if quantum { "quantum" } else { "classical" };
+        let message = format!("'{}' is not a {kind} register", arg.name);
+        Err(self.error(arg.offset, message))
+    }
+
+    fn check_distinct(&self, args: &[ElementId], offset: usize) -> Result<(), ParseQasmError> {
+        for (i, arg) in args.iter().enumerate() {
+            if args[..i].contains(arg) {
+                return Err(self.error(offset, format!("{arg} is repeated in the arguments")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the argument lists of the operations applied by a statement.
+    ///
+    /// Whole registers are broadcast over their elements, so they must all
+    /// have the same size.
+    fn broadcast(&self, args: &[Arg]) -> Result<Vec<Vec<ElementId>>, ParseQasmError> {
+        let mut size = None;
+        for arg in args.iter().filter(|arg| arg.index.is_none()) {
+            match size {
+                Some(size) if size != arg.size => {
+                    let message = format!("'{}' does not have {size} elements", arg.name);
+                    return Err(self.error(arg.offset, message));
+                }
+                _ => size = Some(arg.size),
+            }
+        }
+        let lists = (0..size.unwrap_or(1)).map(|i| {
+            args.iter()
+                .map(|arg| element(&arg.name, arg.index.unwrap_or(i)))
+                .collect()
+        });
+        Ok(lists.collect())
+    }
+
+    /// Adds a command to the circuit, wrapping the operation in a
+    /// `Conditional` if needed.
+    fn push(&mut self, op: Operation, args: Vec<ElementId>, condition: Option<&Condition>) {
+        let (op, args) = match condition {
+            None => (op, args),
+            Some((bits, value)) => {
+                let mut conditional = Operation::from_optype(OpType::Conditional);
+                conditional.conditional = Some(Conditional {
+                    op: Box::new(op),
+                    width: bits.len() as u32,
+                    value: *value,
+                });
+                (conditional, bits.iter().cloned().chain(args).collect())
+            }
+        };
+        self.circ.commands.push(Command {
+            op,
+            args,
+            opgroup: None,
+        });
+    }
+
+    /// `assignment := arg '=' classical ';'`
+    fn assignment(&mut self, condition: Option<Condition>) -> Result<(), ParseQasmError> {
+        let offset = self.offset();
+        let target = self.arg()?;
+        let name = target.name.clone();
+        self.check_kind(&target, false)?;
+        let outputs: Vec<ElementId> = match target.index {
+            Some(index) => vec![element(&name, index)],
+            None => (0..target.size).map(|i| element(&name, i)).collect(),
+        };
+        self.expect("=")?;
+        let mut builder = ClExprBuilder::new();
+        let value = self.classical(0, &mut builder)?;
+        self.expect(";")?;
+        let command = builder.build_command(value, outputs).map_err(|errors| {
+            let message = format!("invalid classical expression: {}", errors[0]);
+            self.error(offset, message)
+        })?;
+        self.push(command.op, command.args, condition.as_ref());
+        Ok(())
+    }
+
+    /// Parses a classical expression whose operators bind at least as
+    /// tightly as `min_precedence`, with the precedence of C.
+    fn classical(
+        &mut self,
+        min_precedence: u8,
+        builder: &mut ClExprBuilder,
+    ) -> Result<ClValue, ParseQasmError> {
+        let mut lhs = self.classical_unary(builder)?;
+        while let Some(&Token::Symbol(op)) = self.peek() {
+            let precedence = match op {
+                "|" => 1,
+                "^" => 2,
+                "&" => 3,
+                "==" | "!=" => 4,
+                "<" | ">" | "<=" | ">=" => 5,
+                "<<" | ">>" => 6,
+                "+" | "-" => 7,
+                "*" | "/" => 8,
+                _ => break,
+            };
+            if precedence < min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.classical(precedence + 1, builder)?;
+            lhs = match op {
+                "|" => lhs | rhs,
+                "^" => lhs ^ rhs,
+                "&" => lhs & rhs,
+                "==" => lhs.eq(rhs),
+                "!=" => lhs.ne(rhs),
+                "<" => lhs.lt(rhs),
+                ">" => lhs.gt(rhs),
+                "<=" => lhs.le(rhs),
+                ">=" => lhs.ge(rhs),
+                "<<" => lhs << rhs,
+                ">>" => lhs >> rhs,
+                "+" => lhs + rhs,
+                "-" => lhs - rhs,
+                "*" => lhs * rhs,
+                _ => lhs / rhs,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn classical_unary(&mut self, builder: &mut ClExprBuilder) -> Result<ClValue, ParseQasmError> {
+        if self.eat("~") || self.eat("!") {
+            return Ok(!self.classical_unary(builder)?);
+        }
+        if self.eat("-") {
+            return Ok(-self.classical_unary(builder)?);
+        }
+        match self.peek() {
+            Some(&Token::Int(n)) => {
+                self.pos += 1;
+                Ok(ClValue::int(n))
+            }
+            Some(Token::Ident(_)) => {
+                let arg = self.arg()?;
+                self.check_kind(&arg, false)?;
+                Ok(match arg.index {
+                    Some(index) => builder.bit(element(&arg.name, index)),
+                    None => builder.register(&BitRegister {
+                        name: arg.name,
+                        size: arg.size as u32,
+                    }),
+                })
+            }
+            Some(Token::Symbol("(")) => {
+                self.pos += 1;
+                let value = self.classical(0, builder)?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// Parses a parameter expression whose operators bind at least as tightly
+    /// as `min_precedence`. Identifiers in `symbols` become symbols.
+    fn expr(&mut self, min_precedence: u8, symbols: &[String]) -> Result<Expr, ParseQasmError> {
+        let mut lhs = if self.eat("-") {
+            Expr::Neg(Box::new(self.expr(3, symbols)?))
+        } else {
+            self.atom(symbols)?
+        };
+        loop {
+            let (op, precedence) = match self.peek() {
+                Some(Token::Symbol(op @ ("+" | "-"))) => (*op, 1),
+                Some(Token::Symbol(op @ ("*" | "/"))) => (*op, 2),
+                Some(Token::Symbol(op @ "^")) => (*op, 4),
+                _ => break,
+            };
+            if precedence < min_precedence {
+                break;
+            }
+            self.pos += 1;
+            // Exponentiation is right-associative.
+            let rhs_precedence = if op == "^" {
+                precedence
+            } else {
+                precedence + 1
+            };
+            let rhs = Box::new(self.expr(rhs_precedence, symbols)?);
+            let lhs_box = Box::new(lhs);
+            lhs = match op {
+                "+" => Expr::Add(lhs_box, rhs),
+                "-" => Expr::Sub(lhs_box, rhs),
+                "*" => Expr::Mul(lhs_box, rhs),
+                "/" => Expr::Div(lhs_box, rhs),
+                _ => Expr::Pow(lhs_box, rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn atom(&mut self, symbols: &[String]) -> Result<Expr, ParseQasmError> {
+        let offset = self.offset();
+        match self.next()? {
+            Token::Int(n) => i64::try_from(n)
+                .map(Expr::Integer)
+                .map_err(|_| self.error(offset, format!("the integer {n} is too large"))),
+            Token::Real(x) => Ok(Expr::Float(x)),
+            Token::Symbol("(") => {
+                let expr = self.expr(0, symbols)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Ident(name) if name == "pi" => Ok(Expr::Pi),
+            Token::Ident(name) if symbols.contains(&name) => Ok(Expr::Symbol(name)),
+            Token::Ident(name) => {
+                let func = match name.as_str() {
+                    "sin" => Function::Sin,
+                    "cos" => Function::Cos,
+                    "tan" => Function::Tan,
+                    "exp" => Function::Exp,
+                    "ln" => Function::Log,
+                    "sqrt" => Function::Sqrt,
+                    _ => return Err(self.error(offset, format!("unknown parameter '{name}'"))),
+                };
+                self.expect("(")?;
+                let arg = self.expr(0, symbols)?;
+                self.expect(")")?;
+                Ok(Expr::Func(func, vec![arg]))
+            }
+            token => Err(self.error(offset, format!("unexpected '{token}'"))),
+        }
+    }
+}
+
+fn element(register: &str, index: usize) -> ElementId {
+    ElementId(register.to_string(), vec![index as i64])
+}
+
+/// Converts an angle in radians to half-turns.
+///
+/// Symbols stand for gate parameters, which are already divided by π.
+/// Multiples of π are divided exactly; other constant angles are evaluated.
+fn half_turns(radians: &Expr) -> Expr {
+    if let Some(expr) = divide_by_pi(radians) {
+        return expr;
+    }
+    let symbols = radians.free_symbols();
+    if symbols.is_empty() {
+        if let Ok(value) = radians.evaluate(&HashMap::new()) {
+            return Expr::Float(value / PI);
+        }
+    }
+    let scaled = symbols
+        .into_iter()
+        .map(|s| {
+            let value = Expr::Mul(Box::new(Expr::Symbol(s.clone())), Box::new(Expr::Pi));
+            (s, value)
+        })
+        .collect();
+    Expr::Div(Box::new(radians.substitute(&scaled)), Box::new(Expr::Pi))
+}
+
+/// Divides an expression that is linear in π and the symbols by π, or
+/// returns `None`.
+///
+/// Only one factor of a product is divided, so the other factor, like a
+/// divisor, must not contain symbols, which carry their own factor of π.
+fn divide_by_pi(expr: &Expr) -> Option<Expr> {
+    let one = Expr::Integer(1);
+    let constant = |e: &Expr| e.free_symbols().is_empty();
+    Some(match expr {
+        Expr::Pi => one,
+        Expr::Symbol(_) => expr.clone(),
+        Expr::Neg(a) => Expr::Neg(Box::new(divide_by_pi(a)?)),
+        Expr::Add(a, b) => Expr::Add(Box::new(divide_by_pi(a)?), Box::new(divide_by_pi(b)?)),
+        Expr::Sub(a, b) => Expr::Sub(Box::new(divide_by_pi(a)?), Box::new(divide_by_pi(b)?)),
+        Expr::Mul(a, b) => {
+            let divided = if constant(b) { divide_by_pi(a) } else { None };
+            let (a, b) = match divided {
+                Some(a) => (a, (**b).clone()),
+                None if constant(a) => ((**a).clone(), divide_by_pi(b)?),
+                None => return None,
+            };
+            match (a, b) {
+                (a, b) if b == one => a,
+                (a, b) if a == one => b,
+                (a, b) => Expr::Mul(Box::new(a), Box::new(b)),
+            }
+        }
+        Expr::Div(a, b) if constant(b) => match divide_by_pi(a)? {
+            a if **b == one => a,
+            a => Expr::Div(Box::new(a), b.clone()),
+        },
+        _ => return None,
+    })
+}
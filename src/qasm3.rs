@@ -0,0 +1,180 @@
+//! Conversion of circuits to OpenQASM 3 programs.
+//!
+//! [`SerialCircuit::to_qasm3`] writes a circuit as a program using the gates
+//! of the standard `stdgates.inc` library, with the `inv` and `ctrl`
+//! modifiers for the gates it lacks. Qubits and bits are grouped into
+//! `qubit` and `bit` registers by the name of their [`ElementId`], and must
+//! have one-dimensional indices.
+//!
+//! Unlike OpenQASM 2.0, OpenQASM 3 can express the classical parts of a
+//! circuit: `ClExpr` operations and the other classical operations become
+//! assignments of integer and bit expressions, `Conditional` operations
+//! become `if` blocks, and `Label`, `Branch` and `Goto` operations become
+//! `if` and `while` blocks where the jumps are nested properly. WASM calls
+//! and RNG operations become calls to `extern` functions.
+//!
+//!   [`SerialCircuit::to_qasm3`]: crate::circuit_json::SerialCircuit::to_qasm3
+//!   [`ElementId`]: crate::register::ElementId
+
+pub mod export;
+
+use crate::optype::OpType;
+
+/// The gates of `stdgates.inc`, and the modified gates used for other
+/// operation types, with their numbers of parameters and qubits.
+///
+/// `U2`, `U3` and `CU3` use the built-in `U` gate, as the `u2` and `u3` gates
+/// of `stdgates.inc` differ from them by a phase. `U2` is written with a
+/// first parameter of π/2.
+const STDGATES: &[(OpType, &str, usize, usize)] = &[
+    (OpType::noop, "id", 0, 1),
+    (OpType::X, "x", 0, 1),
+    (OpType::Y, "y", 0, 1),
+    (OpType::Z, "z", 0, 1),
+    (OpType::H, "h", 0, 1),
+    (OpType::S, "s", 0, 1),
+    (OpType::Sdg, "sdg", 0, 1),
+    (OpType::T, "t", 0, 1),
+    (OpType::Tdg, "tdg", 0, 1),
+    (OpType::SX, "sx", 0, 1),
+    (OpType::SXdg, "inv @ sx", 0, 1),
+    (OpType::Rx, "rx", 1, 1),
+    (OpType::Ry, "ry", 1, 1),
+    (OpType::Rz, "rz", 1, 1),
+    (OpType::U1, "p", 1, 1),
+    (OpType::U2, "U", 2, 1),
+    (OpType::U3, "U", 3, 1),
+    (OpType::CX, "cx", 0, 2),
+    (OpType::CY, "cy", 0, 2),
+    (OpType::CZ, "cz", 0, 2),
+    (OpType::CH, "ch", 0, 2),
+    (OpType::CSX, "ctrl @ sx", 0, 2),
+    (OpType::CRx, "crx", 1, 2),
+    (OpType::CRy, "cry", 1, 2),
+    (OpType::CRz, "crz", 1, 2),
+    (OpType::CU1, "cp", 1, 2),
+    (OpType::CU3, "ctrl @ U", 3, 2),
+    (OpType::SWAP, "swap", 0, 2),
+    (OpType::CCX, "ccx", 0, 3),
+    (OpType::CSWAP, "cswap", 0, 3),
+    (OpType::CnX, "cx", 0, 2),
+    (OpType::CnX, "ccx", 0, 3),
+    (OpType::CnY, "cy", 0, 2),
+    (OpType::CnZ, "cz", 0, 2),
+];
+
+/// Returns the gate implementing an operation type on the given number of
+/// qubits, with its number of parameters.
+///
+/// Multi-controlled gates without a `stdgates.inc` equivalent use the `ctrl`
+/// modifier.
+fn stdgate(op_type: OpType, n_qubits: usize) -> Option<(String, usize)> {
+    let multi_controlled = matches!(op_type, OpType::CnX | OpType::CnY | OpType::CnZ);
+    let gate = STDGATES
+        .iter()
+        .find(|&&(t, _, _, n)| t == op_type && (!multi_controlled || n == n_qubits));
+    if let Some(&(_, name, n_params, _)) = gate {
+        return Some((name.to_string(), n_params));
+    }
+    let target = match op_type {
+        OpType::CnX => "x",
+        OpType::CnY => "y",
+        OpType::CnZ => "z",
+        _ => return None,
+    };
+    match n_qubits {
+        0 => None,
+        1 => Some((target.to_string(), 0)),
+        _ => Some((format!("ctrl({}) @ {target}", n_qubits - 1), 0)),
+    }
+}
+
+/// Returns `true` if `name` is a valid OpenQASM 3 identifier that is not a
+/// keyword or a built-in name.
+fn is_identifier(name: &str) -> bool {
+    const RESERVED: &[&str] = &[
+        "OPENQASM",
+        "include",
+        "defcalgrammar",
+        "def",
+        "cal",
+        "defcal",
+        "gate",
+        "extern",
+        "box",
+        "let",
+        "break",
+        "continue",
+        "if",
+        "else",
+        "end",
+        "return",
+        "for",
+        "while",
+        "in",
+        "switch",
+        "case",
+        "default",
+        "input",
+        "output",
+        "const",
+        "readonly",
+        "mutable",
+        "qreg",
+        "qubit",
+        "creg",
+        "bool",
+        "bit",
+        "int",
+        "uint",
+        "float",
+        "angle",
+        "complex",
+        "array",
+        "void",
+        "duration",
+        "stretch",
+        "gphase",
+        "inv",
+        "pow",
+        "ctrl",
+        "negctrl",
+        "durationof",
+        "delay",
+        "reset",
+        "measure",
+        "barrier",
+        "true",
+        "false",
+        "pi",
+        "tau",
+        "euler",
+        "sizeof",
+        "sin",
+        "cos",
+        "tan",
+        "arcsin",
+        "arccos",
+        "arctan",
+        "exp",
+        "log",
+        "sqrt",
+        "U",
+    ];
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !RESERVED.contains(&name)
+}
+
+/// Returns `true` if `name` can name a register, a gate or an `extern`
+/// function: it must be an identifier distinct from the `stdgates.inc`
+/// gates.
+fn is_global_name(name: &str) -> bool {
+    const OTHER_GATES: &[&str] = &["cu", "u1", "CX", "phase", "cphase"];
+    is_identifier(name)
+        && !STDGATES.iter().any(|&(_, gate, _, _)| gate == name)
+        && !OTHER_GATES.contains(&name)
+}
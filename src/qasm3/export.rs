@@ -0,0 +1,1165 @@
+//! Export of circuits as OpenQASM 3 programs.
+//!
+//! Classical expressions are written with `uint` values: a register variable
+//! of a `ClExpr` becomes a cast such as `uint[3](a)`, and a register result
+//! is cast back with `bit[n](...)`, first resizing it with `uint[n](...)` if
+//! it is wider or narrower than its `n` output bits, as allowed by
+//! [`ClExpr::check_resizing`]. Register variables and results that are not a
+//! whole classical register or a contiguous slice of one are copied through
+//! temporary registers, declared in a block around the assignment.
+//!
+//! The flow operations are only supported at the top level of the circuit,
+//! with the name of their label in their `data` field. A `Branch` or `Goto`
+//! jumping forwards to a label becomes an `if` or `if`/`else` block, and a
+//! jump back to a label becomes a `while` loop, provided that the jumps do
+//! not cross the boundaries of each other's blocks. `Stop` becomes `end`.
+//!
+//! WASM calls and RNG operations become calls to `extern` functions, named
+//! after the WASM function and `rng_seed`, `rng_bound`, `rng_index`,
+//! `rng_num` and `job_shot_num` respectively.
+//!
+//! The implicit permutation of a circuit, or of the circuit of a box, is
+//! written as `swap` gates after its commands.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+use derive_more::{Display, Error};
+
+use super::{is_global_name, is_identifier, stdgate};
+use crate::circuit_json::{Command, CustomGate, Operation, SerialCircuit};
+use crate::clexpr::check::ClType;
+use crate::clexpr::lower::{lower, LowerErrorKind};
+use crate::clexpr::op::ClOp;
+use crate::clexpr::operator::{ClArgument, ClOperator, ClTerminal, ClVariable};
+use crate::clexpr::ClExpr;
+use crate::expr::write::{write_angle, Dialect};
+use crate::expr::{Expr, ParseExprError};
+use crate::opbox::OpBox;
+use crate::optype::OpType;
+use crate::register::ElementId;
+
+/// A problem preventing a circuit from being written as OpenQASM 3.
+#[derive(Clone, Debug, PartialEq, Display, Error)]
+#[display("{path}: {kind}")]
+#[non_exhaustive]
+pub struct ExportError {
+    /// JSON pointer to the offending element, e.g. `/commands/3/op`.
+    pub path: String,
+    /// The problem found at that location.
+    #[error(not(source))]
+    pub kind: ExportErrorKind,
+}
+
+/// The kinds of problems reported by [`SerialCircuit::to_qasm3`].
+#[derive(Clone, Debug, PartialEq, Display)]
+#[non_exhaustive]
+pub enum ExportErrorKind {
+    /// A register, gate or function name is not a valid OpenQASM 3
+    /// identifier, or clashes with a keyword, a standard gate or another
+    /// name.
+    #[display("`{_0}` is not a valid OpenQASM 3 identifier")]
+    InvalidName(String),
+    /// A qubit or bit does not have a single non-negative index.
+    #[display("{_0} does not have a one-dimensional index")]
+    InvalidIndex(ElementId),
+    /// A register name is used by both qubits and bits.
+    #[display("`{_0}` names both a quantum and a classical register")]
+    RegisterClash(String),
+    /// A command refers to a wire that is not declared.
+    #[display("{_0} is not declared in the circuit")]
+    UndeclaredWire(ElementId),
+    /// The implicit permutation of a circuit is not a permutation of its
+    /// qubits.
+    #[display("the implicit permutation is not a permutation of the qubits")]
+    InvalidPermutation,
+    /// The operation has no OpenQASM 3 equivalent.
+    #[display("{_0} operations cannot be written in OpenQASM 3")]
+    UnsupportedOperation(OpType),
+    /// An operation lacks the field describing it.
+    #[display("{op_type} operation has no `{field}` field")]
+    MissingField {
+        /// The operation type.
+        op_type: OpType,
+        /// The name of the missing field.
+        field: &'static str,
+    },
+    /// Bits used as a register are not a whole classical register or a
+    /// contiguous slice of one, in order.
+    #[display("the bits cannot be written as a classical register or slice")]
+    UnsupportedBits,
+    /// A classical operation cannot be converted to a classical expression.
+    #[display("{_0}")]
+    InvalidClassical(LowerErrorKind),
+    /// A classical expression is not well typed.
+    #[display("invalid classical expression: {_0}")]
+    InvalidExpression(String),
+    /// A flow operation cannot be written as structured control flow.
+    #[display("the jump cannot be written as an `if` or `while` block")]
+    UnsupportedControlFlow,
+    /// A `Branch` or `Goto` operation jumps to a label that is not defined.
+    #[display("the label `{_0}` is not defined")]
+    UnknownLabel(String),
+    /// Two `Label` operations have the same name.
+    #[display("the label `{_0}` is defined twice")]
+    DuplicateLabel(String),
+    /// Two `CustomGate` operations, or two WASM calls, have the same name
+    /// but different definitions.
+    #[display("`{_0}` has conflicting definitions")]
+    ConflictingDefinition(String),
+    /// A command does not have the number of arguments its operation acts on.
+    #[display("{op_type} acts on {expected} arguments, but {found} were given")]
+    ArgCount {
+        /// The operation type.
+        op_type: OpType,
+        /// The number of arguments the operation acts on.
+        expected: usize,
+        /// The number of arguments in the command.
+        found: usize,
+    },
+    /// An operation does not have the number of parameters its type expects.
+    #[display("{op_type} takes {expected} parameters, but {found} were given")]
+    ParamCount {
+        /// The operation type.
+        op_type: OpType,
+        /// The number of parameters of the operation type.
+        expected: usize,
+        /// The number of parameters of the operation.
+        found: usize,
+    },
+    /// A parameter is not a valid expression.
+    #[display("{_0}")]
+    InvalidParam(ParseExprError),
+    /// A parameter uses free symbols, functions or numbers that cannot be
+    /// written in OpenQASM 3.
+    #[display("the parameter `{_0}` cannot be written in OpenQASM 3")]
+    UnsupportedParam(String),
+}
+
+impl SerialCircuit {
+    /// Writes the circuit as an OpenQASM 3 program using `stdgates.inc`.
+    ///
+    /// Returns the first problem found if some part of the circuit cannot be
+    /// written.
+    pub fn to_qasm3(&self) -> Result<String, ExportError> {
+        let qregs = registers(self.qubits.iter().map(|qb| &qb.id), "/qubits")?;
+        let cregs = registers(self.bits.iter().map(|bit| &bit.id), "/bits")?;
+        for (name, _) in &qregs {
+            if cregs.iter().any(|(creg, _)| creg == name) {
+                return Err(error("/bits", ExportErrorKind::RegisterClash(name.clone())));
+            }
+        }
+
+        let mut labels = HashMap::new();
+        for (i, command) in self.commands.iter().enumerate() {
+            if command.op.op_type == OpType::Label {
+                let label = label(&command.op, &format!("/commands/{i}"))?;
+                if labels.insert(label, i).is_some() {
+                    let kind = ExportErrorKind::DuplicateLabel(label.to_string());
+                    return Err(error(format!("/commands/{i}/op/data"), kind));
+                }
+            }
+        }
+        let mut exporter = Exporter {
+            commands: &self.commands,
+            labels,
+            qubits: self.qubits.iter().map(|qb| qb.id.clone()).collect(),
+            bits: self.bits.iter().map(|bit| bit.id.clone()).collect(),
+            registers: qregs
+                .iter()
+                .chain(&cregs)
+                .map(|(name, _)| name.clone())
+                .collect(),
+            cregs: cregs.iter().cloned().collect(),
+            definitions: String::new(),
+            defined: HashMap::new(),
+            externs: Vec::new(),
+        };
+
+        let mut body = Vec::new();
+        let phase: Expr = self
+            .phase
+            .parse()
+            .map_err(|e| error("/phase", ExportErrorKind::InvalidParam(e)))?;
+        if phase.evaluate(&HashMap::new()) != Ok(0.0) {
+            body.push(format!("gphase({});", angle(&self.phase, &[], "/phase")?));
+        }
+        body.extend(exporter.block(0..self.commands.len())?);
+        for (a, b) in permutation_swaps(self, "")? {
+            body.push(format!("swap {a}, {b};"));
+        }
+
+        let mut out = String::from("OPENQASM 3.0;\ninclude \"stdgates.inc\";\n\n");
+        out.push_str(&exporter.definitions);
+        for (_, declaration) in &exporter.externs {
+            writeln!(out, "{declaration}").unwrap();
+        }
+        for (name, size) in qregs {
+            writeln!(out, "qubit[{size}] {name};").unwrap();
+        }
+        for (name, size) in cregs {
+            writeln!(out, "bit[{size}] {name};").unwrap();
+        }
+        for line in body {
+            writeln!(out, "{line}").unwrap();
+        }
+        Ok(out)
+    }
+}
+
+fn error(path: impl Into<String>, kind: ExportErrorKind) -> ExportError {
+    ExportError {
+        path: path.into(),
+        kind,
+    }
+}
+
+/// Returns the swaps implementing the implicit permutation of a circuit.
+/// `path` is the JSON pointer to the circuit.
+fn permutation_swaps<'a>(
+    circ: &'a SerialCircuit,
+    path: &str,
+) -> Result<Vec<(&'a ElementId, &'a ElementId)>, ExportError> {
+    circ.permutation_swaps().ok_or_else(|| {
+        error(
+            format!("{path}/implicit_permutation"),
+            ExportErrorKind::InvalidPermutation,
+        )
+    })
+}
+
+/// Returns the registers of a list of wires, with their sizes, in order of
+/// first appearance.
+fn registers<'a>(
+    ids: impl Iterator<Item = &'a ElementId>,
+    path: &str,
+) -> Result<Vec<(String, i64)>, ExportError> {
+    let mut registers: Vec<(String, i64)> = Vec::new();
+    for (i, id) in ids.enumerate() {
+        let path = format!("{path}/{i}");
+        let index = match id.1.as_slice() {
+            [index] if *index >= 0 => *index,
+            _ => return Err(error(path, ExportErrorKind::InvalidIndex(id.clone()))),
+        };
+        if !is_global_name(&id.0) {
+            return Err(error(path, ExportErrorKind::InvalidName(id.0.clone())));
+        }
+        match registers.iter_mut().find(|(name, _)| *name == id.0) {
+            Some((_, size)) => *size = (*size).max(index + 1),
+            None => registers.push((id.0.clone(), index + 1)),
+        }
+    }
+    Ok(registers)
+}
+
+/// Returns the label of a flow operation.
+fn label<'c>(op: &'c Operation, path: &str) -> Result<&'c str, ExportError> {
+    op.data.as_deref().ok_or_else(|| {
+        let kind = ExportErrorKind::MissingField {
+            op_type: op.op_type,
+            field: "data",
+        };
+        error(format!("{path}/op"), kind)
+    })
+}
+
+/// Indents the lines of a nested block.
+fn indent(lines: Vec<String>) -> impl Iterator<Item = String> {
+    lines.into_iter().map(|line| format!("  {line}"))
+}
+
+/// Appends a block statement such as `if (c == 1) { ... }`.
+fn push_block(lines: &mut Vec<String>, header: String, body: Vec<String>) {
+    lines.push(format!("{header} {{"));
+    lines.extend(indent(body));
+    lines.push("}".to_string());
+}
+
+/// The state of an export in progress.
+struct Exporter<'c> {
+    /// The top-level commands of the circuit.
+    commands: &'c [Command],
+    /// The index of the command defining each label.
+    labels: HashMap<&'c str, usize>,
+    /// The declared qubits of the circuit.
+    qubits: HashSet<ElementId>,
+    /// The declared bits of the circuit.
+    bits: HashSet<ElementId>,
+    /// The names of all the registers.
+    registers: HashSet<String>,
+    /// The size of each classical register.
+    cregs: HashMap<String, i64>,
+    /// The `gate` definitions written so far.
+    definitions: String,
+    /// The `CustomGate` definitions written so far, by name.
+    defined: HashMap<&'c str, &'c CustomGate>,
+    /// The `extern` functions declared so far, with their declarations.
+    externs: Vec<(String, String)>,
+}
+
+impl<'c> Exporter<'c> {
+    /// Returns the statements implementing a range of top-level commands,
+    /// turning the jumps between them into nested blocks.
+    fn block(&mut self, range: Range<usize>) -> Result<Vec<String>, ExportError> {
+        let mut lines = Vec::new();
+        let mut i = range.start;
+        while i < range.end {
+            let command = &self.commands[i];
+            let path = format!("/commands/{i}");
+            match command.op.op_type {
+                OpType::Label => {
+                    let name = label(&command.op, &path)?;
+                    // The last jump back to the label closes a loop.
+                    let back = (i + 1..range.end)
+                        .rev()
+                        .find(|&j| self.jump_label(j) == Some(name));
+                    let Some(j) = back else {
+                        i += 1;
+                        continue;
+                    };
+                    let mut body = self.block(i + 1..j)?;
+                    let jump = &self.commands[j];
+                    if jump.op.op_type == OpType::Branch {
+                        let stay = self.stay(jump, &format!("/commands/{j}"))?;
+                        push_block(&mut body, format!("if ({stay})"), vec!["break;".into()]);
+                    }
+                    push_block(&mut lines, "while (true)".to_string(), body);
+                    i = j + 1;
+                }
+                OpType::Branch | OpType::Goto => {
+                    let target = self.target(command, &path)?;
+                    if target <= i || target >= range.end {
+                        return Err(error(&path, ExportErrorKind::UnsupportedControlFlow));
+                    }
+                    if command.op.op_type == OpType::Goto {
+                        // The skipped commands are unreachable, unless some
+                        // other jump leads to them.
+                        let reachable =
+                            (i + 1..target).any(|k| self.commands[k].op.op_type == OpType::Label);
+                        if reachable {
+                            return Err(error(&path, ExportErrorKind::UnsupportedControlFlow));
+                        }
+                        i = target;
+                        continue;
+                    }
+                    let stay = self.stay(command, &path)?;
+                    // A jump over the commands following the label makes
+                    // them an `else` block.
+                    let last = target - 1;
+                    let end = match self.commands[last].op.op_type {
+                        OpType::Goto if last > i => {
+                            let end =
+                                self.target(&self.commands[last], &format!("/commands/{last}"))?;
+                            (end > target && end < range.end).then_some(end)
+                        }
+                        _ => None,
+                    };
+                    match end {
+                        Some(end) => {
+                            let then = self.block(i + 1..last)?;
+                            let otherwise = self.block(target + 1..end)?;
+                            push_block(&mut lines, format!("if ({stay})"), then);
+                            lines.pop();
+                            push_block(&mut lines, "} else".to_string(), otherwise);
+                            i = end;
+                        }
+                        None => {
+                            let then = self.block(i + 1..target)?;
+                            push_block(&mut lines, format!("if ({stay})"), then);
+                            i = target;
+                        }
+                    }
+                }
+                _ => {
+                    let statements = self.statements(&command.op, &command.args, &path)?;
+                    lines.extend(statements);
+                    i += 1;
+                }
+            }
+        }
+        Ok(lines)
+    }
+
+    /// Returns the label a top-level command jumps to, if it is a jump.
+    fn jump_label(&self, command: usize) -> Option<&'c str> {
+        let op = &self.commands[command].op;
+        match op.op_type {
+            OpType::Branch | OpType::Goto => op.data.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the index of the label a jump leads to.
+    fn target(&self, command: &Command, path: &str) -> Result<usize, ExportError> {
+        let name = label(&command.op, path)?;
+        self.labels.get(name).copied().ok_or_else(|| {
+            let kind = ExportErrorKind::UnknownLabel(name.to_string());
+            error(format!("{path}/op/data"), kind)
+        })
+    }
+
+    /// Returns the condition under which a `Branch` does not jump.
+    fn stay(&self, branch: &Command, path: &str) -> Result<String, ExportError> {
+        match branch.args.as_slice() {
+            [bit] => Ok(format!(
+                "{} == 0",
+                self.bit(bit, &format!("{path}/args/0"))?
+            )),
+            args => Err(arg_count(&branch.op, 1, args.len(), path)),
+        }
+    }
+
+    /// Returns the statements implementing an operation on the given
+    /// arguments of the top-level circuit.
+    fn statements(
+        &mut self,
+        op: &'c Operation,
+        args: &[ElementId],
+        path: &str,
+    ) -> Result<Vec<String>, ExportError> {
+        let op_path = format!("{path}/op");
+        let missing = |field| {
+            let kind = ExportErrorKind::MissingField {
+                op_type: op.op_type,
+                field,
+            };
+            error(&op_path, kind)
+        };
+        match op.op_type {
+            OpType::Label | OpType::Branch | OpType::Goto => {
+                Err(error(path, ExportErrorKind::UnsupportedControlFlow))
+            }
+            OpType::Stop => Ok(vec!["end;".to_string()]),
+            OpType::Phase => match op.params.as_deref() {
+                Some([phase]) => {
+                    let phase = angle(phase, &[], &format!("{op_path}/params/0"))?;
+                    Ok(vec![format!("gphase({phase});")])
+                }
+                params => Err(param_count(op, 1, params.map_or(0, <[_]>::len), path)),
+            },
+            OpType::Measure => match args {
+                [qubit, bit] => {
+                    self.qubit(qubit, &format!("{path}/args/0"))?;
+                    let bit = self.bit(bit, &format!("{path}/args/1"))?;
+                    Ok(vec![format!("{bit} = measure {qubit};")])
+                }
+                _ => Err(arg_count(op, 2, args.len(), path)),
+            },
+            OpType::Reset => match args {
+                [qubit] => Ok(vec![format!("reset {};", self.qubit(qubit, path)?)]),
+                _ => Err(arg_count(op, 1, args.len(), path)),
+            },
+            OpType::Barrier => {
+                // Barriers only apply to qubits in OpenQASM 3.
+                let qubits: Vec<String> = args
+                    .iter()
+                    .filter(|id| self.qubits.contains(id))
+                    .map(ToString::to_string)
+                    .collect();
+                if qubits.is_empty() {
+                    return Ok(Vec::new());
+                }
+                Ok(vec![format!("barrier {};", qubits.join(", "))])
+            }
+            OpType::Conditional => {
+                let conditional = op
+                    .conditional
+                    .as_ref()
+                    .ok_or_else(|| missing("conditional"))?;
+                let width = conditional.width as usize;
+                if args.len() < width {
+                    return Err(arg_count(op, width, args.len(), path));
+                }
+                let (condition, args) = args.split_at(width);
+                let condition = self.condition(condition, conditional.value, path)?;
+                let inner_path = format!("{op_path}/conditional");
+                let body = self.statements(&conditional.op, args, &inner_path)?;
+                let mut lines = Vec::new();
+                push_block(&mut lines, format!("if ({condition})"), body);
+                Ok(lines)
+            }
+            OpType::ClExpr => {
+                let expr = op.classical_expr.as_ref().ok_or_else(|| missing("expr"))?;
+                self.assignment(expr, args, path)
+            }
+            OpType::WASM => self.wasm(op, args, path).map(|line| vec![line]),
+            OpType::RNGSeed
+            | OpType::RNGBound
+            | OpType::RNGIndex
+            | OpType::RNGNum
+            | OpType::JobShotNum => self.rng(op, args, path).map(|line| vec![line]),
+            OpType::CircBox => {
+                let Some(OpBox::CircBox { circuit, .. }) = &op.op_box else {
+                    return Err(missing("box"));
+                };
+                self.inline(circuit, args, path)
+            }
+            OpType::CustomGate => self.gate_statement(op, args, path),
+            op_type => {
+                if let Some(lowered) = lower(op_type, op.classical.as_deref(), args, &op_path) {
+                    let lowered = lowered
+                        .map_err(|e| error(e.path, ExportErrorKind::InvalidClassical(e.kind)))?;
+                    let mut lines = Vec::new();
+                    for (expr, args) in &lowered {
+                        lines.extend(self.assignment(expr, args, path)?);
+                    }
+                    return Ok(lines);
+                }
+                self.gate_statement(op, args, path)
+            }
+        }
+    }
+
+    /// Returns the statement applying a gate to declared qubits.
+    fn gate_statement(
+        &mut self,
+        op: &'c Operation,
+        args: &[ElementId],
+        path: &str,
+    ) -> Result<Vec<String>, ExportError> {
+        if op.op_type != OpType::CustomGate && stdgate(op.op_type, args.len()).is_none() {
+            return Err(unsupported(op, path));
+        }
+        let args = args
+            .iter()
+            .enumerate()
+            .map(|(i, id)| {
+                self.qubit(id, &format!("{path}/args/{i}"))
+                    .map(ToString::to_string)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(vec![self.gate(op, &args, &[], path)?])
+    }
+
+    /// Returns the statements implementing the commands of a `CircBox`
+    /// circuit, whose qubits and bits are mapped to `args`. `path` is the
+    /// JSON pointer to the `CircBox` command.
+    fn inline(
+        &mut self,
+        circ: &'c SerialCircuit,
+        args: &[ElementId],
+        path: &str,
+    ) -> Result<Vec<String>, ExportError> {
+        let inner = circ.qubits.iter().map(|qb| &qb.id);
+        let inner: Vec<&ElementId> = inner.chain(circ.bits.iter().map(|bit| &bit.id)).collect();
+        if inner.len() != args.len() {
+            let kind = ExportErrorKind::ArgCount {
+                op_type: OpType::CircBox,
+                expected: inner.len(),
+                found: args.len(),
+            };
+            return Err(error(path, kind));
+        }
+        let wires: HashMap<&ElementId, &ElementId> = inner.into_iter().zip(args).collect();
+        let path = format!("{path}/op/box/circuit");
+        let mut statements = Vec::new();
+        for (i, command) in circ.commands.iter().enumerate() {
+            let path = format!("{path}/commands/{i}");
+            let args = command
+                .args
+                .iter()
+                .enumerate()
+                .map(|(j, id)| match wires.get(id) {
+                    Some(&outer) => Ok(outer.clone()),
+                    None => Err(error(
+                        format!("{path}/args/{j}"),
+                        ExportErrorKind::UndeclaredWire(id.clone()),
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            statements.extend(self.statements(&command.op, &args, &path)?);
+        }
+        for (a, b) in permutation_swaps(circ, &path)? {
+            statements.push(format!("swap {}, {};", wires[a], wires[b]));
+        }
+        Ok(statements)
+    }
+
+    fn qubit<'a>(&self, id: &'a ElementId, path: &str) -> Result<&'a ElementId, ExportError> {
+        if !self.qubits.contains(id) {
+            return Err(error(path, ExportErrorKind::UndeclaredWire(id.clone())));
+        }
+        Ok(id)
+    }
+
+    fn bit<'a>(&self, id: &'a ElementId, path: &str) -> Result<&'a ElementId, ExportError> {
+        if !self.bits.contains(id) {
+            return Err(error(path, ExportErrorKind::UndeclaredWire(id.clone())));
+        }
+        Ok(id)
+    }
+
+    /// Returns a list of bits as a whole classical register or a slice of
+    /// one, such as `c[1:3]`.
+    fn bit_array(&self, bits: &[ElementId], path: &str) -> Result<String, ExportError> {
+        for (i, bit) in bits.iter().enumerate() {
+            self.bit(bit, &format!("{path}/args/{i}"))?;
+        }
+        let unsupported = || error(path, ExportErrorKind::UnsupportedBits);
+        let first = bits.first().ok_or_else(unsupported)?;
+        let (name, start) = (&first.0, first.1[0]);
+        let contiguous = bits
+            .iter()
+            .enumerate()
+            .all(|(i, id)| id.0 == *name && id.1 == [start + i as i64]);
+        if !contiguous {
+            return Err(unsupported());
+        }
+        let end = start + bits.len() as i64 - 1;
+        Ok(if start == 0 && self.cregs.get(name) == Some(&(end + 1)) {
+            name.clone()
+        } else {
+            format!("{name}[{start}:{end}]")
+        })
+    }
+
+    /// Returns the condition of a `Conditional` operation.
+    ///
+    /// A whole register is compared with the value, and other bits are
+    /// compared one by one with the bits of the value.
+    fn condition(&self, bits: &[ElementId], value: u32, path: &str) -> Result<String, ExportError> {
+        if let [bit] = bits {
+            return Ok(format!(
+                "{} == {value}",
+                self.bit(bit, &format!("{path}/args/0"))?
+            ));
+        }
+        match self.bit_array(bits, path) {
+            Ok(register) if self.cregs.contains_key(&register) => {
+                return Ok(format!("{register} == {value}"));
+            }
+            Err(e) if e.kind != ExportErrorKind::UnsupportedBits => return Err(e),
+            _ => {}
+        }
+        let tests: Vec<String> = bits
+            .iter()
+            .enumerate()
+            .map(|(i, bit)| format!("{bit} == {}", value.checked_shr(i as u32).unwrap_or(0) & 1))
+            .collect();
+        Ok(tests.join(" && "))
+    }
+
+    /// Returns the assignment evaluating a classical expression.
+    fn assignment(
+        &self,
+        expr: &ClExpr,
+        args: &[ElementId],
+        path: &str,
+    ) -> Result<Vec<String>, ExportError> {
+        let op_path = format!("{path}/op");
+        let ty = expr.check_resizing().map_err(|errors| {
+            let kind = ExportErrorKind::InvalidExpression(errors[0].to_string());
+            error(&op_path, kind)
+        })?;
+        let bits = |positions: &[u32]| {
+            positions
+                .iter()
+                .map(|&p| {
+                    let bit = args.get(p as usize).ok_or_else(|| {
+                        let kind = ExportErrorKind::ArgCount {
+                            op_type: OpType::ClExpr,
+                            expected: p as usize + 1,
+                            found: args.len(),
+                        };
+                        error(path, kind)
+                    })?;
+                    self.bit(bit, &format!("{path}/args/{p}")).cloned()
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        // Groups of bits that are not a register or a slice are copied to
+        // and from temporary registers, declared in a block of their own.
+        let mut copies = Vec::new();
+        let mut results = Vec::new();
+        let mut variables = Variables::default();
+        for &(index, position) in &expr.bit_posn {
+            let [bit] = <[ElementId; 1]>::try_from(bits(&[position])?).unwrap();
+            variables.bits.insert(index, bit.to_string());
+        }
+        for register in &expr.reg_posn {
+            let bits = bits(&register.bits.0)?;
+            let array = match self.bit_array(&bits, path) {
+                Err(e) if e.kind == ExportErrorKind::UnsupportedBits => {
+                    let name = self.fresh(&format!("r{}", register.index));
+                    copies.push(format!("bit[{}] {name};", bits.len()));
+                    for (i, bit) in bits.iter().enumerate() {
+                        copies.push(format!("{name}[{i}] = {bit};"));
+                    }
+                    name
+                }
+                array => array?,
+            };
+            let value = format!("uint[{}]({array})", bits.len());
+            variables.registers.insert(register.index, value);
+        }
+        let mut value = String::new();
+        write_operator(&mut value, &expr.expr, 0, &variables).ok_or_else(|| {
+            error(
+                &op_path,
+                ExportErrorKind::UnsupportedOperation(OpType::ClExpr),
+            )
+        })?;
+
+        let outputs = bits(&expr.output_posn.0)?;
+        let assignment = match (ty, outputs.as_slice()) {
+            (ClType::Bit, [bit]) => format!("{bit} = {value};"),
+            (ClType::Bit, _) => {
+                let kind = ExportErrorKind::InvalidExpression(
+                    "a bit expression must have one output".to_string(),
+                );
+                return Err(error(op_path, kind));
+            }
+            (_, outputs) => {
+                let width = outputs.len();
+                let target = match self.bit_array(outputs, path) {
+                    Err(e) if e.kind == ExportErrorKind::UnsupportedBits => {
+                        let name = self.fresh("out");
+                        for (i, bit) in outputs.iter().enumerate() {
+                            results.push(format!("{bit} = {name}[{i}];"));
+                        }
+                        format!("bit[{width}] {name}")
+                    }
+                    target => target?,
+                };
+                let value = match expr.result_width() {
+                    Some(n) if n != width => format!("uint[{width}]({value})"),
+                    _ => value,
+                };
+                format!("{target} = bit[{width}]({value});")
+            }
+        };
+        if copies.is_empty() && results.is_empty() {
+            return Ok(vec![assignment]);
+        }
+        copies.push(assignment);
+        copies.extend(results);
+        let mut lines = vec!["{".to_string()];
+        lines.extend(indent(copies));
+        lines.push("}".to_string());
+        Ok(lines)
+    }
+
+    /// Returns a name for a temporary register that does not hide a global
+    /// name.
+    fn fresh(&self, base: &str) -> String {
+        let used = |name: &str| {
+            self.registers.contains(name)
+                || self.defined.contains_key(name)
+                || self.externs.iter().any(|(n, _)| n == name)
+        };
+        let mut name = base.to_string();
+        let mut i = 0;
+        while used(&name) {
+            i += 1;
+            name = format!("{base}_{i}");
+        }
+        name
+    }
+
+    /// Returns the call of a WASM function, declaring it if needed.
+    fn wasm(
+        &mut self,
+        op: &Operation,
+        args: &[ElementId],
+        path: &str,
+    ) -> Result<String, ExportError> {
+        let Some(wasm) = &op.wasm else {
+            let kind = ExportErrorKind::MissingField {
+                op_type: op.op_type,
+                field: "wasm",
+            };
+            return Err(error(format!("{path}/op"), kind));
+        };
+        let n_inputs: u64 = wasm.width_i_parameter.iter().sum();
+        let n_outputs: u64 = wasm.width_o_parameter.iter().sum();
+        let expected = (n_inputs + n_outputs + wasm.ww_n) as usize;
+        if args.len() != expected {
+            return Err(arg_count(op, expected, args.len(), path));
+        }
+
+        // Empty groups of bits have no OpenQASM 3 type, and are skipped.
+        let mut groups = Vec::new();
+        let mut start = 0;
+        for &width in wasm.width_i_parameter.iter().chain(&wasm.width_o_parameter) {
+            let end = start + width as usize;
+            groups.push((
+                width,
+                (width > 0).then(|| self.bit_array(&args[start..end], path)),
+            ));
+            start = end;
+        }
+        let outputs = groups.split_off(wasm.width_i_parameter.len());
+        let mut types = Vec::new();
+        let mut inputs = Vec::new();
+        for (width, group) in groups {
+            if let Some(group) = group {
+                types.push(format!("bit[{width}]"));
+                inputs.push(group?);
+            }
+        }
+        let outputs: Vec<(u64, String)> = outputs
+            .into_iter()
+            .filter_map(|(width, group)| Some(group?.map(|group| (width, group))))
+            .collect::<Result<_, _>>()?;
+        let name = &wasm.func_name;
+        let call = format!("{name}({})", inputs.join(", "));
+        match outputs.as_slice() {
+            [] => {
+                let declaration = format!("extern {name}({});", types.join(", "));
+                self.declare_extern(name, declaration, path)?;
+                Ok(format!("{call};"))
+            }
+            [(width, output)] => {
+                let declaration = format!("extern {name}({}) -> bit[{width}];", types.join(", "));
+                self.declare_extern(name, declaration, path)?;
+                Ok(format!("{output} = {call};"))
+            }
+            // Functions return a single value.
+            _ => Err(unsupported(op, path)),
+        }
+    }
+
+    /// Returns the call of the `extern` function implementing an RNG
+    /// operation, declaring it if needed.
+    fn rng(
+        &mut self,
+        op: &Operation,
+        args: &[ElementId],
+        path: &str,
+    ) -> Result<String, ExportError> {
+        let metadata = op.op_type.metadata();
+        let width = metadata.bits.fixed().unwrap_or_default() as usize;
+        let expected = width + metadata.rng_wires.fixed().unwrap_or_default() as usize;
+        if args.len() != expected {
+            return Err(arg_count(op, expected, args.len(), path));
+        }
+        let bits = self.bit_array(&args[..width], path)?;
+        let (name, output) = match op.op_type {
+            OpType::RNGSeed => ("rng_seed", false),
+            OpType::RNGBound => ("rng_bound", false),
+            OpType::RNGIndex => ("rng_index", false),
+            OpType::RNGNum => ("rng_num", true),
+            _ => ("job_shot_num", true),
+        };
+        if output {
+            let declaration = format!("extern {name}() -> bit[{width}];");
+            self.declare_extern(name, declaration, path)?;
+            Ok(format!("{bits} = {name}();"))
+        } else {
+            let declaration = format!("extern {name}(bit[{width}]);");
+            self.declare_extern(name, declaration, path)?;
+            Ok(format!("{name}({bits});"))
+        }
+    }
+
+    /// Declares an `extern` function, unless it has already been declared.
+    fn declare_extern(
+        &mut self,
+        name: &str,
+        declaration: String,
+        path: &str,
+    ) -> Result<(), ExportError> {
+        if let Some((_, existing)) = self.externs.iter().find(|(n, _)| n == name) {
+            if *existing == declaration {
+                return Ok(());
+            }
+            let kind = ExportErrorKind::ConflictingDefinition(name.to_string());
+            return Err(error(format!("{path}/op"), kind));
+        }
+        let clash = self.registers.contains(name) || self.defined.contains_key(name);
+        if clash || !is_global_name(name) {
+            let kind = ExportErrorKind::InvalidName(name.to_string());
+            return Err(error(format!("{path}/op"), kind));
+        }
+        self.externs.push((name.to_string(), declaration));
+        Ok(())
+    }
+
+    /// Returns a gate call statement. `symbols` are the parameters of the
+    /// enclosing gate definition, if any.
+    fn gate(
+        &mut self,
+        op: &'c Operation,
+        args: &[String],
+        symbols: &[String],
+        path: &str,
+    ) -> Result<String, ExportError> {
+        let op_path = format!("{path}/op");
+        let (name, params, params_path) = match (&op.op_box, op.op_type) {
+            (Some(OpBox::CustomGate { gate, params, .. }), OpType::CustomGate) => {
+                self.define(gate, &format!("{op_path}/box/gate"))?;
+                let n_qubits = gate.definition.qubits.len();
+                if args.len() != n_qubits {
+                    return Err(arg_count(op, n_qubits, args.len(), path));
+                }
+                if params.len() != gate.args.len() {
+                    return Err(param_count(op, gate.args.len(), params.len(), path));
+                }
+                (
+                    gate.name.clone(),
+                    params.as_slice(),
+                    format!("{op_path}/box/params"),
+                )
+            }
+            _ => {
+                let Some((name, n_params)) = stdgate(op.op_type, args.len()) else {
+                    return Err(unsupported(op, path));
+                };
+                let expected = op.op_type.metadata().qubits.fixed();
+                let expected = expected.map_or(args.len(), |n| n as usize);
+                if args.len() != expected {
+                    return Err(arg_count(op, expected, args.len(), path));
+                }
+                let params = op.params.as_deref().unwrap_or_default();
+                if params.len() != n_params {
+                    return Err(param_count(op, n_params, params.len(), path));
+                }
+                (name, params, format!("{op_path}/params"))
+            }
+        };
+
+        let mut statement = name;
+        if !params.is_empty() {
+            let mut angles = params
+                .iter()
+                .enumerate()
+                .map(|(i, param)| angle(param, symbols, &format!("{params_path}/{i}")))
+                .collect::<Result<Vec<_>, _>>()?;
+            if op.op_type == OpType::U2 {
+                angles.insert(0, "0.5*pi".to_string());
+            }
+            write!(statement, "({})", angles.join(", ")).unwrap();
+        }
+        write!(statement, " {};", args.join(", ")).unwrap();
+        Ok(statement)
+    }
+
+    /// Writes the `gate` definition of a `CustomGate`, unless it has already
+    /// been written.
+    fn define(&mut self, gate: &'c CustomGate, path: &str) -> Result<(), ExportError> {
+        if let Some(&defined) = self.defined.get(gate.name.as_str()) {
+            if defined == gate {
+                return Ok(());
+            }
+            let kind = ExportErrorKind::ConflictingDefinition(gate.name.clone());
+            return Err(error(path, kind));
+        }
+        let clash = self.registers.contains(&gate.name)
+            || self.externs.iter().any(|(name, _)| *name == gate.name);
+        if clash || !is_global_name(&gate.name) {
+            return Err(error(path, ExportErrorKind::InvalidName(gate.name.clone())));
+        }
+        for (i, arg) in gate.args.iter().enumerate() {
+            if !is_identifier(arg) {
+                let path = format!("{path}/args/{i}");
+                return Err(error(path, ExportErrorKind::InvalidName(arg.clone())));
+            }
+        }
+
+        // The qubits of the definition are named by their index.
+        let circ = &gate.definition;
+        let names: HashMap<&ElementId, String> = circ
+            .qubits
+            .iter()
+            .enumerate()
+            .map(|(i, qb)| (&qb.id, format!("q{i}")))
+            .collect();
+        let def_path = format!("{path}/definition");
+        let mut body = Vec::new();
+        for (i, command) in circ.commands.iter().enumerate() {
+            let path = format!("{def_path}/commands/{i}");
+            let args = command
+                .args
+                .iter()
+                .enumerate()
+                .map(|(j, id)| match names.get(id) {
+                    Some(name) => Ok(name.clone()),
+                    None => Err(error(
+                        format!("{path}/args/{j}"),
+                        ExportErrorKind::UndeclaredWire(id.clone()),
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            match command.op.op_type {
+                OpType::Phase => {
+                    let params = command.op.params.as_deref().unwrap_or_default();
+                    let [phase] = params else {
+                        return Err(param_count(&command.op, 1, params.len(), &path));
+                    };
+                    let phase = angle(phase, &gate.args, &format!("{path}/op/params/0"))?;
+                    body.push(format!("gphase({phase});"));
+                }
+                OpType::Barrier => body.push(format!("barrier {};", args.join(", "))),
+                _ => body.push(self.gate(&command.op, &args, &gate.args, &path)?),
+            }
+        }
+        for (a, b) in permutation_swaps(circ, &def_path)? {
+            body.push(format!("swap {}, {};", names[a], names[b]));
+        }
+
+        let mut header = format!("gate {}", gate.name);
+        if !gate.args.is_empty() {
+            write!(header, "({})", gate.args.join(", ")).unwrap();
+        }
+        let qubits: Vec<String> = (0..circ.qubits.len()).map(|i| format!("q{i}")).collect();
+        writeln!(header, " {} {{", qubits.join(", ")).unwrap();
+        self.definitions.push_str(&header);
+        for statement in body {
+            writeln!(self.definitions, "  {statement}").unwrap();
+        }
+        self.definitions.push_str("}\n");
+        self.defined.insert(&gate.name, gate);
+        Ok(())
+    }
+}
+
+/// The OpenQASM 3 expressions standing for the variables of a classical
+/// expression.
+#[derive(Default)]
+struct Variables {
+    bits: HashMap<u32, String>,
+    registers: HashMap<u32, String>,
+}
+
+/// The precedence of the unary operators `~` and `-`.
+const UNARY_PRECEDENCE: u8 = 9;
+/// The precedence of terminals and parenthesized expressions.
+const ATOM_PRECEDENCE: u8 = 11;
+
+/// Returns the OpenQASM 3 operator of a binary operation, with its
+/// precedence.
+fn binary_operator(op: &ClOp) -> Option<(&'static str, u8)> {
+    Some(match op {
+        ClOp::BitOr | ClOp::RegOr => ("|", 1),
+        ClOp::BitXor | ClOp::RegXor => ("^", 2),
+        ClOp::BitAnd | ClOp::RegAnd => ("&", 3),
+        ClOp::BitEq | ClOp::RegEq => ("==", 4),
+        ClOp::BitNeq | ClOp::RegNeq => ("!=", 4),
+        ClOp::RegLt => ("<", 5),
+        ClOp::RegGt => (">", 5),
+        ClOp::RegLeq => ("<=", 5),
+        ClOp::RegGeq => (">=", 5),
+        ClOp::RegLsh => ("<<", 6),
+        ClOp::RegRsh => (">>", 6),
+        ClOp::RegAdd => ("+", 7),
+        ClOp::RegSub => ("-", 7),
+        ClOp::RegMul => ("*", 8),
+        ClOp::RegDiv => ("/", 8),
+        ClOp::RegPow => ("**", 10),
+        _ => return None,
+    })
+}
+
+/// Returns the precedence of a classical operation, or `None` if it has no
+/// OpenQASM 3 equivalent.
+fn precedence(op: &ClOp) -> Option<u8> {
+    match op {
+        ClOp::BitZero | ClOp::RegZero | ClOp::BitOne => Some(ATOM_PRECEDENCE),
+        ClOp::RegOne | ClOp::BitNot | ClOp::RegNot | ClOp::RegNeg => Some(UNARY_PRECEDENCE),
+        op => binary_operator(op).map(|(_, precedence)| precedence),
+    }
+}
+
+/// Writes a classical operation in OpenQASM 3 syntax, wrapped in parentheses
+/// if it binds less tightly than `min_precedence`. Returns `None` for
+/// operations without an equivalent.
+fn write_operator(
+    out: &mut String,
+    operator: &ClOperator,
+    min_precedence: u8,
+    variables: &Variables,
+) -> Option<()> {
+    let precedence = precedence(&operator.op)?;
+    if precedence < min_precedence {
+        out.push('(');
+    }
+    match &operator.op {
+        ClOp::BitZero | ClOp::RegZero => out.push('0'),
+        ClOp::BitOne => out.push('1'),
+        // All the bits of the result are set.
+        ClOp::RegOne => out.push_str("~0"),
+        op @ (ClOp::BitNot | ClOp::RegNot | ClOp::RegNeg) => {
+            let [arg] = operator.args.as_slice() else {
+                return None;
+            };
+            out.push(if *op == ClOp::RegNeg { '-' } else { '~' });
+            write_argument(out, arg, UNARY_PRECEDENCE, variables)?;
+        }
+        op => {
+            let (symbol, _) = binary_operator(op)?;
+            // Exponentiation is right-associative.
+            let (lhs, rhs) = match op {
+                ClOp::RegPow => (precedence + 1, precedence),
+                _ => (precedence, precedence + 1),
+            };
+            let (first, rest) = operator.args.split_first()?;
+            write_argument(out, first, lhs, variables)?;
+            for arg in rest {
+                write!(out, " {symbol} ").unwrap();
+                write_argument(out, arg, rhs, variables)?;
+            }
+        }
+    }
+    if precedence < min_precedence {
+        out.push(')');
+    }
+    Some(())
+}
+
+fn write_argument(
+    out: &mut String,
+    arg: &ClArgument,
+    min_precedence: u8,
+    variables: &Variables,
+) -> Option<()> {
+    match arg {
+        ClArgument::Terminal(ClTerminal::Int(n)) => write!(out, "{n}").ok(),
+        ClArgument::Terminal(ClTerminal::Variable(ClVariable::Bit { index })) => {
+            out.push_str(variables.bits.get(index)?);
+            Some(())
+        }
+        ClArgument::Terminal(ClTerminal::Variable(ClVariable::Register { index })) => {
+            out.push_str(variables.registers.get(index)?);
+            Some(())
+        }
+        ClArgument::Expression(operator) => {
+            write_operator(out, operator, min_precedence, variables)
+        }
+    }
+}
+
+/// Writes a parameter in half-turns as an OpenQASM 3 angle.
+fn angle(param: &str, symbols: &[String], path: &str) -> Result<String, ExportError> {
+    let expr: Expr = param
+        .parse()
+        .map_err(|e| error(path, ExportErrorKind::InvalidParam(e)))?;
+    write_angle(&expr, symbols, Dialect::Qasm3)
+        .ok_or_else(|| error(path, ExportErrorKind::UnsupportedParam(param.to_string())))
+}
+
+fn arg_count(op: &Operation, expected: usize, found: usize, path: &str) -> ExportError {
+    let kind = ExportErrorKind::ArgCount {
+        op_type: op.op_type,
+        expected,
+        found,
+    };
+    error(path, kind)
+}
+
+fn param_count(op: &Operation, expected: usize, found: usize, path: &str) -> ExportError {
+    let kind = ExportErrorKind::ParamCount {
+        op_type: op.op_type,
+        expected,
+        found,
+    };
+    error(format!("{path}/op"), kind)
+}
+
+fn unsupported(op: &Operation, path: &str) -> ExportError {
+    error(
+        format!("{path}/op"),
+        ExportErrorKind::UnsupportedOperation(op.op_type),
+    )
+}
@@ -0,0 +1,600 @@
+//! Export of circuits as QIR programs.
+//!
+//! [`SerialCircuit::to_qir`] writes a circuit with numeric parameters as a
+//! textual LLVM module following the QIR base or adaptive profile, with a
+//! single entry point calling the `__quantum__qis__*` intrinsics. Qubits and
+//! bits are identified with the static `%Qubit*` and `%Result*` addresses of
+//! their positions in [`SerialCircuit::qubits`] and [`SerialCircuit::bits`],
+//! and every bit is recorded as an output at the end of the program, labelled
+//! with its name.
+//!
+//! Measurements become `mz` calls. Under the adaptive profile, `Conditional`
+//! operations become branches on the measured results, and qubits may be
+//! reset or reused after a measurement. `CircBox` and `CustomGate`
+//! operations are decomposed into their circuits; other boxes are rejected.
+//!
+//! Parameters are converted from half-turns to radians. Global phases, from
+//! the circuit or from `Phase` operations, are dropped, and so is the
+//! implicit permutation of the circuit.
+//!
+//!   [`SerialCircuit::qubits`]: crate::circuit_json::SerialCircuit::qubits
+//!   [`SerialCircuit::bits`]: crate::circuit_json::SerialCircuit::bits
+
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::PI;
+use std::fmt::Write;
+
+use derive_more::{Display, Error};
+
+use crate::circuit_json::{Operation, SerialCircuit};
+use crate::expr::{evaluate_at, EvaluateParamsError, ParseExprError};
+use crate::opbox::OpBox;
+use crate::optype::OpType;
+use crate::register::ElementId;
+
+/// The QIR profile a program is written for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// The base profile: a straight-line program, measuring each qubit at
+    /// most once and only after its last gate.
+    #[default]
+    Base,
+    /// The adaptive profile, allowing mid-circuit measurements, resets and
+    /// branching on measurement results.
+    Adaptive,
+}
+
+/// A problem preventing a circuit from being written as QIR.
+#[derive(Clone, Debug, PartialEq, Display, Error)]
+#[display("{path}: {kind}")]
+#[non_exhaustive]
+pub struct QirError {
+    /// JSON pointer to the offending element, e.g. `/commands/3/op`.
+    pub path: String,
+    /// The problem found at that location.
+    #[error(not(source))]
+    pub kind: QirErrorKind,
+}
+
+/// The kinds of problems reported by [`SerialCircuit::to_qir`].
+#[derive(Clone, Debug, PartialEq, Display)]
+#[non_exhaustive]
+pub enum QirErrorKind {
+    /// The operation has no QIR intrinsic, and cannot be decomposed.
+    #[display("{_0} operations cannot be written in QIR")]
+    UnsupportedOperation(OpType),
+    /// The operation can only be written under the adaptive profile.
+    #[display("{_0} operation requires the adaptive profile")]
+    RequiresAdaptiveProfile(OpType),
+    /// A command refers to a wire that is not declared.
+    #[display("{_0} is not declared in the circuit")]
+    UndeclaredWire(ElementId),
+    /// An operation lacks the field describing it.
+    #[display("{op_type} operation has no `{field}` field")]
+    MissingField {
+        /// The operation type.
+        op_type: OpType,
+        /// The name of the missing field.
+        field: &'static str,
+    },
+    /// A command does not have the number of arguments its operation acts on.
+    #[display("{op_type} acts on {expected} arguments, but {found} were given")]
+    ArgCount {
+        /// The operation type.
+        op_type: OpType,
+        /// The number of arguments the operation acts on.
+        expected: usize,
+        /// The number of arguments in the command.
+        found: usize,
+    },
+    /// An operation does not have the number of parameters its type expects.
+    #[display("{op_type} takes {expected} parameters, but {found} were given")]
+    ParamCount {
+        /// The operation type.
+        op_type: OpType,
+        /// The number of parameters of the operation type.
+        expected: usize,
+        /// The number of parameters of the operation.
+        found: usize,
+    },
+    /// A parameter of a nested operation is not a valid expression.
+    #[display("{_0}")]
+    InvalidParam(ParseExprError),
+    /// A parameter of a nested operation refers to a symbol with no value.
+    #[display("unbound symbol `{_0}`")]
+    UnboundSymbol(String),
+}
+
+/// The intrinsics implementing gates, with the operation type they implement
+/// and their numbers of parameters and qubits.
+///
+/// `U1` differs from `rz` by a global phase, which QIR does not track.
+const GATES: &[(OpType, &str, usize, usize)] = &[
+    (OpType::X, "x", 0, 1),
+    (OpType::Y, "y", 0, 1),
+    (OpType::Z, "z", 0, 1),
+    (OpType::H, "h", 0, 1),
+    (OpType::S, "s", 0, 1),
+    (OpType::Sdg, "s__adj", 0, 1),
+    (OpType::T, "t", 0, 1),
+    (OpType::Tdg, "t__adj", 0, 1),
+    (OpType::Rx, "rx", 1, 1),
+    (OpType::Ry, "ry", 1, 1),
+    (OpType::Rz, "rz", 1, 1),
+    (OpType::U1, "rz", 1, 1),
+    (OpType::CX, "cnot", 0, 2),
+    (OpType::CZ, "cz", 0, 2),
+    (OpType::SWAP, "swap", 0, 2),
+    (OpType::XXPhase, "rxx", 1, 2),
+    (OpType::YYPhase, "ryy", 1, 2),
+    (OpType::ZZPhase, "rzz", 1, 2),
+    (OpType::CCX, "ccx", 0, 3),
+    (OpType::CnX, "cnot", 0, 2),
+    (OpType::CnX, "ccx", 0, 3),
+    (OpType::CnZ, "cz", 0, 2),
+];
+
+/// Returns the intrinsic implementing an operation type on the given number
+/// of qubits, with its numbers of parameters and qubits.
+fn gate(op_type: OpType, n_qubits: usize) -> Option<(&'static str, usize, usize)> {
+    let variadic = matches!(op_type, OpType::CnX | OpType::CnZ);
+    GATES
+        .iter()
+        .find(|&&(t, _, _, n)| t == op_type && (!variadic || n == n_qubits))
+        .map(|&(_, name, n_params, n)| (name, n_params, n))
+}
+
+impl SerialCircuit<f64> {
+    /// Writes the circuit as a textual QIR module for the given profile.
+    ///
+    /// Returns the first problem found if some part of the circuit cannot be
+    /// written.
+    pub fn to_qir(&self, profile: Profile) -> Result<String, QirError> {
+        let mut writer = Writer {
+            profile,
+            qubits: index(self.qubits.iter().map(|qb| &qb.id)),
+            results: index(self.bits.iter().map(|bit| &bit.id)),
+            measured: HashSet::new(),
+            body: String::new(),
+            declarations: Vec::new(),
+            values: 0,
+            blocks: 0,
+        };
+        writer.call("__quantum__rt__initialize", "void", "i8*", "i8* null");
+        let args: Vec<ElementId> = self.qubits.iter().map(|qb| qb.id.clone()).collect();
+        let args = [args, self.bits.iter().map(|bit| bit.id.clone()).collect()].concat();
+        writer.circuit(self, &args, "")?;
+
+        let mut labels = String::new();
+        for (i, bit) in self.bits.iter().enumerate() {
+            let label = bit.id.to_string();
+            let len = label.len() + 1;
+            writeln!(
+                labels,
+                "@{i} = internal constant [{len} x i8] c\"{}\\00\"",
+                escape(&label)
+            )
+            .unwrap();
+            let label = format!(
+                "i8* getelementptr inbounds ([{len} x i8], [{len} x i8]* @{i}, i32 0, i32 0)"
+            );
+            let args = format!("{}, {label}", result(i));
+            writer.call(
+                "__quantum__rt__result_record_output",
+                "void",
+                "%Result*, i8*",
+                &args,
+            );
+        }
+
+        let profile = match profile {
+            Profile::Base => "base_profile",
+            Profile::Adaptive => "adaptive_profile",
+        };
+        let mut out = String::from("%Qubit = type opaque\n%Result = type opaque\n\n");
+        if !labels.is_empty() {
+            writeln!(out, "{labels}").unwrap();
+        }
+        writeln!(
+            out,
+            "define void @main() #0 {{\nentry:\n{}  ret void\n}}\n",
+            writer.body
+        )
+        .unwrap();
+        for (_, declaration) in &writer.declarations {
+            writeln!(out, "{declaration}").unwrap();
+        }
+        writeln!(
+            out,
+            "\nattributes #0 = {{ \"entry_point\" \"output_labeling_schema\" \
+             \"qir_profiles\"=\"{profile}\" \"required_num_qubits\"=\"{}\" \
+             \"required_num_results\"=\"{}\" }}",
+            self.qubits.len(),
+            self.bits.len()
+        )
+        .unwrap();
+        out.push_str(
+            "attributes #1 = { \"irreversible\" }
+
+!llvm.module.flags = !{!0, !1, !2, !3}
+
+!0 = !{i32 1, !\"qir_major_version\", i32 1}
+!1 = !{i32 7, !\"qir_minor_version\", i32 0}
+!2 = !{i32 1, !\"dynamic_qubit_management\", i1 false}
+!3 = !{i32 1, !\"dynamic_result_management\", i1 false}
+",
+        );
+        Ok(out)
+    }
+}
+
+fn error(path: impl Into<String>, kind: QirErrorKind) -> QirError {
+    QirError {
+        path: path.into(),
+        kind,
+    }
+}
+
+impl From<EvaluateParamsError> for QirError {
+    fn from(e: EvaluateParamsError) -> Self {
+        match e {
+            EvaluateParamsError::Parse { path, source } => {
+                error(path, QirErrorKind::InvalidParam(source))
+            }
+            EvaluateParamsError::UnboundSymbol { path, symbol } => {
+                error(path, QirErrorKind::UnboundSymbol(symbol))
+            }
+        }
+    }
+}
+
+/// Returns the position of each wire in a list.
+fn index<'a>(ids: impl Iterator<Item = &'a ElementId>) -> HashMap<ElementId, usize> {
+    ids.enumerate().map(|(i, id)| (id.clone(), i)).collect()
+}
+
+/// Returns the static address of a qubit.
+fn qubit(index: usize) -> String {
+    match index {
+        0 => "%Qubit* null".to_string(),
+        _ => format!("%Qubit* inttoptr (i64 {index} to %Qubit*)"),
+    }
+}
+
+/// Returns the static address of a result.
+fn result(index: usize) -> String {
+    match index {
+        0 => "%Result* null".to_string(),
+        _ => format!("%Result* inttoptr (i64 {index} to %Result*)"),
+    }
+}
+
+/// Escapes a string for an LLVM string constant.
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'"' | b'\\' => write!(out, "\\{byte:02X}").unwrap(),
+            b' '..=b'~' => out.push(byte as char),
+            _ => write!(out, "\\{byte:02X}").unwrap(),
+        }
+    }
+    out
+}
+
+/// Writes an angle in radians as an LLVM `double` constant.
+fn angle(half_turns: f64) -> String {
+    // Decimal constants must have a decimal point.
+    let mut literal = format!("{:?}", half_turns * PI);
+    if !literal.contains('.') {
+        let exponent = literal.find('e').unwrap_or(literal.len());
+        literal.insert_str(exponent, ".0");
+    }
+    format!("double {literal}")
+}
+
+/// The state of an export in progress.
+struct Writer {
+    profile: Profile,
+    /// The position of each qubit of the circuit.
+    qubits: HashMap<ElementId, usize>,
+    /// The position of each bit of the circuit.
+    results: HashMap<ElementId, usize>,
+    /// The qubits measured so far, under the base profile.
+    measured: HashSet<ElementId>,
+    /// The instructions of the entry point written so far.
+    body: String,
+    /// The functions called so far, with their declarations.
+    declarations: Vec<(String, String)>,
+    /// The number of unnamed values defined so far.
+    values: usize,
+    /// The number of conditional blocks written so far.
+    blocks: usize,
+}
+
+impl Writer {
+    /// Writes a call, declaring the function if needed.
+    fn call(&mut self, name: &str, ret: &str, types: &str, args: &str) {
+        if !self.declarations.iter().any(|(n, _)| n == name) {
+            // Measurements and resets must not be reordered.
+            let attributes = match name {
+                "__quantum__qis__mz__body" => ("%Qubit*, %Result* writeonly", " #1"),
+                "__quantum__qis__reset__body" => (types, " #1"),
+                _ => (types, ""),
+            };
+            let declaration = format!("declare {ret} @{name}({}){}", attributes.0, attributes.1);
+            self.declarations.push((name.to_string(), declaration));
+        }
+        if ret == "void" {
+            writeln!(self.body, "  call void @{name}({args})").unwrap();
+        } else {
+            writeln!(self.body, "  %{} = call {ret} @{name}({args})", self.values).unwrap();
+            self.values += 1;
+        }
+    }
+
+    /// Writes the commands of a circuit whose qubits and bits, in order, are
+    /// mapped to `args`. `path` is the JSON pointer to the circuit.
+    fn circuit(
+        &mut self,
+        circ: &SerialCircuit<f64>,
+        args: &[ElementId],
+        path: &str,
+    ) -> Result<(), QirError> {
+        let inner = circ.qubits.iter().map(|qb| &qb.id);
+        let inner = inner.chain(circ.bits.iter().map(|bit| &bit.id));
+        let wires: HashMap<&ElementId, &ElementId> = inner.zip(args).collect();
+        for (i, command) in circ.commands.iter().enumerate() {
+            let path = format!("{path}/commands/{i}");
+            let args = command
+                .args
+                .iter()
+                .enumerate()
+                .map(|(j, id)| match wires.get(id) {
+                    Some(&outer) => Ok(outer.clone()),
+                    None => Err(error(
+                        format!("{path}/args/{j}"),
+                        QirErrorKind::UndeclaredWire(id.clone()),
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            self.operation(&command.op, &args, &path)?;
+        }
+        Ok(())
+    }
+
+    /// Writes an operation applied to the given wires of the top-level
+    /// circuit. `path` is the JSON pointer to the command.
+    fn operation(
+        &mut self,
+        op: &Operation<f64>,
+        args: &[ElementId],
+        path: &str,
+    ) -> Result<(), QirError> {
+        let op_path = format!("{path}/op");
+        let missing = |field| {
+            let kind = QirErrorKind::MissingField {
+                op_type: op.op_type,
+                field,
+            };
+            error(&op_path, kind)
+        };
+        match op.op_type {
+            OpType::noop | OpType::Barrier | OpType::Phase => Ok(()),
+            OpType::Measure => match args {
+                [qb, bit] => {
+                    let qb_index = self.qubit(qb, &format!("{path}/args/0"))?;
+                    let bit = self.result(bit, &format!("{path}/args/1"))?;
+                    if self.profile == Profile::Base && !self.measured.insert(qb.clone()) {
+                        let kind = QirErrorKind::RequiresAdaptiveProfile(op.op_type);
+                        return Err(error(op_path, kind));
+                    }
+                    let args = format!("{}, {}", qubit(qb_index), result(bit));
+                    let types = "%Qubit*, %Result*";
+                    self.call("__quantum__qis__mz__body", "void", types, &args);
+                    Ok(())
+                }
+                _ => Err(arg_count(op.op_type, 2, args.len(), path)),
+            },
+            OpType::Reset => {
+                if self.profile == Profile::Base {
+                    let kind = QirErrorKind::RequiresAdaptiveProfile(op.op_type);
+                    return Err(error(op_path, kind));
+                }
+                match args {
+                    [qb] => {
+                        let qb = qubit(self.qubit(qb, &format!("{path}/args/0"))?);
+                        self.call("__quantum__qis__reset__body", "void", "%Qubit*", &qb);
+                        Ok(())
+                    }
+                    _ => Err(arg_count(op.op_type, 1, args.len(), path)),
+                }
+            }
+            OpType::Conditional => {
+                if self.profile == Profile::Base {
+                    let kind = QirErrorKind::RequiresAdaptiveProfile(op.op_type);
+                    return Err(error(op_path, kind));
+                }
+                let conditional = op
+                    .conditional
+                    .as_ref()
+                    .ok_or_else(|| missing("conditional"))?;
+                let width = conditional.width as usize;
+                if args.len() < width {
+                    return Err(arg_count(op.op_type, width, args.len(), path));
+                }
+                let inner_path = format!("{op_path}/conditional");
+                let inner = evaluate_op(&conditional.op, &inner_path)?;
+                let (condition, args) = args.split_at(width);
+                let block = self.conditional(condition, conditional.value, path)?;
+                self.operation(&inner, args, &inner_path)?;
+                writeln!(self.body, "  br label %continue{block}\ncontinue{block}:").unwrap();
+                Ok(())
+            }
+            OpType::CircBox => {
+                let Some(OpBox::CircBox { circuit, .. }) = &op.op_box else {
+                    return Err(missing("box"));
+                };
+                let circ_path = format!("{op_path}/box/circuit");
+                let circ = circuit
+                    .evaluate_params(&HashMap::new())
+                    .map_err(|e| e.with_path_prefix(&circ_path))?;
+                self.boxed(&circ, op.op_type, args, path, &circ_path)
+            }
+            OpType::CustomGate => {
+                let Some(OpBox::CustomGate { gate, params, .. }) = &op.op_box else {
+                    return Err(missing("box"));
+                };
+                if params.len() != gate.args.len() {
+                    let kind = QirErrorKind::ParamCount {
+                        op_type: op.op_type,
+                        expected: gate.args.len(),
+                        found: params.len(),
+                    };
+                    return Err(error(op_path, kind));
+                }
+                let bindings = gate
+                    .args
+                    .iter()
+                    .zip(params)
+                    .enumerate()
+                    .map(|(i, (arg, param))| {
+                        let param_path = format!("{op_path}/box/params/{i}");
+                        Ok((
+                            arg.clone(),
+                            evaluate_at(param, &param_path, &HashMap::new())?,
+                        ))
+                    })
+                    .collect::<Result<_, EvaluateParamsError>>()?;
+                let circ_path = format!("{op_path}/box/gate/definition");
+                let circ = gate
+                    .definition
+                    .evaluate_params(&bindings)
+                    .map_err(|e| e.with_path_prefix(&circ_path))?;
+                self.boxed(&circ, op.op_type, args, path, &circ_path)
+            }
+            op_type => {
+                let Some((name, n_params, n_qubits)) = gate(op_type, args.len()) else {
+                    return Err(error(op_path, QirErrorKind::UnsupportedOperation(op_type)));
+                };
+                if args.len() != n_qubits {
+                    return Err(arg_count(op_type, n_qubits, args.len(), path));
+                }
+                let params = op.params.as_deref().unwrap_or_default();
+                if params.len() != n_params {
+                    let kind = QirErrorKind::ParamCount {
+                        op_type,
+                        expected: n_params,
+                        found: params.len(),
+                    };
+                    return Err(error(op_path, kind));
+                }
+                let mut call_args: Vec<String> = params.iter().map(|&p| angle(p)).collect();
+                for (i, qb) in args.iter().enumerate() {
+                    if self.profile == Profile::Base && self.measured.contains(qb) {
+                        let kind = QirErrorKind::RequiresAdaptiveProfile(op_type);
+                        return Err(error(op_path, kind));
+                    }
+                    call_args.push(qubit(self.qubit(qb, &format!("{path}/args/{i}"))?));
+                }
+                let types: Vec<&str> = std::iter::repeat("double")
+                    .take(n_params)
+                    .chain(std::iter::repeat("%Qubit*").take(n_qubits))
+                    .collect();
+                let name = format!("__quantum__qis__{name}__body");
+                self.call(&name, "void", &types.join(", "), &call_args.join(", "));
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes the circuit of a box applied to the given wires.
+    fn boxed(
+        &mut self,
+        circ: &SerialCircuit<f64>,
+        op_type: OpType,
+        args: &[ElementId],
+        path: &str,
+        circ_path: &str,
+    ) -> Result<(), QirError> {
+        let expected = circ.qubits.len() + circ.bits.len();
+        if args.len() != expected {
+            return Err(arg_count(op_type, expected, args.len(), path));
+        }
+        self.circuit(circ, args, circ_path)
+    }
+
+    /// Writes the branches testing the condition of a `Conditional`
+    /// operation, ending in the block applying the operation.
+    ///
+    /// Each bit of the condition is read in turn, and compared with the
+    /// corresponding bit of `value`. A failed comparison jumps to the
+    /// `continue` block of the conditional, which the caller must write.
+    /// Returns the number of the conditional's blocks.
+    fn conditional(
+        &mut self,
+        bits: &[ElementId],
+        value: u32,
+        path: &str,
+    ) -> Result<usize, QirError> {
+        let block = self.blocks;
+        self.blocks += 1;
+        for (i, bit) in bits.iter().enumerate() {
+            let bit = result(self.result(bit, &format!("{path}/args/{i}"))?);
+            self.call("__quantum__qis__read_result__body", "i1", "%Result*", &bit);
+            let next = if i + 1 == bits.len() {
+                format!("then{block}")
+            } else {
+                format!("condition{block}_{}", i + 1)
+            };
+            let skip = format!("continue{block}");
+            let (on_one, on_zero) = match value.checked_shr(i as u32).unwrap_or(0) & 1 {
+                1 => (&next, &skip),
+                _ => (&skip, &next),
+            };
+            writeln!(
+                self.body,
+                "  br i1 %{}, label %{on_one}, label %{on_zero}\n{next}:",
+                self.values - 1
+            )
+            .unwrap();
+        }
+        if bits.is_empty() {
+            writeln!(self.body, "  br label %then{block}\nthen{block}:").unwrap();
+        }
+        Ok(block)
+    }
+
+    fn qubit(&self, id: &ElementId, path: &str) -> Result<usize, QirError> {
+        self.qubits
+            .get(id)
+            .copied()
+            .ok_or_else(|| error(path, QirErrorKind::UndeclaredWire(id.clone())))
+    }
+
+    fn result(&self, id: &ElementId, path: &str) -> Result<usize, QirError> {
+        self.results
+            .get(id)
+            .copied()
+            .ok_or_else(|| error(path, QirErrorKind::UndeclaredWire(id.clone())))
+    }
+}
+
+/// Evaluates the parameters of a nested operation.
+fn evaluate_op(op: &Operation, path: &str) -> Result<Operation<f64>, QirError> {
+    let params = op.params.iter().flatten().enumerate();
+    let mut values = params
+        .map(|(i, p)| evaluate_at(p, &format!("{path}/op/params/{i}"), &HashMap::new()))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter();
+    Ok(op.clone().map_params(|_| values.next().unwrap_or_default()))
+}
+
+fn arg_count(op_type: OpType, expected: usize, found: usize, path: &str) -> QirError {
+    let kind = QirErrorKind::ArgCount {
+        op_type,
+        expected,
+        found,
+    };
+    error(path, kind)
+}
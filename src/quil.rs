@@ -0,0 +1,604 @@
+//! Export of circuits as Quil programs.
+//!
+//! [`SerialCircuit::to_quil`] writes a circuit as a Quil 2 program. Qubits
+//! are identified by their position in [`SerialCircuit::qubits`], and bits by
+//! their position in [`SerialCircuit::bits`] within a `ro` memory region
+//! declared with `DECLARE ro BIT[n]`.
+//!
+//! Operations are written as the standard Quil gates where possible, using
+//! the `DAGGER` and `CONTROLLED` modifiers for the gates Quil lacks. `ISWAP`
+//! and `PhasedISWAP` operations become `XY` gates when this is exact, which
+//! requires the phase of a `PhasedISWAP` to be a multiple of 1/2. The
+//! commands of `CircBox` and `CustomGate` operations are written in place of
+//! the box.
+//!
+//! A `Conditional` operation becomes a block skipped by `JUMP-WHEN` and
+//! `JUMP-UNLESS` instructions testing its condition bits. The flow
+//! operations map onto Quil's own control flow: a `Label` becomes a `LABEL`,
+//! named after its `data` field, `Goto` becomes `JUMP`, `Branch` becomes
+//! `JUMP-WHEN`, and `Stop` becomes `HALT`. Flow operations other than `Stop`
+//! are not allowed inside boxes.
+//!
+//! The implicit permutation of a circuit, or of the circuit of a box, is
+//! written as `SWAP` gates after its commands. The global phase of the
+//! circuit and `Phase` operations are dropped, as Quil programs cannot
+//! express them. A `Barrier` becomes a `FENCE` on its qubits.
+//!
+//!   [`SerialCircuit::qubits`]: crate::circuit_json::SerialCircuit::qubits
+//!   [`SerialCircuit::bits`]: crate::circuit_json::SerialCircuit::bits
+
+use std::collections::{HashMap, HashSet};
+
+use derive_more::{Display, Error};
+
+use crate::circuit_json::{Operation, SerialCircuit};
+use crate::expr::write::{write_angle, Dialect};
+use crate::expr::{Expr, ParseExprError};
+use crate::opbox::OpBox;
+use crate::optype::OpType;
+use crate::register::ElementId;
+
+/// A problem preventing a circuit from being written as Quil.
+#[derive(Clone, Debug, PartialEq, Display, Error)]
+#[display("{path}: {kind}")]
+#[non_exhaustive]
+pub struct QuilError {
+    /// JSON pointer to the offending element, e.g. `/commands/3/op`.
+    pub path: String,
+    /// The problem found at that location.
+    #[error(not(source))]
+    pub kind: QuilErrorKind,
+}
+
+/// The kinds of problems reported by [`SerialCircuit::to_quil`].
+#[derive(Clone, Debug, PartialEq, Display)]
+#[non_exhaustive]
+pub enum QuilErrorKind {
+    /// The operation has no Quil equivalent.
+    #[display("{_0} operations cannot be written in Quil")]
+    UnsupportedOperation(OpType),
+    /// The operation has no exact Quil equivalent for its parameters.
+    #[display("{_0} operation has no exact Quil equivalent for its parameters")]
+    InexactGate(OpType),
+    /// A label is not a valid Quil identifier.
+    #[display("`{_0}` is not a valid Quil label")]
+    InvalidName(String),
+    /// A `Branch` or `Goto` operation jumps to a label that is not defined.
+    #[display("the label `{_0}` is not defined")]
+    UnknownLabel(String),
+    /// Two `Label` operations have the same name.
+    #[display("the label `{_0}` is defined twice")]
+    DuplicateLabel(String),
+    /// A command refers to a wire that is not declared.
+    #[display("{_0} is not declared in the circuit")]
+    UndeclaredWire(ElementId),
+    /// The implicit permutation of a circuit is not a permutation of its
+    /// qubits.
+    #[display("the implicit permutation is not a permutation of the qubits")]
+    InvalidPermutation,
+    /// An operation lacks the field describing it.
+    #[display("{op_type} operation has no `{field}` field")]
+    MissingField {
+        /// The operation type.
+        op_type: OpType,
+        /// The name of the missing field.
+        field: &'static str,
+    },
+    /// A command does not have the number of arguments its operation acts on.
+    #[display("{op_type} acts on {expected} arguments, but {found} were given")]
+    ArgCount {
+        /// The operation type.
+        op_type: OpType,
+        /// The number of arguments the operation acts on.
+        expected: usize,
+        /// The number of arguments in the command.
+        found: usize,
+    },
+    /// An operation does not have the number of parameters its type expects.
+    #[display("{op_type} takes {expected} parameters, but {found} were given")]
+    ParamCount {
+        /// The operation type.
+        op_type: OpType,
+        /// The number of parameters of the operation type.
+        expected: usize,
+        /// The number of parameters of the operation.
+        found: usize,
+    },
+    /// A parameter is not a valid expression.
+    #[display("{_0}")]
+    InvalidParam(ParseExprError),
+    /// A parameter uses free symbols, functions or numbers that cannot be
+    /// written in Quil.
+    #[display("the parameter `{_0}` cannot be written in Quil")]
+    UnsupportedParam(String),
+}
+
+/// The Quil gates, with the operation type they implement and their numbers
+/// of parameters and qubits.
+const GATES: &[(OpType, &str, usize, usize)] = &[
+    (OpType::noop, "I", 0, 1),
+    (OpType::X, "X", 0, 1),
+    (OpType::Y, "Y", 0, 1),
+    (OpType::Z, "Z", 0, 1),
+    (OpType::H, "H", 0, 1),
+    (OpType::S, "S", 0, 1),
+    (OpType::Sdg, "DAGGER S", 0, 1),
+    (OpType::T, "T", 0, 1),
+    (OpType::Tdg, "DAGGER T", 0, 1),
+    (OpType::Rx, "RX", 1, 1),
+    (OpType::Ry, "RY", 1, 1),
+    (OpType::Rz, "RZ", 1, 1),
+    (OpType::U1, "PHASE", 1, 1),
+    (OpType::CX, "CNOT", 0, 2),
+    (OpType::CY, "CONTROLLED Y", 0, 2),
+    (OpType::CZ, "CZ", 0, 2),
+    (OpType::CH, "CONTROLLED H", 0, 2),
+    (OpType::CRx, "CONTROLLED RX", 1, 2),
+    (OpType::CRy, "CONTROLLED RY", 1, 2),
+    (OpType::CRz, "CONTROLLED RZ", 1, 2),
+    (OpType::CU1, "CPHASE", 1, 2),
+    (OpType::SWAP, "SWAP", 0, 2),
+    (OpType::ISWAPMax, "ISWAP", 0, 2),
+    (OpType::ISWAP, "XY", 1, 2),
+    (OpType::CCX, "CCNOT", 0, 3),
+    (OpType::CSWAP, "CSWAP", 0, 3),
+];
+
+/// Returns the gate implementing an operation type on the given number of
+/// qubits, with its number of parameters.
+fn gate(op_type: OpType, n_qubits: usize) -> Option<(String, usize)> {
+    let target = match op_type {
+        OpType::CnX => "X",
+        OpType::CnY => "Y",
+        OpType::CnZ => "Z",
+        _ => {
+            return GATES
+                .iter()
+                .find(|&&(t, _, _, n)| t == op_type && n == n_qubits)
+                .map(|&(_, name, n_params, _)| (name.to_string(), n_params));
+        }
+    };
+    Some(match (target, n_qubits) {
+        (_, 0) => return None,
+        ("X", 2) => ("CNOT".to_string(), 0),
+        ("Z", 2) => ("CZ".to_string(), 0),
+        ("X", 3) => ("CCNOT".to_string(), 0),
+        _ => ("CONTROLLED ".repeat(n_qubits - 1) + target, 0),
+    })
+}
+
+/// Returns `true` if `name` is a valid Quil identifier.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !name.ends_with('-')
+}
+
+impl SerialCircuit {
+    /// Writes the circuit as a Quil program.
+    ///
+    /// Returns the first problem found if some part of the circuit cannot be
+    /// written.
+    pub fn to_quil(&self) -> Result<String, QuilError> {
+        let mut labels = HashSet::new();
+        for (i, command) in self.commands.iter().enumerate() {
+            if command.op.op_type == OpType::Label {
+                let path = format!("/commands/{i}/op");
+                let label = label(&command.op, &path)?;
+                if !labels.insert(label) {
+                    let kind = QuilErrorKind::DuplicateLabel(label.to_string());
+                    return Err(error(format!("{path}/data"), kind));
+                }
+            }
+        }
+        let mut writer = Writer {
+            qubits: self
+                .qubits
+                .iter()
+                .enumerate()
+                .map(|(i, qb)| (qb.id.clone(), i))
+                .collect(),
+            bits: self
+                .bits
+                .iter()
+                .enumerate()
+                .map(|(i, bit)| (bit.id.clone(), i))
+                .collect(),
+            labels,
+            skips: 0,
+            lines: Vec::new(),
+        };
+        if !self.bits.is_empty() {
+            writer
+                .lines
+                .push(format!("DECLARE ro BIT[{}]", self.bits.len()));
+        }
+        for (i, command) in self.commands.iter().enumerate() {
+            let path = format!("/commands/{i}");
+            if command.op.op_type == OpType::Label {
+                let label = label(&command.op, &format!("{path}/op"))?;
+                writer.lines.push(format!("LABEL @{label}"));
+                continue;
+            }
+            let nested = Nested {
+                bindings: &HashMap::new(),
+                in_box: false,
+            };
+            writer.operation(&command.op, &command.args, &path, nested)?;
+        }
+        for (a, b) in permutation_swaps(self, "")? {
+            let swap = format!("SWAP {} {}", writer.qubits[a], writer.qubits[b]);
+            writer.lines.push(swap);
+        }
+
+        let mut out = String::new();
+        for line in writer.lines {
+            out.push_str(&line);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+fn error(path: impl Into<String>, kind: QuilErrorKind) -> QuilError {
+    QuilError {
+        path: path.into(),
+        kind,
+    }
+}
+
+/// Returns the swaps implementing the implicit permutation of a circuit.
+/// `path` is the JSON pointer to the circuit.
+fn permutation_swaps<'a>(
+    circ: &'a SerialCircuit,
+    path: &str,
+) -> Result<Vec<(&'a ElementId, &'a ElementId)>, QuilError> {
+    circ.permutation_swaps().ok_or_else(|| {
+        error(
+            format!("{path}/implicit_permutation"),
+            QuilErrorKind::InvalidPermutation,
+        )
+    })
+}
+
+/// Returns the label of a flow operation. `path` is the JSON pointer to the
+/// operation.
+fn label<'c>(op: &'c Operation, path: &str) -> Result<&'c str, QuilError> {
+    let label = op.data.as_deref().ok_or_else(|| {
+        let kind = QuilErrorKind::MissingField {
+            op_type: op.op_type,
+            field: "data",
+        };
+        error(path, kind)
+    })?;
+    if !is_identifier(label) {
+        let kind = QuilErrorKind::InvalidName(label.to_string());
+        return Err(error(format!("{path}/data"), kind));
+    }
+    Ok(label)
+}
+
+/// The context of an operation nested in a box.
+#[derive(Clone, Copy)]
+struct Nested<'a> {
+    /// The values of the parameters of the enclosing `CustomGate`
+    /// definitions.
+    bindings: &'a HashMap<String, Expr>,
+    /// Whether the operation is in a box.
+    in_box: bool,
+}
+
+/// The state of an export in progress.
+struct Writer<'c> {
+    /// The index of each qubit of the circuit.
+    qubits: HashMap<ElementId, usize>,
+    /// The index of each bit of the circuit in the `ro` region.
+    bits: HashMap<ElementId, usize>,
+    /// The labels defined by `Label` operations.
+    labels: HashSet<&'c str>,
+    /// The number of blocks skipped by conditions written so far.
+    skips: usize,
+    /// The instructions written so far.
+    lines: Vec<String>,
+}
+
+impl Writer<'_> {
+    /// Writes an operation applied to the given wires of the top-level
+    /// circuit. `path` is the JSON pointer to the command.
+    fn operation(
+        &mut self,
+        op: &Operation,
+        args: &[ElementId],
+        path: &str,
+        nested: Nested,
+    ) -> Result<(), QuilError> {
+        let op_path = format!("{path}/op");
+        let missing = |field| {
+            let kind = QuilErrorKind::MissingField {
+                op_type: op.op_type,
+                field,
+            };
+            error(&op_path, kind)
+        };
+        match op.op_type {
+            OpType::Phase => Ok(()),
+            OpType::Stop => {
+                self.lines.push("HALT".to_string());
+                Ok(())
+            }
+            OpType::Goto | OpType::Branch if !nested.in_box => {
+                let label = label(op, &op_path)?;
+                if !self.labels.contains(label) {
+                    let kind = QuilErrorKind::UnknownLabel(label.to_string());
+                    return Err(error(format!("{op_path}/data"), kind));
+                }
+                match (op.op_type, args) {
+                    (OpType::Goto, []) => self.lines.push(format!("JUMP @{label}")),
+                    (OpType::Branch, [bit]) => {
+                        let bit = self.bit(bit, &format!("{path}/args/0"))?;
+                        self.lines.push(format!("JUMP-WHEN @{label} ro[{bit}]"));
+                    }
+                    (op_type, _) => {
+                        let expected = (op_type == OpType::Branch) as usize;
+                        return Err(arg_count(op_type, expected, args.len(), path));
+                    }
+                }
+                Ok(())
+            }
+            OpType::Measure => match args {
+                [qubit, bit] => {
+                    let qubit = self.qubit(qubit, &format!("{path}/args/0"))?;
+                    let bit = self.bit(bit, &format!("{path}/args/1"))?;
+                    self.lines.push(format!("MEASURE {qubit} ro[{bit}]"));
+                    Ok(())
+                }
+                _ => Err(arg_count(op.op_type, 2, args.len(), path)),
+            },
+            OpType::Reset => match args {
+                [qubit] => {
+                    let qubit = self.qubit(qubit, &format!("{path}/args/0"))?;
+                    self.lines.push(format!("RESET {qubit}"));
+                    Ok(())
+                }
+                _ => Err(arg_count(op.op_type, 1, args.len(), path)),
+            },
+            OpType::Barrier => {
+                // Fences only apply to qubits.
+                let qubits: Vec<String> = args
+                    .iter()
+                    .filter_map(|id| self.qubits.get(id))
+                    .map(ToString::to_string)
+                    .collect();
+                if !qubits.is_empty() {
+                    self.lines.push(format!("FENCE {}", qubits.join(" ")));
+                }
+                Ok(())
+            }
+            OpType::Conditional => {
+                let conditional = op
+                    .conditional
+                    .as_ref()
+                    .ok_or_else(|| missing("conditional"))?;
+                let width = conditional.width as usize;
+                if args.len() < width {
+                    return Err(arg_count(op.op_type, width, args.len(), path));
+                }
+                let (condition, args) = args.split_at(width);
+                let skip = self.skip_label();
+                for (i, bit) in condition.iter().enumerate() {
+                    let bit = self.bit(bit, &format!("{path}/args/{i}"))?;
+                    let jump = match conditional.value.checked_shr(i as u32).unwrap_or(0) & 1 {
+                        1 => "JUMP-UNLESS",
+                        _ => "JUMP-WHEN",
+                    };
+                    self.lines.push(format!("{jump} @{skip} ro[{bit}]"));
+                }
+                let inner_path = format!("{op_path}/conditional");
+                self.operation(&conditional.op, args, &inner_path, nested)?;
+                self.lines.push(format!("LABEL @{skip}"));
+                Ok(())
+            }
+            OpType::CircBox => {
+                let Some(OpBox::CircBox { circuit, .. }) = &op.op_box else {
+                    return Err(missing("box"));
+                };
+                let circ_path = format!("{op_path}/box/circuit");
+                let nested = Nested {
+                    in_box: true,
+                    ..nested
+                };
+                self.inline(circuit, op.op_type, args, path, &circ_path, nested)
+            }
+            OpType::CustomGate => {
+                let Some(OpBox::CustomGate { gate, params, .. }) = &op.op_box else {
+                    return Err(missing("box"));
+                };
+                if params.len() != gate.args.len() {
+                    let kind = QuilErrorKind::ParamCount {
+                        op_type: op.op_type,
+                        expected: gate.args.len(),
+                        found: params.len(),
+                    };
+                    return Err(error(op_path, kind));
+                }
+                let bindings = gate
+                    .args
+                    .iter()
+                    .zip(params)
+                    .enumerate()
+                    .map(|(i, (arg, param))| {
+                        let param_path = format!("{op_path}/box/params/{i}");
+                        Ok((arg.clone(), parse(param, &param_path, nested.bindings)?))
+                    })
+                    .collect::<Result<_, QuilError>>()?;
+                let circ_path = format!("{op_path}/box/gate/definition");
+                let nested = Nested {
+                    bindings: &bindings,
+                    in_box: true,
+                };
+                self.inline(&gate.definition, op.op_type, args, path, &circ_path, nested)
+            }
+            OpType::PhasedISWAP => {
+                if args.len() != 2 {
+                    return Err(arg_count(op.op_type, 2, args.len(), path));
+                }
+                let params = op.params.as_deref().unwrap_or_default();
+                let [phase, angle] = params else {
+                    return Err(param_count(op.op_type, 2, params.len(), path));
+                };
+                let phase_path = format!("{op_path}/params/0");
+                let phase = parse(phase, &phase_path, nested.bindings)?;
+                let angle_path = format!("{op_path}/params/1");
+                let angle = parse(angle, &angle_path, nested.bindings)?;
+                // A phase of 1/2 conjugates the off-diagonal terms.
+                let angle = match phase.evaluate(&HashMap::new()).map(|p| 2.0 * p) {
+                    Ok(p) if p == p.round() && p.rem_euclid(2.0) == 0.0 => angle,
+                    Ok(p) if p == p.round() => Expr::Neg(Box::new(angle)),
+                    _ => return Err(error(op_path, QuilErrorKind::InexactGate(op.op_type))),
+                };
+                let mut statement = format!("XY({})", write(&angle, &angle_path)?);
+                for (i, qubit) in args.iter().enumerate() {
+                    let qubit = self.qubit(qubit, &format!("{path}/args/{i}"))?;
+                    statement.push_str(&format!(" {qubit}"));
+                }
+                self.lines.push(statement);
+                Ok(())
+            }
+            op_type => {
+                let Some((name, n_params)) = gate(op_type, args.len()) else {
+                    return Err(match GATES.iter().find(|&&(t, _, _, _)| t == op_type) {
+                        Some(&(_, _, _, n)) => arg_count(op_type, n, args.len(), path),
+                        None => error(op_path, QuilErrorKind::UnsupportedOperation(op_type)),
+                    });
+                };
+                let params = op.params.as_deref().unwrap_or_default();
+                if params.len() != n_params {
+                    return Err(param_count(op_type, n_params, params.len(), path));
+                }
+                let mut statement = name;
+                if !params.is_empty() {
+                    let angles = params
+                        .iter()
+                        .enumerate()
+                        .map(|(i, param)| {
+                            let param_path = format!("{op_path}/params/{i}");
+                            write(&parse(param, &param_path, nested.bindings)?, &param_path)
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    statement.push_str(&format!("({})", angles.join(", ")));
+                }
+                for (i, qubit) in args.iter().enumerate() {
+                    let qubit = self.qubit(qubit, &format!("{path}/args/{i}"))?;
+                    statement.push_str(&format!(" {qubit}"));
+                }
+                self.lines.push(statement);
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes the commands of a box circuit, whose qubits and bits are mapped
+    /// to `args`.
+    fn inline(
+        &mut self,
+        circ: &SerialCircuit,
+        op_type: OpType,
+        args: &[ElementId],
+        path: &str,
+        circ_path: &str,
+        nested: Nested,
+    ) -> Result<(), QuilError> {
+        let inner = circ.qubits.iter().map(|qb| &qb.id);
+        let inner: Vec<&ElementId> = inner.chain(circ.bits.iter().map(|bit| &bit.id)).collect();
+        if inner.len() != args.len() {
+            return Err(arg_count(op_type, inner.len(), args.len(), path));
+        }
+        let wires: HashMap<&ElementId, &ElementId> = inner.into_iter().zip(args).collect();
+        for (i, command) in circ.commands.iter().enumerate() {
+            let path = format!("{circ_path}/commands/{i}");
+            let args = command
+                .args
+                .iter()
+                .enumerate()
+                .map(|(j, id)| match wires.get(id) {
+                    Some(&outer) => Ok(outer.clone()),
+                    None => Err(error(
+                        format!("{path}/args/{j}"),
+                        QuilErrorKind::UndeclaredWire(id.clone()),
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            self.operation(&command.op, &args, &path, nested)?;
+        }
+        for (a, b) in permutation_swaps(circ, circ_path)? {
+            let (a, b) = (self.qubit(wires[a], path)?, self.qubit(wires[b], path)?);
+            self.lines.push(format!("SWAP {a} {b}"));
+        }
+        Ok(())
+    }
+
+    /// Returns a fresh label for a block skipped by a condition.
+    fn skip_label(&mut self) -> String {
+        loop {
+            let label = format!("skip{}", self.skips);
+            self.skips += 1;
+            if !self.labels.contains(label.as_str()) {
+                return label;
+            }
+        }
+    }
+
+    fn qubit(&self, id: &ElementId, path: &str) -> Result<usize, QuilError> {
+        self.qubits
+            .get(id)
+            .copied()
+            .ok_or_else(|| error(path, QuilErrorKind::UndeclaredWire(id.clone())))
+    }
+
+    fn bit(&self, id: &ElementId, path: &str) -> Result<usize, QuilError> {
+        self.bits
+            .get(id)
+            .copied()
+            .ok_or_else(|| error(path, QuilErrorKind::UndeclaredWire(id.clone())))
+    }
+}
+
+/// Parses a parameter, replacing the parameters of the enclosing
+/// `CustomGate` definitions by their values.
+fn parse(param: &str, path: &str, bindings: &HashMap<String, Expr>) -> Result<Expr, QuilError> {
+    let expr: Expr = param
+        .parse()
+        .map_err(|e| error(path, QuilErrorKind::InvalidParam(e)))?;
+    Ok(expr.substitute(bindings))
+}
+
+/// Writes a parameter in half-turns as an angle in radians.
+fn write(half_turns: &Expr, path: &str) -> Result<String, QuilError> {
+    write_angle(half_turns, &[], Dialect::Quil).ok_or_else(|| {
+        error(
+            path,
+            QuilErrorKind::UnsupportedParam(half_turns.to_string()),
+        )
+    })
+}
+
+fn arg_count(op_type: OpType, expected: usize, found: usize, path: &str) -> QuilError {
+    let kind = QuilErrorKind::ArgCount {
+        op_type,
+        expected,
+        found,
+    };
+    error(path, kind)
+}
+
+fn param_count(op_type: OpType, expected: usize, found: usize, path: &str) -> QuilError {
+    let kind = QuilErrorKind::ParamCount {
+        op_type,
+        expected,
+        found,
+    };
+    error(format!("{path}/op"), kind)
+}
@@ -0,0 +1,414 @@
+//! A dense statevector simulator for small circuits.
+//!
+//! [`SerialCircuit::statevector`] applies the commands of a circuit one by
+//! one to the all-zero state. Only unitary operations are supported; this is
+//! meant for checking the output of compilation on a handful of qubits, not
+//! for executing programs with measurements or classical control.
+//!
+//! Amplitudes are indexed following the ILO-BE convention: the first qubit
+//! in [`SerialCircuit::qubits`] is the most significant bit of the basis
+//! state index.
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use derive_more::{Display, Error, From};
+
+use crate::circuit_json::{Command, Operation, SerialCircuit};
+use crate::expr::{evaluate_at, EvaluateParamsError};
+use crate::linalg::{Complex, SquareMatrix};
+use crate::opbox::OpBox;
+use crate::optype::metadata::Arity;
+use crate::optype::OpType;
+use crate::register::ElementId;
+use crate::unitary::{self, op_unitary};
+
+/// The largest number of qubits [`SerialCircuit::statevector`] simulates,
+/// which keeps the state within 256 MiB.
+pub const MAX_QUBITS: usize = 24;
+
+/// Error produced when a circuit cannot be simulated.
+#[derive(Clone, Debug, PartialEq, Display, Error, From)]
+#[non_exhaustive]
+pub enum SimulationError {
+    /// The circuit contains a non-unitary operation, or one whose matrix is
+    /// not known.
+    #[display("{path}: cannot simulate {op_type} operations")]
+    #[from(ignore)]
+    UnsupportedOperation {
+        /// JSON pointer to the command.
+        path: String,
+        /// The operation type.
+        op_type: OpType,
+    },
+    /// A command acts on a wire that is not one of the circuit's qubits.
+    #[display("{path}: {id} is not a qubit of the circuit")]
+    #[from(ignore)]
+    UnknownQubit {
+        /// JSON pointer to the argument.
+        path: String,
+        /// The argument.
+        id: ElementId,
+    },
+    /// A command has the wrong number of qubits for its operation.
+    #[display("{path}: {op_type} acts on {expected} qubits, but {found} were given")]
+    #[from(ignore)]
+    ArgCount {
+        /// JSON pointer to the command.
+        path: String,
+        /// The operation type.
+        op_type: OpType,
+        /// The number of qubits the operation acts on.
+        expected: usize,
+        /// The number of arguments in the command.
+        found: usize,
+    },
+    /// The implicit permutation does not map the circuit's qubits to
+    /// themselves one-to-one.
+    #[display("{path}: invalid implicit permutation")]
+    #[from(ignore)]
+    InvalidPermutation {
+        /// JSON pointer to the permutation.
+        path: String,
+    },
+    /// The circuit has too many qubits to be simulated.
+    #[display("cannot simulate {n_qubits} qubits, the limit is {max}")]
+    #[from(ignore)]
+    TooManyQubits {
+        /// The number of qubits of the circuit.
+        n_qubits: usize,
+        /// The largest number of qubits supported.
+        max: usize,
+    },
+    /// The parameters of a circuit nested in a box cannot be evaluated.
+    #[display("{_0}")]
+    Params(EvaluateParamsError),
+}
+
+impl SerialCircuit<f64> {
+    /// Simulates the circuit on the all-zero state, returning the amplitudes
+    /// of the final state.
+    ///
+    /// The global phase and the implicit permutation of the circuit are taken
+    /// into account. Circuits nested in `CircBox` and `CustomGate` operations
+    /// are simulated recursively.
+    ///
+    /// Returns an error if the circuit contains a non-unitary operation, such
+    /// as a measurement or a classical operation, or if it has more than
+    /// [`MAX_QUBITS`] qubits.
+    pub fn statevector(&self) -> Result<Vec<(f64, f64)>, SimulationError> {
+        check_size(self.qubits.len(), MAX_QUBITS)?;
+        let mut state = Statevector::zero(self.qubits.len());
+        state.apply_circuit(self, &(0..self.qubits.len()).collect::<Vec<_>>(), "")?;
+        Ok(state.amplitudes.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Checks that a circuit has at most `max` qubits.
+pub(crate) fn check_size(n_qubits: usize, max: usize) -> Result<(), SimulationError> {
+    if n_qubits > max {
+        return Err(SimulationError::TooManyQubits { n_qubits, max });
+    }
+    Ok(())
+}
+
+/// The state of a register of qubits.
+pub(crate) struct Statevector {
+    n_qubits: usize,
+    pub amplitudes: Vec<Complex>,
+}
+
+impl Statevector {
+    /// Returns the all-zero state.
+    pub fn zero(n_qubits: usize) -> Self {
+        Self::basis(n_qubits, 0)
+    }
+
+    /// Returns the computational basis state with the given index.
+    pub fn basis(n_qubits: usize, index: usize) -> Self {
+        let mut amplitudes = vec![Complex::ZERO; 1 << n_qubits];
+        amplitudes[index] = Complex::ONE;
+        Self {
+            n_qubits,
+            amplitudes,
+        }
+    }
+
+    /// Returns the bit mask of a qubit in the basis state indices.
+    fn mask(&self, qubit: usize) -> usize {
+        1 << (self.n_qubits - 1 - qubit)
+    }
+
+    /// Applies a unitary to a list of qubits, the first one being the most
+    /// significant in the unitary's basis.
+    pub fn apply_unitary(&mut self, u: &SquareMatrix, qubits: &[usize]) {
+        let masks: Vec<usize> = qubits.iter().map(|&q| self.mask(q)).collect();
+        let all = masks.iter().fold(0, |acc, m| acc | m);
+        let offsets: Vec<usize> = (0..u.dim())
+            .map(|local| {
+                masks
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| local & (1 << (masks.len() - 1 - j)) != 0)
+                    .fold(0, |acc, (_, m)| acc | m)
+            })
+            .collect();
+        let mut local = vec![Complex::ZERO; u.dim()];
+        for base in (0..self.amplitudes.len()).filter(|i| i & all == 0) {
+            for (amp, &offset) in local.iter_mut().zip(&offsets) {
+                *amp = self.amplitudes[base | offset];
+            }
+            for (r, &offset) in offsets.iter().enumerate() {
+                let mut acc = Complex::ZERO;
+                for (c, &amp) in local.iter().enumerate() {
+                    acc += u[(r, c)] * amp;
+                }
+                self.amplitudes[base | offset] = acc;
+            }
+        }
+    }
+
+    /// Applies a single-qubit unitary to the last of `qubits`, controlled
+    /// on all the others being in the one state.
+    fn apply_controlled(&mut self, u: &SquareMatrix, qubits: &[usize]) {
+        let (&target, controls) = qubits.split_last().expect("a target qubit");
+        let controls = controls.iter().fold(0, |acc, &q| acc | self.mask(q));
+        let target = self.mask(target);
+        for base in 0..self.amplitudes.len() {
+            if base & target != 0 || base & controls != controls {
+                continue;
+            }
+            let a0 = self.amplitudes[base];
+            let a1 = self.amplitudes[base | target];
+            self.amplitudes[base] = u[(0, 0)] * a0 + u[(0, 1)] * a1;
+            self.amplitudes[base | target] = u[(1, 0)] * a0 + u[(1, 1)] * a1;
+        }
+    }
+
+    /// Multiplies each amplitude by the diagonal entry of a single-qubit
+    /// matrix selected by the parity of `qubits` in its basis state.
+    fn apply_parity(&mut self, u: &SquareMatrix, qubits: &[usize]) {
+        let all = qubits.iter().fold(0, |acc, &q| acc | self.mask(q));
+        for (index, amp) in self.amplitudes.iter_mut().enumerate() {
+            let parity = (index & all).count_ones() as usize % 2;
+            *amp = *amp * u[(parity, parity)];
+        }
+    }
+
+    /// Moves the state of each qubit `i` in `qubits` to `qubits[perm[i]]`.
+    fn permute(&mut self, qubits: &[usize], perm: &[usize]) {
+        let moves: Vec<(usize, usize)> = perm
+            .iter()
+            .enumerate()
+            .filter(|(from, to)| from != *to)
+            .map(|(from, &to)| (self.mask(qubits[from]), self.mask(qubits[to])))
+            .collect();
+        if moves.is_empty() {
+            return;
+        }
+        let all = moves.iter().fold(0, |acc, (from, _)| acc | from);
+        let mut permuted = vec![Complex::ZERO; self.amplitudes.len()];
+        for (index, &amp) in self.amplitudes.iter().enumerate() {
+            let target = moves
+                .iter()
+                .filter(|(from, _)| index & from != 0)
+                .fold(index & !all, |acc, (_, to)| acc | to);
+            permuted[target] = amp;
+        }
+        self.amplitudes = permuted;
+    }
+
+    /// Applies a circuit to the given qubits of the state. `qubits[i]` is
+    /// the qubit in the state corresponding to `circ.qubits[i]`.
+    pub fn apply_circuit(
+        &mut self,
+        circ: &SerialCircuit<f64>,
+        qubits: &[usize],
+        path: &str,
+    ) -> Result<(), SimulationError> {
+        let index: HashMap<&ElementId, usize> = circ
+            .qubits
+            .iter()
+            .enumerate()
+            .map(|(i, q)| (&q.id, i))
+            .collect();
+
+        let phase = Complex::cis(PI * circ.phase);
+        self.amplitudes
+            .iter_mut()
+            .for_each(|amp| *amp = *amp * phase);
+
+        for (i, command) in circ.commands.iter().enumerate() {
+            let path = format!("{path}/commands/{i}");
+            self.apply_command(command, &index, qubits, &path)?;
+        }
+
+        let invalid_permutation = || SimulationError::InvalidPermutation {
+            path: format!("{path}/implicit_permutation"),
+        };
+        // Qubits missing from the implicit permutation map to themselves.
+        let mut perm: Vec<usize> = (0..qubits.len()).collect();
+        for entry in &circ.implicit_permutation {
+            let from = index.get(&entry.0.id).ok_or_else(invalid_permutation)?;
+            let to = index.get(&entry.1.id).ok_or_else(invalid_permutation)?;
+            perm[*from] = *to;
+        }
+        let mut is_target = vec![false; qubits.len()];
+        perm.iter().for_each(|&to| is_target[to] = true);
+        if is_target.contains(&false) {
+            return Err(invalid_permutation());
+        }
+        self.permute(qubits, &perm);
+        Ok(())
+    }
+
+    fn apply_command(
+        &mut self,
+        command: &Command<f64>,
+        index: &HashMap<&ElementId, usize>,
+        qubits: &[usize],
+        path: &str,
+    ) -> Result<(), SimulationError> {
+        let op = &command.op;
+        if matches!(op.op_type, OpType::Barrier | OpType::noop) {
+            return Ok(());
+        }
+
+        let arg_count = |expected: usize| SimulationError::ArgCount {
+            path: path.to_string(),
+            op_type: op.op_type,
+            expected,
+            found: command.args.len(),
+        };
+        // Check the declared size before building anything from it.
+        if let Some(n_qb) = op.n_qb {
+            if n_qb as usize != command.args.len() {
+                return Err(arg_count(n_qb as usize));
+            }
+        }
+        let action = Action::new(op, command.args.len(), path)?;
+        let expected = action.n_qubits();
+        if command.args.len() != expected {
+            return Err(arg_count(expected));
+        }
+
+        let args = command
+            .args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| match index.get(arg) {
+                Some(&q) => Ok(qubits[q]),
+                None => Err(SimulationError::UnknownQubit {
+                    path: format!("{path}/args/{i}"),
+                    id: arg.clone(),
+                }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match action {
+            Action::Unitary(u) => self.apply_unitary(&u, &args),
+            Action::Controlled(u, _) => self.apply_controlled(&u, &args),
+            Action::Parity(u, _) => self.apply_parity(&u, &args),
+            Action::Each(u, _) => args.iter().for_each(|&q| self.apply_unitary(&u, &[q])),
+            Action::Circuit(circ, circ_path) => self.apply_circuit(&circ, &args, &circ_path)?,
+        }
+        Ok(())
+    }
+}
+
+/// The effect of an operation on the state.
+enum Action {
+    /// Apply a unitary matrix.
+    Unitary(SquareMatrix),
+    /// Apply a single-qubit unitary to the last of the given number of
+    /// qubits, controlled on the others.
+    Controlled(SquareMatrix, usize),
+    /// Apply the phase of a diagonal single-qubit unitary selected by the
+    /// parity of the given number of qubits.
+    Parity(SquareMatrix, usize),
+    /// Apply a single-qubit unitary to each of the given number of qubits.
+    Each(SquareMatrix, usize),
+    /// Apply a nested circuit, located at the given JSON pointer.
+    Circuit(SerialCircuit<f64>, String),
+}
+
+impl Action {
+    /// Determines the action of an operation applied to `n_args` wires.
+    fn new(op: &Operation<f64>, n_args: usize, path: &str) -> Result<Self, SimulationError> {
+        match &op.op_box {
+            Some(OpBox::CircBox { circuit, .. }) => {
+                let circ_path = format!("{path}/op/box/circuit");
+                let circ = circuit
+                    .evaluate_params(&HashMap::new())
+                    .map_err(|e| e.with_path_prefix(&circ_path))?;
+                return Ok(Action::Circuit(circ, circ_path));
+            }
+            Some(OpBox::CustomGate { gate, params, .. }) => {
+                let bindings = gate
+                    .args
+                    .iter()
+                    .zip(params)
+                    .enumerate()
+                    .map(|(i, (arg, param))| {
+                        let param_path = format!("{path}/op/box/params/{i}");
+                        let value = evaluate_at(param, &param_path, &HashMap::new())?;
+                        Ok((arg.clone(), value))
+                    })
+                    .collect::<Result<_, EvaluateParamsError>>()?;
+                let circ_path = format!("{path}/op/box/gate/definition");
+                let circ = gate
+                    .definition
+                    .evaluate_params(&bindings)
+                    .map_err(|e| e.with_path_prefix(&circ_path))?;
+                return Ok(Action::Circuit(circ, circ_path));
+            }
+            _ => {}
+        }
+
+        let unsupported = || SimulationError::UnsupportedOperation {
+            path: path.to_string(),
+            op_type: op.op_type,
+        };
+        if op.op_type.metadata().qubits == Arity::Variadic {
+            // Variadic gates act on all their arguments, and are applied
+            // without building their unitary on that many qubits.
+            let n_qubits = op.n_qb.map_or(n_args, |n| n as usize);
+            let single = |op_type| {
+                let mut single = Operation::from_optype(op_type);
+                single.params.clone_from(&op.params);
+                op_unitary(&single).ok_or_else(unsupported)
+            };
+            let controlled = |op_type| match n_qubits {
+                0 => Err(unsupported()),
+                _ => Ok(Action::Controlled(single(op_type)?, n_qubits)),
+            };
+            return match op.op_type {
+                OpType::CnX => controlled(OpType::X),
+                OpType::CnY => controlled(OpType::Y),
+                OpType::CnZ => controlled(OpType::Z),
+                OpType::CnRx => controlled(OpType::Rx),
+                OpType::CnRy => controlled(OpType::Ry),
+                OpType::CnRz => controlled(OpType::Rz),
+                // exp(-iπθ/2 Z⊗…⊗Z) is Rz(θ) applied to the parity.
+                OpType::PhaseGadget => Ok(Action::Parity(single(OpType::Rz)?, n_qubits)),
+                OpType::NPhasedX => Ok(Action::Each(single(OpType::PhasedX)?, n_qubits)),
+                _ => {
+                    check_size(n_qubits, unitary::MAX_QUBITS)?;
+                    let mut op = op.clone();
+                    op.n_qb = Some(n_qubits as u32);
+                    op_unitary(&op).map(Action::Unitary).ok_or_else(unsupported)
+                }
+            };
+        }
+        op_unitary(op).map(Action::Unitary).ok_or_else(unsupported)
+    }
+
+    /// Returns the number of qubits the action applies to.
+    fn n_qubits(&self) -> usize {
+        match self {
+            Action::Unitary(u) => u.dim().trailing_zeros() as usize,
+            Action::Controlled(_, n) | Action::Parity(_, n) | Action::Each(_, n) => *n,
+            Action::Circuit(circ, _) => circ.qubits.len(),
+        }
+    }
+}
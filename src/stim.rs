@@ -0,0 +1,66 @@
+//! Conversion between circuits and Stim circuits.
+//!
+//! [`SerialCircuit::to_stim`] writes a Clifford circuit in the text format of
+//! the [Stim](https://github.com/quantumlib/Stim) simulator, and
+//! [`SerialCircuit::from_stim`] parses such a circuit back. The supported
+//! operations are the Pauli gates, `H`, `S`, `Sdg`, `CX`, `CY`, `CZ`, `SWAP`,
+//! `Measure` and `Reset`. A `Barrier` is written as a `TICK`.
+//!
+//! Stim identifies qubits by their index, which is the position of the qubit
+//! in [`SerialCircuit::qubits`]. Measurement results are not written to bits
+//! but appended to a measurement record, so the bit written by a measurement
+//! is identified by the position of the measurement in the record. Pauli
+//! gates conditioned on one measured bit are written as gates controlled by
+//! the record, such as `CX rec[-1] 2`.
+//!
+//!   [`SerialCircuit::to_stim`]: crate::circuit_json::SerialCircuit::to_stim
+//!   [`SerialCircuit::from_stim`]: crate::circuit_json::SerialCircuit::from_stim
+//!   [`SerialCircuit::qubits`]: crate::circuit_json::SerialCircuit::qubits
+
+pub mod export;
+pub mod import;
+
+use crate::optype::OpType;
+
+/// The Stim gates, with the operation type they implement and their number
+/// of qubits.
+const GATES: &[(OpType, &str, usize)] = &[
+    (OpType::noop, "I", 1),
+    (OpType::X, "X", 1),
+    (OpType::Y, "Y", 1),
+    (OpType::Z, "Z", 1),
+    (OpType::H, "H", 1),
+    (OpType::S, "S", 1),
+    (OpType::Sdg, "S_DAG", 1),
+    (OpType::CX, "CX", 2),
+    (OpType::CY, "CY", 2),
+    (OpType::CZ, "CZ", 2),
+    (OpType::SWAP, "SWAP", 2),
+];
+
+/// Other names of the gates in [`GATES`], and of the measurement and reset
+/// instructions.
+const ALIASES: &[(OpType, &str)] = &[
+    (OpType::H, "H_XZ"),
+    (OpType::S, "SQRT_Z"),
+    (OpType::Sdg, "SQRT_Z_DAG"),
+    (OpType::CX, "CNOT"),
+    (OpType::CX, "ZCX"),
+    (OpType::CY, "ZCY"),
+    (OpType::CZ, "ZCZ"),
+    (OpType::Measure, "M"),
+    (OpType::Measure, "MZ"),
+    (OpType::Reset, "R"),
+    (OpType::Reset, "RZ"),
+];
+
+/// Returns the Pauli gate controlled by a measurement record in a two-qubit
+/// Stim gate, such as `X` for `CX`.
+fn controlled_pauli(op_type: OpType) -> Option<OpType> {
+    match op_type {
+        OpType::CX => Some(OpType::X),
+        OpType::CY => Some(OpType::Y),
+        OpType::CZ => Some(OpType::Z),
+        _ => None,
+    }
+}
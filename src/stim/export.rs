@@ -0,0 +1,288 @@
+//! Export of circuits as Stim circuits.
+//!
+//! Consecutive operations of the same kind are written as a single
+//! instruction with all their targets, as in `H 0 1 2`. The commands of
+//! `CircBox` operations are written in place of the box. A `Conditional`
+//! operation must apply a Pauli gate when a single bit is 1, and that bit
+//! must have been measured before.
+//!
+//! The global phase and the implicit permutation of the circuit are dropped,
+//! as Stim circuits cannot express them.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use derive_more::{Display, Error};
+
+use super::{controlled_pauli, GATES};
+use crate::circuit_json::{Operation, SerialCircuit};
+use crate::opbox::OpBox;
+use crate::optype::OpType;
+use crate::register::ElementId;
+
+/// A problem preventing a circuit from being written as a Stim circuit.
+#[derive(Clone, Debug, PartialEq, Display, Error)]
+#[display("{path}: {kind}")]
+#[non_exhaustive]
+pub struct ExportError {
+    /// JSON pointer to the offending element, e.g. `/commands/3/op`.
+    pub path: String,
+    /// The problem found at that location.
+    #[error(not(source))]
+    pub kind: ExportErrorKind,
+}
+
+/// The kinds of problems reported by [`SerialCircuit::to_stim`].
+#[derive(Clone, Debug, PartialEq, Display)]
+#[non_exhaustive]
+pub enum ExportErrorKind {
+    /// The operation is not one of the Clifford operations supported by Stim
+    /// circuits.
+    #[display("{_0} operations cannot be written as Stim instructions")]
+    UnsupportedOperation(OpType),
+    /// A `Conditional` operation does not apply a Pauli gate when a single
+    /// bit is 1.
+    #[display("only Pauli gates conditioned on a single bit being 1 can be written")]
+    UnsupportedCondition,
+    /// A condition uses a bit that has not been measured yet.
+    #[display("{_0} has not been measured")]
+    UnmeasuredBit(ElementId),
+    /// A command refers to a wire that is not declared.
+    #[display("{_0} is not declared in the circuit")]
+    UndeclaredWire(ElementId),
+    /// An operation lacks the field describing it.
+    #[display("{op_type} operation has no `{field}` field")]
+    MissingField {
+        /// The operation type.
+        op_type: OpType,
+        /// The name of the missing field.
+        field: &'static str,
+    },
+    /// A command does not have the number of arguments its operation acts on.
+    #[display("{op_type} acts on {expected} arguments, but {found} were given")]
+    ArgCount {
+        /// The operation type.
+        op_type: OpType,
+        /// The number of arguments the operation acts on.
+        expected: usize,
+        /// The number of arguments in the command.
+        found: usize,
+    },
+}
+
+impl<P> SerialCircuit<P> {
+    /// Writes the circuit as a Stim circuit.
+    ///
+    /// Returns the first problem found if some part of the circuit cannot be
+    /// written.
+    pub fn to_stim(&self) -> Result<String, ExportError> {
+        let mut writer = Writer {
+            qubits: self
+                .qubits
+                .iter()
+                .enumerate()
+                .map(|(i, qb)| (&qb.id, i))
+                .collect(),
+            bits: self.bits.iter().map(|bit| &bit.id).collect(),
+            measurements: 0,
+            measured: HashMap::new(),
+            instructions: Vec::new(),
+        };
+        for (i, command) in self.commands.iter().enumerate() {
+            let path = format!("/commands/{i}");
+            writer.operation(&command.op, &command.args, &path)?;
+        }
+
+        let mut out = String::new();
+        for (name, targets) in writer.instructions {
+            out.push_str(name);
+            for target in targets {
+                write!(out, " {target}").unwrap();
+            }
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+fn error(path: impl Into<String>, kind: ExportErrorKind) -> ExportError {
+    ExportError {
+        path: path.into(),
+        kind,
+    }
+}
+
+/// The state of an export in progress.
+struct Writer<'c> {
+    /// The index of each qubit of the circuit.
+    qubits: HashMap<&'c ElementId, usize>,
+    /// The bits of the circuit.
+    bits: Vec<&'c ElementId>,
+    /// The number of measurements written so far.
+    measurements: usize,
+    /// The position in the measurement record of the last measurement of
+    /// each bit.
+    measured: HashMap<ElementId, usize>,
+    /// The instructions written so far, with their targets.
+    instructions: Vec<(&'static str, Vec<String>)>,
+}
+
+impl Writer<'_> {
+    /// Appends an instruction, merging it with the previous one if they have
+    /// the same name.
+    fn push(&mut self, name: &'static str, targets: Vec<String>) {
+        match self.instructions.last_mut() {
+            Some((last, last_targets)) if *last == name && name != "TICK" => {
+                last_targets.extend(targets);
+            }
+            _ => self.instructions.push((name, targets)),
+        }
+    }
+
+    /// Writes an operation applied to the given wires of the top-level
+    /// circuit. `path` is the JSON pointer to the command.
+    fn operation<P>(
+        &mut self,
+        op: &Operation<P>,
+        args: &[ElementId],
+        path: &str,
+    ) -> Result<(), ExportError> {
+        let op_path = format!("{path}/op");
+        let missing = |field| {
+            let kind = ExportErrorKind::MissingField {
+                op_type: op.op_type,
+                field,
+            };
+            error(&op_path, kind)
+        };
+        match op.op_type {
+            OpType::Barrier => {
+                self.push("TICK", Vec::new());
+                Ok(())
+            }
+            OpType::Measure => match args {
+                [qubit, bit] => {
+                    let qubit = self.qubit(qubit, &format!("{path}/args/0"))?;
+                    if !self.bits.contains(&bit) {
+                        let kind = ExportErrorKind::UndeclaredWire(bit.clone());
+                        return Err(error(format!("{path}/args/1"), kind));
+                    }
+                    self.measured.insert(bit.clone(), self.measurements);
+                    self.measurements += 1;
+                    self.push("M", vec![qubit.to_string()]);
+                    Ok(())
+                }
+                _ => Err(arg_count(op.op_type, 2, args.len(), path)),
+            },
+            OpType::Reset => match args {
+                [qubit] => {
+                    let qubit = self.qubit(qubit, &format!("{path}/args/0"))?;
+                    self.push("R", vec![qubit.to_string()]);
+                    Ok(())
+                }
+                _ => Err(arg_count(op.op_type, 1, args.len(), path)),
+            },
+            OpType::Conditional => {
+                let conditional = op
+                    .conditional
+                    .as_ref()
+                    .ok_or_else(|| missing("conditional"))?;
+                let inner = conditional.op.op_type;
+                let gate = [OpType::CX, OpType::CY, OpType::CZ]
+                    .into_iter()
+                    .find(|&gate| controlled_pauli(gate) == Some(inner));
+                let (Some(gate), 1, 1) = (gate, conditional.width, conditional.value) else {
+                    return Err(error(op_path, ExportErrorKind::UnsupportedCondition));
+                };
+                let [bit, qubit] = args else {
+                    return Err(arg_count(op.op_type, 2, args.len(), path));
+                };
+                let Some(&measurement) = self.measured.get(bit) else {
+                    let kind = ExportErrorKind::UnmeasuredBit(bit.clone());
+                    return Err(error(format!("{path}/args/0"), kind));
+                };
+                let qubit = self.qubit(qubit, &format!("{path}/args/1"))?;
+                let record = format!("rec[-{}]", self.measurements - measurement);
+                self.push(name(gate).unwrap(), vec![record, qubit.to_string()]);
+                Ok(())
+            }
+            OpType::CircBox => {
+                let Some(OpBox::CircBox { circuit, .. }) = &op.op_box else {
+                    return Err(missing("box"));
+                };
+                let inner = circuit.qubits.iter().map(|qb| &qb.id);
+                let inner: Vec<&ElementId> = inner
+                    .chain(circuit.bits.iter().map(|bit| &bit.id))
+                    .collect();
+                if inner.len() != args.len() {
+                    return Err(arg_count(op.op_type, inner.len(), args.len(), path));
+                }
+                let wires: HashMap<&ElementId, &ElementId> = inner.into_iter().zip(args).collect();
+                let circ_path = format!("{op_path}/box/circuit");
+                for (i, command) in circuit.commands.iter().enumerate() {
+                    let path = format!("{circ_path}/commands/{i}");
+                    let args = command
+                        .args
+                        .iter()
+                        .enumerate()
+                        .map(|(j, id)| match wires.get(id) {
+                            Some(&outer) => Ok(outer.clone()),
+                            None => Err(error(
+                                format!("{path}/args/{j}"),
+                                ExportErrorKind::UndeclaredWire(id.clone()),
+                            )),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.operation(&command.op, &args, &path)?;
+                }
+                Ok(())
+            }
+            op_type => {
+                let Some(&(_, name, n_qubits)) = GATES.iter().find(|&&(t, _, _)| t == op_type)
+                else {
+                    return Err(error(
+                        op_path,
+                        ExportErrorKind::UnsupportedOperation(op_type),
+                    ));
+                };
+                if args.len() != n_qubits {
+                    return Err(arg_count(op_type, n_qubits, args.len(), path));
+                }
+                let targets = args
+                    .iter()
+                    .enumerate()
+                    .map(|(i, qubit)| {
+                        let qubit = self.qubit(qubit, &format!("{path}/args/{i}"))?;
+                        Ok(qubit.to_string())
+                    })
+                    .collect::<Result<_, _>>()?;
+                self.push(name, targets);
+                Ok(())
+            }
+        }
+    }
+
+    fn qubit(&self, id: &ElementId, path: &str) -> Result<usize, ExportError> {
+        self.qubits
+            .get(id)
+            .copied()
+            .ok_or_else(|| error(path, ExportErrorKind::UndeclaredWire(id.clone())))
+    }
+}
+
+/// Returns the Stim name of a gate.
+fn name(op_type: OpType) -> Option<&'static str> {
+    GATES
+        .iter()
+        .find(|&&(t, _, _)| t == op_type)
+        .map(|&(_, name, _)| name)
+}
+
+fn arg_count(op_type: OpType, expected: usize, found: usize, path: &str) -> ExportError {
+    let kind = ExportErrorKind::ArgCount {
+        op_type,
+        expected,
+        found,
+    };
+    error(path, kind)
+}
@@ -0,0 +1,287 @@
+//! Import of Stim circuits as circuits.
+//!
+//! [`SerialCircuit::from_stim`] accepts the gates listed in the [module
+//! documentation](crate::stim) under their Stim names and common aliases,
+//! `M`, `R`, `TICK`, and `REPEAT` blocks, which are unrolled. Instruction
+//! names are case-insensitive, and `#` starts a comment. Gates applied to
+//! several targets are split into one command per qubit, or per pair of
+//! qubits for two-qubit gates.
+//!
+//! A `CX`, `CY` or `CZ` gate controlled by a measurement record target such
+//! as `rec[-1]` becomes a Pauli gate conditioned on the bit of that
+//! measurement. Noise channels, annotations such as `DETECTOR`, inverted
+//! targets and instruction arguments are rejected.
+//!
+//! As in Stim, qubit indices must be below [`MAX_QUBITS`]. Unrolling is
+//! limited to [`MAX_COMMANDS`] commands, so that a short input cannot
+//! describe an arbitrarily large circuit.
+
+use derive_more::{Display, Error};
+
+use super::{controlled_pauli, ALIASES, GATES};
+use crate::circuit_json::{Command, Conditional, ImplicitPermutation, Operation, SerialCircuit};
+use crate::optype::OpType;
+use crate::register::ElementId;
+
+/// The number of qubit indices allowed in a Stim circuit.
+pub const MAX_QUBITS: usize = 1 << 24;
+
+/// The largest number of commands in a circuit parsed from Stim.
+pub const MAX_COMMANDS: usize = 1 << 20;
+
+/// Error produced when parsing a Stim circuit.
+#[derive(Clone, Debug, PartialEq, Eq, Display, Error)]
+#[display("invalid Stim circuit at line {line}: {message}")]
+pub struct ParseStimError {
+    /// Line of the error in the input, starting from 1.
+    pub line: usize,
+    /// Description of the error.
+    pub message: String,
+}
+
+impl SerialCircuit {
+    /// Parses a Stim circuit.
+    ///
+    /// The qubits are named `q[i]` after their Stim index, up to the largest
+    /// index used, and the result of the `k`-th measurement is written to the
+    /// bit `c[k]`. Each `TICK` becomes a `Barrier` on all the qubits. The
+    /// circuit has no global phase.
+    pub fn from_stim(source: &str) -> Result<Self, ParseStimError> {
+        let lines: Vec<(usize, &str)> = source
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.split('#').next().unwrap().trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .collect();
+        let mut parser = Parser {
+            n_qubits: 0,
+            n_bits: 0,
+            ticks: Vec::new(),
+            circ: SerialCircuit::new(None, "0.0".to_string()),
+        };
+        parser.block(&lines)?;
+
+        let mut circ = parser.circ;
+        circ.qubits = (0..parser.n_qubits)
+            .map(|i| element("q", i).into())
+            .collect();
+        circ.bits = (0..parser.n_bits).map(|i| element("c", i).into()).collect();
+        let qubits: Vec<ElementId> = circ.qubits.iter().map(|qb| qb.id.clone()).collect();
+        for i in parser.ticks {
+            circ.commands[i].args = qubits.clone();
+        }
+        circ.implicit_permutation = circ
+            .qubits
+            .iter()
+            .map(|qb| ImplicitPermutation(qb.clone(), qb.clone()))
+            .collect();
+        circ.created_qubits = Some(Vec::new());
+        circ.discarded_qubits = Some(Vec::new());
+        Ok(circ)
+    }
+}
+
+fn error(line: usize, message: impl Into<String>) -> ParseStimError {
+    ParseStimError {
+        line,
+        message: message.into(),
+    }
+}
+
+fn element(name: &str, index: usize) -> ElementId {
+    ElementId(name.to_string(), vec![index as i64])
+}
+
+/// A target of an instruction.
+enum Target {
+    /// A qubit index.
+    Qubit(usize),
+    /// A measurement record, counted back from the latest measurement.
+    Record(usize),
+}
+
+/// The state of a parse in progress.
+struct Parser {
+    /// The number of qubits used so far.
+    n_qubits: usize,
+    /// The number of measurements so far.
+    n_bits: usize,
+    /// The positions of the commands written for `TICK` instructions.
+    ticks: Vec<usize>,
+    circ: SerialCircuit,
+}
+
+impl Parser {
+    /// Parses a sequence of non-empty lines, without comments.
+    fn block(&mut self, lines: &[(usize, &str)]) -> Result<(), ParseStimError> {
+        let mut i = 0;
+        while i < lines.len() {
+            let (line, text) = lines[i];
+            i += 1;
+            let mut words = text.split_whitespace();
+            let name = words.next().unwrap();
+            if name.eq_ignore_ascii_case("REPEAT") {
+                let count = match (words.next(), words.next(), words.next()) {
+                    (Some(count), Some("{"), None) => count.parse::<u64>().ok(),
+                    _ => None,
+                };
+                let count = count.ok_or_else(|| error(line, "expected 'REPEAT <count> {'"))?;
+                // Find the matching closing brace.
+                let mut depth = 1;
+                let start = i;
+                while depth > 0 {
+                    let Some(&(_, text)) = lines.get(i) else {
+                        return Err(error(line, "unclosed REPEAT block"));
+                    };
+                    if text == "}" {
+                        depth -= 1;
+                    } else if text.ends_with('{') {
+                        depth += 1;
+                    }
+                    i += 1;
+                }
+                for _ in 0..count {
+                    let n_commands = self.circ.commands.len();
+                    self.block(&lines[start..i - 1])?;
+                    if self.circ.commands.len() == n_commands {
+                        // Repeating an empty block changes nothing.
+                        break;
+                    }
+                }
+            } else if name == "}" {
+                return Err(error(line, "unexpected '}'"));
+            } else {
+                let targets = words
+                    .map(|word| {
+                        target(word)
+                            .ok_or_else(|| error(line, format!("unsupported target '{word}'")))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.instruction(name, &targets, line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies an instruction to its targets.
+    fn instruction(
+        &mut self,
+        name: &str,
+        targets: &[Target],
+        line: usize,
+    ) -> Result<(), ParseStimError> {
+        if name.contains('(') {
+            return Err(error(line, "instruction arguments are not supported"));
+        }
+        let upper = name.to_ascii_uppercase();
+        if upper == "TICK" {
+            if !targets.is_empty() {
+                return Err(error(line, "TICK takes no targets"));
+            }
+            self.ticks.push(self.circ.commands.len());
+            return self.push(Operation::from_optype(OpType::Barrier), Vec::new(), line);
+        }
+        let names = GATES.iter().map(|&(op_type, name, _)| (op_type, name));
+        let op_type = names
+            .chain(ALIASES.iter().copied())
+            .find(|&(_, alias)| alias == upper)
+            .map(|(op_type, _)| op_type)
+            .ok_or_else(|| error(line, format!("unsupported instruction '{name}'")))?;
+        let n_qubits = GATES
+            .iter()
+            .find(|&&(t, _, _)| t == op_type)
+            .map_or(1, |&(_, _, n)| n);
+        if targets.len() % n_qubits != 0 {
+            return Err(error(line, format!("{name} takes pairs of targets")));
+        }
+        for group in targets.chunks(n_qubits) {
+            match (op_type, group) {
+                (OpType::Measure, &[Target::Qubit(q)]) => {
+                    let args = vec![self.qubit(q, line)?, element("c", self.n_bits)];
+                    self.n_bits += 1;
+                    self.push(Operation::from_optype(op_type), args, line)?;
+                }
+                (_, &[Target::Qubit(q)]) => {
+                    let args = vec![self.qubit(q, line)?];
+                    self.push(Operation::from_optype(op_type), args, line)?;
+                }
+                (_, &[Target::Qubit(a), Target::Qubit(b)]) if a == b => {
+                    return Err(error(line, format!("{name} is applied twice to qubit {a}")));
+                }
+                (_, &[Target::Qubit(a), Target::Qubit(b)]) => {
+                    let args = vec![self.qubit(a, line)?, self.qubit(b, line)?];
+                    self.push(Operation::from_optype(op_type), args, line)?;
+                }
+                (OpType::CX | OpType::CY | OpType::CZ, &[Target::Record(k), Target::Qubit(q)])
+                | (OpType::CZ, &[Target::Qubit(q), Target::Record(k)]) => {
+                    if k > self.n_bits {
+                        return Err(error(line, format!("rec[-{k}] refers to no measurement")));
+                    }
+                    let pauli = controlled_pauli(op_type).unwrap();
+                    let mut op = Operation::from_optype(OpType::Conditional);
+                    op.conditional = Some(Conditional {
+                        op: Box::new(Operation::from_optype(pauli)),
+                        width: 1,
+                        value: 1,
+                    });
+                    let args = vec![element("c", self.n_bits - k), self.qubit(q, line)?];
+                    self.push(op, args, line)?;
+                }
+                _ => {
+                    return Err(error(
+                        line,
+                        format!("{name} cannot target a measurement record"),
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the qubit with the given index, adding it to the circuit.
+    fn qubit(&mut self, index: usize, line: usize) -> Result<ElementId, ParseStimError> {
+        if index >= MAX_QUBITS {
+            return Err(error(
+                line,
+                format!("qubit index {index} is not below {MAX_QUBITS}"),
+            ));
+        }
+        self.n_qubits = self.n_qubits.max(index + 1);
+        Ok(element("q", index))
+    }
+
+    fn push(
+        &mut self,
+        op: Operation,
+        args: Vec<ElementId>,
+        line: usize,
+    ) -> Result<(), ParseStimError> {
+        if self.circ.commands.len() == MAX_COMMANDS {
+            return Err(error(
+                line,
+                format!("the circuit has more than {MAX_COMMANDS} commands"),
+            ));
+        }
+        self.circ.commands.push(Command {
+            op,
+            args,
+            opgroup: None,
+        });
+        Ok(())
+    }
+}
+
+/// Parses a qubit index or a measurement record target such as `rec[-1]`.
+fn target(word: &str) -> Option<Target> {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if let Some(record) = word.strip_prefix("rec[-").and_then(|w| w.strip_suffix(']')) {
+        return match record.parse::<u32>() {
+            Ok(k) if digits(record) && k > 0 => Some(Target::Record(k as usize)),
+            _ => None,
+        };
+    }
+    match word.parse::<u32>() {
+        Ok(q) if digits(word) => Some(Target::Qubit(q as usize)),
+        _ => None,
+    }
+}
@@ -0,0 +1,378 @@
+//! Unitary matrices of operations and circuits.
+//!
+//! Parameters are expressed in half-turns, and matrices follow the ILO-BE
+//! convention used by pytket: the first qubit of an operation (or of
+//! [`SerialCircuit::qubits`]) is the most significant bit of the basis state
+//! index.
+
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+use crate::circuit_json::{Matrix, Operation, SerialCircuit};
+use crate::linalg::{Complex, SquareMatrix};
+use crate::opbox::OpBox;
+use crate::optype::OpType;
+use crate::simulator::{check_size, SimulationError, Statevector};
+
+/// The largest number of qubits [`SerialCircuit::unitary`], [`equivalent`]
+/// and [`Operation::unitary`] support, which keeps each matrix within 256 MiB.
+pub const MAX_QUBITS: usize = 12;
+
+impl SerialCircuit<f64> {
+    /// Returns the unitary matrix implemented by the circuit, including its
+    /// global phase and implicit permutation.
+    ///
+    /// The matrix is computed by simulating the circuit on each basis state,
+    /// so this is only practical for small circuits. Returns an error under
+    /// the same conditions as [`SerialCircuit::statevector`], or if the
+    /// circuit has more than [`MAX_QUBITS`] qubits.
+    pub fn unitary(&self) -> Result<Matrix, SimulationError> {
+        circuit_unitary(self).map(|u| u.to_matrix())
+    }
+}
+
+/// Checks whether two circuits implement the same unitary, up to a global
+/// phase.
+///
+/// The circuits must act on the same set of qubits, although they may be
+/// declared in a different order; otherwise they are not equivalent. Each
+/// entry of the unitaries may differ by at most `tol`, after correcting for
+/// the global phase.
+pub fn equivalent(
+    a: &SerialCircuit<f64>,
+    b: &SerialCircuit<f64>,
+    tol: f64,
+) -> Result<bool, SimulationError> {
+    let same_qubits =
+        a.qubits.len() == b.qubits.len() && a.qubits.iter().all(|q| b.qubits.contains(q));
+    if !same_qubits {
+        return Ok(false);
+    }
+    // Use the same qubit ordering for both unitaries.
+    let mut b = b.clone();
+    b.qubits = a.qubits.clone();
+
+    let u_a = circuit_unitary(a)?;
+    let u_b = circuit_unitary(&b)?;
+
+    // The phase of the overlap tr(A†B) is the relative global phase.
+    let dim = u_a.dim();
+    let overlap = (0..dim)
+        .flat_map(|r| (0..dim).map(move |c| (r, c)))
+        .fold(Complex::ZERO, |acc, rc| acc + u_a[rc].conj() * u_b[rc]);
+    let norm = overlap.abs();
+    let phase = if norm > 0.0 {
+        overlap * (1.0 / norm)
+    } else {
+        Complex::ONE
+    };
+    Ok((0..dim)
+        .flat_map(|r| (0..dim).map(move |c| (r, c)))
+        .all(|rc| (u_a[rc] * phase - u_b[rc]).abs() <= tol))
+}
+
+/// Computes the unitary of a circuit. See [`SerialCircuit::unitary`].
+pub(crate) fn circuit_unitary(circ: &SerialCircuit<f64>) -> Result<SquareMatrix, SimulationError> {
+    let n_qubits = circ.qubits.len();
+    check_size(n_qubits, MAX_QUBITS)?;
+    let qubits: Vec<usize> = (0..n_qubits).collect();
+    let mut u = SquareMatrix::zeros(1 << n_qubits);
+    for c in 0..u.dim() {
+        let mut state = Statevector::basis(n_qubits, c);
+        state.apply_circuit(circ, &qubits, "")?;
+        for (r, amp) in state.amplitudes.into_iter().enumerate() {
+            u[(r, c)] = amp;
+        }
+    }
+    Ok(u)
+}
+
+impl Operation<f64> {
+    /// Returns the unitary matrix of the operation.
+    ///
+    /// Returns `None` for non-unitary operations such as measurements,
+    /// for classical and boxed operations without an explicit matrix, for
+    /// variadic gates without an `n_qb` count or with more than
+    /// [`MAX_QUBITS`] qubits, and for operations with the wrong number of
+    /// parameters.
+    ///
+    /// `ExpBox` operations are computed numerically, through matrix
+    /// exponentiation.
+    pub fn unitary(&self) -> Option<Matrix> {
+        op_unitary(self).map(|u| u.to_matrix())
+    }
+}
+
+/// Computes the unitary of an operation. See [`Operation::unitary`].
+pub(crate) fn op_unitary(op: &Operation<f64>) -> Option<SquareMatrix> {
+    if op.op_type.is_box() {
+        return box_unitary(op.op_box.as_ref()?);
+    }
+
+    let meta = op.op_type.metadata();
+    let params = op.params.as_deref().unwrap_or_default();
+    if params.len() != meta.params as usize {
+        return None;
+    }
+    let n_qubits = op.n_qb.or(meta.qubits.fixed());
+    if n_qubits.is_some_and(|n| n as usize > MAX_QUBITS) {
+        return None;
+    }
+    // Parameters are in half-turns.
+    let p = |i: usize| params[i];
+
+    Some(match op.op_type {
+        OpType::Phase => SquareMatrix::diagonal(&[Complex::cis(PI * p(0))]),
+        OpType::noop => SquareMatrix::identity(2),
+        OpType::X => x(),
+        OpType::Y => y(),
+        OpType::Z => z(),
+        OpType::H => h(),
+        OpType::S => phase_shift(0.5),
+        OpType::Sdg => phase_shift(-0.5),
+        OpType::T => phase_shift(0.25),
+        OpType::Tdg => phase_shift(-0.25),
+        OpType::V => rx(0.5),
+        OpType::Vdg => rx(-0.5),
+        OpType::SX => sx(),
+        OpType::SXdg => sx().adjoint(),
+        OpType::Rx => rx(p(0)),
+        OpType::Ry => ry(p(0)),
+        OpType::Rz => rz(p(0)),
+        OpType::U3 => u3(p(0), p(1), p(2)),
+        OpType::U2 => u3(0.5, p(0), p(1)),
+        OpType::U1 => phase_shift(p(0)),
+        OpType::TK1 => rz(p(0)).matmul(&rx(p(1))).matmul(&rz(p(2))),
+        OpType::TK2 => xx_phase(p(0))
+            .matmul(&yy_phase(p(1)))
+            .matmul(&zz_phase(p(2))),
+        OpType::CX => x().controlled(1),
+        OpType::CY => y().controlled(1),
+        OpType::CZ => z().controlled(1),
+        OpType::CH => h().controlled(1),
+        OpType::CV => rx(0.5).controlled(1),
+        OpType::CVdg => rx(-0.5).controlled(1),
+        OpType::CSX => sx().controlled(1),
+        OpType::CSXdg => sx().adjoint().controlled(1),
+        OpType::CS => phase_shift(0.5).controlled(1),
+        OpType::CSdg => phase_shift(-0.5).controlled(1),
+        OpType::CRz => rz(p(0)).controlled(1),
+        OpType::CRx => rx(p(0)).controlled(1),
+        OpType::CRy => ry(p(0)).controlled(1),
+        OpType::CU1 => phase_shift(p(0)).controlled(1),
+        OpType::CU3 => u3(p(0), p(1), p(2)).controlled(1),
+        OpType::PhaseGadget => pauli_exp(&vec![z(); n_qubits? as usize], p(0)),
+        OpType::CCX => x().controlled(2),
+        OpType::SWAP => swap(),
+        OpType::CSWAP => swap().controlled(1),
+        // A CX from the first to the third qubit, bridging over the second.
+        OpType::BRIDGE => SquareMatrix::from_fn(8, |r, c| {
+            let target = if c & 0b100 != 0 { c ^ 0b001 } else { c };
+            Complex::from(if r == target { 1.0 } else { 0.0 })
+        }),
+        OpType::ECR => ecr(),
+        OpType::ISWAP => iswap(p(0)),
+        OpType::ISWAPMax => iswap(1.0),
+        OpType::PhasedISWAP => {
+            let mut u = iswap(p(1));
+            u[(1, 2)] = u[(1, 2)] * Complex::cis(2.0 * PI * p(0));
+            u[(2, 1)] = u[(2, 1)] * Complex::cis(-2.0 * PI * p(0));
+            u
+        }
+        OpType::PhasedX => phased_x(p(0), p(1)),
+        OpType::NPhasedX => {
+            let single = phased_x(p(0), p(1));
+            (0..n_qubits?).fold(SquareMatrix::identity(1), |u, _| u.kron(&single))
+        }
+        OpType::ZZMax => zz_phase(0.5),
+        OpType::XXPhase => xx_phase(p(0)),
+        OpType::YYPhase => yy_phase(p(0)),
+        OpType::ZZPhase => zz_phase(p(0)),
+        OpType::XXPhase3 => {
+            let id = SquareMatrix::identity(2);
+            pauli_exp(&[x(), x(), id.clone()], p(0))
+                .matmul(&pauli_exp(&[x(), id.clone(), x()], p(0)))
+                .matmul(&pauli_exp(&[id, x(), x()], p(0)))
+        }
+        OpType::ESWAP => {
+            let (c, s) = half_angle(p(0));
+            SquareMatrix::identity(4)
+                .scale(c.into())
+                .add(&swap().scale(Complex::new(0.0, -s)))
+        }
+        OpType::FSim => fsim(p(0), p(1)),
+        OpType::Sycamore => fsim(0.5, 1.0 / 6.0),
+        OpType::CnRx => rx(p(0)).controlled(n_qubits?.checked_sub(1)?),
+        OpType::CnRy => ry(p(0)).controlled(n_qubits?.checked_sub(1)?),
+        OpType::CnRz => rz(p(0)).controlled(n_qubits?.checked_sub(1)?),
+        OpType::CnX => x().controlled(n_qubits?.checked_sub(1)?),
+        OpType::CnY => y().controlled(n_qubits?.checked_sub(1)?),
+        OpType::CnZ => z().controlled(n_qubits?.checked_sub(1)?),
+        OpType::GPI => SquareMatrix::from_rows(&[
+            [Complex::ZERO, Complex::cis(-PI * p(0))],
+            [Complex::cis(PI * p(0)), Complex::ZERO],
+        ]),
+        OpType::GPI2 => {
+            let minus_i = Complex::new(0.0, -1.0);
+            SquareMatrix::from_rows(&[
+                [Complex::ONE, minus_i * Complex::cis(-PI * p(0))],
+                [minus_i * Complex::cis(PI * p(0)), Complex::ONE],
+            ])
+            .scale(FRAC_1_SQRT_2.into())
+        }
+        OpType::AAMS => aams(p(0), p(1), p(2)),
+        _ => return None,
+    })
+}
+
+/// Computes the unitary of an operation box with an explicit matrix.
+fn box_unitary(op_box: &OpBox) -> Option<SquareMatrix> {
+    Some(match op_box {
+        OpBox::Unitary1qBox { matrix, .. } => from_array(matrix),
+        OpBox::Unitary2qBox { matrix, .. } => from_array(matrix),
+        OpBox::Unitary3qBox { matrix, .. } => from_array(matrix),
+        // `e^{itA}`, for a hermitian matrix `A` and a real `t` in radians.
+        OpBox::ExpBox { matrix, phase, .. } => {
+            from_array(matrix).scale(Complex::new(0.0, *phase)).exp()
+        }
+        _ => return None,
+    })
+}
+
+fn from_array<const N: usize>(matrix: &[[(f64, f64); N]; N]) -> SquareMatrix {
+    SquareMatrix::from_fn(N, |r, c| matrix[r][c].into())
+}
+
+/// Returns the cosine and sine of half the angle `α`, given in half-turns.
+fn half_angle(half_turns: f64) -> (f64, f64) {
+    let theta = PI * half_turns / 2.0;
+    (theta.cos(), theta.sin())
+}
+
+fn x() -> SquareMatrix {
+    SquareMatrix::from_rows(&[[Complex::ZERO, Complex::ONE], [Complex::ONE, Complex::ZERO]])
+}
+
+fn y() -> SquareMatrix {
+    let i = Complex::I;
+    SquareMatrix::from_rows(&[[Complex::ZERO, -i], [i, Complex::ZERO]])
+}
+
+fn z() -> SquareMatrix {
+    SquareMatrix::diagonal(&[Complex::ONE, -Complex::ONE])
+}
+
+fn h() -> SquareMatrix {
+    SquareMatrix::from_rows(&[[1.0, 1.0], [1.0, -1.0]].map(|row| row.map(Complex::from)))
+        .scale(FRAC_1_SQRT_2.into())
+}
+
+fn sx() -> SquareMatrix {
+    rx(0.5).scale(Complex::cis(PI / 4.0))
+}
+
+fn swap() -> SquareMatrix {
+    SquareMatrix::from_fn(4, |r, c| {
+        let swapped = ((c & 1) << 1) | (c >> 1);
+        Complex::from(if r == swapped { 1.0 } else { 0.0 })
+    })
+}
+
+/// `U1(λ) = diag(1, e^{iπλ})`.
+fn phase_shift(half_turns: f64) -> SquareMatrix {
+    SquareMatrix::diagonal(&[Complex::ONE, Complex::cis(PI * half_turns)])
+}
+
+/// Returns `e^{-½iπα P}` for a tensor product `P` of Pauli matrices.
+fn pauli_exp(paulis: &[SquareMatrix], half_turns: f64) -> SquareMatrix {
+    let pauli = paulis
+        .iter()
+        .fold(SquareMatrix::identity(1), |acc, p| acc.kron(p));
+    let (c, s) = half_angle(half_turns);
+    SquareMatrix::identity(pauli.dim())
+        .scale(c.into())
+        .add(&pauli.scale(Complex::new(0.0, -s)))
+}
+
+fn rx(half_turns: f64) -> SquareMatrix {
+    pauli_exp(&[x()], half_turns)
+}
+
+fn ry(half_turns: f64) -> SquareMatrix {
+    pauli_exp(&[y()], half_turns)
+}
+
+fn rz(half_turns: f64) -> SquareMatrix {
+    pauli_exp(&[z()], half_turns)
+}
+
+fn xx_phase(half_turns: f64) -> SquareMatrix {
+    pauli_exp(&[x(), x()], half_turns)
+}
+
+fn yy_phase(half_turns: f64) -> SquareMatrix {
+    pauli_exp(&[y(), y()], half_turns)
+}
+
+fn zz_phase(half_turns: f64) -> SquareMatrix {
+    pauli_exp(&[z(), z()], half_turns)
+}
+
+/// `U3(θ, φ, λ) = e^{½iπ(λ+φ)} Rz(φ) Ry(θ) Rz(λ)`.
+fn u3(theta: f64, phi: f64, lambda: f64) -> SquareMatrix {
+    rz(phi)
+        .matmul(&ry(theta))
+        .matmul(&rz(lambda))
+        .scale(Complex::cis(PI * (lambda + phi) / 2.0))
+}
+
+/// `PhasedX(α, β) = Rz(β) Rx(α) Rz(-β)`.
+fn phased_x(alpha: f64, beta: f64) -> SquareMatrix {
+    rz(beta).matmul(&rx(alpha)).matmul(&rz(-beta))
+}
+
+fn ecr() -> SquareMatrix {
+    let (o, l, i) = (Complex::ZERO, Complex::ONE, Complex::I);
+    SquareMatrix::from_rows(&[[o, o, l, i], [o, o, i, l], [l, -i, o, o], [-i, l, o, o]])
+        .scale(FRAC_1_SQRT_2.into())
+}
+
+fn iswap(half_turns: f64) -> SquareMatrix {
+    let (c, s) = half_angle(half_turns);
+    let (o, l, c, is) = (
+        Complex::ZERO,
+        Complex::ONE,
+        Complex::from(c),
+        Complex::new(0.0, s),
+    );
+    SquareMatrix::from_rows(&[[l, o, o, o], [o, c, is, o], [o, is, c, o], [o, o, o, l]])
+}
+
+fn fsim(alpha: f64, beta: f64) -> SquareMatrix {
+    let (c, s) = ((PI * alpha).cos(), (PI * alpha).sin());
+    let (o, l, c, mis) = (
+        Complex::ZERO,
+        Complex::ONE,
+        Complex::from(c),
+        Complex::new(0.0, -s),
+    );
+    let phase = Complex::cis(-PI * beta);
+    SquareMatrix::from_rows(&[
+        [l, o, o, o],
+        [o, c, mis, o],
+        [o, mis, c, o],
+        [o, o, o, phase],
+    ])
+}
+
+fn aams(theta: f64, phi0: f64, phi1: f64) -> SquareMatrix {
+    let (c, s) = half_angle(theta);
+    let o = Complex::ZERO;
+    let c = Complex::from(c);
+    let off = |phase: f64| Complex::new(0.0, -s) * Complex::cis(PI * phase);
+    SquareMatrix::from_rows(&[
+        [c, o, o, off(-(phi0 + phi1))],
+        [o, c, off(phi1 - phi0), o],
+        [o, off(phi0 - phi1), c, o],
+        [off(phi0 + phi1), o, o, c],
+    ])
+}
@@ -0,0 +1,460 @@
+//! Structural validation of serialized circuits.
+//!
+//! Deserializing a [`SerialCircuit`] only checks that the JSON has the right
+//! shape. [`SerialCircuit::validate`] additionally checks that the commands
+//! are consistent with the declared wires, reporting every problem found as a
+//! [`Diagnostic`] located by a [JSON pointer](https://www.rfc-editor.org/rfc/rfc6901)
+//! into the serialized circuit.
+
+use std::collections::HashSet;
+
+use derive_more::Display;
+
+use crate::circuit_json::{Classical, Command, Operation, SerialCircuit};
+use crate::opbox::OpBox;
+use crate::optype::OpType;
+use crate::register::ElementId;
+
+/// Register name used by pytket for WASM wires.
+const WASM_REGISTER: &str = "_w";
+/// Register name used by pytket for RNG wires.
+const RNG_REGISTER: &str = "_r";
+
+/// A problem found while validating a [`SerialCircuit`].
+#[derive(Clone, Debug, PartialEq, Display)]
+#[display("{path}: {kind}")]
+#[non_exhaustive]
+pub struct Diagnostic {
+    /// JSON pointer to the offending element, e.g. `/commands/12/args/1`.
+    pub path: String,
+    /// The problem found at that location.
+    pub kind: DiagnosticKind,
+}
+
+/// The kinds of problems reported by [`SerialCircuit::validate`].
+#[derive(Clone, Debug, PartialEq, Display)]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    /// A command or permutation refers to a wire that is not declared.
+    #[display("{_0} is not declared in the circuit")]
+    UndeclaredWire(ElementId),
+    /// A wire is declared more than once in `qubits` and `bits`.
+    #[display("{_0} is declared more than once")]
+    DuplicateDeclaration(ElementId),
+    /// A wire appears more than once in the arguments of a command.
+    #[display("{_0} is used more than once by the same command")]
+    DuplicateArgument(ElementId),
+    /// A command has the wrong number of arguments for its operation.
+    #[display("{op_type} expects {expected} arguments, but {found} were given")]
+    ArgCount {
+        /// The operation type.
+        op_type: OpType,
+        /// The number of arguments the operation expects.
+        expected: usize,
+        /// The number of arguments in the command.
+        found: usize,
+    },
+    /// A command argument is not of the kind of wire expected by the operation.
+    #[display("expected a {expected} wire, but {id} is a {found} wire")]
+    WireKindMismatch {
+        /// The argument.
+        id: ElementId,
+        /// The kind of wire the operation expects.
+        expected: WireKind,
+        /// The kind of wire that was declared.
+        found: WireKind,
+    },
+    /// An operation is missing a field required by its type.
+    #[display("{op_type} operation is missing its `{field}` field")]
+    MissingField {
+        /// The operation type.
+        op_type: OpType,
+        /// The name of the missing field.
+        field: &'static str,
+    },
+    /// A qubit appears more than once as a source or a target of the implicit
+    /// permutation.
+    #[display("{_0} is mapped more than once by the implicit permutation")]
+    DuplicatePermutationEntry(ElementId),
+    /// A qubit is the target of the implicit permutation but not a source, or
+    /// vice versa.
+    #[display("{_0} is not both a source and a target of the implicit permutation")]
+    UnmatchedPermutationEntry(ElementId),
+}
+
+/// The kind of a wire in a circuit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Display)]
+#[non_exhaustive]
+pub enum WireKind {
+    /// A quantum wire.
+    #[display("qubit")]
+    Qubit,
+    /// A classical bit wire.
+    #[display("bit")]
+    Bit,
+    /// A WASM state wire.
+    #[display("WASM")]
+    Wasm,
+    /// An RNG state wire.
+    #[display("RNG")]
+    Rng,
+}
+
+impl<P> SerialCircuit<P> {
+    /// Checks the structural consistency of the circuit.
+    ///
+    /// Walks the wire declarations, the commands (including the circuits
+    /// nested inside boxes), and the implicit permutation, returning every
+    /// problem found. An empty list means the circuit is well formed.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut validator = Validator::default();
+        validator.check_circuit(self, "");
+        validator.diagnostics
+    }
+}
+
+/// Accumulates the diagnostics for a circuit and its nested circuits.
+#[derive(Default)]
+struct Validator {
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// The wires declared by a circuit.
+struct Wires<'a> {
+    qubits: HashSet<&'a ElementId>,
+    bits: HashSet<&'a ElementId>,
+    number_of_ws: u64,
+    number_of_rs: u64,
+}
+
+impl Wires<'_> {
+    /// Returns the kind of a wire, if it is declared.
+    fn kind(&self, id: &ElementId) -> Option<WireKind> {
+        if self.qubits.contains(id) {
+            Some(WireKind::Qubit)
+        } else if self.bits.contains(id) {
+            Some(WireKind::Bit)
+        } else if id.0 == WASM_REGISTER && register_index(id).is_some_and(|i| i < self.number_of_ws)
+        {
+            Some(WireKind::Wasm)
+        } else if id.0 == RNG_REGISTER && register_index(id).is_some_and(|i| i < self.number_of_rs)
+        {
+            Some(WireKind::Rng)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the index of a wire in a one-dimensional register.
+fn register_index(id: &ElementId) -> Option<u64> {
+    match id.1.as_slice() {
+        [i] => u64::try_from(*i).ok(),
+        _ => None,
+    }
+}
+
+impl Validator {
+    fn report(&mut self, path: impl Into<String>, kind: DiagnosticKind) {
+        self.diagnostics.push(Diagnostic {
+            path: path.into(),
+            kind,
+        });
+    }
+
+    fn check_circuit<P>(&mut self, circ: &SerialCircuit<P>, path: &str) {
+        let mut declared = HashSet::new();
+        for (i, qb) in circ.qubits.iter().enumerate() {
+            if !declared.insert(&qb.id) {
+                self.report(
+                    format!("{path}/qubits/{i}"),
+                    DiagnosticKind::DuplicateDeclaration(qb.id.clone()),
+                );
+            }
+        }
+        for (i, bit) in circ.bits.iter().enumerate() {
+            if !declared.insert(&bit.id) {
+                self.report(
+                    format!("{path}/bits/{i}"),
+                    DiagnosticKind::DuplicateDeclaration(bit.id.clone()),
+                );
+            }
+        }
+
+        let wires = Wires {
+            qubits: circ.qubits.iter().map(|q| &q.id).collect(),
+            bits: circ.bits.iter().map(|b| &b.id).collect(),
+            number_of_ws: circ.number_of_ws.unwrap_or_default(),
+            number_of_rs: circ.number_of_rs.unwrap_or_default(),
+        };
+
+        for (i, command) in circ.commands.iter().enumerate() {
+            self.check_command(command, &wires, &format!("{path}/commands/{i}"));
+        }
+
+        self.check_permutation(circ, &wires, path);
+
+        let created = circ.created_qubits.iter().flatten().map(|q| &q.id);
+        for (i, id) in created.enumerate() {
+            if !wires.qubits.contains(id) {
+                self.report(
+                    format!("{path}/created_qubits/{i}"),
+                    DiagnosticKind::UndeclaredWire(id.clone()),
+                );
+            }
+        }
+        let discarded = circ.discarded_qubits.iter().flatten().map(|q| &q.id);
+        for (i, id) in discarded.enumerate() {
+            if !wires.qubits.contains(id) {
+                self.report(
+                    format!("{path}/discarded_qubits/{i}"),
+                    DiagnosticKind::UndeclaredWire(id.clone()),
+                );
+            }
+        }
+    }
+
+    fn check_permutation<P>(&mut self, circ: &SerialCircuit<P>, wires: &Wires, path: &str) {
+        let mut sources = HashSet::new();
+        let mut targets = HashSet::new();
+        for (i, perm) in circ.implicit_permutation.iter().enumerate() {
+            for (j, qb, seen) in [(0, &perm.0, &mut sources), (1, &perm.1, &mut targets)] {
+                let entry_path = format!("{path}/implicit_permutation/{i}/{j}");
+                if !wires.qubits.contains(&qb.id) {
+                    self.report(entry_path, DiagnosticKind::UndeclaredWire(qb.id.clone()));
+                } else if !seen.insert(&qb.id) {
+                    self.report(
+                        entry_path,
+                        DiagnosticKind::DuplicatePermutationEntry(qb.id.clone()),
+                    );
+                }
+            }
+        }
+        for id in sources.symmetric_difference(&targets) {
+            self.report(
+                format!("{path}/implicit_permutation"),
+                DiagnosticKind::UnmatchedPermutationEntry((*id).clone()),
+            );
+        }
+    }
+
+    fn check_command<P>(&mut self, command: &Command<P>, wires: &Wires, path: &str) {
+        let mut seen = HashSet::new();
+        let mut kinds = Vec::with_capacity(command.args.len());
+        for (i, arg) in command.args.iter().enumerate() {
+            let kind = wires.kind(arg);
+            if kind.is_none() {
+                self.report(
+                    format!("{path}/args/{i}"),
+                    DiagnosticKind::UndeclaredWire(arg.clone()),
+                );
+            }
+            if !seen.insert(arg) {
+                self.report(
+                    format!("{path}/args/{i}"),
+                    DiagnosticKind::DuplicateArgument(arg.clone()),
+                );
+            }
+            kinds.push(kind);
+        }
+
+        self.check_op_fields(&command.op, &format!("{path}/op"));
+
+        let Some(expected) = signature(&command.op) else {
+            return;
+        };
+        if expected.len() != command.args.len() {
+            self.report(
+                format!("{path}/args"),
+                DiagnosticKind::ArgCount {
+                    op_type: command.op.op_type,
+                    expected: expected.len(),
+                    found: command.args.len(),
+                },
+            );
+            return;
+        }
+        for (i, (arg, (expected, found))) in command
+            .args
+            .iter()
+            .zip(expected.into_iter().zip(kinds))
+            .enumerate()
+        {
+            if let Some(found) = found.filter(|&found| found != expected) {
+                self.report(
+                    format!("{path}/args/{i}"),
+                    DiagnosticKind::WireKindMismatch {
+                        id: arg.clone(),
+                        expected,
+                        found,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Checks the required fields of an operation, and recurses into the
+    /// operations and circuits nested inside it.
+    fn check_op_fields<P>(&mut self, op: &Operation<P>, path: &str) {
+        let missing = |field| DiagnosticKind::MissingField {
+            op_type: op.op_type,
+            field,
+        };
+        match op.op_type {
+            OpType::Conditional => match &op.conditional {
+                Some(cond) => self.check_op_fields(&cond.op, &format!("{path}/conditional/op")),
+                None => self.report(path, missing("conditional")),
+            },
+            OpType::ClExpr if op.classical_expr.is_none() => self.report(path, missing("expr")),
+            OpType::WASM if op.wasm.is_none() => self.report(path, missing("wasm")),
+            OpType::ClassicalTransform
+            | OpType::SetBits
+            | OpType::CopyBits
+            | OpType::RangePredicate
+            | OpType::ExplicitPredicate
+            | OpType::ExplicitModifier
+            | OpType::MultiBit
+                if op.classical.is_none() =>
+            {
+                self.report(path, missing("classical"))
+            }
+            _ if op.op_type.is_box() && op.op_box.is_none() => self.report(path, missing("box")),
+            _ => {}
+        }
+
+        if let Some(Classical::MultiBit { op: inner, .. }) = op.classical.as_deref() {
+            self.check_op_fields(inner, &format!("{path}/classical/op"));
+        }
+
+        let Some(op_box) = &op.op_box else {
+            return;
+        };
+        let path = format!("{path}/box");
+        match op_box {
+            OpBox::CircBox { circuit, .. } => {
+                self.check_circuit(circuit, &format!("{path}/circuit"));
+            }
+            OpBox::CustomGate { gate, .. } => {
+                self.check_circuit(&gate.definition, &format!("{path}/gate/definition"));
+            }
+            OpBox::QControlBox { op, .. } => self.check_op_fields(op, &format!("{path}/op")),
+            OpBox::ConjugationBox {
+                compute,
+                action,
+                uncompute,
+                ..
+            } => {
+                self.check_op_fields(compute, &format!("{path}/compute"));
+                self.check_op_fields(action, &format!("{path}/action"));
+                if let Some(uncompute) = uncompute {
+                    self.check_op_fields(uncompute, &format!("{path}/uncompute"));
+                }
+            }
+            OpBox::MultiplexorBox { op_map, .. }
+            | OpBox::MultiplexedRotationBox { op_map, .. }
+            | OpBox::MultiplexedU2Box { op_map, .. }
+            | OpBox::MultiplexedTensoredU2Box { op_map, .. } => {
+                for (i, (_, op)) in op_map.iter().enumerate() {
+                    self.check_op_fields(op, &format!("{path}/op_map/{i}/1"));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns the kinds of the wires an operation acts on, if they can be
+/// determined from the operation alone.
+fn signature<P>(op: &Operation<P>) -> Option<Vec<WireKind>> {
+    if let Some(signature) = &op.signature {
+        return signature
+            .iter()
+            .map(|s| match s.as_str() {
+                "Q" => Some(WireKind::Qubit),
+                "C" | "B" => Some(WireKind::Bit),
+                "W" => Some(WireKind::Wasm),
+                "R" => Some(WireKind::Rng),
+                _ => None,
+            })
+            .collect();
+    }
+
+    let repeat = |kind, n: usize| std::iter::repeat(kind).take(n);
+
+    let meta = op.op_type.metadata();
+    if let (Some(qubits), Some(bits), Some(wasm_wires), Some(rng_wires)) = (
+        meta.qubits.fixed(),
+        meta.bits.fixed(),
+        meta.wasm_wires.fixed(),
+        meta.rng_wires.fixed(),
+    ) {
+        let sig = repeat(WireKind::Qubit, qubits as usize)
+            .chain(repeat(WireKind::Bit, bits as usize))
+            .chain(repeat(WireKind::Wasm, wasm_wires as usize))
+            .chain(repeat(WireKind::Rng, rng_wires as usize));
+        return Some(sig.collect());
+    }
+
+    match op.op_type {
+        OpType::Conditional => {
+            let cond = op.conditional.as_ref()?;
+            let mut sig: Vec<_> = repeat(WireKind::Bit, cond.width as usize).collect();
+            sig.extend(signature(&cond.op)?);
+            Some(sig)
+        }
+        OpType::WASM => {
+            let wasm = op.wasm.as_ref()?;
+            let sig = repeat(WireKind::Bit, wasm.n as usize)
+                .chain(repeat(WireKind::Wasm, wasm.ww_n as usize));
+            Some(sig.collect())
+        }
+        _ if op.classical.is_some() => classical_signature(op.classical.as_deref()?),
+        _ => box_signature(op.op_box.as_ref()?),
+    }
+}
+
+/// Returns the number of bits acted on by a classical operation.
+fn classical_signature(classical: &Classical) -> Option<Vec<WireKind>> {
+    let n = match classical {
+        Classical::MultiBit { op, n } => {
+            let inner = signature(op)?;
+            let sig = std::iter::repeat(inner).take(*n as usize).flatten();
+            return Some(sig.collect());
+        }
+        Classical::RangePredicate { n_i, .. } | Classical::Explicit { n_i, .. } => *n_i + 1,
+        Classical::ClassicalTransform { n_io, .. } => *n_io,
+        // The `n_i` inputs are followed by as many outputs.
+        Classical::CopyBits { n_i } => 2 * *n_i,
+        Classical::SetBits { values } => values.len() as u32,
+    };
+    Some(vec![WireKind::Bit; n as usize])
+}
+
+/// Returns the wires acted on by a box, when they are determined by its
+/// contents.
+fn box_signature(op_box: &OpBox) -> Option<Vec<WireKind>> {
+    let (n_qubits, n_bits) = match op_box {
+        OpBox::CircBox { circuit, .. } => (circuit.qubits.len(), circuit.bits.len()),
+        OpBox::CustomGate { gate, .. } => {
+            (gate.definition.qubits.len(), gate.definition.bits.len())
+        }
+        OpBox::Unitary1qBox { .. } => (1, 0),
+        OpBox::Unitary2qBox { .. } | OpBox::ExpBox { .. } => (2, 0),
+        OpBox::Unitary3qBox { .. } => (3, 0),
+        OpBox::PauliExpBox { paulis, .. } => (paulis.len(), 0),
+        OpBox::PhasePolyBox { n_qubits, .. } => (*n_qubits as usize, 0),
+        OpBox::DummyBox {
+            n_qubits, n_bits, ..
+        } => (*n_qubits as usize, *n_bits as usize),
+        OpBox::QControlBox { n_controls, op, .. } => {
+            let mut sig = vec![WireKind::Qubit; *n_controls as usize];
+            sig.extend(signature(op)?);
+            return Some(sig);
+        }
+        OpBox::ConjugationBox { action, .. } => return signature(action),
+        _ => return None,
+    };
+    let mut sig = vec![WireKind::Qubit; n_qubits];
+    sig.extend(std::iter::repeat(WireKind::Bit).take(n_bits));
+    Some(sig)
+}
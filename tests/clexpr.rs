@@ -0,0 +1,751 @@
+//! Tests for classical expressions.
+use rstest::rstest;
+use serde_json::{json, Value};
+use tket_json_rs::clexpr::builder::{ClExprBuilder, ClValue};
+use tket_json_rs::clexpr::check::{ClType, TypeError, TypeErrorKind};
+use tket_json_rs::clexpr::eval::EvalError;
+use tket_json_rs::clexpr::legacy::ConvertErrorKind;
+use tket_json_rs::clexpr::lower::LowerErrorKind;
+use tket_json_rs::clexpr::op::ClOp;
+use tket_json_rs::clexpr::operator::ClVariable;
+use tket_json_rs::clexpr::ClExpr;
+use tket_json_rs::optype::OpType;
+use tket_json_rs::register::{BitRegister, ElementId};
+use tket_json_rs::SerialCircuit;
+
+const QASM: &str = include_str!("data/qasm.json");
+const CLASSICAL: &str = include_str!("data/classical.json");
+
+/// Returns the `ClExpr` in the qasm example, computing `d = (a + b) / 2 - c`
+/// over 3-bit registers.
+fn qasm_expr() -> ClExpr {
+    let circ: SerialCircuit = serde_json::from_str(QASM).unwrap();
+    circ.commands
+        .into_iter()
+        .find_map(|c| c.op.classical_expr)
+        .unwrap()
+}
+
+/// Encodes a little-endian integer.
+fn bits(value: u64, width: usize) -> Vec<bool> {
+    (0..width).map(|i| (value >> i) & 1 == 1).collect()
+}
+
+fn reg(index: u32) -> Value {
+    json!({"type": "term", "input": {"type": "var", "term": {"type": "reg", "var": {"index": index}}}})
+}
+
+fn bit(index: u32) -> Value {
+    json!({"type": "term", "input": {"type": "var", "term": {"type": "bit", "var": {"index": index}}}})
+}
+
+fn int(value: u64) -> Value {
+    json!({"type": "term", "input": {"type": "int", "term": value}})
+}
+
+fn op(op: &str, args: Vec<Value>) -> Value {
+    json!({"type": "expr", "input": {"op": op, "args": args}})
+}
+
+/// Builds an expression over two 4-bit registers in arguments 0..8 and two
+/// bits in arguments 12 and 13, writing to `output_width` bits starting at
+/// argument 8.
+fn reg_expr(expr: Value, output_width: u32) -> ClExpr {
+    serde_json::from_value(json!({
+        "bit_posn": [[0, 12], [1, 13]],
+        "expr": expr["input"],
+        "reg_posn": [[0, [0, 1, 2, 3]], [1, [4, 5, 6, 7]]],
+        "output_posn": (8..8 + output_width).collect::<Vec<_>>(),
+    }))
+    .unwrap()
+}
+
+#[rstest]
+#[case::simple(5, 2, 1, 2)]
+#[case::intermediate_overflow(5, 3, 1, 7)]
+#[case::truncating_division(5, 2, 0, 3)]
+#[case::wraparound(0, 0, 1, 7)]
+fn qasm_example(#[case] a: u64, #[case] b: u64, #[case] c: u64, #[case] d: u64) {
+    let expr = qasm_expr();
+    let args = [bits(a, 3), bits(b, 3), bits(c, 3), vec![false; 3]].concat();
+    assert_eq!(expr.evaluate(&args).unwrap(), bits(d, 3));
+
+    let mut args = args;
+    expr.apply(&mut args).unwrap();
+    assert_eq!(args[9..], bits(d, 3));
+}
+
+#[rstest]
+#[case::add(op("RegAdd", vec![reg(0), reg(1)]), 9, 10, 4, 3)]
+#[case::mul(op("RegMul", vec![reg(0), reg(1), int(2)]), 3, 5, 8, 14)]
+#[case::lsh(op("RegLsh", vec![reg(0), int(2)]), 3, 0, 4, 12)]
+#[case::rsh(op("RegRsh", vec![reg(0), reg(1)]), 12, 2, 4, 3)]
+#[case::pow(op("RegPow", vec![reg(0), reg(1)]), 3, 3, 8, 11)]
+#[case::neg(op("RegNeg", vec![reg(0)]), 1, 0, 4, 15)]
+#[case::not(op("RegNot", vec![reg(0)]), 5, 0, 4, 10)]
+#[case::not_eq(op("RegEq", vec![op("RegNot", vec![reg(0)]), int(10)]), 5, 0, 1, 1)]
+#[case::neg_gt(op("RegGt", vec![op("RegNeg", vec![reg(0)]), int(15)]), 1, 0, 1, 0)]
+#[case::sub_lt(op("RegLt", vec![op("RegSub", vec![reg(0), reg(1)]), int(15)]), 2, 3, 1, 0)]
+#[case::const_one(op("RegOne", vec![]), 0, 0, 8, 255)]
+#[case::xor(op("RegXor", vec![reg(0), reg(1)]), 6, 3, 4, 5)]
+#[case::lt(op("RegLt", vec![reg(0), reg(1)]), 2, 3, 1, 1)]
+#[case::geq(op("RegGeq", vec![reg(0), reg(1)]), 2, 3, 1, 0)]
+#[case::bits(op("BitXor", vec![bit(0), bit(1)]), 1, 0, 1, 1)]
+#[case::bit_eq(op("BitEq", vec![bit(0), op("BitNot", vec![bit(1)])]), 1, 0, 1, 1)]
+#[case::bit_const(op("BitAnd", vec![bit(0), int(1)]), 1, 0, 1, 1)]
+fn operations(
+    #[case] expr: Value,
+    #[case] a: u64,
+    #[case] b: u64,
+    #[case] output_width: u32,
+    #[case] expected: u64,
+) {
+    let expr = reg_expr(expr, output_width);
+    let args = [
+        bits(a, 4),
+        bits(b, 4),
+        vec![false; 4],
+        bits(a, 1),
+        bits(b, 1),
+    ]
+    .concat();
+    assert_eq!(
+        expr.evaluate(&args).unwrap(),
+        bits(expected, output_width as usize)
+    );
+}
+
+#[rstest]
+#[case::division_by_zero(op("RegDiv", vec![reg(0), reg(1)]), EvalError::DivisionByZero)]
+#[case::bit_op_on_register(
+    op("BitAnd", vec![reg(0), bit(0)]),
+    EvalError::ExpectedBit { op: ClOp::BitAnd }
+)]
+#[case::arg_count(op("RegSub", vec![reg(0)]), EvalError::ArgCount { op: ClOp::RegSub, found: 1 })]
+#[case::invalid_bit_constant(op("BitOr", vec![bit(0), int(2)]), EvalError::InvalidBitConstant(2))]
+fn errors(#[case] expr: Value, #[case] error: EvalError) {
+    let expr = reg_expr(expr, 4);
+    assert_eq!(expr.evaluate(&[false; 14]), Err(error));
+}
+
+#[test]
+fn position_out_of_range() {
+    let expr = reg_expr(op("RegAdd", vec![reg(0), reg(1)]), 4);
+    assert_eq!(
+        expr.evaluate(&[false; 6]),
+        Err(EvalError::PositionOutOfRange {
+            position: 6,
+            len: 6
+        })
+    );
+}
+
+/// Returns the `(path, kind)` pairs of the type errors of an expression.
+fn type_errors(expr: &ClExpr) -> Vec<(String, TypeErrorKind)> {
+    expr.check()
+        .unwrap_err()
+        .into_iter()
+        .map(|TypeError { path, kind, .. }| (path, kind))
+        .collect()
+}
+
+#[test]
+fn check_well_typed() {
+    assert_eq!(qasm_expr().check(), Ok(ClType::Register));
+    let comparison = reg_expr(
+        op("RegLt", vec![reg(0), op("RegAdd", vec![reg(1), int(1)])]),
+        1,
+    );
+    assert_eq!(comparison.check(), Ok(ClType::Bit));
+    let shift = reg_expr(op("RegLsh", vec![reg(0), int(1)]), 4);
+    assert_eq!(shift.check(), Ok(ClType::Register));
+}
+
+#[test]
+fn check_operands() {
+    let expr = reg_expr(
+        op(
+            "RegAdd",
+            vec![
+                op("BitNot", vec![reg(0)]),
+                bit(3),
+                op("RegSub", vec![reg(1)]),
+            ],
+        ),
+        4,
+    );
+    assert_eq!(
+        type_errors(&expr),
+        vec![
+            (
+                "/expr/args/0/input/args/0".to_string(),
+                TypeErrorKind::TypeMismatch {
+                    op: ClOp::BitNot,
+                    expected: ClType::Bit,
+                    found: ClType::Register
+                }
+            ),
+            (
+                "/expr/args/1".to_string(),
+                TypeErrorKind::UndeclaredVariable(ClVariable::Bit { index: 3 })
+            ),
+            (
+                "/expr/args/2/input".to_string(),
+                TypeErrorKind::ArgCount {
+                    op: ClOp::RegSub,
+                    found: 1
+                }
+            ),
+            (
+                "/expr/args/0".to_string(),
+                TypeErrorKind::TypeMismatch {
+                    op: ClOp::RegAdd,
+                    expected: ClType::Register,
+                    found: ClType::Bit
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn check_declarations() {
+    let expr: ClExpr = serde_json::from_value(json!({
+        "bit_posn": [[0, 0], [0, 1]],
+        "expr": op("RegAdd", vec![reg(0), reg(1)])["input"],
+        "reg_posn": [[0, [1, 2]], [1, [3, 3]]],
+        "output_posn": [4, 4, 5],
+    }))
+    .unwrap();
+    assert_eq!(
+        type_errors(&expr),
+        vec![
+            (
+                "/bit_posn/1".to_string(),
+                TypeErrorKind::DuplicateVariable(ClVariable::Bit { index: 0 })
+            ),
+            (
+                "/reg_posn/0/1/0".to_string(),
+                TypeErrorKind::ReusedPosition(1)
+            ),
+            (
+                "/reg_posn/1/1/1".to_string(),
+                TypeErrorKind::ReusedPosition(3)
+            ),
+            (
+                "/output_posn/1".to_string(),
+                TypeErrorKind::ReusedPosition(4)
+            ),
+            (
+                "/output_posn".to_string(),
+                TypeErrorKind::OutputWidth {
+                    expected: 2,
+                    found: 3
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn check_resizing() {
+    let mut expr: ClExpr = serde_json::from_value(json!({
+        "bit_posn": [],
+        "expr": op("RegAdd", vec![reg(0), reg(1)])["input"],
+        "reg_posn": [[0, [0, 1]], [1, [2, 3]]],
+        "output_posn": [4, 5, 6],
+    }))
+    .unwrap();
+    assert_eq!(
+        type_errors(&expr),
+        [(
+            "/output_posn".to_string(),
+            TypeErrorKind::OutputWidth {
+                expected: 2,
+                found: 3
+            }
+        )]
+    );
+    assert_eq!(expr.check_resizing(), Ok(ClType::Register));
+
+    // 3 + 3 wraps around to 0b10, which is zero-extended or truncated.
+    let mut args = vec![true; 4];
+    args.extend([false; 3]);
+    assert_eq!(expr.evaluate(&args).unwrap(), [false, true, false]);
+    expr.output_posn.0.truncate(1);
+    assert_eq!(expr.check_resizing(), Ok(ClType::Register));
+    assert_eq!(expr.evaluate(&args).unwrap(), [false]);
+
+    // Bit results still need exactly one output bit.
+    let expr: ClExpr = serde_json::from_value(json!({
+        "bit_posn": [[0, 0], [1, 1]],
+        "expr": op("BitAnd", vec![bit(0), bit(1)])["input"],
+        "reg_posn": [],
+        "output_posn": [2, 3],
+    }))
+    .unwrap();
+    assert_eq!(
+        expr.check_resizing().unwrap_err()[0].kind,
+        TypeErrorKind::OutputWidth {
+            expected: 1,
+            found: 2
+        }
+    );
+}
+
+fn register(name: &str, size: u32) -> BitRegister {
+    BitRegister {
+        name: name.to_string(),
+        size,
+    }
+}
+
+fn element(name: &str, index: i64) -> ElementId {
+    ElementId(name.to_string(), vec![index])
+}
+
+#[test]
+fn builder_matches_qasm_example() {
+    let mut builder = ClExprBuilder::new();
+    let a = builder.register(&register("a", 3));
+    let b = builder.register(&register("b", 3));
+    let c = builder.register(&register("c", 3));
+    let outputs = (0..3).map(|i| element("d", i));
+    let (expr, args) = builder.build((a + b) / 2 - c, outputs).unwrap();
+
+    let names: Vec<&str> = args.iter().map(|id| id.0.as_str()).collect();
+    assert_eq!(
+        names,
+        ["a", "a", "a", "b", "b", "b", "c", "c", "c", "d", "d", "d"]
+    );
+    let input = [bits(5, 3), bits(2, 3), bits(1, 3), vec![false; 3]].concat();
+    assert_eq!(expr.evaluate(&input).unwrap(), bits(2, 3));
+    assert_eq!(
+        expr.evaluate(&input).unwrap(),
+        qasm_expr().evaluate(&input).unwrap()
+    );
+}
+
+/// The variables available to the builder test cases.
+struct Vars {
+    a: ClValue,
+    b: ClValue,
+    x: ClValue,
+    y: ClValue,
+}
+
+#[rstest]
+#[case::bit_xor(|v: Vars| v.x ^ v.y ^ v.a.eq(3u64), 3, 0, 1, 1, 1)]
+#[case::bit_not(|v: Vars| !v.x & v.y, 0, 0, 0, 1, 1)]
+#[case::comparison(|v: Vars| (v.a + 1u64).lt(v.b), 2, 4, 0, 0, 1)]
+#[case::int_and_bit(|v: Vars| ClValue::int(1) & v.x, 0, 0, 1, 0, 1)]
+fn builder_bit_results(
+    #[case] f: fn(Vars) -> ClValue,
+    #[case] a: u64,
+    #[case] b: u64,
+    #[case] x: u64,
+    #[case] y: u64,
+    #[case] expected: u64,
+) {
+    let mut builder = ClExprBuilder::new();
+    let vars = Vars {
+        a: builder.register(&register("a", 4)),
+        b: builder.register(&register("b", 4)),
+        x: builder.bit(element("x", 0)),
+        y: builder.bit(element("y", 0)),
+    };
+    let value = f(vars);
+    assert_eq!(value.ty(), ClType::Bit);
+    let (expr, args) = builder.build(value, [element("out", 0)]).unwrap();
+    assert_eq!(args.len(), 11);
+    let input = [bits(a, 4), bits(b, 4), bits(x, 1), bits(y, 1), vec![false]].concat();
+    assert_eq!(expr.evaluate(&input).unwrap(), bits(expected, 1));
+}
+
+#[test]
+fn builder_reuses_bits() {
+    let mut builder = ClExprBuilder::new();
+    let a = builder.register(&register("a", 2));
+    let same = builder.bits([element("a", 0), element("a", 1)]);
+    let command = builder
+        .build_command(a + same, [element("a", 0), element("a", 1)])
+        .unwrap();
+    assert_eq!(command.op.op_type, OpType::ClExpr);
+    assert_eq!(command.args, [element("a", 0), element("a", 1)]);
+
+    let expr = command.op.classical_expr.unwrap();
+    assert_eq!(expr.reg_posn.len(), 1);
+    let mut args = bits(1, 2);
+    expr.apply(&mut args).unwrap();
+    assert_eq!(args, bits(2, 2));
+}
+
+#[test]
+fn builder_reports_type_errors() {
+    let mut builder = ClExprBuilder::new();
+    let a = builder.register(&register("a", 2));
+    let errors = builder
+        .build(a.clone() + a, [element("out", 0)])
+        .unwrap_err();
+    assert_eq!(
+        errors.into_iter().map(|e| e.kind).collect::<Vec<_>>(),
+        [TypeErrorKind::OutputWidth {
+            expected: 2,
+            found: 1
+        }]
+    );
+}
+
+#[test]
+fn display_qasm_example() {
+    let expr = qasm_expr();
+    assert_eq!(
+        expr.to_string(),
+        "out[9..12] = (r0 + r1) / 2 - r2 where r0 = [0..3], r1 = [3..6], r2 = [6..9]"
+    );
+}
+
+#[rstest]
+#[case::shift(op("RegLsh", vec![op("RegAdd", vec![reg(0), reg(1)]), int(2)]), "(r0 + r1) << 2")]
+#[case::left_associative(op("RegSub", vec![reg(0), op("RegSub", vec![reg(1), int(1)])]), "r0 - (r1 - 1)")]
+#[case::bits(op("BitXor", vec![bit(1), op("BitNot", vec![bit(0)])]), "b1 ^ !b0")]
+#[case::comparison(op("RegLt", vec![op("RegMul", vec![reg(0), int(2)]), reg(1)]), "r0 * 2 < r1")]
+#[case::bit_comparison(op("BitEq", vec![op("RegGeq", vec![reg(0), reg(1)]), bit(0)]), "(r0 >= r1) == b0")]
+#[case::pow(op("RegPow", vec![op("RegNeg", vec![reg(0)]), op("RegPow", vec![reg(1), int(2)])]), "(-r0) ** r1 ** 2")]
+#[case::neg_pow(op("RegNeg", vec![op("RegPow", vec![reg(0), int(2)])]), "-r0 ** 2")]
+#[case::n_ary(op("RegAdd", vec![reg(0), reg(1), int(1)]), "RegAdd(r0, r1, 1)")]
+#[case::constant(op("BitAnd", vec![op("BitOne", vec![]), int(1)]), "BitOne() & 1")]
+#[case::ambiguous(op("BitAnd", vec![int(1), int(0)]), "BitAnd(1, 0)")]
+fn display_roundtrip(#[case] expr: Value, #[case] text: &str) {
+    let expr = reg_expr(expr, 4);
+    let expected = format!("out[8..12] = {text} where b0 = 12, b1 = 13, r0 = [0..4], r1 = [4..8]");
+    assert_eq!(expr.to_string(), expected);
+    assert_eq!(expected.parse::<ClExpr>(), Ok(expr));
+}
+
+#[rstest]
+#[case::bits("b2 = b0 ^ b1", &[true, false, false], &[true])]
+#[case::bit_order("b5 = b3 & !b1", &[false, false, false, true, false, false], &[true])]
+#[case::registers(
+    "out[0..3] = (r0 + r1) << 2 where r0 = [3..6], r1 = [6..9]",
+    &[false, false, false, true, false, false, false, true, false],
+    &[false, false, true]
+)]
+#[case::mixed(
+    "b4 = (r0 == 2) ^ b3 where r0 = [0..3]",
+    &[false, true, false, false, false],
+    &[true]
+)]
+fn display_positional(#[case] text: &str, #[case] args: &[bool], #[case] expected: &[bool]) {
+    let expr: ClExpr = text.parse().unwrap();
+    assert_eq!(expr.check().err(), None);
+    assert_eq!(expr.to_string(), text);
+    assert_eq!(expr.evaluate(args).unwrap(), expected);
+}
+
+#[rstest]
+#[case::positions(
+    "out[0, 2..4, 9] = r0 + 1 where r0 = [5, 6]",
+    "out[0, 2, 3, 9] = r0 + 1 where r0 = [5, 6]"
+)]
+#[case::spacing(
+    "out[0..3]=RegNot(r0)where r0=[3..6]",
+    "out[0..3] = !r0 where r0 = [3..6]"
+)]
+#[case::declared_bits("out[2] = b0 ^ b1 where b0 = 0, b1 = 1", "b2 = b0 ^ b1")]
+fn parse_normalizes(#[case] input: &str, #[case] output: &str) {
+    let expr: ClExpr = input.parse().unwrap();
+    assert_eq!(expr.to_string(), output);
+}
+
+#[rstest]
+#[case::missing_output("r0 + r1", 0)]
+#[case::terminal("out[0] = b0 where b0 = 1", 9)]
+#[case::unknown_variable("out[0] = x + 1", 9)]
+#[case::unknown_operation("out[0] = RegFoo(r0)", 9)]
+#[case::unclosed("out[0] = (r0 + 1", 16)]
+#[case::bad_declaration("out[0] = r0 + 1 where r0 = 1", 27)]
+#[case::trailing("out[0] = r0 + 1 r1", 16)]
+#[case::bad_character("out[0] = r0 % 2", 12)]
+fn parse_errors(#[case] input: &str, #[case] position: usize) {
+    let error = input.parse::<ClExpr>().unwrap_err();
+    assert_eq!(error.position, position, "{error}");
+}
+
+fn bit_id(name: &str, index: i64) -> Value {
+    json!([name, [index]])
+}
+
+fn legacy_box(n_i: u32, n_io: u32, n_o: u32, exp: Value) -> Value {
+    json!({
+        "type": "ClassicalExpBox",
+        "box": {
+            "type": "ClassicalExpBox",
+            "id": "6a7f1a7c-3b6e-4a1e-9d55-3f0b2f7f6c11",
+            "n_i": n_i,
+            "n_io": n_io,
+            "n_o": n_o,
+            "exp": exp,
+        }
+    })
+}
+
+/// A circuit computing `c = a + b` and, if `c[0]` is set,
+/// `g[0] = a[0] & !b[0]`, with legacy boxes.
+fn legacy_circuit(add_op: &str) -> SerialCircuit {
+    let a = json!({"name": "a", "size": 3});
+    let b = json!({"name": "b", "size": 3});
+    let add = legacy_box(6, 0, 3, json!({"op": add_op, "args": [a, b]}));
+    let and_not = legacy_box(
+        2,
+        0,
+        1,
+        json!({"op": "BitWiseOp.AND", "args": [
+            bit_id("a", 0),
+            {"op": "BitWiseOp.NOT", "args": [bit_id("b", 0)]},
+        ]}),
+    );
+    let bits: Vec<Value> = ["a", "b", "c"]
+        .iter()
+        .flat_map(|name| (0..3).map(move |i| bit_id(name, i)))
+        .chain([bit_id("g", 0)])
+        .collect();
+    serde_json::from_value(json!({
+        "phase": "0",
+        "qubits": [],
+        "bits": bits,
+        "implicit_permutation": [],
+        "commands": [
+            {"op": add, "args": bits[..9]},
+            {
+                "op": {
+                    "type": "Conditional",
+                    "conditional": {"op": and_not, "width": 1, "value": 1},
+                },
+                "args": [bit_id("c", 0), bit_id("a", 0), bit_id("b", 0), bit_id("g", 0)],
+            },
+        ],
+    }))
+    .unwrap()
+}
+
+#[test]
+fn convert_legacy_boxes() {
+    let mut circ = legacy_circuit("RegWiseOp.ADD");
+    circ.convert_classical_exp_boxes().unwrap();
+
+    let add = &circ.commands[0];
+    assert_eq!(add.op.op_type, OpType::ClExpr);
+    assert!(add.op.op_box.is_none());
+    let expr = add.op.classical_expr.as_ref().unwrap();
+    assert_eq!(
+        expr.to_string(),
+        "out[6..9] = r0 + r1 where r0 = [0..3], r1 = [3..6]"
+    );
+    assert_eq!(
+        add.args,
+        circ.bits[..9]
+            .iter()
+            .map(|b| b.id.clone())
+            .collect::<Vec<_>>()
+    );
+    let args = [bits(5, 3), bits(1, 3), vec![false; 3]].concat();
+    assert_eq!(expr.evaluate(&args).unwrap(), bits(6, 3));
+
+    let conditional = circ.commands[1].op.conditional.as_ref().unwrap();
+    assert_eq!(conditional.op.op_type, OpType::ClExpr);
+    let expr = conditional.op.classical_expr.as_ref().unwrap();
+    assert_eq!(expr.to_string(), "b2 = b0 & !b1");
+    assert_eq!(
+        circ.commands[1].args,
+        [
+            element("c", 0),
+            element("a", 0),
+            element("b", 0),
+            element("g", 0)
+        ]
+    );
+}
+
+#[rstest]
+#[case::zero_extended(4, bits(6, 4))]
+#[case::truncated(2, bits(2, 2))]
+fn convert_legacy_resized_output(#[case] n_o: i64, #[case] expected: Vec<bool>) {
+    let a = json!({"name": "a", "size": 3});
+    let b = json!({"name": "b", "size": 3});
+    let add = legacy_box(
+        6,
+        0,
+        n_o as u32,
+        json!({"op": "RegWiseOp.ADD", "args": [a, b]}),
+    );
+    let ids: Vec<Value> = ["a", "b"]
+        .iter()
+        .flat_map(|name| (0..3).map(move |i| bit_id(name, i)))
+        .chain((0..n_o).map(|i| bit_id("c", i)))
+        .collect();
+    let mut circ: SerialCircuit = serde_json::from_value(json!({
+        "phase": "0",
+        "qubits": [],
+        "bits": ids,
+        "implicit_permutation": [],
+        "commands": [{"op": add, "args": ids}],
+    }))
+    .unwrap();
+    circ.convert_classical_exp_boxes().unwrap();
+
+    let expr = circ.commands[0].op.classical_expr.as_ref().unwrap();
+    assert!(expr.check().is_err());
+    let args = [bits(5, 3), bits(1, 3), vec![false; n_o as usize]].concat();
+    assert_eq!(expr.evaluate(&args).unwrap(), expected);
+}
+
+#[test]
+fn convert_legacy_unknown_op() {
+    let mut circ = legacy_circuit("RegWiseOp.MOD");
+    let original = circ.clone();
+    let errors = circ.convert_classical_exp_boxes().unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].path, "/commands/0/op/box/exp");
+    assert_eq!(
+        errors[0].kind,
+        ConvertErrorKind::UnknownOp("RegWiseOp.MOD".to_string())
+    );
+    assert_eq!(circ, original);
+}
+
+/// A circuit applying a classical operation to the bits `w[0..n_args]`.
+fn classical_circuit(op_type: &str, classical: Value, n_args: i64) -> SerialCircuit {
+    let bits: Vec<Value> = (0..n_args).map(|i| bit_id("w", i)).collect();
+    serde_json::from_value(json!({
+        "phase": "0",
+        "qubits": [],
+        "bits": bits,
+        "implicit_permutation": [],
+        "commands": [{"op": {"type": op_type, "classical": classical}, "args": bits}],
+    }))
+    .unwrap()
+}
+
+#[rstest]
+#[case::set_bits("SetBits", json!({"values": [true, false, true]}), 3, |_: u64| 0b101)]
+#[case::copy_bits("CopyBits", json!({"n_i": 2}), 4, |x: u64| (x & 3) | (x & 3) << 2)]
+#[case::range_predicate(
+    "RangePredicate",
+    json!({"n_i": 3, "lower": 2, "upper": 5}),
+    4,
+    |x: u64| (x & 7) | ((2..=5).contains(&(x & 7)) as u64) << 3
+)]
+#[case::explicit_predicate(
+    "ExplicitPredicate",
+    json!({"n_i": 2, "name": "XOR", "values": [false, true, true, false]}),
+    3,
+    |x: u64| (x & 3) | ((x ^ x >> 1) & 1) << 2
+)]
+#[case::explicit_modifier(
+    "ExplicitModifier",
+    json!({"n_i": 1, "name": "XorWith", "values": [false, true, true, false]}),
+    2,
+    |x: u64| x & 1 | ((x ^ x >> 1) & 1) << 1
+)]
+#[case::classical_transform(
+    "ClassicalTransform",
+    json!({"n_io": 2, "name": "ClassicalCX", "values": [0, 3, 2, 1]}),
+    2,
+    |x: u64| [0, 3, 2, 1][x as usize]
+)]
+#[case::wide_classical_transform(
+    "ClassicalTransform",
+    json!({"n_io": 6, "name": "Affine", "values": (0..64).map(|x| (5 * x + 3) % 64).collect::<Vec<u32>>()}),
+    6,
+    |x: u64| (5 * x + 3) % 64
+)]
+fn lower_classical_ops(
+    #[case] op_type: &str,
+    #[case] classical: Value,
+    #[case] n_args: i64,
+    #[case] expected: fn(u64) -> u64,
+) {
+    let mut circ = classical_circuit(op_type, classical, n_args);
+    circ.lower_classical_ops().unwrap();
+    assert_eq!(circ.commands.len(), 1);
+    let command = &circ.commands[0];
+    assert_eq!(command.op.op_type, OpType::ClExpr);
+    assert!(command.op.classical.is_none());
+
+    let expr = command.op.classical_expr.as_ref().unwrap();
+    assert!(expr.check().is_ok());
+    let n = n_args as usize;
+    let positions: Vec<usize> = command.args.iter().map(|id| id.1[0] as usize).collect();
+    for x in 0..1 << n {
+        let mut args: Vec<bool> = positions.iter().map(|&p| (x >> p) & 1 == 1).collect();
+        expr.apply(&mut args).unwrap();
+        let result = positions
+            .iter()
+            .zip(args)
+            .fold(0, |acc, (&p, b)| acc | (b as u64) << p);
+        assert_eq!(result, expected(x), "input {x:#b}");
+    }
+}
+
+#[test]
+fn lower_multi_bit() {
+    let and = json!({
+        "type": "ExplicitPredicate",
+        "classical": {"n_i": 2, "name": "AND", "values": [false, false, false, true]},
+    });
+    let mut circ = classical_circuit("MultiBit", json!({"op": and, "n": 2}), 6);
+    circ.lower_classical_ops().unwrap();
+    let texts: Vec<String> = circ
+        .commands
+        .iter()
+        .map(|c| c.op.classical_expr.as_ref().unwrap().to_string())
+        .collect();
+    assert_eq!(texts, ["b2 = r0 == 3 where r0 = [0, 1]"; 2]);
+    // The inputs of both applications come before their outputs.
+    assert_eq!(
+        circ.commands[0].args,
+        [element("w", 0), element("w", 1), element("w", 4)]
+    );
+    assert_eq!(
+        circ.commands[1].args,
+        [element("w", 2), element("w", 3), element("w", 5)]
+    );
+}
+
+/// The `CopyBits` command of the classical example has `n_i = 2`, but only
+/// two arguments.
+#[test]
+fn lower_copy_bits_arg_count() {
+    let mut circ: SerialCircuit = serde_json::from_str(CLASSICAL).unwrap();
+    let errors = circ.lower_classical_ops().unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].path, "/commands/2/op");
+    assert_eq!(
+        errors[0].kind,
+        LowerErrorKind::ArgCount {
+            op_type: OpType::CopyBits,
+            expected: 4,
+            found: 2
+        }
+    );
+}
+
+#[test]
+fn lower_invalid_table() {
+    let classical = json!({"n_i": 2, "name": "AND", "values": [false, true]});
+    let mut circ = classical_circuit("ExplicitPredicate", classical, 3);
+    let original = circ.clone();
+    let errors = circ.lower_classical_ops().unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].path, "/commands/0/op");
+    assert_eq!(
+        errors[0].kind,
+        LowerErrorKind::TableSize {
+            op_type: OpType::ExplicitPredicate,
+            expected: 4,
+            found: 2
+        }
+    );
+    assert_eq!(circ, original);
+}
@@ -0,0 +1,233 @@
+//! Tests for the command dependency graph.
+use rstest::rstest;
+use serde_json::{json, Value};
+use tket_json_rs::opbox::{ResourceBounds, ResourceData};
+use tket_json_rs::register::ElementId;
+use tket_json_rs::{OpType, SerialCircuit};
+
+fn element(name: &str, index: i64) -> ElementId {
+    ElementId(name.to_string(), vec![index])
+}
+
+fn id(name: &str, index: i64) -> Value {
+    json!([name, [index]])
+}
+
+/// A circuit on three qubits and two bits.
+///
+/// ```text
+/// 0: H q0
+/// 1: CX q0 q1
+/// 2: H q2
+/// 3: Measure q1 c0
+/// 4: if (c0 == 1) X q2
+/// 5: WASM c1 _w0
+/// 6: CX q0 q2
+/// ```
+fn circuit() -> SerialCircuit {
+    let x = json!({"type": "X"});
+    let wasm = json!({
+        "func_name": "f", "ww_n": 1, "n": 1, "width_i_parameter": [1], "width_o_parameter": [],
+        "wasm_file_uid": "uid",
+    });
+    serde_json::from_value(json!({
+        "phase": "0",
+        "qubits": [id("q", 0), id("q", 1), id("q", 2)],
+        "bits": [id("c", 0), id("c", 1)],
+        "implicit_permutation": [],
+        "number_of_ws": 1,
+        "commands": [
+            {"op": {"type": "H"}, "args": [id("q", 0)]},
+            {"op": {"type": "CX"}, "args": [id("q", 0), id("q", 1)]},
+            {"op": {"type": "H"}, "args": [id("q", 2)]},
+            {"op": {"type": "Measure"}, "args": [id("q", 1), id("c", 0)]},
+            {
+                "op": {"type": "Conditional", "conditional": {"op": x, "width": 1, "value": 1}},
+                "args": [id("c", 0), id("q", 2)],
+            },
+            {"op": {"type": "WASM", "wasm": wasm}, "args": [id("c", 1), id("_w", 0)]},
+            {"op": {"type": "CX"}, "args": [id("q", 0), id("q", 2)]},
+        ],
+    }))
+    .unwrap()
+}
+
+#[test]
+fn wires() {
+    let circ = circuit();
+    let dag = circ.dag();
+    assert_eq!(dag.len(), 7);
+    let wires: Vec<String> = dag.wires().map(|id| id.to_string()).collect();
+    assert_eq!(wires, ["q[0]", "q[1]", "q[2]", "c[0]", "c[1]", "_w[0]"]);
+
+    let q2 = element("q", 2);
+    assert_eq!(dag.wire_commands(&q2).collect::<Vec<_>>(), [2, 4, 6]);
+    assert_eq!(dag.first(&q2), Some(2));
+    assert_eq!(dag.last(&element("q", 0)), Some(6));
+    assert_eq!(dag.first(&element("_w", 0)), Some(5));
+    assert_eq!(dag.first(&element("q", 7)), None);
+}
+
+#[rstest]
+#[case::hadamard(0, &[], &[("q[0]", 1)])]
+#[case::measure(3, &[("q[1]", 1)], &[("c[0]", 4)])]
+#[case::conditional(4, &[("c[0]", 3), ("q[2]", 2)], &[("q[2]", 6)])]
+#[case::wasm(5, &[], &[])]
+#[case::last(6, &[("q[0]", 1), ("q[2]", 4)], &[])]
+fn neighbours(
+    #[case] command: usize,
+    #[case] predecessors: &[(&str, usize)],
+    #[case] successors: &[(&str, usize)],
+) {
+    let circ = circuit();
+    let dag = circ.dag();
+    let show = |(wire, c): (&ElementId, usize)| (wire.to_string(), c);
+    let expected = |pairs: &[(&str, usize)]| -> Vec<(String, usize)> {
+        pairs.iter().map(|&(w, c)| (w.to_string(), c)).collect()
+    };
+    assert_eq!(
+        dag.predecessors(command).map(show).collect::<Vec<_>>(),
+        expected(predecessors)
+    );
+    assert_eq!(
+        dag.successors(command).map(show).collect::<Vec<_>>(),
+        expected(successors)
+    );
+}
+
+#[test]
+fn per_wire_neighbours() {
+    let circ = circuit();
+    let dag = circ.dag();
+    let c0 = element("c", 0);
+    assert_eq!(dag.predecessor(4, &c0), Some(3));
+    assert_eq!(dag.successor(3, &c0), Some(4));
+    assert_eq!(dag.successor(4, &c0), None);
+    // Command 0 does not act on c[0].
+    assert_eq!(dag.successor(0, &c0), None);
+}
+
+#[test]
+fn layers_and_order() {
+    let circ = circuit();
+    let dag = circ.dag();
+    assert_eq!(dag.front_layer(), [0, 2, 5]);
+    assert_eq!(dag.back_layer(), [5, 6]);
+    assert_eq!(dag.topological().collect::<Vec<_>>(), [0, 2, 5, 1, 3, 4, 6]);
+}
+
+#[test]
+fn topological_respects_edges() {
+    let json = include_str!("data/rng.json");
+    let circ: SerialCircuit = serde_json::from_str(json).unwrap();
+    let dag = circ.dag();
+    let order: Vec<usize> = dag.topological().collect();
+    assert_eq!(order.len(), circ.commands.len());
+    let mut position = vec![0; order.len()];
+    for (p, &c) in order.iter().enumerate() {
+        position[c] = p;
+    }
+    for c in 0..dag.len() {
+        for (_, s) in dag.successors(c) {
+            assert!(position[c] < position[s]);
+        }
+    }
+}
+
+#[test]
+fn depth() {
+    let circ = circuit();
+    assert_eq!(circ.depth(), 5);
+    assert_eq!(circ.two_qubit_depth(), 2);
+    assert_eq!(circ.depth_by_type(&[OpType::H]), 1);
+    assert_eq!(circ.depth_by_type(&[OpType::CX, OpType::Measure]), 3);
+    assert_eq!(circ.depth_by_type(&[]), 0);
+    assert_eq!(circ.dag().critical_path(), [0, 1, 3, 4, 6]);
+
+    let layers: Vec<Vec<usize>> = circ.dag().layers().collect();
+    assert_eq!(layers, [vec![0, 2, 5], vec![1], vec![3], vec![4], vec![6]]);
+    let types: Vec<Vec<OpType>> = circ
+        .layers()
+        .map(|layer| layer.iter().map(|c| c.op.op_type).collect())
+        .collect();
+    assert_eq!(types[0], [OpType::H, OpType::H, OpType::WASM]);
+}
+
+#[test]
+fn depth_ignores_barriers() {
+    let circ: SerialCircuit = serde_json::from_value(json!({
+        "phase": "0",
+        "qubits": [id("q", 0), id("q", 1)],
+        "bits": [],
+        "implicit_permutation": [],
+        "commands": [
+            {"op": {"type": "H"}, "args": [id("q", 0)]},
+            {"op": {"type": "Barrier"}, "args": [id("q", 0), id("q", 1)]},
+            {"op": {"type": "CZ"}, "args": [id("q", 0), id("q", 1)]},
+        ],
+    }))
+    .unwrap();
+    assert_eq!(circ.depth(), 2);
+    assert_eq!(circ.two_qubit_depth(), 1);
+    assert_eq!(circ.depth_by_type(&[OpType::Barrier]), 1);
+    assert_eq!(circ.dag().critical_path(), [0, 2]);
+    assert_eq!(circ.layers().count(), 3);
+
+    let empty = SerialCircuit::<String>::new(None, "0".to_string());
+    assert_eq!(empty.depth(), 0);
+    assert_eq!(empty.layers().count(), 0);
+    assert!(empty.dag().critical_path().is_empty());
+}
+
+#[test]
+fn resources() {
+    let bounds = |min: u32, max: u32| json!({"min": min, "max": max});
+    let dummy = json!({
+        "type": "DummyBox",
+        "box": {
+            "type": "DummyBox",
+            "id": "8a6c1e1e-5c1b-4d43-8f6d-2a4d7c1f0b6e",
+            "n_qubits": 2,
+            "n_bits": 0,
+            "resource_data": {
+                "op_type_count": {"CX": bounds(1, 3), "H": bounds(0, 2)},
+                "gate_depth": bounds(2, 5),
+                "op_type_depth": {"CX": bounds(1, 3)},
+                "two_qubit_gate_depth": bounds(1, 3),
+            },
+        },
+    });
+    let circ: SerialCircuit = serde_json::from_value(json!({
+        "phase": "0",
+        "qubits": [id("q", 0), id("q", 1)],
+        "bits": [],
+        "implicit_permutation": [],
+        "commands": [
+            {"op": {"type": "H"}, "args": [id("q", 0)]},
+            {"op": dummy, "args": [id("q", 0), id("q", 1)]},
+            {"op": {"type": "CX"}, "args": [id("q", 0), id("q", 1)]},
+            {"op": {"type": "Barrier"}, "args": [id("q", 0), id("q", 1)]},
+            {"op": {"type": "H"}, "args": [id("q", 1)]},
+        ],
+    }))
+    .unwrap();
+
+    let resources = circ.resources();
+    let expected: ResourceData = serde_json::from_value(json!({
+        "op_type_count": {"H": bounds(2, 4), "CX": bounds(2, 4), "Barrier": bounds(1, 1)},
+        "gate_depth": bounds(5, 8),
+        "op_type_depth": {"H": bounds(2, 2), "CX": bounds(2, 4), "Barrier": bounds(1, 1)},
+        "two_qubit_gate_depth": bounds(2, 4),
+    }))
+    .unwrap();
+    assert_eq!(resources, expected);
+
+    // Without the box, the bounds are exact.
+    let mut concrete = circ.clone();
+    concrete.commands.remove(1);
+    let resources = concrete.resources();
+    let exact = |n: u32| ResourceBounds { min: n, max: n };
+    assert_eq!(resources.gate_depth, exact(concrete.depth() as u32));
+    assert_eq!(resources.two_qubit_gate_depth, exact(1));
+    assert_eq!(resources.op_type_count[&OpType::H], exact(2));
+}
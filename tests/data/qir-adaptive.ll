@@ -0,0 +1,51 @@
+%Qubit = type opaque
+%Result = type opaque
+
+@0 = internal constant [5 x i8] c"c[0]\00"
+@1 = internal constant [5 x i8] c"c[1]\00"
+
+define void @main() #0 {
+entry:
+  call void @__quantum__rt__initialize(i8* null)
+  call void @__quantum__qis__h__body(%Qubit* null)
+  call void @__quantum__qis__mz__body(%Qubit* null, %Result* null)
+  %0 = call i1 @__quantum__qis__read_result__body(%Result* null)
+  br i1 %0, label %then0, label %continue0
+then0:
+  call void @__quantum__qis__x__body(%Qubit* inttoptr (i64 1 to %Qubit*))
+  br label %continue0
+continue0:
+  call void @__quantum__qis__reset__body(%Qubit* null)
+  call void @__quantum__qis__mz__body(%Qubit* inttoptr (i64 1 to %Qubit*), %Result* inttoptr (i64 1 to %Result*))
+  %1 = call i1 @__quantum__qis__read_result__body(%Result* null)
+  br i1 %1, label %continue1, label %condition1_1
+condition1_1:
+  %2 = call i1 @__quantum__qis__read_result__body(%Result* inttoptr (i64 1 to %Result*))
+  br i1 %2, label %then1, label %continue1
+then1:
+  call void @__quantum__qis__ry__body(double 1.5707963267948966, %Qubit* inttoptr (i64 2 to %Qubit*))
+  br label %continue1
+continue1:
+  call void @__quantum__rt__result_record_output(%Result* null, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @0, i32 0, i32 0))
+  call void @__quantum__rt__result_record_output(%Result* inttoptr (i64 1 to %Result*), i8* getelementptr inbounds ([5 x i8], [5 x i8]* @1, i32 0, i32 0))
+  ret void
+}
+
+declare void @__quantum__rt__initialize(i8*)
+declare void @__quantum__qis__h__body(%Qubit*)
+declare void @__quantum__qis__mz__body(%Qubit*, %Result* writeonly) #1
+declare i1 @__quantum__qis__read_result__body(%Result*)
+declare void @__quantum__qis__x__body(%Qubit*)
+declare void @__quantum__qis__reset__body(%Qubit*) #1
+declare void @__quantum__qis__ry__body(double, %Qubit*)
+declare void @__quantum__rt__result_record_output(%Result*, i8*)
+
+attributes #0 = { "entry_point" "output_labeling_schema" "qir_profiles"="adaptive_profile" "required_num_qubits"="3" "required_num_results"="2" }
+attributes #1 = { "irreversible" }
+
+!llvm.module.flags = !{!0, !1, !2, !3}
+
+!0 = !{i32 1, !"qir_major_version", i32 1}
+!1 = !{i32 7, !"qir_minor_version", i32 0}
+!2 = !{i32 1, !"dynamic_qubit_management", i1 false}
+!3 = !{i32 1, !"dynamic_result_management", i1 false}
@@ -0,0 +1,285 @@
+//! Tests for parsing and printing symbolic parameter expressions
+use std::collections::{BTreeSet, HashMap};
+
+use rstest::rstest;
+use serde_json::json;
+use tket_json_rs::expr::{EvaluateParamsError, Expr, Function, InvalidParamError};
+use tket_json_rs::opbox::OpBox;
+use tket_json_rs::SerialCircuit;
+
+const SIMPLE: &str = include_str!("data/simple.json");
+
+#[rstest]
+#[case::float("0.5", "0.5")]
+#[case::integer("2", "2")]
+#[case::rational("1/3", "1/3")]
+#[case::negative_rational("-1/3", "-1/3")]
+#[case::scientific("1e-7", "1e-7")]
+#[case::sum("0.5*a + 1/3", "0.5*a + 1/3")]
+#[case::spacing("a+b-c", "a + b - c")]
+#[case::right_assoc_sub("a - (b - c)", "a - (b - c)")]
+#[case::neg_pow("-a**2", "-a**2")]
+#[case::pow_neg_base("(-a)**2", "(-a)**2")]
+#[case::pow_right_assoc("a**b**c", "a**b**c")]
+#[case::caret("a^2", "a**2")]
+#[case::pi("pi/2", "pi/2")]
+#[case::functions("sin(pi*theta) + atan2(y, x)", "sin(pi*theta) + atan2(y, x)")]
+#[case::neg_product("-(a*b)", "-(a*b)")]
+#[case::nested("2*(a + b)/sqrt(2)", "2*(a + b)/sqrt(2)")]
+#[case::big_integer("9223372036854775808", "9.223372036854776e18")]
+#[case::overflowing_float("1e400", "oo")]
+#[case::infinity("2*oo", "2*oo")]
+#[case::negative_infinity("-oo", "-oo")]
+#[case::infinity_power("2**-oo", "2**-oo")]
+fn roundtrip(#[case] input: &str, #[case] printed: &str) {
+    let expr: Expr = input.parse().unwrap();
+    assert_eq!(expr.to_string(), printed);
+    let reparsed: Expr = printed.parse().unwrap();
+    assert_eq!(reparsed, expr);
+}
+
+#[rstest]
+#[case::nan("nan")]
+#[case::complex_infinity("zoo")]
+fn parse_nan(#[case] input: &str) {
+    let expr: Expr = input.parse().unwrap();
+    assert!(matches!(expr, Expr::Float(x) if x.is_nan()));
+    assert_eq!(expr.to_string(), "nan");
+    assert!(expr.free_symbols().is_empty());
+}
+
+#[test]
+fn ast_shape() {
+    let expr: Expr = "-2**x + cos(a_1)".parse().unwrap();
+    assert_eq!(
+        expr,
+        Expr::Add(
+            Box::new(Expr::Neg(Box::new(Expr::Pow(
+                Box::new(Expr::Integer(2)),
+                Box::new(Expr::symbol("x"))
+            )))),
+            Box::new(Expr::Func(Function::Cos, vec![Expr::symbol("a_1")])),
+        )
+    );
+}
+
+#[rstest]
+#[case::trailing("a +", 3)]
+#[case::unknown_function("foo(a)", 0)]
+#[case::bad_char("a $ b", 2)]
+#[case::unbalanced("(a + b", 6)]
+#[case::arity("atan2(a)", 0)]
+fn parse_errors(#[case] input: &str, #[case] position: usize) {
+    let err = input.parse::<Expr>().unwrap_err();
+    assert_eq!(err.position, position, "{err}");
+}
+
+#[test]
+fn circuit_params() {
+    let circ: SerialCircuit = serde_json::from_str(SIMPLE).unwrap();
+    let parsed: SerialCircuit<Expr> = circ.clone().map_params(|p| p.parse().unwrap());
+    assert_eq!(parsed.phase, Expr::Float(0.0));
+
+    let json = serde_json::to_value(&parsed).unwrap();
+    assert_eq!(json, serde_json::to_value(&circ).unwrap());
+    let reparsed: SerialCircuit<Expr> = serde_json::from_value(json).unwrap();
+    assert_eq!(reparsed, parsed);
+}
+
+#[rstest]
+#[case::rational("0.5*a + 1/3", 0.5 * 2.0 + 1.0 / 3.0)]
+#[case::pi("pi/2", std::f64::consts::FRAC_PI_2)]
+#[case::pow("-2**a", -4.0)]
+#[case::functions("sqrt(a**2)*cos(0)", 2.0)]
+fn evaluate(#[case] input: &str, #[case] value: f64) {
+    let bindings = HashMap::from([("a".to_string(), 2.0)]);
+    let expr: Expr = input.parse().unwrap();
+    assert!((expr.evaluate(&bindings).unwrap() - value).abs() < 1e-12);
+}
+
+/// A circuit with symbolic parameters at the top level and inside boxes.
+fn symbolic_circuit() -> SerialCircuit {
+    serde_json::from_value(json!({
+        "phase": "a/2",
+        "qubits": [["q", [0]], ["q", [1]]],
+        "bits": [],
+        "implicit_permutation": [[["q", [0]], ["q", [0]]], [["q", [1]], ["q", [1]]]],
+        "commands": [
+            {"args": [["q", [0]]], "op": {"type": "Rz", "params": ["a + 1"]}},
+            {
+                "args": [["q", [0]], ["q", [1]]],
+                "op": {"type": "PauliExpBox", "box": {
+                    "type": "PauliExpBox",
+                    "id": "0dadbfea-5391-41c2-ad71-fb01fdaecddd",
+                    "paulis": ["X", "Z"],
+                    "phase": "2*b"
+                }}
+            },
+            {
+                "args": [["q", [1]]],
+                "op": {"type": "CustomGate", "box": {
+                    "type": "CustomGate",
+                    "id": "919b6c13-b9c4-4ffc-ad1a-660088402d02",
+                    "gate": {
+                        "name": "g",
+                        "args": ["t"],
+                        "definition": {
+                            "phase": "0.0",
+                            "qubits": [["q", [0]]],
+                            "bits": [],
+                            "implicit_permutation": [[["q", [0]], ["q", [0]]]],
+                            "commands": [{"args": [["q", [0]]], "op": {"type": "Rx", "params": ["t"]}}]
+                        }
+                    },
+                    "params": ["b - a"]
+                }}
+            }
+        ]
+    }))
+    .unwrap()
+}
+
+#[test]
+fn evaluate_circuit_params() {
+    let circ = symbolic_circuit();
+    let bindings = HashMap::from([("a".to_string(), 0.5), ("b".to_string(), 0.25)]);
+    let evaluated = circ.evaluate_params(&bindings).unwrap();
+
+    assert_eq!(evaluated.phase, 0.25);
+    assert_eq!(evaluated.commands[0].op.params, Some(vec![1.5]));
+    let Some(OpBox::PauliExpBox { phase, .. }) = &evaluated.commands[1].op.op_box else {
+        panic!("expected a PauliExpBox");
+    };
+    assert_eq!(phase, "0.5");
+    let Some(OpBox::CustomGate { gate, params, .. }) = &evaluated.commands[2].op.op_box else {
+        panic!("expected a CustomGate");
+    };
+    assert_eq!(params, &vec!["-0.25".to_string()]);
+    // The gate definition depends on its own argument, and is left untouched.
+    assert_eq!(
+        gate.definition.commands[0].op.params,
+        Some(vec!["t".to_string()])
+    );
+}
+
+#[test]
+fn evaluate_unbound_symbol() {
+    let circ = symbolic_circuit();
+    let bindings = HashMap::from([("a".to_string(), 0.5)]);
+    let err = circ.evaluate_params(&bindings).unwrap_err();
+    assert_eq!(
+        err,
+        EvaluateParamsError::UnboundSymbol {
+            path: "/commands/1/op/box/phase".to_string(),
+            symbol: "b".to_string(),
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "/commands/1/op/box/phase: unbound symbol `b`"
+    );
+}
+
+#[test]
+fn evaluate_gate_definition_params() {
+    let mut circ = symbolic_circuit();
+    let Some(OpBox::CustomGate { gate, .. }) = &mut circ.commands[2].op.op_box else {
+        panic!("expected a CustomGate");
+    };
+    gate.definition.commands[0].op.params = Some(vec!["t + b".to_string()]);
+
+    let bindings = HashMap::from([("a".to_string(), 0.5), ("b".to_string(), 0.25)]);
+    let evaluated = circ.evaluate_params(&bindings).unwrap();
+    let Some(OpBox::CustomGate { gate, .. }) = &evaluated.commands[2].op.op_box else {
+        panic!("expected a CustomGate");
+    };
+    assert_eq!(
+        gate.definition.commands[0].op.params,
+        Some(vec!["t + 0.25".to_string()])
+    );
+
+    // `b` is neither bound nor an argument of the gate.
+    circ.commands.drain(..2);
+    let Some(OpBox::CustomGate { params, .. }) = &mut circ.commands[0].op.op_box else {
+        panic!("expected a CustomGate");
+    };
+    params[0] = "a".to_string();
+    let err = circ
+        .evaluate_params(&HashMap::from([("a".to_string(), 0.5)]))
+        .unwrap_err();
+    assert_eq!(
+        err,
+        EvaluateParamsError::UnboundSymbol {
+            path: "/commands/0/op/box/gate/definition/commands/0/op/params/0".to_string(),
+            symbol: "b".to_string(),
+        }
+    );
+}
+
+#[test]
+fn substitute() {
+    let expr: Expr = "2*a + sin(b)".parse().unwrap();
+    let map = HashMap::from([
+        ("a".to_string(), "x - 1".parse().unwrap()),
+        ("b".to_string(), Expr::from(0.5)),
+    ]);
+    assert_eq!(expr.substitute(&map).to_string(), "2*(x - 1) + sin(0.5)");
+}
+
+#[test]
+fn circuit_symbol_substitution() {
+    let mut circ = symbolic_circuit();
+    let map = HashMap::from([
+        ("a".to_string(), Expr::symbol("c")),
+        ("b".to_string(), Expr::from(0.25)),
+        ("t".to_string(), Expr::from(1.0)),
+    ]);
+    circ.symbol_substitution(&map).unwrap();
+
+    assert_eq!(circ.phase, "c/2");
+    assert_eq!(circ.commands[0].op.params, Some(vec!["c + 1".to_string()]));
+    let Some(OpBox::PauliExpBox { phase, .. }) = &circ.commands[1].op.op_box else {
+        panic!("expected a PauliExpBox");
+    };
+    assert_eq!(phase, "2*0.25");
+    let Some(OpBox::CustomGate { gate, params, .. }) = &circ.commands[2].op.op_box else {
+        panic!("expected a CustomGate");
+    };
+    assert_eq!(params, &vec!["0.25 - c".to_string()]);
+    // The gate's own argument shadows the substituted symbol.
+    assert_eq!(
+        gate.definition.commands[0].op.params,
+        Some(vec!["t".to_string()])
+    );
+}
+
+#[test]
+fn circuit_symbol_substitution_invalid() {
+    let mut circ = symbolic_circuit();
+    circ.commands[0].op.params = Some(vec!["a +".to_string()]);
+    let original = circ.clone();
+    let map = HashMap::from([("a".to_string(), Expr::from(1))]);
+    let err: InvalidParamError = circ.symbol_substitution(&map).unwrap_err();
+    assert_eq!(err.path, "/commands/0/op/params/0");
+    assert_eq!(circ, original);
+}
+
+#[test]
+fn free_symbols() {
+    let expr: Expr = "sin(a)*b**E + pi".parse().unwrap();
+    assert_eq!(
+        expr.free_symbols(),
+        BTreeSet::from(["a".to_string(), "b".to_string()])
+    );
+
+    // `t` is bound by the custom gate definition.
+    let mut circ = symbolic_circuit();
+    assert_eq!(
+        circ.free_symbols(),
+        Ok(BTreeSet::from(["a".to_string(), "b".to_string()]))
+    );
+
+    circ.commands[0].op.params = Some(vec!["a +".to_string()]);
+    let err = circ.free_symbols().unwrap_err();
+    assert_eq!(err.path, "/commands/0/op/params/0");
+}
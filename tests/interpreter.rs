@@ -0,0 +1,225 @@
+//! Tests for the classical interpreter.
+use std::collections::HashMap;
+
+use rstest::rstest;
+use serde_json::{json, Value};
+use tket_json_rs::circuit_json::Operation;
+use tket_json_rs::clexpr::ClExpr;
+use tket_json_rs::interpreter::{ClassicalOnly, InterpretError, QuantumOpError};
+use tket_json_rs::register::{Bit, ElementId};
+use tket_json_rs::{OpType, SerialCircuit};
+
+fn element(name: &str, index: i64) -> ElementId {
+    ElementId(name.to_string(), vec![index])
+}
+
+fn id(name: &str, index: i64) -> Value {
+    json!([name, [index]])
+}
+
+/// A circuit with the given commands, acting on the bits `c[0..8]`.
+fn circuit(commands: Value) -> SerialCircuit {
+    let bits: Vec<Value> = (0..8).map(|i| id("c", i)).collect();
+    serde_json::from_value(json!({
+        "phase": "0",
+        "qubits": [id("q", 0)],
+        "bits": bits,
+        "implicit_permutation": [],
+        "commands": commands,
+    }))
+    .unwrap()
+}
+
+/// Sets the bits `c[0..8]` to the binary digits of `value`.
+fn store(value: u64) -> HashMap<Bit, bool> {
+    (0..8)
+        .map(|i| {
+            (
+                Bit {
+                    id: element("c", i),
+                },
+                (value >> i) & 1 == 1,
+            )
+        })
+        .collect()
+}
+
+/// Reads back the value of the bits `c[0..8]`.
+fn load(bits: &HashMap<Bit, bool>) -> u64 {
+    (0..8).fold(0, |acc, i| {
+        let bit = bits[&Bit {
+            id: element("c", i),
+        }];
+        acc | (bit as u64) << i
+    })
+}
+
+fn run(commands: Value, input: u64) -> u64 {
+    let mut bits = store(input);
+    circuit(commands)
+        .run_classical(&mut bits, &mut ClassicalOnly)
+        .unwrap();
+    load(&bits)
+}
+
+#[rstest]
+#[case::set_bits(
+    json!({"type": "SetBits", "classical": {"values": [true, false, true]}}),
+    3,
+    |x: u64| x & !7 | 0b101
+)]
+#[case::copy_bits(
+    json!({"type": "CopyBits", "classical": {"n_i": 2}}),
+    4,
+    |x: u64| x & !15 | x & 3 | (x & 3) << 2
+)]
+#[case::range_predicate(
+    json!({"type": "RangePredicate", "classical": {"n_i": 3, "lower": 2, "upper": 5}}),
+    4,
+    |x: u64| x & !8 | ((2..=5).contains(&(x & 7)) as u64) << 3
+)]
+#[case::explicit_modifier(
+    json!({
+        "type": "ExplicitModifier",
+        "classical": {"n_i": 1, "name": "XorWith", "values": [false, true, true, false]},
+    }),
+    2,
+    |x: u64| x ^ (x & 1) << 1
+)]
+#[case::classical_transform(
+    json!({
+        "type": "ClassicalTransform",
+        "classical": {"n_io": 2, "name": "ClassicalCX", "values": [0, 3, 2, 1]},
+    }),
+    2,
+    |x: u64| x & !3 | [0, 3, 2, 1][(x & 3) as usize]
+)]
+#[case::wide_classical_transform(
+    json!({
+        "type": "ClassicalTransform",
+        "classical": {
+            "n_io": 5,
+            "name": "Affine",
+            "values": (0..32).map(|x| (7 * x + 1) % 32).collect::<Vec<u32>>(),
+        },
+    }),
+    5,
+    |x: u64| x & !31 | ((7 * (x & 31) + 1) % 32)
+)]
+#[case::multi_bit(
+    json!({
+        "type": "MultiBit",
+        "classical": {
+            "op": {
+                "type": "ExplicitPredicate",
+                "classical": {"n_i": 2, "name": "AND", "values": [false, false, false, true]},
+            },
+            "n": 2,
+        },
+    }),
+    6,
+    |x: u64| x & !0b110000 | (x & x >> 1 & 1) << 4 | (x >> 2 & x >> 3 & 1) << 5
+)]
+#[case::multi_bit_modifier(
+    json!({
+        "type": "MultiBit",
+        "classical": {
+            "op": {
+                "type": "ExplicitModifier",
+                "classical": {"n_i": 1, "name": "XorWith", "values": [false, true, true, false]},
+            },
+            "n": 2,
+        },
+    }),
+    4,
+    |x: u64| x ^ (x & 3) << 2
+)]
+#[case::clexpr(
+    json!({
+        "type": "ClExpr",
+        "expr": "out[4..8] = r0 + r1 where r0 = [0, 1], r1 = [2, 3]".parse::<ClExpr>().unwrap(),
+    }),
+    8,
+    |x: u64| x & 15 | ((x + (x >> 2)) & 3) << 4
+)]
+fn classical_ops(#[case] op: Value, #[case] n_args: i64, #[case] expected: fn(u64) -> u64) {
+    let args: Vec<Value> = (0..n_args).map(|i| id("c", i)).collect();
+    let commands = json!([{"op": op, "args": args}]);
+    for x in 0..1 << 8 {
+        assert_eq!(run(commands.clone(), x), expected(x), "input {x:#b}");
+    }
+}
+
+#[rstest]
+#[case::taken(0b10, 0b11110010)]
+#[case::not_taken(0b01, 0b00000001)]
+fn conditional(#[case] input: u64, #[case] output: u64) {
+    // Sets c[4..8] if c[0..2] == 2.
+    let set = json!({"type": "SetBits", "classical": {"values": [true, true, true, true]}});
+    let args: Vec<Value> = (0..2).chain(4..8).map(|i| id("c", i)).collect();
+    let commands = json!([{
+        "op": {"type": "Conditional", "conditional": {"op": set, "width": 2, "value": 2}},
+        "args": args,
+    }]);
+    assert_eq!(run(commands, input), output);
+}
+
+#[test]
+fn quantum_handler() {
+    // The handler measures 1, and the outcome is then copied classically.
+    let commands = json!([
+        {"op": {"type": "H"}, "args": [id("q", 0)]},
+        {"op": {"type": "Barrier"}, "args": [id("q", 0)]},
+        {"op": {"type": "Measure"}, "args": [id("q", 0), id("c", 0)]},
+        {"op": {"type": "CopyBits", "classical": {"n_i": 1}}, "args": [id("c", 0), id("c", 1)]},
+    ]);
+    let circ = circuit(commands);
+    let mut seen = Vec::new();
+    let mut handler = |op: &Operation, args: &[ElementId], bits: &mut HashMap<Bit, bool>| {
+        seen.push(op.op_type);
+        if op.op_type == OpType::Measure {
+            bits.insert(
+                Bit {
+                    id: args[1].clone(),
+                },
+                true,
+            );
+        }
+        Ok::<_, QuantumOpError>(())
+    };
+    let mut bits = HashMap::new();
+    circ.run_classical(&mut bits, &mut handler).unwrap();
+    assert_eq!(seen, [OpType::H, OpType::Measure]);
+    assert_eq!(load(&store(0).into_iter().chain(bits).collect()), 0b11);
+
+    let error = circ
+        .run_classical(&mut HashMap::new(), &mut ClassicalOnly)
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        InterpretError::Quantum { path, source } if path == "/commands/0/op" && source.op_type == OpType::H
+    ));
+}
+
+#[test]
+fn unsupported_operation() {
+    let wasm = json!({"type": "WASM", "wasm": {
+        "func_name": "f", "ww_n": 1, "n": 0, "width_i_parameter": [], "width_o_parameter": [],
+        "wasm_file_uid": "uid",
+    }});
+    let commands = json!([{
+        "op": {"type": "Conditional", "conditional": {"op": wasm, "width": 1, "value": 1}},
+        "args": [id("c", 0), id("_w", 0)],
+    }]);
+    let circ = circuit(commands);
+    assert!(circ
+        .run_classical(&mut store(0), &mut ClassicalOnly)
+        .is_ok());
+    let error = circ
+        .run_classical(&mut store(1), &mut ClassicalOnly)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "/commands/0/op/conditional/op: cannot interpret WASM operations"
+    );
+}
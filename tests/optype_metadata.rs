@@ -0,0 +1,36 @@
+//! Tests for the static `OpType` metadata
+use rstest::rstest;
+use tket_json_rs::optype::metadata::Arity;
+use tket_json_rs::OpType;
+
+#[rstest]
+#[case::cx(OpType::CX, Arity::Fixed(2), Arity::Fixed(0), 0)]
+#[case::tk2(OpType::TK2, Arity::Fixed(2), Arity::Fixed(0), 3)]
+#[case::cnry(OpType::CnRy, Arity::Variadic, Arity::Fixed(0), 1)]
+#[case::measure(OpType::Measure, Arity::Fixed(1), Arity::Fixed(1), 0)]
+#[case::phase(OpType::Phase, Arity::Fixed(0), Arity::Fixed(0), 1)]
+#[case::barrier(OpType::Barrier, Arity::Variadic, Arity::Variadic, 0)]
+#[case::rng_seed(OpType::RNGSeed, Arity::Fixed(0), Arity::Fixed(64), 0)]
+fn arity(#[case] op_type: OpType, #[case] qubits: Arity, #[case] bits: Arity, #[case] params: u32) {
+    let meta = op_type.metadata();
+    assert_eq!(meta.qubits, qubits);
+    assert_eq!(meta.bits, bits);
+    assert_eq!(meta.params, params);
+}
+
+#[test]
+fn flags() {
+    assert!(OpType::H.is_gate());
+    assert!(OpType::Measure.is_gate());
+    assert!(!OpType::CircBox.is_gate());
+    assert!(OpType::CircBox.is_box());
+    assert!(OpType::ClassicalExpBox.is_box() && OpType::ClassicalExpBox.is_classical());
+    assert!(OpType::ClExpr.is_classical());
+    assert!(OpType::Branch.is_flow());
+    assert!(OpType::Input.is_meta() && OpType::Barrier.is_meta());
+    assert!(!OpType::Conditional.is_gate() && !OpType::Conditional.is_box());
+
+    let wasm = OpType::WASM.metadata();
+    assert_eq!(wasm.wasm_wires, Arity::Variadic);
+    assert_eq!(OpType::RNGNum.metadata().rng_wires, Arity::Fixed(1));
+}
@@ -0,0 +1,406 @@
+//! Tests for the OpenQASM 2.0 conversion.
+use std::collections::HashMap;
+
+use rstest::rstest;
+use serde_json::{json, Value};
+use tket_json_rs::opbox::OpBox;
+use tket_json_rs::qasm2::export::ExportErrorKind;
+use tket_json_rs::unitary::equivalent;
+use tket_json_rs::{OpType, SerialCircuit};
+
+const QASM: &str = include_str!("data/qasm.json");
+
+fn id(name: &str, index: i64) -> Value {
+    json!([name, [index]])
+}
+
+fn op(op_type: &str, params: &[&str]) -> Value {
+    if params.is_empty() {
+        json!({"type": op_type})
+    } else {
+        json!({"type": op_type, "params": params})
+    }
+}
+
+/// A circuit on the qubits `q[0..3]` and the bits `c[0..2]`.
+fn circuit(commands: Value) -> SerialCircuit {
+    serde_json::from_value(json!({
+        "phase": "0.5",
+        "qubits": [id("q", 0), id("q", 1), id("q", 2)],
+        "bits": [id("c", 0), id("c", 1)],
+        "implicit_permutation": [],
+        "commands": commands,
+    }))
+    .unwrap()
+}
+
+fn conditional(inner: Value, width: u32, value: u32) -> Value {
+    json!({"type": "Conditional", "conditional": {"op": inner, "width": width, "value": value}})
+}
+
+#[test]
+fn export_gates() {
+    let circ = circuit(json!([
+        {"op": op("H", &[]), "args": [id("q", 0)]},
+        {"op": op("CX", &[]), "args": [id("q", 0), id("q", 1)]},
+        {"op": op("Rz", &["0.5"]), "args": [id("q", 1)]},
+        {"op": op("U3", &["1", "-0.25", "1/3 + 2"]), "args": [id("q", 2)]},
+        {"op": op("CnX", &[]), "args": [id("q", 0), id("q", 1), id("q", 2)]},
+        {"op": op("Barrier", &[]), "args": [id("q", 0), id("q", 1), id("c", 0)]},
+        {"op": op("Measure", &[]), "args": [id("q", 0), id("c", 0)]},
+        {"op": conditional(op("X", &[]), 2, 1), "args": [id("c", 0), id("c", 1), id("q", 1)]},
+        {"op": op("Reset", &[]), "args": [id("q", 0)]},
+    ]));
+    let expected = r#"OPENQASM 2.0;
+include "qelib1.inc";
+
+qreg q[3];
+creg c[2];
+h q[0];
+cx q[0],q[1];
+rz(0.5*pi) q[1];
+u3(1*pi,-0.25*pi,(1/3 + 2)*pi) q[2];
+ccx q[0],q[1],q[2];
+barrier q[0],q[1];
+measure q[0] -> c[0];
+if(c==1) x q[1];
+reset q[0];
+"#;
+    assert_eq!(circ.to_qasm2().unwrap(), expected);
+}
+
+#[test]
+fn export_boxes() {
+    let definition = json!({
+        "phase": "0",
+        "qubits": [id("a", 0), id("a", 1)],
+        "bits": [],
+        "implicit_permutation": [],
+        "commands": [
+            {"op": op("Rz", &["t"]), "args": [id("a", 1)]},
+            {"op": op("CZ", &[]), "args": [id("a", 1), id("a", 0)]},
+        ],
+    });
+    let custom = json!({
+        "type": "CustomGate",
+        "box": {
+            "type": "CustomGate",
+            "id": "3c5e0f6a-2f0b-4a8e-9d0e-7a1b2c3d4e5f",
+            "gate": {"name": "my_gate", "args": ["t"], "definition": definition},
+            "params": ["0.25"],
+        },
+    });
+    let inner = json!({
+        "phase": "0",
+        "qubits": [id("r", 0)],
+        "bits": [id("m", 0)],
+        "implicit_permutation": [],
+        "commands": [
+            {"op": op("H", &[]), "args": [id("r", 0)]},
+            {"op": op("Measure", &[]), "args": [id("r", 0), id("m", 0)]},
+        ],
+    });
+    let circ_box = json!({
+        "type": "CircBox",
+        "box": {"type": "CircBox", "id": "3c5e0f6a-2f0b-4a8e-9d0e-7a1b2c3d4e60", "circuit": inner},
+    });
+    let circ = circuit(json!([
+        {"op": custom, "args": [id("q", 0), id("q", 2)]},
+        {"op": circ_box, "args": [id("q", 1), id("c", 1)]},
+        {"op": custom, "args": [id("q", 2), id("q", 1)]},
+    ]));
+    let expected = r#"OPENQASM 2.0;
+include "qelib1.inc";
+
+gate my_gate(t) q0,q1 {
+  rz(t) q1;
+  cz q1,q0;
+}
+qreg q[3];
+creg c[2];
+my_gate(0.25*pi) q[0],q[2];
+h q[1];
+measure q[1] -> c[1];
+my_gate(0.25*pi) q[2],q[1];
+"#;
+    assert_eq!(circ.to_qasm2().unwrap(), expected);
+}
+
+#[rstest]
+#[case::partial_condition(
+    json!([{"op": conditional(op("X", &[]), 1, 1), "args": [id("c", 0), id("q", 0)]}]),
+    "/commands/0/op",
+    ExportErrorKind::UnsupportedCondition
+)]
+#[case::free_symbol(
+    json!([{"op": op("Rz", &["a"]), "args": [id("q", 0)]}]),
+    "/commands/0/op/params/0",
+    ExportErrorKind::UnsupportedParam("a".to_string())
+)]
+#[case::classical(
+    json!([{"op": {"type": "SetBits", "classical": {"values": [true]}}, "args": [id("c", 0)]}]),
+    "/commands/0/op",
+    ExportErrorKind::UnsupportedOperation(OpType::SetBits)
+)]
+#[case::param_count(
+    json!([{"op": op("Rx", &[]), "args": [id("q", 0)]}]),
+    "/commands/0/op",
+    ExportErrorKind::ParamCount { op_type: OpType::Rx, expected: 1, found: 0 }
+)]
+#[case::arg_count(
+    json!([{"op": op("CX", &[]), "args": [id("q", 0)]}]),
+    "/commands/0",
+    ExportErrorKind::ArgCount { op_type: OpType::CX, expected: 2, found: 1 }
+)]
+fn export_errors(#[case] commands: Value, #[case] path: &str, #[case] kind: ExportErrorKind) {
+    let error = circuit(commands).to_qasm2().unwrap_err();
+    assert_eq!(error.path, path);
+    assert_eq!(error.kind, kind);
+}
+
+#[test]
+fn export_implicit_permutation() {
+    let mut circ = circuit(json!([
+        {"op": op("X", &[]), "args": [id("q", 0)]},
+        {"op": op("H", &[]), "args": [id("q", 1)]},
+    ]));
+    circ.implicit_permutation = serde_json::from_value(json!([
+        [id("q", 0), id("q", 1)],
+        [id("q", 1), id("q", 2)],
+        [id("q", 2), id("q", 0)],
+    ]))
+    .unwrap();
+    let qasm = circ.to_qasm2().unwrap();
+    assert!(qasm.ends_with("h q[1];\nswap q[0],q[2];\nswap q[1],q[2];\n"));
+
+    let bindings = HashMap::new();
+    let evaluated = circ.evaluate_params(&bindings).unwrap();
+    let imported = SerialCircuit::from_qasm2(&qasm).unwrap();
+    let imported = imported.evaluate_params(&bindings).unwrap();
+    assert!(equivalent(&evaluated, &imported, 1e-10).unwrap());
+
+    circ.implicit_permutation.truncate(1);
+    let error = circ.to_qasm2().unwrap_err();
+    assert_eq!(error.path, "/implicit_permutation");
+    assert_eq!(error.kind, ExportErrorKind::InvalidPermutation);
+}
+
+#[test]
+fn export_invalid_registers() {
+    let mut circ = circuit(json!([]));
+    circ.bits[1].id.1 = vec![1, 0];
+    let error = circ.to_qasm2().unwrap_err();
+    assert_eq!(error.path, "/bits/1");
+    assert!(matches!(error.kind, ExportErrorKind::InvalidIndex(_)));
+
+    let mut circ = circuit(json!([]));
+    circ.bits[0].id.0 = "q".to_string();
+    let error = circ.to_qasm2().unwrap_err();
+    assert_eq!(error.kind, ExportErrorKind::RegisterClash("q".to_string()));
+}
+
+#[test]
+fn import_fixture() {
+    let source = r#"OPENQASM 2.0;
+include "qelib1.inc";
+qreg q[3];
+creg a[3];
+creg b[3];
+creg c[3];
+creg d[3];
+d = (((a + b) / 2) - c);
+
+h q[0];
+z q[2];
+cx q[2], q[1];
+"#;
+    let expected: SerialCircuit = serde_json::from_str(QASM).unwrap();
+    assert_eq!(SerialCircuit::from_qasm2(source).unwrap(), expected);
+}
+
+#[test]
+fn import_statements() {
+    let source = r#"OPENQASM 2.0;
+include "qelib1.inc";
+// Comments are ignored.
+qreg q[2];
+creg c[2];
+h q;
+cx q[0], q[1];
+rz(-pi/4) q[1];
+u3(pi, 0.5, 2*pi/3) q[0];
+barrier q;
+measure q -> c;
+if(c==3) x q[0];
+reset q[1];
+c[0] = c[1] ^ 1;
+"#;
+    let circ = SerialCircuit::from_qasm2(source).unwrap();
+    let commands: Vec<String> = circ
+        .commands
+        .iter()
+        .map(|command| {
+            let args: Vec<String> = command.args.iter().map(|arg| arg.to_string()).collect();
+            let params = command.op.params.clone().unwrap_or_default();
+            format!(
+                "{:?}({}) {}",
+                command.op.op_type,
+                params.join(", "),
+                args.join(" ")
+            )
+        })
+        .collect();
+    let expected = [
+        "H() q[0]",
+        "H() q[1]",
+        "CX() q[0] q[1]",
+        "Rz(-1/4) q[1]",
+        "U3(1, 0.15915494309189535, 2/3) q[0]",
+        "Barrier() q[0] q[1]",
+        "Measure() q[0] c[0]",
+        "Measure() q[1] c[1]",
+        "Conditional() c[0] c[1] q[0]",
+        "Reset() q[1]",
+        "ClExpr() c[1] c[0]",
+    ];
+    assert_eq!(commands, expected);
+    let conditional = circ.commands[8].op.conditional.as_ref().unwrap();
+    assert_eq!(
+        (conditional.op.op_type, conditional.width, conditional.value),
+        (OpType::X, 2, 3)
+    );
+}
+
+#[test]
+fn import_gate_definitions() {
+    let source = r#"OPENQASM 2.0;
+include "qelib1.inc";
+gate inner(t) a { rz(t/2) a; }
+gate outer(t) a, b { inner(2*t) b; cx a, b; }
+gate product(a, b) q { rz(a*b) q; rz(pi*a) q; }
+qreg q[2];
+outer(pi/2) q[1], q[0];
+product(pi, pi/2) q[0];
+"#;
+    let circ = SerialCircuit::from_qasm2(source).unwrap();
+    assert_eq!(circ.commands.len(), 2);
+    let Some(OpBox::CustomGate { gate, params, .. }) = &circ.commands[0].op.op_box else {
+        panic!("expected a custom gate");
+    };
+    assert_eq!(
+        (gate.name.as_str(), params.as_slice()),
+        ("outer", ["1/2".to_string()].as_slice())
+    );
+    assert_eq!(gate.args, ["t"]);
+    let definition = &gate.definition;
+    assert_eq!(definition.qubits.len(), 2);
+    assert_eq!(definition.commands[1].op.op_type, OpType::CX);
+    let Some(OpBox::CustomGate { gate, params, .. }) = &definition.commands[0].op.op_box else {
+        panic!("expected a custom gate");
+    };
+    assert_eq!(params, &["2*t"]);
+    assert_eq!(
+        gate.definition.commands[0].op.params,
+        Some(vec!["t/2".to_string()])
+    );
+    let Some(OpBox::CustomGate { gate, .. }) = &circ.commands[1].op.op_box else {
+        panic!("expected a custom gate");
+    };
+    let params: Vec<_> = gate
+        .definition
+        .commands
+        .iter()
+        .map(|command| command.op.params.clone().unwrap())
+        .collect();
+    assert_eq!(params, [["a*pi*(b*pi)/pi"], ["pi*a"]]);
+}
+
+#[test]
+fn import_roundtrip() {
+    let source = r#"OPENQASM 2.0;
+include "qelib1.inc";
+
+gate my_gate(t) q0,q1 {
+  rz(t) q1;
+  cz q1,q0;
+}
+qreg q[3];
+creg c[2];
+my_gate(0.25*pi) q[0],q[2];
+h q[1];
+rx(1/3*pi) q[2];
+ccx q[0],q[1],q[2];
+barrier q[0],q[1];
+measure q[1] -> c[1];
+if(c==1) x q[1];
+reset q[0];
+"#;
+    let circ = SerialCircuit::from_qasm2(source).unwrap();
+    assert_eq!(circ.to_qasm2().unwrap(), source);
+}
+
+#[rstest]
+#[case::version("OPENQASM 3.0;", 1, 10, "unsupported OpenQASM version '3.0'")]
+#[case::include(
+    "OPENQASM 2.0;\ninclude \"stdgates.inc\";",
+    2,
+    9,
+    "unsupported include file \"stdgates.inc\""
+)]
+#[case::no_include("OPENQASM 2.0;\nqreg q[1];\nh q[0];", 3, 1, "unknown gate 'h'")]
+#[case::unknown_register(
+    "OPENQASM 2.0;\nqreg q[1];\nU(0, 0, 0) r[0];",
+    3,
+    12,
+    "unknown register 'r'"
+)]
+#[case::index(
+    "OPENQASM 2.0;\nqreg q[1];\nU(0, 0, 0) q[1];",
+    3,
+    14,
+    "index 1 is out of range for 'q'"
+)]
+#[case::params(
+    "OPENQASM 2.0;\nqreg q[1];\nU(0) q[0];",
+    3,
+    1,
+    "'U' takes 3 parameters, but 1 were given"
+)]
+#[case::broadcast(
+    "OPENQASM 2.0;\nqreg q[1];\nqreg r[2];\nCX q, r;",
+    4,
+    7,
+    "'r' does not have 1 elements"
+)]
+#[case::repeated(
+    "OPENQASM 2.0;\nqreg q[1];\nCX q[0], q[0];",
+    3,
+    1,
+    "q[0] is repeated in the arguments"
+)]
+#[case::opaque("OPENQASM 2.0;\nopaque g q;", 2, 1, "opaque gates are not supported")]
+#[case::measure(
+    "OPENQASM 2.0;\nqreg q[1];\nmeasure q -> q;",
+    3,
+    14,
+    "'q' is not a classical register"
+)]
+#[case::symbol(
+    "OPENQASM 2.0;\nqreg q[1];\nU(t, 0, 0) q[0];",
+    3,
+    3,
+    "unknown parameter 't'"
+)]
+#[case::end("OPENQASM 2.0;\nqreg q[1]", 2, 10, "expected ';'")]
+fn import_errors(
+    #[case] source: &str,
+    #[case] line: usize,
+    #[case] column: usize,
+    #[case] message: &str,
+) {
+    let error = SerialCircuit::from_qasm2(source).unwrap_err();
+    assert_eq!(
+        (error.line, error.column, error.message.as_str()),
+        (line, column, message)
+    );
+}
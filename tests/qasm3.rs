@@ -0,0 +1,375 @@
+//! Tests for the OpenQASM 3 export.
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use rstest::rstest;
+use serde_json::{json, Value};
+use tket_json_rs::circuit_json::Operation;
+use tket_json_rs::qasm3::export::ExportErrorKind;
+use tket_json_rs::{OpType, SerialCircuit};
+
+const QASM: &str = include_str!("data/qasm.json");
+const RNG: &str = include_str!("data/rng.json");
+
+fn id(name: &str, index: i64) -> Value {
+    json!([name, [index]])
+}
+
+fn op(op_type: &str, params: &[&str]) -> Value {
+    if params.is_empty() {
+        json!({"type": op_type})
+    } else {
+        json!({"type": op_type, "params": params})
+    }
+}
+
+fn flow(op_type: &str, label: &str) -> Value {
+    json!({"type": op_type, "data": label})
+}
+
+/// A circuit on the qubits `q[0..4]` and the bits `c[0..2]`.
+fn circuit(commands: Value) -> SerialCircuit {
+    serde_json::from_value(json!({
+        "phase": "0",
+        "qubits": [id("q", 0), id("q", 1), id("q", 2), id("q", 3)],
+        "bits": [id("c", 0), id("c", 1)],
+        "implicit_permutation": [],
+        "commands": commands,
+    }))
+    .unwrap()
+}
+
+fn conditional(inner: Value, width: u32, value: u32) -> Value {
+    json!({"type": "Conditional", "conditional": {"op": inner, "width": width, "value": value}})
+}
+
+fn header(declarations: &str) -> String {
+    format!("OPENQASM 3.0;\ninclude \"stdgates.inc\";\n\n{declarations}qubit[4] q;\nbit[2] c;\n")
+}
+
+#[test]
+fn export_gates() {
+    let mut circ = circuit(json!([
+        {"op": op("H", &[]), "args": [id("q", 0)]},
+        {"op": op("SXdg", &[]), "args": [id("q", 1)]},
+        {"op": op("U1", &["0.5"]), "args": [id("q", 1)]},
+        {"op": op("CnX", &[]), "args": [id("q", 0), id("q", 1), id("q", 2), id("q", 3)]},
+        {"op": op("Phase", &["-0.25"]), "args": []},
+        {"op": op("Barrier", &[]), "args": [id("q", 0), id("c", 0)]},
+        {"op": op("Measure", &[]), "args": [id("q", 0), id("c", 0)]},
+        {"op": conditional(op("X", &[]), 2, 2), "args": [id("c", 0), id("c", 1), id("q", 1)]},
+        {"op": conditional(op("Z", &[]), 1, 0), "args": [id("c", 1), id("q", 2)]},
+        {"op": conditional(op("Y", &[]), 2, 1), "args": [id("c", 1), id("c", 0), id("q", 3)]},
+        {"op": op("Reset", &[]), "args": [id("q", 0)]},
+    ]));
+    circ.phase = "0.5".to_string();
+    let expected = header("")
+        + "gphase(0.5*pi);
+h q[0];
+inv @ sx q[1];
+p(0.5*pi) q[1];
+ctrl(3) @ x q[0], q[1], q[2], q[3];
+gphase(-0.25*pi);
+barrier q[0];
+c[0] = measure q[0];
+if (c == 2) {
+  x q[1];
+}
+if (c[1] == 0) {
+  z q[2];
+}
+if (c[1] == 1 && c[0] == 0) {
+  y q[3];
+}
+reset q[0];
+";
+    assert_eq!(circ.to_qasm3().unwrap(), expected);
+}
+
+#[test]
+fn export_u_gates() {
+    let (theta, phi, lambda) = (0.3, 0.7, -0.4);
+    let circ = circuit(json!([
+        {"op": op("CU3", &["0.3", "0.7", "-0.4"]), "args": [id("q", 0), id("q", 1)]},
+        {"op": op("U3", &["0.3", "0.7", "-0.4"]), "args": [id("q", 2)]},
+        {"op": op("U2", &["0.7", "-0.4"]), "args": [id("q", 3)]},
+    ]));
+    let expected = header("")
+        + "ctrl @ U(0.3*pi, 0.7*pi, -0.4*pi) q[0], q[1];
+U(0.3*pi, 0.7*pi, -0.4*pi) q[2];
+U(0.5*pi, 0.7*pi, -0.4*pi) q[3];
+";
+    assert_eq!(circ.to_qasm3().unwrap(), expected);
+
+    // The controlled built-in U gate is the CU3 gate.
+    let (theta, phi, lambda) = (theta * PI, phi * PI, lambda * PI);
+    let polar = |r: f64, angle: f64| (r * angle.cos(), r * angle.sin());
+    let (cos, sin) = ((theta / 2.0).cos(), (theta / 2.0).sin());
+    let zero = (0.0, 0.0);
+    let expected = [
+        [(1.0, 0.0), zero, zero, zero],
+        [zero, (1.0, 0.0), zero, zero],
+        [zero, zero, (cos, 0.0), polar(-sin, lambda)],
+        [zero, zero, polar(sin, phi), polar(cos, phi + lambda)],
+    ];
+    let mut cu3 = Operation::from_optype(OpType::CU3);
+    cu3.params = Some(vec![0.3, 0.7, -0.4]);
+    let unitary = cu3.unitary().unwrap();
+    for (row, expected_row) in unitary.data.iter().zip(&expected) {
+        for (a, e) in row.iter().zip(expected_row) {
+            assert!((a.0 - e.0).abs() < 1e-10 && (a.1 - e.1).abs() < 1e-10);
+        }
+    }
+}
+
+#[test]
+fn export_implicit_permutation() {
+    let mut circ = circuit(json!([
+        {"op": op("X", &[]), "args": [id("q", 0)]},
+        {"op": op("H", &[]), "args": [id("q", 1)]},
+    ]));
+    circ.implicit_permutation = serde_json::from_value(json!([
+        [id("q", 0), id("q", 1)],
+        [id("q", 1), id("q", 3)],
+        [id("q", 3), id("q", 0)],
+    ]))
+    .unwrap();
+    let expected = header("") + "x q[0];\nh q[1];\nswap q[0], q[3];\nswap q[1], q[3];\n";
+    assert_eq!(circ.to_qasm3().unwrap(), expected);
+
+    // The swaps implement the permutation.
+    let mut swapped = circuit(json!([
+        {"op": op("X", &[]), "args": [id("q", 0)]},
+        {"op": op("H", &[]), "args": [id("q", 1)]},
+        {"op": op("SWAP", &[]), "args": [id("q", 0), id("q", 3)]},
+        {"op": op("SWAP", &[]), "args": [id("q", 1), id("q", 3)]},
+    ]));
+    swapped.phase = circ.phase.clone();
+    let bindings = HashMap::new();
+    let (circ, swapped) = (
+        circ.evaluate_params(&bindings).unwrap(),
+        swapped.evaluate_params(&bindings).unwrap(),
+    );
+    assert_eq!(circ.unitary().unwrap(), swapped.unitary().unwrap());
+}
+
+#[test]
+fn export_custom_gates() {
+    let definition = json!({
+        "phase": "0",
+        "qubits": [id("a", 0), id("a", 1)],
+        "bits": [],
+        "implicit_permutation": [],
+        "commands": [
+            {"op": op("Rz", &["2*t"]), "args": [id("a", 1)]},
+            {"op": op("CZ", &[]), "args": [id("a", 1), id("a", 0)]},
+        ],
+    });
+    let custom = json!({
+        "type": "CustomGate",
+        "box": {
+            "type": "CustomGate",
+            "id": "3c5e0f6a-2f0b-4a8e-9d0e-7a1b2c3d4e5f",
+            "gate": {"name": "my_gate", "args": ["t"], "definition": definition},
+            "params": ["0.25"],
+        },
+    });
+    let circ = circuit(json!([
+        {"op": custom, "args": [id("q", 0), id("q", 2)]},
+        {"op": custom, "args": [id("q", 2), id("q", 1)]},
+    ]));
+    let expected = header("gate my_gate(t) q0, q1 {\n  rz(2*t) q1;\n  cz q1, q0;\n}\n")
+        + "my_gate(0.25*pi) q[0], q[2];
+my_gate(0.25*pi) q[2], q[1];
+";
+    assert_eq!(circ.to_qasm3().unwrap(), expected);
+}
+
+#[test]
+fn export_classical() {
+    let circ: SerialCircuit = serde_json::from_str(QASM).unwrap();
+    let qasm = circ.to_qasm3().unwrap();
+    assert!(qasm.contains("d = bit[3]((uint[3](a) + uint[3](b)) / 2 - uint[3](c));\n"));
+
+    let set_bits = json!({"type": "SetBits", "classical": {"values": [true, false]}});
+    let expr = json!({
+        "bit_posn": [],
+        "expr": {"op": "RegAnd", "args": [
+            {"type": "expr", "input": {"op": "RegNot", "args": [
+                {"type": "term", "input": {"type": "var", "term": {"type": "reg", "var": {"index": 0}}}},
+            ]}},
+            {"type": "term", "input": {"type": "int", "term": 1}},
+        ]},
+        "reg_posn": [[0, [1, 0]]],
+        "output_posn": [0, 1],
+    });
+    let circ = circuit(json!([
+        {"op": set_bits, "args": [id("c", 1), id("c", 0)]},
+        {"op": {"type": "ClExpr", "expr": expr}, "args": [id("c", 0), id("c", 1)]},
+    ]));
+    let expected = header("")
+        + "{
+  bit[2] out = bit[2](1 | 0);
+  c[1] = out[0];
+  c[0] = out[1];
+}
+{
+  bit[2] r0;
+  r0[0] = c[1];
+  r0[1] = c[0];
+  c = bit[2](~uint[2](r0) & 1);
+}
+";
+    assert_eq!(circ.to_qasm3().unwrap(), expected);
+
+    let expr = json!({
+        "bit_posn": [],
+        "expr": {"op": "RegNot", "args": [
+            {"type": "term", "input": {"type": "var", "term": {"type": "reg", "var": {"index": 0}}}},
+        ]},
+        "reg_posn": [[0, [0, 1]]],
+        "output_posn": [2],
+    });
+    let mut circ = circuit(json!([
+        {"op": {"type": "ClExpr", "expr": expr}, "args": [id("c", 0), id("c", 1), id("d", 0)]},
+    ]));
+    circ.bits.push(serde_json::from_value(id("d", 0)).unwrap());
+    assert_eq!(
+        circ.to_qasm3().unwrap(),
+        header("") + "bit[1] d;\nd = bit[1](uint[1](~uint[2](c)));\n"
+    );
+}
+
+#[test]
+fn export_externs() {
+    let circ: SerialCircuit = serde_json::from_str(RNG).unwrap();
+    let qasm = circ.to_qasm3().unwrap();
+    assert!(qasm.contains("extern rng_seed(bit[64]);\n"));
+    assert!(qasm.contains("extern rng_num() -> bit[32];\n"));
+    assert!(qasm.contains("rng_seed(seed);\n"));
+    assert!(qasm.contains("num = rng_num();\n"));
+
+    let wasm = json!({
+        "func_name": "add", "ww_n": 1, "n": 3, "width_i_parameter": [1, 0], "width_o_parameter": [1],
+        "wasm_file_uid": "uid",
+    });
+    let circ = circuit(json!([
+        {"op": {"type": "WASM", "wasm": wasm}, "args": [id("c", 0), id("c", 1), id("_w", 0)]},
+    ]));
+    let expected = header("extern add(bit[1]) -> bit[1];\n") + "c[1:1] = add(c[0:0]);\n";
+    assert_eq!(circ.to_qasm3().unwrap(), expected);
+}
+
+#[test]
+fn export_flow() {
+    let circ = circuit(json!([
+        {"op": flow("Label", "loop"), "args": []},
+        {"op": op("H", &[]), "args": [id("q", 0)]},
+        {"op": op("Measure", &[]), "args": [id("q", 0), id("c", 0)]},
+        {"op": flow("Branch", "loop"), "args": [id("c", 0)]},
+        {"op": flow("Branch", "else"), "args": [id("c", 1)]},
+        {"op": op("X", &[]), "args": [id("q", 1)]},
+        {"op": flow("Goto", "end"), "args": []},
+        {"op": flow("Label", "else"), "args": []},
+        {"op": op("Y", &[]), "args": [id("q", 1)]},
+        {"op": flow("Label", "end"), "args": []},
+        {"op": flow("Branch", "skip"), "args": [id("c", 0)]},
+        {"op": op("Stop", &[]), "args": []},
+        {"op": flow("Label", "skip"), "args": []},
+    ]));
+    let expected = header("")
+        + "while (true) {
+  h q[0];
+  c[0] = measure q[0];
+  if (c[0] == 0) {
+    break;
+  }
+}
+if (c[1] == 0) {
+  x q[1];
+} else {
+  y q[1];
+}
+if (c[0] == 0) {
+  end;
+}
+";
+    assert_eq!(circ.to_qasm3().unwrap(), expected);
+}
+
+#[rstest]
+#[case::gate(
+    json!([{"op": op("TK2", &["0", "0", "0"]), "args": [id("q", 0), id("q", 1)]}]),
+    "/commands/0/op",
+    ExportErrorKind::UnsupportedOperation(OpType::TK2)
+)]
+#[case::crossing_jump(
+    json!([
+        {"op": flow("Label", "a"), "args": []},
+        {"op": flow("Branch", "b"), "args": [id("c", 0)]},
+        {"op": flow("Goto", "a"), "args": []},
+        {"op": flow("Label", "b"), "args": []},
+    ]),
+    "/commands/1",
+    ExportErrorKind::UnsupportedControlFlow
+)]
+#[case::conditional_jump(
+    json!([
+        {"op": conditional(flow("Goto", "a"), 1, 1), "args": [id("c", 0)]},
+        {"op": flow("Label", "a"), "args": []},
+    ]),
+    "/commands/0/op/conditional",
+    ExportErrorKind::UnsupportedControlFlow
+)]
+#[case::unknown_label(
+    json!([{"op": flow("Goto", "a"), "args": []}]),
+    "/commands/0/op/data",
+    ExportErrorKind::UnknownLabel("a".to_string())
+)]
+#[case::duplicate_label(
+    json!([{"op": flow("Label", "a"), "args": []}, {"op": flow("Label", "a"), "args": []}]),
+    "/commands/1/op/data",
+    ExportErrorKind::DuplicateLabel("a".to_string())
+)]
+#[case::missing_label(
+    json!([{"op": op("Label", &[]), "args": []}]),
+    "/commands/0/op",
+    ExportErrorKind::MissingField { op_type: OpType::Label, field: "data" }
+)]
+#[case::invalid_classical(
+    json!([{"op": {"type": "CopyBits", "classical": {"n_i": 2}}, "args": [id("c", 0), id("c", 1)]}]),
+    "/commands/0/op",
+    ExportErrorKind::InvalidClassical(tket_json_rs::clexpr::lower::LowerErrorKind::ArgCount {
+        op_type: OpType::CopyBits,
+        expected: 4,
+        found: 2,
+    })
+)]
+#[case::arg_count(
+    json!([{"op": op("CX", &[]), "args": [id("q", 0)]}]),
+    "/commands/0",
+    ExportErrorKind::ArgCount { op_type: OpType::CX, expected: 2, found: 1 }
+)]
+fn export_errors(#[case] commands: Value, #[case] path: &str, #[case] kind: ExportErrorKind) {
+    let error = circuit(commands).to_qasm3().unwrap_err();
+    assert_eq!(error.path, path);
+    assert_eq!(error.kind, kind);
+}
+
+#[test]
+fn export_invalid_names() {
+    let mut circ = circuit(json!([]));
+    circ.bits[0].id.0 = "cx".to_string();
+    circ.bits[1].id.0 = "cx".to_string();
+    let error = circ.to_qasm3().unwrap_err();
+    assert_eq!(error.path, "/bits/0");
+    assert_eq!(error.kind, ExportErrorKind::InvalidName("cx".to_string()));
+
+    let wasm = json!({
+        "func_name": "q", "ww_n": 0, "n": 0, "width_i_parameter": [], "width_o_parameter": [],
+        "wasm_file_uid": "uid",
+    });
+    let circ = circuit(json!([{"op": {"type": "WASM", "wasm": wasm}, "args": []}]));
+    let error = circ.to_qasm3().unwrap_err();
+    assert_eq!(error.kind, ExportErrorKind::InvalidName("q".to_string()));
+}
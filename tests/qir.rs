@@ -0,0 +1,124 @@
+//! Tests for the QIR export.
+use std::collections::HashMap;
+
+use rstest::rstest;
+use serde_json::{json, Value};
+use tket_json_rs::qir::{Profile, QirErrorKind};
+use tket_json_rs::{OpType, SerialCircuit};
+
+const BASE: &str = include_str!("data/qir-base.ll");
+const ADAPTIVE: &str = include_str!("data/qir-adaptive.ll");
+
+fn id(name: &str, index: i64) -> Value {
+    json!([name, [index]])
+}
+
+fn op(op_type: &str, params: &[&str]) -> Value {
+    if params.is_empty() {
+        json!({"type": op_type})
+    } else {
+        json!({"type": op_type, "params": params})
+    }
+}
+
+fn conditional(inner: Value, width: u32, value: u32) -> Value {
+    json!({"type": "Conditional", "conditional": {"op": inner, "width": width, "value": value}})
+}
+
+/// A circuit on the qubits `q[0..3]` and the bits `c[0..2]`.
+fn circuit(commands: Value) -> SerialCircuit<f64> {
+    let circ: SerialCircuit = serde_json::from_value(json!({
+        "phase": "0.5",
+        "qubits": [id("q", 0), id("q", 1), id("q", 2)],
+        "bits": [id("c", 0), id("c", 1)],
+        "implicit_permutation": [],
+        "commands": commands,
+    }))
+    .unwrap();
+    circ.evaluate_params(&HashMap::new()).unwrap()
+}
+
+#[test]
+fn export_base() {
+    let bell = json!({
+        "phase": "0",
+        "qubits": [id("a", 0), id("a", 1)],
+        "bits": [],
+        "implicit_permutation": [],
+        "commands": [
+            {"op": op("H", &[]), "args": [id("a", 0)]},
+            {"op": op("CX", &[]), "args": [id("a", 0), id("a", 1)]},
+        ],
+    });
+    let circ_box = json!({
+        "type": "CircBox",
+        "box": {"type": "CircBox", "id": "3c5e0f6a-2f0b-4a8e-9d0e-7a1b2c3d4e60", "circuit": bell},
+    });
+    let circ = circuit(json!([
+        {"op": circ_box, "args": [id("q", 1), id("q", 0)]},
+        {"op": op("Rz", &["0.5"]), "args": [id("q", 2)]},
+        {"op": op("Phase", &["0.25"]), "args": []},
+        {"op": op("ZZPhase", &["-1/4"]), "args": [id("q", 0), id("q", 2)]},
+        {"op": op("Tdg", &[]), "args": [id("q", 2)]},
+        {"op": op("Barrier", &[]), "args": [id("q", 0), id("q", 1)]},
+        {"op": op("Measure", &[]), "args": [id("q", 0), id("c", 0)]},
+        {"op": op("Measure", &[]), "args": [id("q", 1), id("c", 1)]},
+    ]));
+    assert_eq!(circ.to_qir(Profile::Base).unwrap(), BASE);
+}
+
+#[test]
+fn export_adaptive() {
+    let circ = circuit(json!([
+        {"op": op("H", &[]), "args": [id("q", 0)]},
+        {"op": op("Measure", &[]), "args": [id("q", 0), id("c", 0)]},
+        {"op": conditional(op("X", &[]), 1, 1), "args": [id("c", 0), id("q", 1)]},
+        {"op": op("Reset", &[]), "args": [id("q", 0)]},
+        {"op": op("Measure", &[]), "args": [id("q", 1), id("c", 1)]},
+        {
+            "op": conditional(op("Ry", &["0.5"]), 2, 2),
+            "args": [id("c", 0), id("c", 1), id("q", 2)],
+        },
+    ]));
+    assert_eq!(circ.to_qir(Profile::Adaptive).unwrap(), ADAPTIVE);
+}
+
+#[rstest]
+#[case::conditional(
+    json!([{"op": conditional(op("X", &[]), 1, 1), "args": [id("c", 0), id("q", 0)]}]),
+    "/commands/0/op",
+    QirErrorKind::RequiresAdaptiveProfile(OpType::Conditional)
+)]
+#[case::reset(
+    json!([{"op": op("Reset", &[]), "args": [id("q", 0)]}]),
+    "/commands/0/op",
+    QirErrorKind::RequiresAdaptiveProfile(OpType::Reset)
+)]
+#[case::reuse(
+    json!([
+        {"op": op("Measure", &[]), "args": [id("q", 0), id("c", 0)]},
+        {"op": op("X", &[]), "args": [id("q", 0)]},
+    ]),
+    "/commands/1/op",
+    QirErrorKind::RequiresAdaptiveProfile(OpType::X)
+)]
+#[case::gate(
+    json!([{"op": op("CH", &[]), "args": [id("q", 0), id("q", 1)]}]),
+    "/commands/0/op",
+    QirErrorKind::UnsupportedOperation(OpType::CH)
+)]
+#[case::undeclared(
+    json!([{"op": op("H", &[]), "args": [id("r", 0)]}]),
+    "/commands/0/args/0",
+    QirErrorKind::UndeclaredWire(serde_json::from_value(id("r", 0)).unwrap())
+)]
+#[case::param_count(
+    json!([{"op": op("Rx", &[]), "args": [id("q", 0)]}]),
+    "/commands/0/op",
+    QirErrorKind::ParamCount { op_type: OpType::Rx, expected: 1, found: 0 }
+)]
+fn export_errors(#[case] commands: Value, #[case] path: &str, #[case] kind: QirErrorKind) {
+    let error = circuit(commands).to_qir(Profile::Base).unwrap_err();
+    assert_eq!(error.path, path);
+    assert_eq!(error.kind, kind);
+}
@@ -0,0 +1,260 @@
+//! Tests for the Quil export.
+use rstest::rstest;
+use serde_json::{json, Value};
+use tket_json_rs::optype::OpType;
+use tket_json_rs::quil::QuilErrorKind;
+use tket_json_rs::SerialCircuit;
+
+fn id(name: &str, index: i64) -> Value {
+    json!([name, [index]])
+}
+
+fn op(op_type: &str, params: &[&str]) -> Value {
+    if params.is_empty() {
+        json!({"type": op_type})
+    } else {
+        json!({"type": op_type, "params": params})
+    }
+}
+
+fn flow(op_type: &str, label: &str) -> Value {
+    json!({"type": op_type, "data": label})
+}
+
+/// A circuit on the qubits `q[0..4]` and the bits `c[0..2]`.
+fn circuit(commands: Value) -> SerialCircuit {
+    serde_json::from_value(json!({
+        "phase": "0.5",
+        "qubits": [id("q", 0), id("q", 1), id("q", 2), id("q", 3)],
+        "bits": [id("c", 0), id("c", 1)],
+        "implicit_permutation": [],
+        "commands": commands,
+    }))
+    .unwrap()
+}
+
+fn conditional(inner: Value, width: u32, value: u32) -> Value {
+    json!({"type": "Conditional", "conditional": {"op": inner, "width": width, "value": value}})
+}
+
+#[test]
+fn export_gates() {
+    let circ = circuit(json!([
+        {"op": op("H", &[]), "args": [id("q", 0)]},
+        {"op": op("Tdg", &[]), "args": [id("q", 1)]},
+        {"op": op("Rz", &["0.5"]), "args": [id("q", 1)]},
+        {"op": op("U1", &["-1/4"]), "args": [id("q", 2)]},
+        {"op": op("CX", &[]), "args": [id("q", 0), id("q", 1)]},
+        {"op": op("CH", &[]), "args": [id("q", 1), id("q", 0)]},
+        {"op": op("CU1", &["0.25"]), "args": [id("q", 0), id("q", 3)]},
+        {"op": op("ISWAPMax", &[]), "args": [id("q", 1), id("q", 2)]},
+        {"op": op("ISWAP", &["0.5"]), "args": [id("q", 2), id("q", 3)]},
+        {"op": op("PhasedISWAP", &["0", "1"]), "args": [id("q", 0), id("q", 1)]},
+        {"op": op("PhasedISWAP", &["1.5", "0.5"]), "args": [id("q", 0), id("q", 1)]},
+        {"op": op("CnX", &[]), "args": [id("q", 0), id("q", 1), id("q", 2)]},
+        {"op": op("CnZ", &[]), "args": [id("q", 0), id("q", 1), id("q", 2), id("q", 3)]},
+        {"op": op("Phase", &["0.5"]), "args": []},
+        {"op": op("Barrier", &[]), "args": [id("q", 0), id("q", 2), id("c", 0)]},
+        {"op": op("Measure", &[]), "args": [id("q", 0), id("c", 1)]},
+        {"op": op("Reset", &[]), "args": [id("q", 0)]},
+    ]));
+    let expected = "DECLARE ro BIT[2]
+H 0
+DAGGER T 1
+RZ(0.5*pi) 1
+PHASE(-1/4*pi) 2
+CNOT 0 1
+CONTROLLED H 1 0
+CPHASE(0.25*pi) 0 3
+ISWAP 1 2
+XY(0.5*pi) 2 3
+XY(1*pi) 0 1
+XY(-0.5*pi) 0 1
+CCNOT 0 1 2
+CONTROLLED CONTROLLED CONTROLLED Z 0 1 2 3
+FENCE 0 2
+MEASURE 0 ro[1]
+RESET 0
+";
+    assert_eq!(circ.to_quil().unwrap(), expected);
+}
+
+#[test]
+fn export_conditionals() {
+    let circ = circuit(json!([
+        {"op": op("Measure", &[]), "args": [id("q", 0), id("c", 0)]},
+        {"op": conditional(op("X", &[]), 2, 2), "args": [id("c", 0), id("c", 1), id("q", 1)]},
+        {"op": conditional(op("Rx", &["0.5"]), 1, 1), "args": [id("c", 1), id("q", 2)]},
+    ]));
+    let expected = "DECLARE ro BIT[2]
+MEASURE 0 ro[0]
+JUMP-WHEN @skip0 ro[0]
+JUMP-UNLESS @skip0 ro[1]
+X 1
+LABEL @skip0
+JUMP-UNLESS @skip1 ro[1]
+RX(0.5*pi) 2
+LABEL @skip1
+";
+    assert_eq!(circ.to_quil().unwrap(), expected);
+}
+
+#[test]
+fn export_flow() {
+    let circ = circuit(json!([
+        {"op": flow("Label", "skip0"), "args": []},
+        {"op": op("H", &[]), "args": [id("q", 0)]},
+        {"op": op("Measure", &[]), "args": [id("q", 0), id("c", 0)]},
+        {"op": flow("Branch", "skip0"), "args": [id("c", 0)]},
+        {"op": conditional(flow("Goto", "end"), 1, 0), "args": [id("c", 1)]},
+        {"op": op("Stop", &[]), "args": []},
+        {"op": flow("Label", "end"), "args": []},
+    ]));
+    let expected = "DECLARE ro BIT[2]
+LABEL @skip0
+H 0
+MEASURE 0 ro[0]
+JUMP-WHEN @skip0 ro[0]
+JUMP-WHEN @skip1 ro[1]
+JUMP @end
+LABEL @skip1
+HALT
+LABEL @end
+";
+    assert_eq!(circ.to_quil().unwrap(), expected);
+}
+
+#[test]
+fn export_boxes() {
+    let definition = json!({
+        "phase": "0",
+        "qubits": [id("a", 0), id("a", 1)],
+        "bits": [],
+        "implicit_permutation": [],
+        "commands": [
+            {"op": op("Ry", &["2*t"]), "args": [id("a", 1)]},
+            {"op": op("CZ", &[]), "args": [id("a", 1), id("a", 0)]},
+        ],
+    });
+    let custom = json!({
+        "type": "CustomGate",
+        "box": {
+            "type": "CustomGate",
+            "id": "3c5e0f6a-2f0b-4a8e-9d0e-7a1b2c3d4e5f",
+            "gate": {"name": "my_gate", "args": ["t"], "definition": definition},
+            "params": ["0.25"],
+        },
+    });
+    let circ_box = json!({
+        "type": "CircBox",
+        "box": {
+            "type": "CircBox",
+            "id": "7f1d0b52-8c4e-4d6a-9b3f-2e5a6c7d8e9f",
+            "circuit": {
+                "phase": "0",
+                "qubits": [id("a", 0)],
+                "bits": [id("b", 0)],
+                "implicit_permutation": [],
+                "commands": [
+                    {"op": op("S", &[]), "args": [id("a", 0)]},
+                    {"op": op("Measure", &[]), "args": [id("a", 0), id("b", 0)]},
+                ],
+            },
+        },
+    });
+    let circ = circuit(json!([
+        {"op": custom, "args": [id("q", 0), id("q", 2)]},
+        {"op": circ_box, "args": [id("q", 3), id("c", 1)]},
+    ]));
+    let expected = "DECLARE ro BIT[2]
+RY(2*0.25*pi) 2
+CZ 2 0
+S 3
+MEASURE 3 ro[1]
+";
+    assert_eq!(circ.to_quil().unwrap(), expected);
+}
+
+#[test]
+fn export_implicit_permutation() {
+    let mut circ = circuit(json!([{"op": op("X", &[]), "args": [id("q", 0)]}]));
+    circ.implicit_permutation =
+        serde_json::from_value(json!([[id("q", 0), id("q", 1)], [id("q", 1), id("q", 0)]]))
+            .unwrap();
+    assert_eq!(
+        circ.to_quil().unwrap(),
+        "DECLARE ro BIT[2]\nX 0\nSWAP 0 1\n"
+    );
+
+    circ.implicit_permutation.truncate(1);
+    let error = circ.to_quil().unwrap_err();
+    assert_eq!(error.path, "/implicit_permutation");
+    assert_eq!(error.kind, QuilErrorKind::InvalidPermutation);
+}
+
+#[test]
+fn export_without_bits() {
+    let mut circ = circuit(json!([{"op": op("Y", &[]), "args": [id("q", 3)]}]));
+    circ.bits.clear();
+    assert_eq!(circ.to_quil().unwrap(), "Y 3\n");
+}
+
+#[rstest]
+#[case::operation(
+    json!([{"op": op("SX", &[]), "args": [id("q", 0)]}]),
+    "/commands/0/op",
+    QuilErrorKind::UnsupportedOperation(OpType::SX)
+)]
+#[case::inexact(
+    json!([{"op": op("PhasedISWAP", &["0.25", "1"]), "args": [id("q", 0), id("q", 1)]}]),
+    "/commands/0/op",
+    QuilErrorKind::InexactGate(OpType::PhasedISWAP)
+)]
+#[case::symbol(
+    json!([{"op": op("Rx", &["a"]), "args": [id("q", 0)]}]),
+    "/commands/0/op/params/0",
+    QuilErrorKind::UnsupportedParam("a".to_string())
+)]
+#[case::label_name(
+    json!([{"op": flow("Label", "1st"), "args": []}]),
+    "/commands/0/op/data",
+    QuilErrorKind::InvalidName("1st".to_string())
+)]
+#[case::duplicate_label(
+    json!([
+        {"op": flow("Label", "a"), "args": []},
+        {"op": flow("Label", "a"), "args": []},
+    ]),
+    "/commands/1/op/data",
+    QuilErrorKind::DuplicateLabel("a".to_string())
+)]
+#[case::unknown_label(
+    json!([{"op": flow("Goto", "nowhere"), "args": []}]),
+    "/commands/0/op/data",
+    QuilErrorKind::UnknownLabel("nowhere".to_string())
+)]
+#[case::missing_label(
+    json!([{"op": op("Branch", &[]), "args": [id("c", 0)]}]),
+    "/commands/0/op",
+    QuilErrorKind::MissingField { op_type: OpType::Branch, field: "data" }
+)]
+#[case::undeclared(
+    json!([{"op": op("Measure", &[]), "args": [id("q", 0), id("d", 0)]}]),
+    "/commands/0/args/1",
+    QuilErrorKind::UndeclaredWire(serde_json::from_value(id("d", 0)).unwrap())
+)]
+#[case::arg_count(
+    json!([{"op": op("CU1", &["0.5"]), "args": [id("q", 0)]}]),
+    "/commands/0",
+    QuilErrorKind::ArgCount { op_type: OpType::CU1, expected: 2, found: 1 }
+)]
+#[case::param_count(
+    json!([{"op": op("Rz", &[]), "args": [id("q", 0)]}]),
+    "/commands/0/op",
+    QuilErrorKind::ParamCount { op_type: OpType::Rz, expected: 1, found: 0 }
+)]
+fn export_errors(#[case] commands: Value, #[case] path: &str, #[case] kind: QuilErrorKind) {
+    let error = circuit(commands).to_quil().unwrap_err();
+    assert_eq!(error.path, path);
+    assert_eq!(error.kind, kind);
+}
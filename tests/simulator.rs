@@ -0,0 +1,232 @@
+//! Tests for the statevector simulator.
+use std::f64::consts::FRAC_1_SQRT_2;
+
+use serde_json::json;
+use tket_json_rs::circuit_json::{Command, ImplicitPermutation, Operation};
+use tket_json_rs::register::{ElementId, Qubit};
+use tket_json_rs::simulator::{SimulationError, MAX_QUBITS};
+use tket_json_rs::unitary;
+use tket_json_rs::{OpType, SerialCircuit};
+
+const SIMPLE: &str = include_str!("data/simple.json");
+
+fn qb(index: i64) -> ElementId {
+    ElementId("q".to_string(), vec![index])
+}
+
+fn command(op_type: OpType, params: &[f64], args: &[i64]) -> Command<f64> {
+    let mut op = Operation::from_optype(op_type);
+    if !params.is_empty() {
+        op.params = Some(params.to_vec());
+    }
+    Command {
+        op,
+        args: args.iter().map(|&i| qb(i)).collect(),
+        opgroup: None,
+    }
+}
+
+fn circuit(n_qubits: i64, commands: Vec<Command<f64>>) -> SerialCircuit<f64> {
+    let mut circ = SerialCircuit::new(None, 0.0);
+    circ.qubits = (0..n_qubits).map(|i| Qubit { id: qb(i) }).collect();
+    circ.commands = commands;
+    circ
+}
+
+fn assert_state(actual: &[(f64, f64)], expected: &[(f64, f64)]) {
+    assert_eq!(actual.len(), expected.len());
+    for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+        assert!(
+            (a.0 - e.0).abs() < 1e-10 && (a.1 - e.1).abs() < 1e-10,
+            "amplitude {i}: expected {e:?}, got {a:?}"
+        );
+    }
+}
+
+/// Returns the basis state with the given index.
+fn basis(n_qubits: u32, index: usize) -> Vec<(f64, f64)> {
+    let mut state = vec![(0.0, 0.0); 1 << n_qubits];
+    state[index] = (1.0, 0.0);
+    state
+}
+
+#[test]
+fn bell_state() {
+    let circ = circuit(
+        2,
+        vec![
+            command(OpType::H, &[], &[0]),
+            command(OpType::CX, &[], &[0, 1]),
+        ],
+    );
+    let h = FRAC_1_SQRT_2;
+    assert_state(
+        &circ.statevector().unwrap(),
+        &[(h, 0.0), (0.0, 0.0), (0.0, 0.0), (h, 0.0)],
+    );
+}
+
+#[test]
+fn qubit_order_and_phase() {
+    // The first qubit is the most significant bit.
+    let mut circ = circuit(3, vec![command(OpType::X, &[], &[2])]);
+    assert_state(&circ.statevector().unwrap(), &basis(3, 0b001));
+
+    circ.phase = 0.5;
+    let mut expected = vec![(0.0, 0.0); 8];
+    expected[0b001] = (0.0, 1.0);
+    assert_state(&circ.statevector().unwrap(), &expected);
+}
+
+#[test]
+fn variadic_gates() {
+    let circ = circuit(
+        3,
+        vec![
+            command(OpType::X, &[], &[0]),
+            command(OpType::X, &[], &[2]),
+            command(OpType::CnX, &[], &[0, 2, 1]),
+        ],
+    );
+    assert_state(&circ.statevector().unwrap(), &basis(3, 0b111));
+}
+
+#[test]
+fn variadic_gates_match_unitaries() {
+    // The simulator applies these directly rather than through their matrix.
+    let gates = [
+        (OpType::CnX, vec![]),
+        (OpType::CnZ, vec![]),
+        (OpType::CnRy, vec![0.3]),
+        (OpType::PhaseGadget, vec![0.7]),
+        (OpType::NPhasedX, vec![0.2, 0.4]),
+    ];
+    for (op_type, params) in gates {
+        let circ = circuit(3, vec![command(op_type, &params, &[1, 2, 0])]);
+        let mut op = Operation::from_optype(op_type);
+        op.params = (!params.is_empty()).then_some(params);
+        op.n_qb = Some(3);
+        let swapped = circuit(
+            3,
+            vec![
+                command(OpType::SWAP, &[], &[0, 1]),
+                command(OpType::SWAP, &[], &[1, 2]),
+                Command {
+                    op,
+                    args: vec![qb(0), qb(1), qb(2)],
+                    opgroup: None,
+                },
+                command(OpType::SWAP, &[], &[1, 2]),
+                command(OpType::SWAP, &[], &[0, 1]),
+            ],
+        );
+        let actual = circ.unitary().unwrap().data.concat();
+        let expected = swapped.unitary().unwrap().data.concat();
+        assert_state(&actual, &expected);
+    }
+}
+
+#[test]
+fn large_variadic_gates() {
+    let mut commands: Vec<_> = (0..19).map(|i| command(OpType::X, &[], &[i])).collect();
+    let all: Vec<i64> = (0..20).collect();
+    commands.push(command(OpType::CnX, &[], &all));
+    let circ = circuit(20, commands);
+    assert_state(&circ.statevector().unwrap(), &basis(20, (1 << 20) - 1));
+
+    let mut circ = circuit(2, vec![command(OpType::CnX, &[], &[0, 1])]);
+    circ.commands[0].op.n_qb = Some(40);
+    assert_eq!(
+        circ.statevector().unwrap_err().to_string(),
+        "/commands/0: CnX acts on 40 qubits, but 2 were given"
+    );
+}
+
+#[test]
+fn implicit_permutation() {
+    let mut circ = circuit(2, vec![command(OpType::X, &[], &[0])]);
+    circ.implicit_permutation = vec![
+        ImplicitPermutation(Qubit { id: qb(0) }, Qubit { id: qb(1) }),
+        ImplicitPermutation(Qubit { id: qb(1) }, Qubit { id: qb(0) }),
+    ];
+    assert_state(&circ.statevector().unwrap(), &basis(2, 0b01));
+
+    circ.implicit_permutation.pop();
+    assert_eq!(
+        circ.statevector(),
+        Err(SimulationError::InvalidPermutation {
+            path: "/implicit_permutation".to_string()
+        })
+    );
+}
+
+#[test]
+fn nested_circuits() {
+    let inner = json!({
+        "phase": "0.0",
+        "qubits": [["a", [0]], ["a", [1]]],
+        "bits": [],
+        "implicit_permutation": [],
+        "commands": [
+            {"args": [["a", [0]]], "op": {"type": "Ry", "params": ["t"]}},
+            {"args": [["a", [0]], ["a", [1]]], "op": {"type": "CX"}}
+        ]
+    });
+    let custom_gate: Operation<f64> = serde_json::from_value(json!({
+        "type": "CustomGate",
+        "box": {
+            "type": "CustomGate",
+            "id": "919b6c13-b9c4-4ffc-ad1a-660088402d02",
+            "gate": {"name": "g", "args": ["t"], "definition": inner},
+            "params": ["1/2 + 1/2"]
+        }
+    }))
+    .unwrap();
+    let circ = circuit(
+        3,
+        vec![Command {
+            op: custom_gate,
+            args: vec![qb(2), qb(0)],
+            opgroup: None,
+        }],
+    );
+    // Ry(1) flips q[2], and the CX then flips q[0].
+    assert_state(&circ.statevector().unwrap(), &basis(3, 0b101));
+}
+
+#[test]
+fn unsupported_operations() {
+    let circ: SerialCircuit = serde_json::from_str(SIMPLE).unwrap();
+    let circ = circ.map_params(|p| p.parse().unwrap());
+    assert_eq!(
+        circ.statevector(),
+        Err(SimulationError::UnsupportedOperation {
+            path: "/commands/2".to_string(),
+            op_type: OpType::Measure,
+        })
+    );
+
+    let circ = circuit(1, vec![command(OpType::CX, &[], &[0])]);
+    assert_eq!(
+        circ.statevector().unwrap_err().to_string(),
+        "/commands/0: CX acts on 2 qubits, but 1 were given"
+    );
+}
+
+#[test]
+fn too_many_qubits() {
+    let circ = circuit(MAX_QUBITS as i64 + 1, vec![]);
+    assert_eq!(
+        circ.statevector(),
+        Err(SimulationError::TooManyQubits {
+            n_qubits: MAX_QUBITS + 1,
+            max: MAX_QUBITS,
+        })
+    );
+
+    let circ = circuit(unitary::MAX_QUBITS as i64 + 1, vec![]);
+    assert_eq!(
+        circ.unitary().unwrap_err().to_string(),
+        "cannot simulate 13 qubits, the limit is 12"
+    );
+}
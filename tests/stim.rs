@@ -0,0 +1,184 @@
+//! Tests for the Stim conversion.
+use rstest::rstest;
+use serde_json::{json, Value};
+use tket_json_rs::stim::export::ExportErrorKind;
+use tket_json_rs::{OpType, SerialCircuit};
+
+fn id(name: &str, index: i64) -> Value {
+    json!([name, [index]])
+}
+
+fn op(op_type: &str) -> Value {
+    json!({"type": op_type})
+}
+
+/// A circuit on the qubits `q[0..3]` and the bits `c[0..2]`.
+fn circuit(commands: Value) -> SerialCircuit {
+    serde_json::from_value(json!({
+        "phase": "0.5",
+        "qubits": [id("q", 0), id("q", 1), id("q", 2)],
+        "bits": [id("c", 0), id("c", 1)],
+        "implicit_permutation": [],
+        "commands": commands,
+    }))
+    .unwrap()
+}
+
+fn conditional(inner: Value, width: u32, value: u32) -> Value {
+    json!({"type": "Conditional", "conditional": {"op": inner, "width": width, "value": value}})
+}
+
+/// Returns the operation types and arguments of the commands of a circuit.
+fn commands(circ: &SerialCircuit) -> Vec<(OpType, String)> {
+    circ.commands
+        .iter()
+        .map(|command| {
+            let op_type = match &command.op.conditional {
+                Some(conditional) => conditional.op.op_type,
+                None => command.op.op_type,
+            };
+            let args: Vec<String> = command.args.iter().map(ToString::to_string).collect();
+            (op_type, args.join(" "))
+        })
+        .collect()
+}
+
+#[test]
+fn export_gates() {
+    let circ = circuit(json!([
+        {"op": op("H"), "args": [id("q", 0)]},
+        {"op": op("H"), "args": [id("q", 1)]},
+        {"op": op("CX"), "args": [id("q", 0), id("q", 2)]},
+        {"op": op("CZ"), "args": [id("q", 1), id("q", 2)]},
+        {"op": op("Sdg"), "args": [id("q", 2)]},
+        {"op": op("Barrier"), "args": [id("q", 0), id("q", 1)]},
+        {"op": op("Measure"), "args": [id("q", 0), id("c", 1)]},
+        {"op": op("Measure"), "args": [id("q", 1), id("c", 0)]},
+        {"op": conditional(op("X"), 1, 1), "args": [id("c", 1), id("q", 2)]},
+        {"op": conditional(op("Z"), 1, 1), "args": [id("c", 0), id("q", 2)]},
+        {"op": op("Reset"), "args": [id("q", 0)]},
+        {"op": op("Y"), "args": [id("q", 1)]},
+        {"op": op("SWAP"), "args": [id("q", 1), id("q", 0)]},
+    ]));
+    let expected = "H 0 1
+CX 0 2
+CZ 1 2
+S_DAG 2
+TICK
+M 0 1
+CX rec[-2] 2
+CZ rec[-1] 2
+R 0
+Y 1
+SWAP 1 0
+";
+    assert_eq!(circ.to_stim().unwrap(), expected);
+}
+
+#[test]
+fn import_statements() {
+    let source = "# A repetition code round.
+h 0
+CNOT 0 1 0 2
+REPEAT 2 {
+    TICK
+    M 1  # Syndrome
+}
+ZCX rec[-1] 0
+CZ 2 rec[-2]
+SQRT_Z_DAG 2
+";
+    let circ = SerialCircuit::from_stim(source).unwrap();
+    assert_eq!(circ.qubits.len(), 3);
+    assert_eq!(circ.bits.len(), 2);
+    let expected = [
+        (OpType::H, "q[0]"),
+        (OpType::CX, "q[0] q[1]"),
+        (OpType::CX, "q[0] q[2]"),
+        (OpType::Barrier, "q[0] q[1] q[2]"),
+        (OpType::Measure, "q[1] c[0]"),
+        (OpType::Barrier, "q[0] q[1] q[2]"),
+        (OpType::Measure, "q[1] c[1]"),
+        (OpType::X, "c[1] q[0]"),
+        (OpType::Z, "c[0] q[2]"),
+        (OpType::Sdg, "q[2]"),
+    ];
+    let expected: Vec<(OpType, String)> = expected
+        .into_iter()
+        .map(|(op_type, args)| (op_type, args.to_string()))
+        .collect();
+    assert_eq!(commands(&circ), expected);
+}
+
+#[test]
+fn import_roundtrip() {
+    let source = "H 0 1
+CY 0 1
+S 1
+TICK
+M 1 0
+CY rec[-2] 1
+R 0 1
+";
+    let circ = SerialCircuit::from_stim(source).unwrap();
+    assert_eq!(circ.to_stim().unwrap(), source);
+}
+
+#[rstest]
+#[case::gate(
+    json!([{"op": {"type": "Rz", "params": ["0.5"]}, "args": [id("q", 0)]}]),
+    "/commands/0/op",
+    ExportErrorKind::UnsupportedOperation(OpType::Rz)
+)]
+#[case::condition_value(
+    json!([{"op": conditional(op("X"), 1, 0), "args": [id("c", 0), id("q", 0)]}]),
+    "/commands/0/op",
+    ExportErrorKind::UnsupportedCondition
+)]
+#[case::condition_gate(
+    json!([{"op": conditional(op("H"), 1, 1), "args": [id("c", 0), id("q", 0)]}]),
+    "/commands/0/op",
+    ExportErrorKind::UnsupportedCondition
+)]
+#[case::unmeasured(
+    json!([{"op": conditional(op("X"), 1, 1), "args": [id("c", 0), id("q", 0)]}]),
+    "/commands/0/args/0",
+    ExportErrorKind::UnmeasuredBit(serde_json::from_value(id("c", 0)).unwrap())
+)]
+#[case::arg_count(
+    json!([{"op": op("CX"), "args": [id("q", 0)]}]),
+    "/commands/0",
+    ExportErrorKind::ArgCount { op_type: OpType::CX, expected: 2, found: 1 }
+)]
+fn export_errors(#[case] commands: Value, #[case] path: &str, #[case] kind: ExportErrorKind) {
+    let error = circuit(commands).to_stim().unwrap_err();
+    assert_eq!(error.path, path);
+    assert_eq!(error.kind, kind);
+}
+
+#[rstest]
+#[case::instruction("H 0\nDETECTOR rec[-1]", 2, "unsupported instruction 'DETECTOR'")]
+#[case::arguments("X_ERROR(0.1) 0", 1, "instruction arguments are not supported")]
+#[case::inverted("M !0", 1, "unsupported target '!0'")]
+#[case::pairs("CX 0 1 2", 1, "CX takes pairs of targets")]
+#[case::repeated("CZ 1 1", 1, "CZ is applied twice to qubit 1")]
+#[case::record("M 0\nH rec[-1]", 2, "H cannot target a measurement record")]
+#[case::old_record("M 0\nCX rec[-2] 1", 2, "rec[-2] refers to no measurement")]
+#[case::unclosed("REPEAT 2 {\nH 0", 1, "unclosed REPEAT block")]
+#[case::brace("H 0\n}", 2, "unexpected '}'")]
+#[case::qubit_index("H 0 16777216", 1, "qubit index 16777216 is not below 16777216")]
+#[case::unrolled(
+    "REPEAT 1000000 {\nREPEAT 1000000 {\nH 0\n}\n}",
+    3,
+    "the circuit has more than 1048576 commands"
+)]
+fn import_errors(#[case] source: &str, #[case] line: usize, #[case] message: &str) {
+    let error = SerialCircuit::from_stim(source).unwrap_err();
+    assert_eq!((error.line, error.message.as_str()), (line, message));
+}
+
+#[test]
+fn import_empty_repeat() {
+    let circ = SerialCircuit::from_stim("REPEAT 1000000000000 {\n}\nH 0").unwrap();
+    assert_eq!(commands(&circ), [(OpType::H, "q[0]".to_string())]);
+}
@@ -0,0 +1,256 @@
+//! Tests for the unitary matrices of operations.
+use std::f64::consts::FRAC_1_SQRT_2;
+
+use rstest::rstest;
+use serde_json::json;
+use tket_json_rs::circuit_json::{Command, ImplicitPermutation, Matrix, Operation};
+use tket_json_rs::register::{ElementId, Qubit};
+use tket_json_rs::unitary::{equivalent, MAX_QUBITS};
+use tket_json_rs::{OpType, SerialCircuit};
+
+const TOL: f64 = 1e-10;
+
+fn op(op_type: OpType, params: &[f64]) -> Operation<f64> {
+    let mut op = Operation::from_optype(op_type);
+    if !params.is_empty() {
+        op.params = Some(params.to_vec());
+    }
+    op
+}
+
+fn assert_close(actual: &Matrix, expected: &[Vec<(f64, f64)>]) {
+    assert_eq!(actual.data.len(), expected.len());
+    for (r, (row, expected_row)) in actual.data.iter().zip(expected).enumerate() {
+        for (c, (a, e)) in row.iter().zip(expected_row).enumerate() {
+            assert!(
+                (a.0 - e.0).abs() < TOL && (a.1 - e.1).abs() < TOL,
+                "entry ({r}, {c}): expected {e:?}, got {a:?}"
+            );
+        }
+    }
+}
+
+fn assert_unitary(u: &Matrix) {
+    let n = u.data.len();
+    for r in 0..n {
+        for c in 0..n {
+            // (U U†)_{rc} = Σ_k U_{rk} conj(U_{ck})
+            let (re, im) = (0..n).fold((0.0, 0.0), |(re, im), k| {
+                let (a, b) = (u.data[r][k], u.data[c][k]);
+                (re + a.0 * b.0 + a.1 * b.1, im + a.1 * b.0 - a.0 * b.1)
+            });
+            let expected = if r == c { 1.0 } else { 0.0 };
+            assert!((re - expected).abs() < TOL && im.abs() < TOL);
+        }
+    }
+}
+
+fn real(rows: &[&[f64]]) -> Vec<Vec<(f64, f64)>> {
+    rows.iter()
+        .map(|row| row.iter().map(|&x| (x, 0.0)).collect())
+        .collect()
+}
+
+#[rstest]
+#[case::x(op(OpType::X, &[]), real(&[&[0., 1.], &[1., 0.]]))]
+#[case::y(op(OpType::Y, &[]), vec![vec![(0., 0.), (0., -1.)], vec![(0., 1.), (0., 0.)]])]
+#[case::h(op(OpType::H, &[]), real(&[&[FRAC_1_SQRT_2, FRAC_1_SQRT_2], &[FRAC_1_SQRT_2, -FRAC_1_SQRT_2]]))]
+#[case::sx(op(OpType::SX, &[]), vec![vec![(0.5, 0.5), (0.5, -0.5)], vec![(0.5, -0.5), (0.5, 0.5)]])]
+#[case::rx(op(OpType::Rx, &[1.0]), vec![vec![(0., 0.), (0., -1.)], vec![(0., -1.), (0., 0.)]])]
+#[case::ry(op(OpType::Ry, &[1.0]), real(&[&[0., -1.], &[1., 0.]]))]
+#[case::rz(op(OpType::Rz, &[1.0]), vec![vec![(0., -1.), (0., 0.)], vec![(0., 0.), (0., 1.)]])]
+#[case::u1(op(OpType::U1, &[0.5]), vec![vec![(1., 0.), (0., 0.)], vec![(0., 0.), (0., 1.)]])]
+#[case::gpi(op(OpType::GPI, &[0.5]), vec![vec![(0., 0.), (0., -1.)], vec![(0., 1.), (0., 0.)]])]
+#[case::phase(op(OpType::Phase, &[0.5]), vec![vec![(0., 1.)]])]
+#[case::cx(op(OpType::CX, &[]), real(&[&[1., 0., 0., 0.], &[0., 1., 0., 0.], &[0., 0., 0., 1.], &[0., 0., 1., 0.]]))]
+#[case::swap(op(OpType::SWAP, &[]), real(&[&[1., 0., 0., 0.], &[0., 0., 1., 0.], &[0., 1., 0., 0.], &[0., 0., 0., 1.]]))]
+#[case::iswap_max(op(OpType::ISWAPMax, &[]), vec![
+    vec![(1., 0.), (0., 0.), (0., 0.), (0., 0.)],
+    vec![(0., 0.), (0., 0.), (0., 1.), (0., 0.)],
+    vec![(0., 0.), (0., 1.), (0., 0.), (0., 0.)],
+    vec![(0., 0.), (0., 0.), (0., 0.), (1., 0.)],
+])]
+#[case::zz_phase(op(OpType::ZZPhase, &[1.0]), vec![
+    vec![(0., -1.), (0., 0.), (0., 0.), (0., 0.)],
+    vec![(0., 0.), (0., 1.), (0., 0.), (0., 0.)],
+    vec![(0., 0.), (0., 0.), (0., 1.), (0., 0.)],
+    vec![(0., 0.), (0., 0.), (0., 0.), (0., -1.)],
+])]
+#[case::fsim(op(OpType::FSim, &[0.5, 1.0]), vec![
+    vec![(1., 0.), (0., 0.), (0., 0.), (0., 0.)],
+    vec![(0., 0.), (0., 0.), (0., -1.), (0., 0.)],
+    vec![(0., 0.), (0., -1.), (0., 0.), (0., 0.)],
+    vec![(0., 0.), (0., 0.), (0., 0.), (-1., 0.)],
+])]
+fn gate_matrix(#[case] op: Operation<f64>, #[case] expected: Vec<Vec<(f64, f64)>>) {
+    assert_close(&op.unitary().unwrap(), &expected);
+}
+
+#[rstest]
+#[case::tk1(op(OpType::TK1, &[0.1, 0.2, 0.3]), 1)]
+#[case::u3(op(OpType::U3, &[0.1, 0.2, 0.3]), 1)]
+#[case::gpi2(op(OpType::GPI2, &[0.3]), 1)]
+#[case::phased_x(op(OpType::PhasedX, &[0.3, 0.7]), 1)]
+#[case::tk2(op(OpType::TK2, &[0.1, 0.2, 0.3]), 2)]
+#[case::cu3(op(OpType::CU3, &[0.1, 0.2, 0.3]), 2)]
+#[case::ecr(op(OpType::ECR, &[]), 2)]
+#[case::eswap(op(OpType::ESWAP, &[0.3]), 2)]
+#[case::phased_iswap(op(OpType::PhasedISWAP, &[0.1, 0.4]), 2)]
+#[case::aams(op(OpType::AAMS, &[0.3, 0.1, 0.2]), 2)]
+#[case::sycamore(op(OpType::Sycamore, &[]), 2)]
+#[case::xxphase3(op(OpType::XXPhase3, &[0.3]), 3)]
+#[case::bridge(op(OpType::BRIDGE, &[]), 3)]
+#[case::cswap(op(OpType::CSWAP, &[]), 3)]
+fn is_unitary(#[case] op: Operation<f64>, #[case] n_qubits: u32) {
+    let u = op.unitary().unwrap();
+    assert_eq!(u.data.len(), 1 << n_qubits);
+    assert_unitary(&u);
+}
+
+fn unitary(op: &Operation<f64>) -> Matrix {
+    op.unitary().unwrap()
+}
+
+#[test]
+fn equivalent_gates() {
+    let tk2 = unitary(&op(OpType::TK2, &[0.5, 0.0, 0.0]));
+    assert_close(&tk2, &unitary(&op(OpType::XXPhase, &[0.5])).data);
+
+    let sycamore = unitary(&op(OpType::Sycamore, &[]));
+    assert_close(
+        &sycamore,
+        &unitary(&op(OpType::FSim, &[0.5, 1.0 / 6.0])).data,
+    );
+
+    let u2 = unitary(&op(OpType::U2, &[0.2, 0.3]));
+    assert_close(&u2, &unitary(&op(OpType::U3, &[0.5, 0.2, 0.3])).data);
+
+    let mut cnx = op(OpType::CnX, &[]);
+    assert_eq!(cnx.unitary(), None);
+    cnx.n_qb = Some(3);
+    assert_close(&unitary(&cnx), &unitary(&op(OpType::CCX, &[])).data);
+}
+
+#[test]
+fn non_unitary() {
+    assert_eq!(op(OpType::Measure, &[]).unitary(), None);
+    assert_eq!(op(OpType::Reset, &[]).unitary(), None);
+    // Missing parameter.
+    assert_eq!(op(OpType::Rz, &[]).unitary(), None);
+    // Too many qubits.
+    for n_qb in [MAX_QUBITS as u32 + 1, 40] {
+        let mut cnx = op(OpType::CnX, &[]);
+        cnx.n_qb = Some(n_qb);
+        assert_eq!(cnx.unitary(), None);
+        let mut gadget = op(OpType::PhaseGadget, &[0.5]);
+        gadget.n_qb = Some(n_qb);
+        assert_eq!(gadget.unitary(), None);
+    }
+}
+
+#[test]
+fn boxes() {
+    let unitary_box: Operation<f64> = serde_json::from_value(json!({
+        "type": "Unitary1qBox",
+        "box": {
+            "type": "Unitary1qBox",
+            "id": "0dadbfea-5391-41c2-ad71-fb01fdaecddd",
+            "matrix": [[[0.0, 0.0], [1.0, 0.0]], [[1.0, 0.0], [0.0, 0.0]]]
+        }
+    }))
+    .unwrap();
+    assert_close(&unitary(&unitary_box), &unitary(&op(OpType::X, &[])).data);
+
+    // e^{it Z⊗Z} with t = π/2 is ZZPhase(-1).
+    let zz = [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, -1.0, 0.0, 0.0],
+        [0.0, 0.0, -1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+    let exp_box: Operation<f64> = serde_json::from_value(json!({
+        "type": "ExpBox",
+        "box": {
+            "type": "ExpBox",
+            "id": "0dadbfea-5391-41c2-ad71-fb01fdaecddd",
+            "matrix": zz.map(|row| row.map(|x| [x, 0.0])),
+            "phase": std::f64::consts::FRAC_PI_2
+        }
+    }))
+    .unwrap();
+    let u = unitary(&exp_box);
+    assert_unitary(&u);
+    assert_close(&u, &unitary(&op(OpType::ZZPhase, &[-1.0])).data);
+}
+
+fn qb(index: i64) -> Qubit {
+    Qubit {
+        id: ElementId("q".to_string(), vec![index]),
+    }
+}
+
+fn circuit(n_qubits: i64, commands: &[(OpType, &[f64], &[i64])]) -> SerialCircuit<f64> {
+    let mut circ = SerialCircuit::new(None, 0.0);
+    circ.qubits = (0..n_qubits).map(qb).collect();
+    circ.commands = commands
+        .iter()
+        .map(|&(op_type, params, args)| Command {
+            op: op(op_type, params),
+            args: args.iter().map(|&i| qb(i).id).collect(),
+            opgroup: None,
+        })
+        .collect();
+    circ
+}
+
+#[test]
+fn circuit_unitary() {
+    let circ = circuit(2, &[(OpType::X, &[], &[1]), (OpType::CX, &[], &[1, 0])]);
+    let u = circ.unitary().unwrap();
+    // |00> -> |11>, |01> -> |00>, |10> -> |01>, |11> -> |10>
+    let expected = real(&[
+        &[0., 1., 0., 0.],
+        &[0., 0., 1., 0.],
+        &[0., 0., 0., 1.],
+        &[1., 0., 0., 0.],
+    ]);
+    assert_close(&u, &expected);
+}
+
+#[test]
+fn equivalence() {
+    let cx = circuit(2, &[(OpType::CX, &[], &[0, 1])]);
+    let hczh = circuit(
+        2,
+        &[
+            (OpType::H, &[], &[1]),
+            (OpType::CZ, &[], &[0, 1]),
+            (OpType::H, &[], &[1]),
+        ],
+    );
+    assert!(equivalent(&cx, &hczh, 1e-10).unwrap());
+    assert!(!equivalent(&cx, &circuit(2, &[(OpType::CX, &[], &[1, 0])]), 1e-10).unwrap());
+
+    // Equal up to a global phase.
+    let z = circuit(1, &[(OpType::Z, &[], &[0])]);
+    let rz = circuit(1, &[(OpType::Rz, &[1.0], &[0])]);
+    assert!(equivalent(&z, &rz, 1e-10).unwrap());
+    assert!(!equivalent(&z, &circuit(1, &[(OpType::X, &[], &[0])]), 1e-10).unwrap());
+
+    // A SWAP can be replaced by an implicit permutation.
+    let swap = circuit(2, &[(OpType::SWAP, &[], &[0, 1])]);
+    let mut relabelled = circuit(2, &[]);
+    relabelled.implicit_permutation = vec![
+        ImplicitPermutation(qb(0), qb(1)),
+        ImplicitPermutation(qb(1), qb(0)),
+    ];
+    assert!(equivalent(&swap, &relabelled, 1e-10).unwrap());
+
+    // Qubits declared in a different order are matched by name.
+    let mut reordered = cx.clone();
+    reordered.qubits.reverse();
+    assert!(equivalent(&cx, &reordered, 1e-10).unwrap());
+
+    // Circuits on different qubits are never equivalent.
+    assert!(!equivalent(&z, &circuit(2, &[(OpType::Z, &[], &[0])]), 1e-10).unwrap());
+}
@@ -0,0 +1,211 @@
+//! Structural validation tests
+use rstest::rstest;
+use tket_json_rs::circuit_json::{Command, ImplicitPermutation, Operation};
+use tket_json_rs::register::{ElementId, Qubit};
+use tket_json_rs::validate::{DiagnosticKind, WireKind};
+use tket_json_rs::{OpType, SerialCircuit};
+
+const SIMPLE: &str = include_str!("data/simple.json");
+const CLASSICAL: &str = include_str!("data/classical.json");
+const DIAGONAL: &str = include_str!("data/diagonal-box.json");
+const QASM: &str = include_str!("data/qasm.json");
+const WASM: &str = include_str!("data/wasm.json");
+const RNG: &str = include_str!("data/rng.json");
+
+fn id(reg: &str, index: i64) -> ElementId {
+    ElementId(reg.to_string(), vec![index])
+}
+
+fn command(op_type: OpType, args: &[ElementId]) -> Command {
+    Command {
+        op: Operation::from_optype(op_type),
+        args: args.to_vec(),
+        opgroup: None,
+    }
+}
+
+fn simple_circuit() -> SerialCircuit {
+    serde_json::from_str(SIMPLE).unwrap()
+}
+
+#[rstest]
+#[case::simple(SIMPLE)]
+#[case::diagonal_box(DIAGONAL)]
+#[case::qasm_box(QASM)]
+#[case::wasm_box(WASM)]
+#[case::rng(RNG)]
+fn valid_circuits(#[case] json: &str) {
+    let circ: SerialCircuit = serde_json::from_str(json).unwrap();
+    assert_eq!(circ.validate(), vec![]);
+}
+
+#[test]
+fn undeclared_and_mismatched_args() {
+    let mut circ = simple_circuit();
+    circ.commands
+        .push(command(OpType::CX, &[id("q", 0), id("r", 0)]));
+    circ.commands
+        .push(command(OpType::Measure, &[id("c", 0), id("q", 1)]));
+
+    let diagnostics = circ.validate();
+    let found: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.path.as_str(), &d.kind))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (
+                "/commands/4/args/1",
+                &DiagnosticKind::UndeclaredWire(id("r", 0))
+            ),
+            (
+                "/commands/5/args/0",
+                &DiagnosticKind::WireKindMismatch {
+                    id: id("c", 0),
+                    expected: WireKind::Qubit,
+                    found: WireKind::Bit,
+                }
+            ),
+            (
+                "/commands/5/args/1",
+                &DiagnosticKind::WireKindMismatch {
+                    id: id("q", 1),
+                    expected: WireKind::Bit,
+                    found: WireKind::Qubit,
+                }
+            ),
+        ]
+    );
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "/commands/4/args/1: r[0] is not declared in the circuit"
+    );
+}
+
+/// The `CopyBits` command of the classical example has `n_i = 2`, so it
+/// should copy two bits to two others, but only has two arguments.
+#[test]
+fn copy_bits_arg_count() {
+    let circ: SerialCircuit = serde_json::from_str(CLASSICAL).unwrap();
+    let found: Vec<_> = circ
+        .validate()
+        .into_iter()
+        .map(|d| (d.path, d.kind))
+        .collect();
+    assert_eq!(
+        found,
+        vec![(
+            "/commands/2/args".to_string(),
+            DiagnosticKind::ArgCount {
+                op_type: OpType::CopyBits,
+                expected: 4,
+                found: 2,
+            }
+        )]
+    );
+}
+
+#[test]
+fn arg_count_and_duplicates() {
+    let mut circ = simple_circuit();
+    circ.qubits.push(Qubit { id: id("q", 1) });
+    circ.commands
+        .push(command(OpType::H, &[id("q", 0), id("q", 1)]));
+    circ.commands
+        .push(command(OpType::CZ, &[id("q", 1), id("q", 1)]));
+
+    let found: Vec<_> = circ
+        .validate()
+        .into_iter()
+        .map(|d| (d.path, d.kind))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (
+                "/qubits/2".to_string(),
+                DiagnosticKind::DuplicateDeclaration(id("q", 1))
+            ),
+            (
+                "/commands/4/args".to_string(),
+                DiagnosticKind::ArgCount {
+                    op_type: OpType::H,
+                    expected: 1,
+                    found: 2,
+                }
+            ),
+            (
+                "/commands/5/args/1".to_string(),
+                DiagnosticKind::DuplicateArgument(id("q", 1))
+            ),
+        ]
+    );
+}
+
+#[test]
+fn permutation_not_bijective() {
+    let mut circ = simple_circuit();
+    circ.implicit_permutation = vec![
+        ImplicitPermutation(Qubit { id: id("q", 0) }, Qubit { id: id("q", 1) }),
+        ImplicitPermutation(Qubit { id: id("q", 1) }, Qubit { id: id("q", 1) }),
+    ];
+
+    let found: Vec<_> = circ
+        .validate()
+        .into_iter()
+        .map(|d| (d.path, d.kind))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (
+                "/implicit_permutation/1/1".to_string(),
+                DiagnosticKind::DuplicatePermutationEntry(id("q", 1))
+            ),
+            (
+                "/implicit_permutation".to_string(),
+                DiagnosticKind::UnmatchedPermutationEntry(id("q", 0))
+            ),
+        ]
+    );
+}
+
+#[test]
+fn nested_box_diagnostics() {
+    let circ: SerialCircuit = serde_json::from_value(serde_json::json!({
+        "phase": "0.0",
+        "qubits": [["q", [0]]],
+        "bits": [],
+        "implicit_permutation": [[["q", [0]], ["q", [0]]]],
+        "commands": [{
+            "args": [["q", [0]]],
+            "op": {
+                "type": "CircBox",
+                "box": {
+                    "type": "CircBox",
+                    "id": "0dadbfea-5391-41c2-ad71-fb01fdaecddd",
+                    "circuit": {
+                        "phase": "0.0",
+                        "qubits": [["q", [0]]],
+                        "bits": [],
+                        "implicit_permutation": [[["q", [0]], ["q", [0]]]],
+                        "commands": [{"args": [["q", [1]]], "op": {"type": "X"}}]
+                    }
+                }
+            }
+        }]
+    }))
+    .unwrap();
+
+    let diagnostics = circ.validate();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].path,
+        "/commands/0/op/box/circuit/commands/0/args/0"
+    );
+    assert_eq!(
+        diagnostics[0].kind,
+        DiagnosticKind::UndeclaredWire(id("q", 1))
+    );
+}